@@ -4,6 +4,7 @@ const efi = std.os.uefi;
 pub const KernelEntry = struct {
     physical: u64,
     virtual: u64,
+    entry: u64,
 };
 
 const Elf64Header = extern struct {
@@ -45,6 +46,12 @@ const PT_LOAD = 1;
 const PF_X = 0x1;
 const PF_W = 0x2;
 const PF_R = 0x4;
+const PAGE_MASK = 0xFFF;
+const HUGE_PAGE_SIZE = 0x200000;
+const HUGE_PAGE_MASK = HUGE_PAGE_SIZE - 1;
+// start.s only identity maps the first 4 GiB before it jumps to the higher
+// half, so the whole image has to be placed below that line.
+const BOOT_IDENTITY_LIMIT: u64 = 0x100000000;
 
 pub fn load_elf(data: []const u8) ?KernelEntry {
     if (data.len < @sizeOf(Elf64Header)) {
@@ -65,9 +72,8 @@ pub fn load_elf(data: []const u8) ?KernelEntry {
         return null;
     }
 
-    const bs = efi.systemtable.boot_services;
-    var kernel_physical: u64 = 0;
-    var kernel_virtual: u64 = 0;
+    var image_start: u64 = std.math.maxInt(u64);
+    var image_end: u64 = 0;
 
     for (0..header.phnum) |i| {
         const ph_offset = header.phoff + i * header.phentsize;
@@ -80,29 +86,49 @@ pub fn load_elf(data: []const u8) ?KernelEntry {
         if (ph.type != PT_LOAD) {
             continue;
         }
-
-        const pages = (ph.memsz + 0xFFF) / 0x1000;
-        const physical_addr = bs.allocate_pages(efi.AllocateType.AllocateAnyPages, efi.MemoryType.LoaderCode, pages) orelse {
+        if (ph.offset + ph.filesz > data.len) {
             return null;
-        };
+        }
+
+        image_start = @min(image_start, ph.vaddr & ~@as(u64, PAGE_MASK));
+        image_end = @max(image_end, ph.vaddr + ph.memsz);
+    }
 
-        if (i == 0) {
-            kernel_physical = physical_addr;
-            kernel_virtual = ph.vaddr;
+    if (image_end <= image_start or header.entry < image_start or header.entry >= image_end) {
+        return null;
+    }
+
+    const image_size = (image_end - image_start + PAGE_MASK) & ~@as(u64, PAGE_MASK);
+    const bs = efi.systemtable.boot_services;
+    var allocation_ptr: [*]align(0x1000) u8 = @ptrFromInt(BOOT_IDENTITY_LIMIT - 1);
+    if (bs.allocatePages(efi.AllocateType.AllocateMaxAddress, efi.MemoryType.LoaderCode, (image_size + HUGE_PAGE_SIZE) / 0x1000, &allocation_ptr) != efi.Status.Success) {
+        return null;
+    }
+    const allocation = @intFromPtr(allocation_ptr);
+
+    const huge_offset = image_start & HUGE_PAGE_MASK;
+    var kernel_physical = (allocation & ~@as(u64, HUGE_PAGE_MASK)) + huge_offset;
+    if (kernel_physical < allocation) {
+        kernel_physical += HUGE_PAGE_SIZE;
+    }
+    @memset(@ptrCast([*]u8, @as(*anyopaque, @ptrFromInt(kernel_physical)))[0..image_size], 0);
+
+    for (0..header.phnum) |i| {
+        const ph_offset = header.phoff + i * header.phentsize;
+        const ph = @ptrCast(*const Elf64ProgramHeader, data.ptr + ph_offset);
+        
+        if (ph.type != PT_LOAD) {
+            continue;
         }
 
+        const physical_addr = kernel_physical + (ph.vaddr - image_start);
         const segment_data = data[ph.offset..ph.offset + ph.filesz];
         @memcpy(@ptrCast([*]u8, @as(*anyopaque, @ptrFromInt(physical_addr)))[0..segment_data.len], segment_data);
-
-        if (ph.memsz > ph.filesz) {
-            const zero_start = physical_addr + ph.filesz;
-            const zero_size = ph.memsz - ph.filesz;
-            @memset(@ptrCast([*]u8, @as(*anyopaque, @ptrFromInt(zero_start)))[0..zero_size], 0);
-        }
     }
 
     return KernelEntry{
         .physical = kernel_physical,
-        .virtual = kernel_virtual,
+        .virtual = image_start,
+        .entry = kernel_physical + (header.entry - image_start),
     };
 }
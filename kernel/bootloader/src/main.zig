@@ -60,7 +60,7 @@ pub fn main() !void {
     _ = conout.output_string(conout, "Exiting boot services...\n");
     _ = bs.exit_boot_services(efi.systemtable.image_handle, map_key);
 
-    const kernel_start: *const fn (*handoff.HandoffData) callconv(.C) noreturn = @ptrCast(kernel_entry.virtual);
+    const kernel_start: *const fn (*handoff.HandoffData) callconv(.C) noreturn = @ptrCast(kernel_entry.entry);
     kernel_start(&handoff_data);
 }
 
@@ -0,0 +1,59 @@
+use super::tables::ACPITableHeader;
+use alloc::vec::Vec;
+
+const ENTRY_LOCAL_APIC: u8 = 0;
+const LOCAL_APIC_ENABLED: u32 = 1 << 0;
+const LOCAL_APIC_ONLINE_CAPABLE: u32 = 1 << 1;
+
+#[repr(C, packed)]
+pub struct MADT {
+    pub header: ACPITableHeader,
+    pub local_apic_address: u32,
+    pub flags: u32,
+}
+
+#[repr(C, packed)]
+struct EntryHeader {
+    entry_type: u8,
+    length: u8,
+}
+
+#[repr(C, packed)]
+struct LocalApicEntry {
+    header: EntryHeader,
+    acpi_processor_id: u8,
+    apic_id: u8,
+    flags: u32,
+}
+
+pub fn parse_madt() -> Option<&'static MADT> {
+    let addr = super::find_table(b"APIC")?;
+    unsafe { Some(&*(crate::memory::paging::phys_to_virt(addr) as *const MADT)) }
+}
+
+pub fn local_apic_ids() -> Vec<u32> {
+    let mut ids = Vec::new();
+    let madt = match parse_madt() {
+        Some(madt) => madt,
+        None => return ids,
+    };
+
+    let base = madt as *const MADT as u64;
+    let end = base + madt.header.length as u64;
+    let mut addr = base + core::mem::size_of::<MADT>() as u64;
+    while addr + core::mem::size_of::<EntryHeader>() as u64 <= end {
+        let header = unsafe { core::ptr::read_unaligned(addr as *const EntryHeader) };
+        if header.length < 2 || addr + header.length as u64 > end {
+            break;
+        }
+
+        if header.entry_type == ENTRY_LOCAL_APIC && header.length as usize >= core::mem::size_of::<LocalApicEntry>() {
+            let entry = unsafe { core::ptr::read_unaligned(addr as *const LocalApicEntry) };
+            if entry.flags & (LOCAL_APIC_ENABLED | LOCAL_APIC_ONLINE_CAPABLE) != 0 {
+                ids.push(entry.apic_id as u32);
+            }
+        }
+        addr += header.length as u64;
+    }
+    ids
+}
@@ -1,33 +1,103 @@
 use crate::vga;
+use core::sync::atomic::{AtomicU64, Ordering};
 
+pub mod madt;
 pub mod rsdp;
 pub mod tables;
 
-static mut ACPI_RSDP: u64 = 0;
+const RSDP_V1_LENGTH: u64 = 20;
+const FADT_FACS_OFFSET: u64 = 36;
+const FADT_DSDT_OFFSET: u64 = 40;
+
+static ACPI_RSDP: AtomicU64 = AtomicU64::new(0);
 
 pub fn init(rsdp_addr: u64) {
-    unsafe {
-        ACPI_RSDP = rsdp_addr;
-        vga::print!("ACPI RSDP at: 0x{:x}\n", rsdp_addr);
+    ACPI_RSDP.store(rsdp_addr, Ordering::Release);
+    vga::print!("ACPI RSDP at: 0x{:x}\n", rsdp_addr);
+    
+    if let Some(rsdp) = rsdp::parse_rsdp(rsdp_addr) {
+        vga::print!("ACPI RSDP signature: {}\n", rsdp.signature);
+        vga::print!("ACPI revision: {}\n", rsdp.revision);
         
-        if let Some(rsdp) = rsdp::parse_rsdp(rsdp_addr) {
-            vga::print!("ACPI RSDP signature: {}\n", rsdp.signature);
-            vga::print!("ACPI revision: {}\n", rsdp.revision);
-            
-            if let Some(rsdt) = tables::parse_rsdt(rsdp.rsdt_address) {
-                vga::print!("ACPI RSDT parsed successfully\n");
-                parse_acpi_tables(rsdt);
+        if let Some(rsdt) = tables::parse_rsdt(rsdp.rsdt_address) {
+            vga::print!("ACPI RSDT parsed successfully\n");
+            parse_acpi_tables(rsdt);
+        }
+    }
+}
+
+pub fn reserve_tables(rsdp_addr: u64, mut reserve: impl FnMut(u64, u64)) {
+    if rsdp_addr == 0 {
+        return;
+    }
+    let Some(rsdp) = rsdp::parse_rsdp(rsdp_addr) else {
+        return;
+    };
+
+    if rsdp.revision >= 2 {
+        reserve(rsdp_addr, rsdp.length as u64);
+        if rsdp.xsdt_address != 0 {
+            reserve_table(rsdp.xsdt_address, &mut reserve);
+        }
+    } else {
+        reserve(rsdp_addr, RSDP_V1_LENGTH);
+    }
+
+    let Some(rsdt) = tables::parse_rsdt(rsdp.rsdt_address) else {
+        return;
+    };
+    reserve(rsdp.rsdt_address as u64, rsdt.header.length as u64);
+
+    for i in 0..rsdt.entry_count() {
+        let address = rsdt.entry(i);
+        let Some(header) = reserve_table(address, &mut reserve) else {
+            continue;
+        };
+        if &header.signature == b"FACP" && header.length as u64 >= FADT_DSDT_OFFSET + 4 {
+            let facs = unsafe { read_physical_u32(address + FADT_FACS_OFFSET) } as u64;
+            let dsdt = unsafe { read_physical_u32(address + FADT_DSDT_OFFSET) } as u64;
+            if facs != 0 {
+                // The FACS has no checksum, only a signature and length.
+                reserve(facs, unsafe { read_physical_u32(facs + 4) } as u64);
+            }
+            if dsdt != 0 {
+                reserve_table(dsdt, &mut reserve);
             }
         }
     }
 }
 
+fn reserve_table(address: u64, reserve: &mut impl FnMut(u64, u64)) -> Option<&'static tables::ACPITableHeader> {
+    let header = tables::parse_table_header(address)?;
+    reserve(address, header.length as u64);
+    Some(header)
+}
+
+unsafe fn read_physical_u32(address: u64) -> u32 {
+    core::ptr::read_unaligned(crate::memory::paging::phys_to_virt(address) as *const u32)
+}
+
+pub fn find_table(signature: &[u8; 4]) -> Option<u64> {
+    let rsdp = rsdp::parse_rsdp(ACPI_RSDP.load(Ordering::Acquire))?;
+    let rsdt = tables::parse_rsdt(rsdp.rsdt_address)?;
+    
+    for i in 0..rsdt.entry_count() {
+        let entry_addr = rsdt.entry(i);
+        if let Some(header) = tables::parse_table_header(entry_addr) {
+            if &header.signature == signature {
+                return Some(entry_addr);
+            }
+        }
+    }
+    None
+}
+
 fn parse_acpi_tables(rsdt: &tables::RSDT) {
-    for i in 0..rsdt.entry_count {
-        let entry_addr = rsdt.entries[i as usize];
+    for i in 0..rsdt.entry_count() {
+        let entry_addr = rsdt.entry(i);
         if let Some(header) = tables::parse_table_header(entry_addr) {
             match &header.signature {
-                b"MADT" => {
+                b"APIC" => {
                     vga::print!("Found MADT table\n");
                 }
                 b"FADT" => {
@@ -36,6 +106,9 @@ fn parse_acpi_tables(rsdt: &tables::RSDT) {
                 b"MCFG" => {
                     vga::print!("Found MCFG table\n");
                 }
+                b"HPET" => {
+                    vga::print!("Found HPET table\n");
+                }
                 _ => {}
             }
         }
@@ -13,6 +13,7 @@ pub struct RSDP {
 
 pub fn parse_rsdp(addr: u64) -> Option<&'static RSDP> {
     unsafe {
+        let addr = crate::memory::paging::phys_to_virt(addr);
         let rsdp = &*(addr as *const RSDP);
         
         if &rsdp.signature != b"RSD PTR " {
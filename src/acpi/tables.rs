@@ -17,8 +17,22 @@ pub struct RSDT {
     pub entries: [u32; 0],
 }
 
+impl RSDT {
+    pub fn entry_count(&self) -> usize {
+        (self.header.length as usize - core::mem::size_of::<ACPITableHeader>()) / 4
+    }
+
+    pub fn entry(&self, index: usize) -> u64 {
+        unsafe {
+            let entries = core::ptr::addr_of!(self.entries) as *const u32;
+            core::ptr::read_unaligned(entries.add(index)) as u64
+        }
+    }
+}
+
 pub fn parse_table_header(addr: u64) -> Option<&'static ACPITableHeader> {
     unsafe {
+        let addr = crate::memory::paging::phys_to_virt(addr);
         let header = &*(addr as *const ACPITableHeader);
         
         let mut sum: u8 = 0;
@@ -37,7 +51,7 @@ pub fn parse_table_header(addr: u64) -> Option<&'static ACPITableHeader> {
 
 pub fn parse_rsdt(rsdt_addr: u32) -> Option<&'static RSDT> {
     unsafe {
-        let rsdt = &*(rsdt_addr as *const RSDT);
+        let rsdt = &*(crate::memory::paging::phys_to_virt(rsdt_addr as u64) as *const RSDT);
         
         if &rsdt.header.signature != b"RSDT" {
             return None;
@@ -1,3 +1,4 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod gdb;
@@ -11,7 +12,7 @@ pub struct Debugger {
 }
 
 impl Debugger {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             gdb_enabled: false,
             breakpoints: [None; 32],
@@ -60,10 +61,8 @@ impl Debugger {
     }
 }
 
-pub static mut DEBUGGER: Debugger = Debugger::new();
+pub static DEBUGGER: SpinLock<Debugger> = SpinLock::new(Debugger::new());
 
 pub fn init() {
-    unsafe {
-        DEBUGGER.init();
-    }
+    DEBUGGER.lock().init();
 }
@@ -1,3 +1,4 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod profiling;
@@ -13,7 +14,7 @@ pub struct AdvancedDebugging {
 }
 
 impl AdvancedDebugging {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             profiling_enabled: false,
             tracing_enabled: false,
@@ -39,10 +40,8 @@ impl AdvancedDebugging {
     }
 }
 
-pub static mut ADVANCED_DEBUGGING: AdvancedDebugging = AdvancedDebugging::new();
+pub static ADVANCED_DEBUGGING: SpinLock<AdvancedDebugging> = SpinLock::new(AdvancedDebugging::new());
 
 pub fn init() {
-    unsafe {
-        ADVANCED_DEBUGGING.init();
-    }
+    ADVANCED_DEBUGGING.lock().init();
 }
@@ -31,6 +31,7 @@ pub struct FAT32BootSector {
     pub file_system_type: [u8; 8],
 }
 
+#[derive(Clone, Copy)]
 #[repr(C, packed)]
 pub struct FAT32DirectoryEntry {
     pub name: [u8; 11],
@@ -47,6 +48,23 @@ pub struct FAT32DirectoryEntry {
     pub file_size: u32,
 }
 
+const ATTRIBUTE_VOLUME_ID: u8 = 0x08;
+const ATTRIBUTE_DIRECTORY: u8 = 0x10;
+const ATTRIBUTE_LONG_NAME: u8 = 0x0F;
+const ENTRY_END: u8 = 0x00;
+const ENTRY_DELETED: u8 = 0xE5;
+const END_OF_CHAIN: u32 = 0x0FFFFFF8;
+
+impl FAT32DirectoryEntry {
+    pub fn first_cluster(&self) -> u32 {
+        (self.first_cluster_high as u32) << 16 | self.first_cluster_low as u32
+    }
+    
+    pub fn is_directory(&self) -> bool {
+        self.attributes & ATTRIBUTE_DIRECTORY != 0
+    }
+}
+
 pub struct FAT32FileSystem {
     boot_sector: FAT32BootSector,
     fat_start: u64,
@@ -107,24 +125,100 @@ impl FAT32FileSystem {
         (cluster - 2) as u64 * self.sectors_per_cluster as u64 + self.data_start / self.bytes_per_sector as u64
     }
     
-    pub fn read_file(&self, device_id: usize, cluster: u32, size: u32, buffer: &mut [u8]) -> usize {
+    pub fn lookup(&self, device_id: usize, path: &str) -> Option<FAT32DirectoryEntry> {
+        let mut directory = self.boot_sector.root_cluster;
+        let mut entry: Option<FAT32DirectoryEntry> = None;
+        
+        for component in path.split('/').filter(|component| !component.is_empty()) {
+            if let Some(parent) = entry {
+                if !parent.is_directory() {
+                    return None;
+                }
+                directory = parent.first_cluster();
+            }
+            entry = Some(self.find_entry(device_id, directory, &short_name(component)?)?);
+        }
+        
+        entry
+    }
+    
+    fn find_entry(&self, device_id: usize, directory: u32, name: &[u8; 11]) -> Option<FAT32DirectoryEntry> {
+        let entry_size = core::mem::size_of::<FAT32DirectoryEntry>();
+        let mut current_cluster = directory;
+        
+        while current_cluster >= 2 && current_cluster < END_OF_CHAIN {
+            let first_sector = self.cluster_to_sector(current_cluster);
+            
+            for sector_index in 0..self.sectors_per_cluster as u64 {
+                let mut sector_data = [0u8; 512];
+                if !self.read_sector(device_id, first_sector + sector_index, &mut sector_data) {
+                    return None;
+                }
+                
+                for offset in (0..512).step_by(entry_size) {
+                    let entry = unsafe {
+                        core::ptr::read_unaligned(sector_data[offset..].as_ptr() as *const FAT32DirectoryEntry)
+                    };
+                    
+                    match entry.name[0] {
+                        ENTRY_END => return None,
+                        ENTRY_DELETED => continue,
+                        _ => {}
+                    }
+                    
+                    if entry.attributes & ATTRIBUTE_LONG_NAME == ATTRIBUTE_LONG_NAME
+                        || entry.attributes & ATTRIBUTE_VOLUME_ID != 0
+                    {
+                        continue;
+                    }
+                    
+                    if &entry.name == name {
+                        return Some(entry);
+                    }
+                }
+            }
+            
+            current_cluster = self.get_next_cluster(device_id, current_cluster);
+        }
+        
+        None
+    }
+    
+    pub fn read_file(&self, device_id: usize, cluster: u32, offset: u32, size: u32, buffer: &mut [u8]) -> usize {
+        let bytes_per_sector = self.bytes_per_sector as usize;
+        let cluster_size = self.sectors_per_cluster * self.bytes_per_sector;
+        let to_read = core::cmp::min(size as usize, buffer.len());
         let mut current_cluster = cluster;
+        let mut skip = offset;
         let mut bytes_read = 0;
-        let mut buffer_offset = 0;
         
-        while current_cluster < 0xFFFFFFF8 && bytes_read < size as usize {
-            let sector = self.cluster_to_sector(current_cluster);
-            let mut sector_data = [0u8; 512];
+        while skip >= cluster_size && current_cluster >= 2 && current_cluster < END_OF_CHAIN {
+            current_cluster = self.get_next_cluster(device_id, current_cluster);
+            skip -= cluster_size;
+        }
+        
+        while current_cluster >= 2 && current_cluster < END_OF_CHAIN && bytes_read < to_read {
+            let first_sector = self.cluster_to_sector(current_cluster);
+            let first_index = skip / self.bytes_per_sector;
+            let mut start = (skip % self.bytes_per_sector) as usize;
+            skip = 0;
             
-            if self.read_sector(device_id, sector, &mut sector_data) {
-                let bytes_to_copy = core::cmp::min(512, size as usize - bytes_read);
-                let bytes_to_copy = core::cmp::min(bytes_to_copy, buffer.len() - buffer_offset);
+            for sector_index in first_index..self.sectors_per_cluster {
+                if bytes_read >= to_read {
+                    break;
+                }
                 
-                buffer[buffer_offset..buffer_offset + bytes_to_copy]
-                    .copy_from_slice(&sector_data[0..bytes_to_copy]);
+                let mut sector_data = [0u8; 512];
+                if !self.read_sector(device_id, first_sector + sector_index as u64, &mut sector_data) {
+                    return bytes_read;
+                }
+                
+                let bytes_to_copy = core::cmp::min(bytes_per_sector - start, to_read - bytes_read);
+                buffer[bytes_read..bytes_read + bytes_to_copy]
+                    .copy_from_slice(&sector_data[start..start + bytes_to_copy]);
                 
-                buffer_offset += bytes_to_copy;
                 bytes_read += bytes_to_copy;
+                start = 0;
             }
             
             current_cluster = self.get_next_cluster(device_id, current_cluster);
@@ -152,3 +246,23 @@ impl FAT32FileSystem {
         }
     }
 }
+
+fn short_name(component: &str) -> Option<[u8; 11]> {
+    let (base, extension) = match component.rfind('.') {
+        Some(index) if index > 0 => (&component[..index], &component[index + 1..]),
+        _ => (component, ""),
+    };
+    
+    if base.is_empty() || base.len() > 8 || extension.len() > 3 {
+        return None;
+    }
+    
+    let mut name = [b' '; 11];
+    for (i, byte) in base.bytes().enumerate() {
+        name[i] = byte.to_ascii_uppercase();
+    }
+    for (i, byte) in extension.bytes().enumerate() {
+        name[8 + i] = byte.to_ascii_uppercase();
+    }
+    Some(name)
+}
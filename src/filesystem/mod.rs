@@ -1,4 +1,8 @@
+use crate::smp::synchronization::Mutex;
 use crate::vga;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 
 pub mod fat32;
 pub mod vfs;
@@ -10,7 +14,7 @@ pub struct FileSystem {
 }
 
 impl FileSystem {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             vfs: VFS::new(),
         }
@@ -40,6 +44,18 @@ impl FileSystem {
         self.vfs.write(file_id, data)
     }
     
+    pub fn seek(&mut self, file_id: usize, position: u32) -> bool {
+        self.vfs.seek(file_id, position)
+    }
+    
+    pub fn size(&self, file_id: usize) -> Option<u32> {
+        self.vfs.size(file_id)
+    }
+    
+    pub fn position(&self, file_id: usize) -> Option<u32> {
+        self.vfs.position(file_id)
+    }
+    
     pub fn close(&mut self, file_id: usize) {
         self.vfs.close(file_id);
     }
@@ -49,40 +65,56 @@ impl FileSystem {
     }
 }
 
-pub static mut FILESYSTEM: FileSystem = FileSystem::new();
+pub static FILESYSTEM: Mutex<FileSystem> = Mutex::new(FileSystem::new());
 
 pub fn init() {
-    unsafe {
-        FILESYSTEM.init();
-    }
+    FILESYSTEM.lock().init();
 }
 
 pub fn mount(device_id: usize, mount_point: &str) -> bool {
-    unsafe {
-        FILESYSTEM.mount(device_id, mount_point)
-    }
+    FILESYSTEM.lock().mount(device_id, mount_point)
 }
 
 pub fn open_file(path: &str) -> Option<usize> {
-    unsafe {
-        FILESYSTEM.open(path)
-    }
+    FILESYSTEM.lock().open(path)
 }
 
 pub fn read_file(file_id: usize, buffer: &mut [u8]) -> usize {
-    unsafe {
-        FILESYSTEM.read(file_id, buffer)
-    }
+    FILESYSTEM.lock().read(file_id, buffer)
 }
 
 pub fn write_file(file_id: usize, data: &[u8]) -> usize {
-    unsafe {
-        FILESYSTEM.write(file_id, data)
-    }
+    FILESYSTEM.lock().write(file_id, data)
 }
 
 pub fn close_file(file_id: usize) {
-    unsafe {
-        FILESYSTEM.close(file_id);
+    FILESYSTEM.lock().close(file_id);
+}
+
+pub fn seek_file(file_id: usize, position: u32) -> bool {
+    FILESYSTEM.lock().seek(file_id, position)
+}
+
+pub fn file_size(file_id: usize) -> Option<u32> {
+    FILESYSTEM.lock().size(file_id)
+}
+
+pub fn file_position(file_id: usize) -> Option<u32> {
+    FILESYSTEM.lock().position(file_id)
+}
+
+pub fn read_entire_file(path: &str) -> Option<Vec<u8>> {
+    let mut filesystem = FILESYSTEM.lock();
+    let file_id = filesystem.open(path)?;
+    let size = filesystem.size(file_id).unwrap_or(0) as usize;
+    
+    let mut data = vec![0u8; size];
+    let bytes_read = filesystem.read(file_id, &mut data);
+    filesystem.close(file_id);
+    
+    if bytes_read != size {
+        vga::print!("Short read of {}: {} of {} bytes\n", path, bytes_read, size);
+        return None;
     }
+    Some(data)
 }
@@ -1,4 +1,6 @@
 use crate::vga;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use crate::filesystem::fat32::FAT32FileSystem;
 
 pub struct File {
@@ -16,6 +18,9 @@ pub struct MountPoint {
     pub filesystem: FAT32FileSystem,
 }
 
+const NO_FILE: Option<File> = None;
+const NO_MOUNT_POINT: Option<MountPoint> = None;
+
 pub struct VFS {
     files: [Option<File>; 32],
     file_count: usize,
@@ -25,12 +30,12 @@ pub struct VFS {
 }
 
 impl VFS {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
-            files: [None; 32],
+            files: [NO_FILE; 32],
             file_count: 0,
             next_file_id: 1,
-            mount_points: [None; 8],
+            mount_points: [NO_MOUNT_POINT; 8],
             mount_count: 0,
         }
     }
@@ -78,13 +83,21 @@ impl VFS {
         let mount_point = self.find_mount_point(path)?;
         let relative_path = &path[mount_point.path.len()..];
         
+        let entry = match mount_point.filesystem.lookup(mount_point.device_id, relative_path) {
+            Some(entry) if !entry.is_directory() => entry,
+            _ => {
+                vga::print!("File not found: {}\n", path);
+                return None;
+            }
+        };
+        
         let file = File {
             id: self.next_file_id,
             name: relative_path.to_string(),
-            size: 0,
+            size: entry.file_size,
             position: 0,
             device_id: mount_point.device_id,
-            cluster: 2,
+            cluster: entry.first_cluster(),
         };
         
         for i in 0..32 {
@@ -104,10 +117,14 @@ impl VFS {
         for i in 0..32 {
             if let Some(file) = &mut self.files[i] {
                 if file.id == file_id {
-                    let mount_point = self.get_mount_point(file.device_id)?;
+                    let mount_point = match Self::mount_point_for(&self.mount_points, file.device_id) {
+                        Some(mount_point) => mount_point,
+                        None => return 0,
+                    };
                     let bytes_read = mount_point.filesystem.read_file(
                         file.device_id,
                         file.cluster,
+                        file.position,
                         file.size - file.position,
                         buffer
                     );
@@ -119,6 +136,26 @@ impl VFS {
         0
     }
     
+    pub fn seek(&mut self, file_id: usize, position: u32) -> bool {
+        for i in 0..32 {
+            if let Some(file) = &mut self.files[i] {
+                if file.id == file_id {
+                    file.position = position.min(file.size);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+    
+    pub fn size(&self, file_id: usize) -> Option<u32> {
+        self.files.iter().flatten().find(|file| file.id == file_id).map(|file| file.size)
+    }
+    
+    pub fn position(&self, file_id: usize) -> Option<u32> {
+        self.files.iter().flatten().find(|file| file.id == file_id).map(|file| file.position)
+    }
+    
     pub fn write(&mut self, file_id: usize, data: &[u8]) -> usize {
         vga::print!("File write not implemented\n");
         0
@@ -155,14 +192,7 @@ impl VFS {
         None
     }
     
-    fn get_mount_point(&mut self, device_id: usize) -> Option<&mut MountPoint> {
-        for mount in &mut self.mount_points {
-            if let Some(mount) = mount {
-                if mount.device_id == device_id {
-                    return Some(mount);
-                }
-            }
-        }
-        None
+    fn mount_point_for(mount_points: &[Option<MountPoint>], device_id: usize) -> Option<&MountPoint> {
+        mount_points.iter().flatten().find(|mount| mount.device_id == device_id)
     }
 }
@@ -0,0 +1,131 @@
+use crate::vga;
+use core::arch::asm;
+
+pub mod tss;
+
+use tss::TaskStateSegment;
+
+pub const KERNEL_CODE_SELECTOR: u16 = 0x08;
+pub const KERNEL_DATA_SELECTOR: u16 = 0x10;
+pub const USER_DATA_SELECTOR: u16 = 0x18 | 3;
+pub const USER_CODE_SELECTOR: u16 = 0x20 | 3;
+pub const TSS_SELECTOR: u16 = 0x28;
+
+pub const DOUBLE_FAULT_IST: u8 = 1;
+pub const NMI_IST: u8 = 2;
+pub const MACHINE_CHECK_IST: u8 = 3;
+const IST_STACK_COUNT: usize = 3;
+const IST_STACK_SIZE: usize = 0x4000;
+
+const MAX_CPUS: usize = crate::smp::MAX_CPUS;
+const GDT_ENTRIES: usize = 7;
+
+const KERNEL_CODE: u64 = 0x00AF_9A00_0000_FFFF;
+const KERNEL_DATA: u64 = 0x00CF_9200_0000_FFFF;
+const USER_DATA: u64 = 0x00CF_F200_0000_FFFF;
+const USER_CODE: u64 = 0x00AF_FA00_0000_FFFF;
+
+#[repr(C, packed)]
+struct GDTPointer {
+    limit: u16,
+    base: u64,
+}
+
+#[repr(C)]
+pub struct CpuLocal {
+    pub kernel_rsp: u64,
+    pub user_rsp: u64,
+    pub cpu_id: u64,
+}
+
+#[repr(C, align(16))]
+struct CpuTables {
+    gdt: [u64; GDT_ENTRIES],
+    tss: TaskStateSegment,
+    local: CpuLocal,
+}
+
+const EMPTY_TABLES: CpuTables = CpuTables {
+    gdt: [0; GDT_ENTRIES],
+    tss: TaskStateSegment::new(),
+    local: CpuLocal {
+        kernel_rsp: 0,
+        user_rsp: 0,
+        cpu_id: 0,
+    },
+};
+
+static mut CPU_TABLES: [CpuTables; MAX_CPUS] = [EMPTY_TABLES; MAX_CPUS];
+
+pub fn init() {
+    vga::print!("Setting up GDT and TSS...\n");
+    init_cpu(0);
+    vga::print!("GDT loaded with ring 3 segments\n");
+}
+
+pub fn init_cpu(cpu: u32) {
+    unsafe {
+        let tables = &mut CPU_TABLES[cpu as usize];
+        tables.local.cpu_id = cpu as u64;
+
+        for index in 1..=IST_STACK_COUNT {
+            let stack = allocate_stack(IST_STACK_SIZE);
+            tables.tss.set_interrupt_stack(index, stack);
+        }
+
+        let (tss_low, tss_high) = tables.tss.descriptor();
+        tables.gdt = [0, KERNEL_CODE, KERNEL_DATA, USER_DATA, USER_CODE, tss_low, tss_high];
+
+        load(&tables.gdt);
+    }
+}
+
+pub fn set_kernel_stack(cpu: u32, stack_top: u64) {
+    unsafe {
+        let tables = &mut CPU_TABLES[cpu as usize];
+        tables.tss.set_kernel_stack(stack_top);
+        tables.local.kernel_rsp = stack_top;
+    }
+}
+
+pub fn cpu_local(cpu: u32) -> u64 {
+    unsafe { &CPU_TABLES[cpu as usize].local as *const CpuLocal as u64 }
+}
+
+fn allocate_stack(size: usize) -> u64 {
+    let stack = crate::vm::protection::allocate_stack(size);
+
+    if stack.is_null() {
+        panic!("Failed to allocate interrupt stack");
+    }
+
+    (stack as u64 + size as u64) & !0xF
+}
+
+unsafe fn load(gdt: &[u64; GDT_ENTRIES]) {
+    let gdt_ptr = GDTPointer {
+        limit: (core::mem::size_of::<[u64; GDT_ENTRIES]>() - 1) as u16,
+        base: gdt.as_ptr() as u64,
+    };
+
+    asm!(
+        "lgdt [{ptr}]",
+        "push {code}",
+        "lea {tmp}, [rip + 2f]",
+        "push {tmp}",
+        "retfq",
+        "2:",
+        "mov ds, {data:x}",
+        "mov es, {data:x}",
+        "mov ss, {data:x}",
+        "xor {tmp:e}, {tmp:e}",
+        "mov fs, {tmp:x}",
+        "mov gs, {tmp:x}",
+        "ltr {tss:x}",
+        ptr = in(reg) &gdt_ptr,
+        code = in(reg) KERNEL_CODE_SELECTOR as u64,
+        data = in(reg) KERNEL_DATA_SELECTOR as u64,
+        tss = in(reg) TSS_SELECTOR as u64,
+        tmp = out(reg) _,
+    );
+}
@@ -0,0 +1,46 @@
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct TaskStateSegment {
+    reserved0: u32,
+    pub privilege_stacks: [u64; 3],
+    reserved1: u64,
+    pub interrupt_stacks: [u64; 7],
+    reserved2: u64,
+    reserved3: u16,
+    pub iomap_base: u16,
+}
+
+impl TaskStateSegment {
+    pub const fn new() -> Self {
+        Self {
+            reserved0: 0,
+            privilege_stacks: [0; 3],
+            reserved1: 0,
+            interrupt_stacks: [0; 7],
+            reserved2: 0,
+            reserved3: 0,
+            iomap_base: core::mem::size_of::<TaskStateSegment>() as u16,
+        }
+    }
+
+    pub fn set_kernel_stack(&mut self, stack_top: u64) {
+        self.privilege_stacks[0] = stack_top;
+    }
+
+    pub fn set_interrupt_stack(&mut self, index: usize, stack_top: u64) {
+        self.interrupt_stacks[index - 1] = stack_top;
+    }
+
+    pub fn descriptor(&self) -> (u64, u64) {
+        let base = self as *const _ as u64;
+        let limit = (core::mem::size_of::<TaskStateSegment>() - 1) as u64;
+
+        let low = (limit & 0xFFFF)
+            | (base & 0xFF_FFFF) << 16
+            | 0x89 << 40
+            | ((limit >> 16) & 0xF) << 48
+            | ((base >> 24) & 0xFF) << 56;
+        let high = base >> 32;
+        (low, high)
+    }
+}
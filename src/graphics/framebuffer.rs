@@ -15,7 +15,7 @@ impl Framebuffer {
         let bytes_per_scanline = width * bytes_per_pixel;
         
         Self {
-            address,
+            address: crate::memory::paging::phys_to_virt(address),
             width,
             height,
             bpp,
@@ -1,3 +1,4 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod vesa;
@@ -16,7 +17,7 @@ pub struct GraphicsManager {
 }
 
 impl GraphicsManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             framebuffer: None,
             current_mode: None,
@@ -100,34 +101,24 @@ impl GraphicsManager {
     }
 }
 
-pub static mut GRAPHICS: GraphicsManager = GraphicsManager::new();
+pub static GRAPHICS: SpinLock<GraphicsManager> = SpinLock::new(GraphicsManager::new());
 
 pub fn init() {
-    unsafe {
-        GRAPHICS.init();
-    }
+    GRAPHICS.lock().init();
 }
 
 pub fn draw_pixel(x: u32, y: u32, color: u32) {
-    unsafe {
-        GRAPHICS.draw_pixel(x, y, color);
-    }
+    GRAPHICS.lock().draw_pixel(x, y, color);
 }
 
 pub fn draw_rectangle(x: u32, y: u32, width: u32, height: u32, color: u32) {
-    unsafe {
-        GRAPHICS.draw_rectangle(x, y, width, height, color);
-    }
+    GRAPHICS.lock().draw_rectangle(x, y, width, height, color);
 }
 
 pub fn draw_string(x: u32, y: u32, text: &str, color: u32, bg_color: u32) {
-    unsafe {
-        GRAPHICS.draw_string(x, y, text, color, bg_color);
-    }
+    GRAPHICS.lock().draw_string(x, y, text, color, bg_color);
 }
 
 pub fn clear_screen(color: u32) {
-    unsafe {
-        GRAPHICS.clear_screen(color);
-    }
+    GRAPHICS.lock().clear_screen(color);
 }
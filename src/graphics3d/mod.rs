@@ -1,3 +1,4 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod gpu;
@@ -14,7 +15,7 @@ pub struct Graphics3D {
 }
 
 impl Graphics3D {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             gpu_initialized: false,
             shader_support: false,
@@ -50,10 +51,8 @@ impl Graphics3D {
     }
 }
 
-pub static mut GRAPHICS_3D: Graphics3D = Graphics3D::new();
+pub static GRAPHICS_3D: SpinLock<Graphics3D> = SpinLock::new(Graphics3D::new());
 
 pub fn init() {
-    unsafe {
-        GRAPHICS_3D.init();
-    }
+    GRAPHICS_3D.lock().init();
 }
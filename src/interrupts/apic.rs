@@ -1,5 +1,6 @@
 use crate::vga;
 use core::arch::asm;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 const APIC_BASE_MSR: u32 = 0x1B;
 const APIC_ENABLE: u64 = 0x800;
@@ -11,17 +12,37 @@ const APIC_EOI: u32 = 0xB0;
 const APIC_SIVR: u32 = 0xF0;
 const APIC_ICR_LOW: u32 = 0x300;
 const APIC_ICR_HIGH: u32 = 0x310;
+const APIC_LVT_TIMER: u32 = 0x320;
+const APIC_TIMER_INITIAL_COUNT: u32 = 0x380;
+const APIC_TIMER_CURRENT_COUNT: u32 = 0x390;
+const APIC_TIMER_DIVIDE: u32 = 0x3E0;
 
-static mut LOCAL_APIC_BASE: u64 = 0;
+const ICR_DELIVERY_INIT: u32 = 0x500;
+const ICR_DELIVERY_STARTUP: u32 = 0x600;
+const ICR_LEVEL_ASSERT: u32 = 0x4000;
+const ICR_DELIVERY_PENDING: u32 = 1 << 12;
+
+const LVT_MASKED: u32 = 1 << 16;
+const IA32_TSC_DEADLINE_MSR: u32 = 0x6E0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimerMode {
+    OneShot = 0b00,
+    Periodic = 0b01,
+    TscDeadline = 0b10,
+}
+
+static LOCAL_APIC_BASE: AtomicU64 = AtomicU64::new(0);
 
 pub fn init() {
     vga::print!("Initializing Local APIC...\n");
     
     unsafe {
         let apic_base = read_msr(APIC_BASE_MSR);
-        LOCAL_APIC_BASE = apic_base & 0xFFFFF000;
+        let base = crate::memory::paging::phys_to_virt(apic_base & 0xFFFFF000);
+        LOCAL_APIC_BASE.store(base, Ordering::Release);
         
-        vga::print!("Local APIC base: 0x{:x}\n", LOCAL_APIC_BASE);
+        vga::print!("Local APIC base: 0x{:x}\n", base);
         
         enable_apic();
         setup_spurious_vector();
@@ -30,6 +51,13 @@ pub fn init() {
     }
 }
 
+pub fn init_cpu() {
+    unsafe {
+        enable_apic();
+        setup_spurious_vector();
+    }
+}
+
 unsafe fn enable_apic() {
     let apic_base = read_msr(APIC_BASE_MSR);
     write_msr(APIC_BASE_MSR, apic_base | APIC_ENABLE);
@@ -56,6 +84,53 @@ pub fn send_ipi(target_cpu: u8, vector: u8) {
     }
 }
 
+pub fn send_init(target_cpu: u8) {
+    send_command(target_cpu, ICR_DELIVERY_INIT | ICR_LEVEL_ASSERT);
+}
+
+pub fn send_startup(target_cpu: u8, page: u8) {
+    send_command(target_cpu, ICR_DELIVERY_STARTUP | ICR_LEVEL_ASSERT | page as u32);
+}
+
+fn send_command(target_cpu: u8, icr_low: u32) {
+    write_apic_register(APIC_ICR_HIGH, (target_cpu as u32) << 24);
+    write_apic_register(APIC_ICR_LOW, icr_low);
+    while read_apic_register(APIC_ICR_LOW) & ICR_DELIVERY_PENDING != 0 {
+        core::hint::spin_loop();
+    }
+}
+
+pub fn configure_timer(vector: u8, mode: TimerMode, masked: bool) {
+    let mut lvt = vector as u32 | ((mode as u32) << 17);
+    if masked {
+        lvt |= LVT_MASKED;
+    }
+    
+    write_apic_register(APIC_TIMER_DIVIDE, 0x3);
+    write_apic_register(APIC_LVT_TIMER, lvt);
+}
+
+pub fn set_timer_count(count: u32) {
+    write_apic_register(APIC_TIMER_INITIAL_COUNT, count);
+}
+
+pub fn timer_current_count() -> u32 {
+    read_apic_register(APIC_TIMER_CURRENT_COUNT)
+}
+
+pub fn set_tsc_deadline(deadline: u64) {
+    write_msr(IA32_TSC_DEADLINE_MSR, deadline);
+}
+
+pub fn stop_timer() {
+    write_apic_register(APIC_TIMER_INITIAL_COUNT, 0);
+    write_apic_register(APIC_LVT_TIMER, LVT_MASKED);
+}
+
+pub fn current_apic_id() -> u8 {
+    (read_apic_register(APIC_ID) >> 24) as u8
+}
+
 fn read_msr(msr: u32) -> u64 {
     let (low, high): (u32, u32);
     unsafe {
@@ -84,14 +159,14 @@ fn write_msr(msr: u32, value: u64) {
 
 fn read_apic_register(offset: u32) -> u32 {
     unsafe {
-        let addr = LOCAL_APIC_BASE + offset as u64;
+        let addr = LOCAL_APIC_BASE.load(Ordering::Acquire) + offset as u64;
         *(addr as *const u32)
     }
 }
 
 fn write_apic_register(offset: u32, value: u32) {
     unsafe {
-        let addr = LOCAL_APIC_BASE + offset as u64;
+        let addr = LOCAL_APIC_BASE.load(Ordering::Acquire) + offset as u64;
         *(addr as *mut u32) = value;
     }
 }
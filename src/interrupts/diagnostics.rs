@@ -0,0 +1,212 @@
+use crate::loader::USER_SPACE_END;
+use crate::memory::paging::PageMapper;
+use crate::scheduler::task::TaskContext;
+use crate::smp::MAX_CPUS;
+use crate::syscalls::user;
+use crate::vga;
+use crate::vm::cow::{FAULT_PRESENT, FAULT_WRITE};
+use crate::vm::demand_paging::{FAULT_INSTRUCTION, FAULT_PROTECTION_KEY, FAULT_RESERVED, FAULT_USER};
+use core::arch::asm;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub const PAGE_FAULT_VECTOR: u8 = 14;
+pub const MAX_STACK_FRAMES: usize = 16;
+
+const FAULT_SHADOW_STACK: u64 = 1 << 6;
+const FAULT_SGX: u64 = 1 << 15;
+
+const SELECTOR_EXTERNAL: u64 = 1 << 0;
+const SELECTOR_IDT: u64 = 1 << 1;
+const SELECTOR_LDT: u64 = 1 << 2;
+
+const NO_NMI: AtomicU64 = AtomicU64::new(0);
+static NMI_COUNTS: [AtomicU64; MAX_CPUS] = [NO_NMI; MAX_CPUS];
+static NMI_RIPS: [AtomicU64; MAX_CPUS] = [NO_NMI; MAX_CPUS];
+
+const EXCEPTION_NAMES: [&str; 21] = [
+    "Division by zero",
+    "Debug",
+    "Non-maskable interrupt",
+    "Breakpoint",
+    "Overflow",
+    "Bound range exceeded",
+    "Invalid opcode",
+    "Device not available",
+    "Double fault",
+    "Coprocessor segment overrun",
+    "Invalid TSS",
+    "Segment not present",
+    "Stack segment fault",
+    "General protection fault",
+    "Page fault",
+    "Reserved",
+    "x87 FPU error",
+    "Alignment check",
+    "Machine check",
+    "SIMD FPU exception",
+    "Virtualization exception",
+];
+
+pub fn exception_name(vector: u8) -> &'static str {
+    EXCEPTION_NAMES.get(vector as usize).copied().unwrap_or("Unknown exception")
+}
+
+pub fn report(vector: u8, context: &TaskContext, error_code: Option<u64>, fault_address: Option<u64>) {
+    let (task, pid) = crate::scheduler::SCHEDULER
+        .try_lock()
+        .map(|scheduler| (Some(scheduler.get_current_task_id()), scheduler.current_process_id()))
+        .unwrap_or((None, None));
+    let mode = if context.cs & 3 == 3 { "user" } else { "kernel" };
+    vga::print!("Exception {}: {} in {} mode on CPU {}, task {:?}, process {:?}\n",
+        vector, exception_name(vector), mode, crate::smp::current_cpu_id(), task, pid);
+
+    if let Some(error_code) = error_code {
+        vga::print!("  error code 0x{:x}", error_code);
+        decode_error(vector, error_code, fault_address.unwrap_or_else(read_cr2));
+        vga::print!("\n");
+    }
+    dump_registers(context, fault_address.unwrap_or_else(read_cr2));
+    backtrace(context);
+}
+
+pub fn record_nmi(context: &TaskContext) {
+    let cpu = crate::smp::current_cpu_id() as usize;
+    NMI_RIPS[cpu].store(context.rip, Ordering::Relaxed);
+    NMI_COUNTS[cpu].fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn print_nmi_stats() {
+    for cpu in 0..MAX_CPUS {
+        let count = NMI_COUNTS[cpu].load(Ordering::Relaxed);
+        if count != 0 {
+            vga::print!("NMI: CPU {} received {} (last at RIP 0x{:x})\n",
+                cpu, count, NMI_RIPS[cpu].load(Ordering::Relaxed));
+        }
+    }
+}
+
+pub fn read_cr2() -> u64 {
+    let value: u64;
+    unsafe {
+        asm!("mov {}, cr2", out(reg) value);
+    }
+    value
+}
+
+fn decode_error(vector: u8, error_code: u64, fault_address: u64) {
+    match vector {
+        PAGE_FAULT_VECTOR => {
+            let flags = [
+                (FAULT_PRESENT, "protection", "not-present"),
+                (FAULT_WRITE, "write", "read"),
+                (FAULT_USER, "user", "supervisor"),
+                (FAULT_RESERVED, "reserved-bit", ""),
+                (FAULT_INSTRUCTION, "instruction-fetch", ""),
+                (FAULT_PROTECTION_KEY, "protection-key", ""),
+                (FAULT_SHADOW_STACK, "shadow-stack", ""),
+                (FAULT_SGX, "sgx", ""),
+            ];
+            vga::print!(" [");
+            for (bit, set, clear) in flags {
+                let name = if error_code & bit != 0 { set } else { clear };
+                if !name.is_empty() {
+                    vga::print!(" {}", name);
+                }
+            }
+            vga::print!(" ] at 0x{:x}", fault_address);
+        }
+        10..=13 if error_code != 0 => {
+            let table = if error_code & SELECTOR_IDT != 0 {
+                "IDT"
+            } else if error_code & SELECTOR_LDT != 0 {
+                "LDT"
+            } else {
+                "GDT"
+            };
+            vga::print!(" [{} index {}{}]", table, error_code >> 3,
+                if error_code & SELECTOR_EXTERNAL != 0 { ", external" } else { "" });
+        }
+        _ => {}
+    }
+}
+
+fn dump_registers(context: &TaskContext, cr2: u64) {
+    let registers = [
+        ("RAX", context.rax), ("RBX", context.rbx), ("RCX", context.rcx), ("RDX", context.rdx),
+        ("RSI", context.rsi), ("RDI", context.rdi), ("RBP", context.rbp), ("RSP", context.rsp),
+        ("R8", context.r8), ("R9", context.r9), ("R10", context.r10), ("R11", context.r11),
+        ("R12", context.r12), ("R13", context.r13), ("R14", context.r14), ("R15", context.r15),
+        ("RIP", context.rip), ("CS", context.cs), ("RFLAGS", context.rflags), ("SS", context.ss),
+        ("CR0", read_cr0()), ("CR2", cr2), ("CR3", read_cr3()), ("CR4", read_cr4()),
+    ];
+    for line in registers.chunks(4) {
+        vga::print!(" ");
+        for (name, value) in line {
+            vga::print!(" {:>6}=0x{:016x}", name, value);
+        }
+        vga::print!("\n");
+    }
+}
+
+fn backtrace(context: &TaskContext) {
+    let user = context.cs & 3 == 3;
+    vga::print!("  Backtrace:\n");
+    vga::print!("    #0 0x{:016x}\n", { context.rip });
+
+    let mut frame = context.rbp;
+    for depth in 1..=MAX_STACK_FRAMES {
+        let (next, return_address) = match read_frame(frame, user) {
+            Some(words) => words,
+            None => break,
+        };
+        if return_address == 0 {
+            break;
+        }
+        vga::print!("    #{} 0x{:016x}\n", depth, return_address);
+        if next <= frame {
+            break;
+        }
+        frame = next;
+    }
+}
+
+fn read_frame(frame: u64, user: bool) -> Option<(u64, u64)> {
+    if frame == 0 || frame % 8 != 0 {
+        return None;
+    }
+    if user {
+        return user::read_value::<[u64; 2]>(frame).ok().map(|words| (words[0], words[1]));
+    }
+
+    let last = frame.checked_add(15)?;
+    let mapper = PageMapper::current();
+    if frame < USER_SPACE_END || mapper.translate(frame).is_none() || mapper.translate(last).is_none() {
+        return None;
+    }
+    let words = unsafe { core::ptr::read(frame as *const [u64; 2]) };
+    Some((words[0], words[1]))
+}
+
+fn read_cr0() -> u64 {
+    let value: u64;
+    unsafe {
+        asm!("mov {}, cr0", out(reg) value);
+    }
+    value
+}
+
+fn read_cr3() -> u64 {
+    let value: u64;
+    unsafe {
+        asm!("mov {}, cr3", out(reg) value);
+    }
+    value
+}
+
+fn read_cr4() -> u64 {
+    let value: u64;
+    unsafe {
+        asm!("mov {}, cr4", out(reg) value);
+    }
+    value
+}
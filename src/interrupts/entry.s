@@ -0,0 +1,146 @@
+.macro SAVE_CONTEXT
+    push rax
+    SAVE_CONTEXT_AFTER_RAX
+.endm
+
+.macro SAVE_CONTEXT_AFTER_RAX
+    push rbx
+    push rcx
+    push rdx
+    push rbp
+    push rsi
+    push rdi
+    push r8
+    push r9
+    push r10
+    push r11
+    push r12
+    push r13
+    push r14
+    push r15
+.endm
+
+.macro RESTORE_CONTEXT
+    pop r15
+    pop r14
+    pop r13
+    pop r12
+    pop r11
+    pop r10
+    pop r9
+    pop r8
+    pop rdi
+    pop rsi
+    pop rbp
+    pop rdx
+    pop rcx
+    pop rbx
+    pop rax
+.endm
+
+.macro CLEAR_AC
+    cmp byte ptr [rip + SMAP_ENABLED], 0
+    je 1f
+    clac
+1:
+.endm
+
+.macro CONTEXT_ENTRY name, handler
+.global \name
+\name:
+    CLEAR_AC
+    SAVE_CONTEXT
+    mov rdi, rsp
+    cld
+    call \handler
+    RESTORE_CONTEXT
+    iretq
+.endm
+
+.macro USER_RETURN_ENTRY name, handler
+.global \name
+\name:
+    CLEAR_AC
+    SAVE_CONTEXT
+    mov rdi, rsp
+    cld
+    call \handler
+    mov rdi, rsp
+    call interrupt_return
+    RESTORE_CONTEXT
+    iretq
+.endm
+
+.macro ERROR_CONTEXT_ENTRY name, handler
+.global \name
+\name:
+    CLEAR_AC
+    xchg rax, qword ptr [rsp]
+    SAVE_CONTEXT_AFTER_RAX
+    mov rdi, rsp
+    mov rsi, rax
+    cld
+    call \handler
+    RESTORE_CONTEXT
+    iretq
+.endm
+
+.section .text
+USER_RETURN_ENTRY timer_interrupt_entry, timer_interrupt
+USER_RETURN_ENTRY lapic_timer_interrupt_entry, lapic_timer_interrupt
+CONTEXT_ENTRY yield_interrupt_entry, yield_interrupt
+CONTEXT_ENTRY reschedule_interrupt_entry, reschedule_interrupt
+CONTEXT_ENTRY tlb_shootdown_entry, tlb_shootdown_interrupt
+CONTEXT_ENTRY syscall_interrupt_entry, syscall_dispatch
+CONTEXT_ENTRY divide_error_entry, divide_error
+CONTEXT_ENTRY debug_entry, debug_exception
+CONTEXT_ENTRY nmi_entry, nmi
+CONTEXT_ENTRY breakpoint_entry, breakpoint
+CONTEXT_ENTRY overflow_entry, overflow
+CONTEXT_ENTRY bound_range_entry, bound_range
+CONTEXT_ENTRY invalid_opcode_entry, invalid_opcode
+CONTEXT_ENTRY device_not_available_entry, device_not_available
+ERROR_CONTEXT_ENTRY double_fault_entry, double_fault
+ERROR_CONTEXT_ENTRY invalid_tss_entry, invalid_tss
+ERROR_CONTEXT_ENTRY segment_not_present_entry, segment_not_present
+ERROR_CONTEXT_ENTRY stack_segment_entry, stack_segment_fault
+ERROR_CONTEXT_ENTRY general_protection_entry, general_protection
+ERROR_CONTEXT_ENTRY page_fault_entry, page_fault
+CONTEXT_ENTRY x87_fpu_error_entry, x87_fpu_error
+ERROR_CONTEXT_ENTRY alignment_check_entry, alignment_check
+CONTEXT_ENTRY machine_check_entry, machine_check
+CONTEXT_ENTRY simd_fpu_exception_entry, simd_fpu_exception
+CONTEXT_ENTRY virtualization_entry, virtualization_exception
+
+.global syscall_entry
+syscall_entry:
+    swapgs
+    mov qword ptr gs:[8], rsp
+    mov rsp, qword ptr gs:[0]
+    push 0x1B
+    push qword ptr gs:[8]
+    push r11
+    push 0x23
+    push rcx
+    swapgs
+    SAVE_CONTEXT
+    mov rdi, rsp
+    cld
+    call syscall_dispatch
+    cli
+    test al, al
+    jnz syscall_iret_return
+    cmp qword ptr [rsp + 128], 0x23
+    jne syscall_iret_return
+    mov rcx, qword ptr [rsp + 120]
+    shr rcx, 47
+    jnz syscall_iret_return
+    RESTORE_CONTEXT
+    pop rcx
+    add rsp, 8
+    pop r11
+    pop rsp
+    sysretq
+syscall_iret_return:
+    RESTORE_CONTEXT
+    iretq
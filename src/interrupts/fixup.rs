@@ -0,0 +1,20 @@
+#[repr(C)]
+struct ExceptionTableEntry {
+    instruction: u64,
+    fixup: u64,
+}
+
+extern "C" {
+    static __ex_table_start: ExceptionTableEntry;
+    static __ex_table_end: ExceptionTableEntry;
+}
+
+pub fn search(rip: u64) -> Option<u64> {
+    let table = unsafe {
+        let start = &__ex_table_start as *const ExceptionTableEntry;
+        let end = &__ex_table_end as *const ExceptionTableEntry;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    };
+
+    table.iter().find(|entry| entry.instruction == rip).map(|entry| entry.fixup)
+}
@@ -1,20 +1,69 @@
 use crate::vga;
 use crate::interrupts::InterruptFrame;
 use crate::pci::devices;
+use crate::scheduler::task::TaskContext;
+use crate::smp::MAX_CPUS;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-extern "x86-interrupt" fn timer_handler(frame: InterruptFrame) -> ! {
-    static mut TICK_COUNT: u64 = 0;
+const NO_SIGNAL_CHECK: AtomicBool = AtomicBool::new(false);
+static SIGNAL_CHECK: [AtomicBool; MAX_CPUS] = [NO_SIGNAL_CHECK; MAX_CPUS];
+
+#[no_mangle]
+extern "C" fn timer_interrupt(context: &mut TaskContext) {
+    crate::interrupts::pic::send_eoi(0);
+    handle_timer(context);
+}
+
+#[no_mangle]
+extern "C" fn lapic_timer_interrupt(context: &mut TaskContext) {
+    crate::time::lapic_timer::handle_interrupt();
+    handle_timer(context);
+}
+
+fn handle_timer(context: &mut TaskContext) {
+    let cpu = crate::smp::current_cpu_id();
+    crate::performance::PERFORMANCE.increment_interrupts();
+    crate::smp::record_interrupt(cpu);
     
-    unsafe {
-        TICK_COUNT += 1;
-        
-        if TICK_COUNT % 1000 == 0 {
-            vga::print!("Timer tick: {}\n", TICK_COUNT);
-        }
+    crate::time::handle_tick(cpu);
+    let task = crate::scheduler::current_task_id();
+    crate::scheduler::timer_tick(context);
+    if context.cs & 3 == 3 && crate::scheduler::current_task_id() == task {
+        SIGNAL_CHECK[cpu as usize].store(true, Ordering::Relaxed);
     }
-    
-    crate::interrupts::pic::send_eoi(0);
-    crate::interrupts::halt();
+}
+
+#[no_mangle]
+extern "C" fn interrupt_return(context: &mut TaskContext) {
+    let cpu = crate::smp::current_cpu_id() as usize;
+    if !SIGNAL_CHECK[cpu].swap(false, Ordering::Relaxed) || context.cs & 3 != 3 {
+        return;
+    }
+
+    crate::interrupts::enable_interrupts();
+    crate::process::signal::deliver_pending(context);
+    crate::interrupts::disable_interrupts();
+}
+
+#[no_mangle]
+extern "C" fn yield_interrupt(context: &mut TaskContext) {
+    crate::scheduler::switch_context(context);
+}
+
+#[no_mangle]
+extern "C" fn tlb_shootdown_interrupt(_context: &mut TaskContext) {
+    crate::memory::paging::handle_tlb_shootdown();
+    crate::interrupts::apic::send_eoi();
+}
+
+#[no_mangle]
+extern "C" fn reschedule_interrupt(context: &mut TaskContext) {
+    crate::smp::record_interrupt(crate::smp::current_cpu_id());
+    crate::smp::scheduler::handle_reschedule(context);
+}
+
+pub fn get_tick_count() -> u64 {
+    crate::time::ticks()
 }
 
 extern "x86-interrupt" fn keyboard_handler(frame: InterruptFrame) -> ! {
@@ -42,7 +91,6 @@ extern "x86-interrupt" fn network_handler(frame: InterruptFrame) -> ! {
 }
 
 pub fn register_handlers() {
-    crate::interrupts::register_handler(32, timer_handler);
     crate::interrupts::register_handler(33, keyboard_handler);
     crate::interrupts::register_handler(44, mouse_handler);
     crate::interrupts::register_handler(46, storage_handler);
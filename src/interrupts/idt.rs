@@ -1,4 +1,9 @@
+use crate::process::signal::{self, SIGBUS, SIGFPE, SIGILL, SIGKILL, SIGSEGV, SIGTRAP};
+use crate::scheduler::task::TaskContext;
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
+use super::diagnostics;
+use crate::vm::FaultError;
 use core::arch::asm;
 
 #[repr(C, packed)]
@@ -18,7 +23,9 @@ pub struct IDTPointer {
     pub base: u64,
 }
 
-static mut IDT: [IDTEntry; 256] = [IDTEntry {
+const RFLAGS_INTERRUPT_ENABLE: u64 = 1 << 9;
+
+const EMPTY_GATE: IDTEntry = IDTEntry {
     offset_low: 0,
     selector: 0,
     ist: 0,
@@ -26,7 +33,9 @@ static mut IDT: [IDTEntry; 256] = [IDTEntry {
     offset_mid: 0,
     offset_high: 0,
     reserved: 0,
-}; 256];
+};
+
+static IDT: SpinLock<[IDTEntry; 256]> = SpinLock::new([EMPTY_GATE; 256]);
 
 pub fn init() {
     vga::print!("Setting up IDT...\n");
@@ -39,145 +48,256 @@ pub fn init() {
 }
 
 unsafe fn setup_exceptions() {
-    set_gate(0, exception_div_by_zero as u64, 0x08, 0x8E);
-    set_gate(1, exception_debug as u64, 0x08, 0x8E);
-    set_gate(2, exception_nmi as u64, 0x08, 0x8E);
-    set_gate(3, exception_breakpoint as u64, 0x08, 0x8E);
-    set_gate(4, exception_overflow as u64, 0x08, 0x8E);
-    set_gate(5, exception_bound_range as u64, 0x08, 0x8E);
-    set_gate(6, exception_invalid_opcode as u64, 0x08, 0x8E);
-    set_gate(7, exception_device_not_available as u64, 0x08, 0x8E);
-    set_gate(8, exception_double_fault as u64, 0x08, 0x8E);
-    set_gate(10, exception_invalid_tss as u64, 0x08, 0x8E);
-    set_gate(11, exception_segment_not_present as u64, 0x08, 0x8E);
-    set_gate(12, exception_stack_segment_fault as u64, 0x08, 0x8E);
-    set_gate(13, exception_general_protection as u64, 0x08, 0x8E);
-    set_gate(14, exception_page_fault as u64, 0x08, 0x8E);
-    set_gate(16, exception_x87_fpu_error as u64, 0x08, 0x8E);
-    set_gate(17, exception_alignment_check as u64, 0x08, 0x8E);
-    set_gate(18, exception_machine_check as u64, 0x08, 0x8E);
-    set_gate(19, exception_simd_fpu_exception as u64, 0x08, 0x8E);
-    set_gate(20, exception_virtualization as u64, 0x08, 0x8E);
+    set_gate(0, divide_error_entry as u64, 0x08, 0x8E);
+    set_gate(1, debug_entry as u64, 0x08, 0x8E);
+    set_gate(2, nmi_entry as u64, 0x08, 0x8E);
+    set_gate(3, breakpoint_entry as u64, 0x08, 0xEE);
+    set_gate(4, overflow_entry as u64, 0x08, 0x8E);
+    set_gate(5, bound_range_entry as u64, 0x08, 0x8E);
+    set_gate(6, invalid_opcode_entry as u64, 0x08, 0x8E);
+    set_gate(7, device_not_available_entry as u64, 0x08, 0x8E);
+    set_gate(8, double_fault_entry as u64, 0x08, 0x8E);
+    set_gate(10, invalid_tss_entry as u64, 0x08, 0x8E);
+    set_gate(11, segment_not_present_entry as u64, 0x08, 0x8E);
+    set_gate(12, stack_segment_entry as u64, 0x08, 0x8E);
+    set_gate(13, general_protection_entry as u64, 0x08, 0x8E);
+    set_gate(14, page_fault_entry as u64, 0x08, 0x8E);
+    set_gate(16, x87_fpu_error_entry as u64, 0x08, 0x8E);
+    set_gate(17, alignment_check_entry as u64, 0x08, 0x8E);
+    set_gate(18, machine_check_entry as u64, 0x08, 0x8E);
+    set_gate(19, simd_fpu_exception_entry as u64, 0x08, 0x8E);
+    set_gate(20, virtualization_entry as u64, 0x08, 0x8E);
+    
+    set_ist(2, crate::gdt::NMI_IST);
+    set_ist(8, crate::gdt::DOUBLE_FAULT_IST);
+    set_ist(18, crate::gdt::MACHINE_CHECK_IST);
+}
+
+extern "C" {
+    fn timer_interrupt_entry();
+    fn yield_interrupt_entry();
+    fn reschedule_interrupt_entry();
+    fn tlb_shootdown_entry();
+    fn lapic_timer_interrupt_entry();
+    fn syscall_interrupt_entry();
+    fn divide_error_entry();
+    fn debug_entry();
+    fn nmi_entry();
+    fn breakpoint_entry();
+    fn overflow_entry();
+    fn bound_range_entry();
+    fn invalid_opcode_entry();
+    fn device_not_available_entry();
+    fn double_fault_entry();
+    fn invalid_tss_entry();
+    fn segment_not_present_entry();
+    fn stack_segment_entry();
+    fn general_protection_entry();
+    fn page_fault_entry();
+    fn x87_fpu_error_entry();
+    fn alignment_check_entry();
+    fn machine_check_entry();
+    fn simd_fpu_exception_entry();
+    fn virtualization_entry();
 }
 
 unsafe fn setup_interrupts() {
     for i in 32..256 {
         set_gate(i, interrupt_stub as u64, 0x08, 0x8E);
     }
+    
+    set_gate(32, timer_interrupt_entry as u64, 0x08, 0x8E);
+    set_gate(crate::time::lapic_timer::LAPIC_TIMER_VECTOR as usize, lapic_timer_interrupt_entry as u64, 0x08, 0x8E);
+    set_gate(crate::scheduler::YIELD_VECTOR as usize, yield_interrupt_entry as u64, 0x08, 0x8E);
+    set_gate(crate::smp::scheduler::RESCHEDULE_VECTOR as usize, reschedule_interrupt_entry as u64, 0x08, 0x8E);
+    set_gate(crate::memory::paging::TLB_SHOOTDOWN_VECTOR as usize, tlb_shootdown_entry as u64, 0x08, 0x8E);
+    set_gate(crate::syscalls::interface::SYSCALL_VECTOR as usize, syscall_interrupt_entry as u64, 0x08, 0xEE);
 }
 
-unsafe fn set_gate(num: usize, handler: u64, selector: u16, type_attributes: u8) {
-    IDT[num].offset_low = (handler & 0xFFFF) as u16;
-    IDT[num].selector = selector;
-    IDT[num].ist = 0;
-    IDT[num].type_attributes = type_attributes;
-    IDT[num].offset_mid = ((handler >> 16) & 0xFFFF) as u16;
-    IDT[num].offset_high = ((handler >> 32) & 0xFFFFFFFF) as u32;
-    IDT[num].reserved = 0;
+fn set_gate(num: usize, handler: u64, selector: u16, type_attributes: u8) {
+    IDT.lock()[num] = IDTEntry {
+        offset_low: (handler & 0xFFFF) as u16,
+        selector,
+        ist: 0,
+        type_attributes,
+        offset_mid: ((handler >> 16) & 0xFFFF) as u16,
+        offset_high: ((handler >> 32) & 0xFFFFFFFF) as u32,
+        reserved: 0,
+    };
+}
+
+fn set_ist(num: usize, ist: u8) {
+    IDT.lock()[num].ist = ist;
+}
+
+pub fn load() {
+    unsafe {
+        load_idt();
+    }
 }
 
 unsafe fn load_idt() {
     let idt_ptr = IDTPointer {
         limit: (core::mem::size_of::<[IDTEntry; 256]>() - 1) as u16,
-        base: &IDT as *const _ as u64,
+        base: IDT.lock().as_ptr() as u64,
     };
     
     asm!("lidt [{}]", in(reg) &idt_ptr);
 }
 
-extern "x86-interrupt" fn exception_div_by_zero(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Division by zero at RIP=0x{:x}\n", frame.rip);
-    loop {}
+fn handle_exception(context: &mut TaskContext, vector: u8, error_code: Option<u64>, signo: u8, code: i32) {
+    if context.cs & 3 != 3 {
+        if let Some(fixup) = crate::interrupts::fixup::search(context.rip) {
+            context.rip = fixup;
+            return;
+        }
+        kernel_fault(context, vector, error_code, None);
+    }
+    
+    if !signal::force(signo, code, context.rip) {
+        diagnostics::report(vector, context, error_code, None);
+    }
+    signal::deliver_pending(context);
 }
 
-extern "x86-interrupt" fn exception_debug(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Debug at RIP=0x{:x}\n", frame.rip);
-    loop {}
+fn kernel_fault(context: &TaskContext, vector: u8, error_code: Option<u64>, fault_address: Option<u64>) -> ! {
+    diagnostics::report(vector, context, error_code, fault_address);
+    vga::print!("Unrecoverable kernel exception, halting CPU {}\n", crate::smp::current_cpu_id());
+    loop {
+        crate::interrupts::disable_interrupts();
+        crate::interrupts::halt();
+    }
 }
 
-extern "x86-interrupt" fn exception_nmi(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: NMI at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn divide_error(context: &mut TaskContext) {
+    handle_exception(context, 0, None, SIGFPE, signal::FPE_INTDIV);
 }
 
-extern "x86-interrupt" fn exception_breakpoint(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Breakpoint at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn debug_exception(context: &mut TaskContext) {
+    handle_exception(context, 1, None, SIGTRAP, signal::TRAP_TRACE);
 }
 
-extern "x86-interrupt" fn exception_overflow(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Overflow at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn nmi(context: &mut TaskContext) {
+    diagnostics::record_nmi(context);
 }
 
-extern "x86-interrupt" fn exception_bound_range(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Bound range exceeded at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn breakpoint(context: &mut TaskContext) {
+    handle_exception(context, 3, None, SIGTRAP, signal::TRAP_BRKPT);
 }
 
-extern "x86-interrupt" fn exception_invalid_opcode(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Invalid opcode at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn overflow(context: &mut TaskContext) {
+    handle_exception(context, 4, None, SIGSEGV, signal::SI_KERNEL);
 }
 
-extern "x86-interrupt" fn exception_device_not_available(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Device not available at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn bound_range(context: &mut TaskContext) {
+    handle_exception(context, 5, None, SIGSEGV, signal::SI_KERNEL);
 }
 
-extern "x86-interrupt" fn exception_double_fault(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Double fault at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn invalid_opcode(context: &mut TaskContext) {
+    handle_exception(context, 6, None, SIGILL, signal::ILL_ILLOPN);
 }
 
-extern "x86-interrupt" fn exception_invalid_tss(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Invalid TSS at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn device_not_available(_context: &mut TaskContext) {
+    crate::scheduler::handle_fpu_trap();
 }
 
-extern "x86-interrupt" fn exception_segment_not_present(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Segment not present at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn double_fault(context: &mut TaskContext, error_code: u64) {
+    crate::vm::protection::report_stack_overflow(diagnostics::read_cr2(), context.rip);
+    kernel_fault(context, 8, Some(error_code), None);
 }
 
-extern "x86-interrupt" fn exception_stack_segment_fault(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Stack segment fault at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn invalid_tss(context: &mut TaskContext, error_code: u64) {
+    handle_exception(context, 10, Some(error_code), SIGSEGV, signal::SI_KERNEL);
 }
 
-extern "x86-interrupt" fn exception_general_protection(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: General protection fault at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn segment_not_present(context: &mut TaskContext, error_code: u64) {
+    handle_exception(context, 11, Some(error_code), SIGBUS, signal::SI_KERNEL);
 }
 
-extern "x86-interrupt" fn exception_page_fault(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Page fault at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn stack_segment_fault(context: &mut TaskContext, error_code: u64) {
+    handle_exception(context, 12, Some(error_code), SIGBUS, signal::SI_KERNEL);
 }
 
-extern "x86-interrupt" fn exception_x87_fpu_error(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: x87 FPU error at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn general_protection(context: &mut TaskContext, error_code: u64) {
+    handle_exception(context, 13, Some(error_code), SIGSEGV, signal::SI_KERNEL);
 }
 
-extern "x86-interrupt" fn exception_alignment_check(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Alignment check at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn page_fault(context: &mut TaskContext, error_code: u64) {
+    let address = diagnostics::read_cr2();
+    if context.rflags & RFLAGS_INTERRUPT_ENABLE != 0 {
+        crate::interrupts::enable_interrupts();
+    }
+    let result = crate::vm::handle_page_fault(address, error_code);
+    crate::interrupts::disable_interrupts();
+    let error = match result {
+        Ok(()) => return,
+        Err(error) => error,
+    };
+    
+    if context.cs & 3 == 0 {
+        if let Some(fixup) = crate::interrupts::fixup::search(context.rip) {
+            context.rip = fixup;
+            return;
+        }
+        crate::vm::protection::report_violation(address, error_code, context.rip, error);
+        kernel_fault(context, diagnostics::PAGE_FAULT_VECTOR, Some(error_code), Some(address));
+    }
+    
+    let (signo, code) = match error {
+        FaultError::NoRegion => (SIGSEGV, signal::SEGV_MAPERR),
+        FaultError::ProtectionKey => (SIGSEGV, signal::SEGV_PKUERR),
+        FaultError::OutOfBounds => (SIGBUS, signal::BUS_ADRERR),
+        FaultError::OutOfMemory => (SIGKILL, signal::SI_KERNEL),
+        FaultError::AccessViolation | FaultError::ReservedBit => (SIGSEGV, signal::SEGV_ACCERR),
+    };
+    if !signal::force(signo, code, address) {
+        crate::vm::protection::report_violation(address, error_code, context.rip, error);
+        diagnostics::report(diagnostics::PAGE_FAULT_VECTOR, context, Some(error_code), Some(address));
+    }
+    signal::deliver_pending(context);
 }
 
-extern "x86-interrupt" fn exception_machine_check(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Machine check at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn x87_fpu_error(context: &mut TaskContext) {
+    handle_exception(context, 16, None, SIGFPE, signal::SI_KERNEL);
 }
 
-extern "x86-interrupt" fn exception_simd_fpu_exception(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: SIMD FPU exception at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn alignment_check(context: &mut TaskContext, error_code: u64) {
+    handle_exception(context, 17, Some(error_code), SIGBUS, signal::BUS_ADRALN);
 }
 
-extern "x86-interrupt" fn exception_virtualization(frame: crate::interrupts::InterruptFrame) -> ! {
-    vga::print!("Exception: Virtualization exception at RIP=0x{:x}\n", frame.rip);
-    loop {}
+#[no_mangle]
+extern "C" fn machine_check(context: &mut TaskContext) {
+    if context.cs & 3 != 3 {
+        kernel_fault(context, 18, None, None);
+    }
+    diagnostics::report(18, context, None, None);
+    signal::force(SIGBUS, signal::SI_KERNEL, context.rip);
+}
+
+#[no_mangle]
+extern "C" fn simd_fpu_exception(context: &mut TaskContext) {
+    handle_exception(context, 19, None, SIGFPE, signal::SI_KERNEL);
+}
+
+#[no_mangle]
+extern "C" fn virtualization_exception(context: &mut TaskContext) {
+    handle_exception(context, 20, None, SIGSEGV, signal::SI_KERNEL);
 }
 
 extern "x86-interrupt" fn interrupt_stub(frame: crate::interrupts::InterruptFrame) -> ! {
@@ -1,10 +1,17 @@
+use crate::smp::MAX_CPUS;
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
-use core::arch::asm;
+use core::arch::{asm, global_asm};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 pub mod idt;
 pub mod handlers;
 pub mod pic;
 pub mod apic;
+pub mod fixup;
+pub mod diagnostics;
+
+global_asm!(include_str!("entry.s"));
 
 #[repr(C, packed)]
 pub struct InterruptFrame {
@@ -17,7 +24,12 @@ pub struct InterruptFrame {
 
 pub type InterruptHandler = fn(InterruptFrame) -> !;
 
-static mut INTERRUPT_HANDLERS: [Option<InterruptHandler>; 256] = [None; 256];
+static INTERRUPT_HANDLERS: SpinLock<[Option<InterruptHandler>; 256]> = SpinLock::new([None; 256]);
+
+const NO_DEPTH: AtomicU32 = AtomicU32::new(0);
+const NOT_SAVED: AtomicBool = AtomicBool::new(false);
+static DISABLE_DEPTH: [AtomicU32; MAX_CPUS] = [NO_DEPTH; MAX_CPUS];
+static SAVED_ENABLED: [AtomicBool; MAX_CPUS] = [NOT_SAVED; MAX_CPUS];
 
 pub fn init() {
     vga::print!("Initializing interrupt system...\n");
@@ -26,24 +38,24 @@ pub fn init() {
         idt::init();
         pic::init();
         apic::init();
+        handlers::register_handlers();
         
         vga::print!("Interrupt system initialized\n");
     }
 }
 
 pub fn register_handler(interrupt: u8, handler: InterruptHandler) {
-    unsafe {
-        INTERRUPT_HANDLERS[interrupt as usize] = Some(handler);
-    }
+    INTERRUPT_HANDLERS.lock()[interrupt as usize] = Some(handler);
 }
 
 pub fn handle_interrupt(frame: InterruptFrame, interrupt: u8) {
-    unsafe {
-        if let Some(handler) = INTERRUPT_HANDLERS[interrupt as usize] {
-            handler(frame);
-        } else {
-            vga::print!("Unhandled interrupt: {}\n", interrupt);
-        }
+    // Handlers never return, so the entry is copied out before the lock
+    // guard would otherwise be leaked across the call.
+    let handler = INTERRUPT_HANDLERS.lock()[interrupt as usize];
+    if let Some(handler) = handler {
+        handler(frame);
+    } else {
+        vga::print!("Unhandled interrupt: {}\n", interrupt);
     }
 }
 
@@ -59,6 +71,46 @@ pub fn disable_interrupts() {
     }
 }
 
+pub fn interrupts_enabled() -> bool {
+    let rflags: u64;
+    unsafe {
+        asm!("pushfq", "pop {}", out(reg) rflags);
+    }
+    rflags & 0x200 != 0
+}
+
+pub fn save_and_disable() -> bool {
+    let enabled = interrupts_enabled();
+    disable_interrupts();
+    enabled
+}
+
+pub fn restore(enabled: bool) {
+    if enabled {
+        enable_interrupts();
+    }
+}
+
+pub fn push_disable() {
+    let enabled = save_and_disable();
+    let cpu = crate::smp::current_cpu_id() as usize;
+    if DISABLE_DEPTH[cpu].fetch_add(1, Ordering::Relaxed) == 0 {
+        SAVED_ENABLED[cpu].store(enabled, Ordering::Relaxed);
+    }
+}
+
+pub fn pop_disable() {
+    let cpu = crate::smp::current_cpu_id() as usize;
+    let depth = DISABLE_DEPTH[cpu].load(Ordering::Relaxed);
+    if depth == 0 {
+        return;
+    }
+    DISABLE_DEPTH[cpu].store(depth - 1, Ordering::Relaxed);
+    if depth == 1 && SAVED_ENABLED[cpu].load(Ordering::Relaxed) {
+        enable_interrupts();
+    }
+}
+
 pub fn halt() {
     unsafe {
         asm!("hlt");
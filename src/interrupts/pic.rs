@@ -42,6 +42,26 @@ pub fn send_eoi(irq: u8) {
     }
 }
 
+pub fn mask_irq(irq: u8) {
+    let port = if irq < 8 { PIC1_DATA } else { PIC2_DATA };
+    let value = inb(port) | (1 << (irq % 8));
+    outb(port, value);
+}
+
+pub fn unmask_irq(irq: u8) {
+    let port = if irq < 8 { PIC1_DATA } else { PIC2_DATA };
+    let value = inb(port) & !(1 << (irq % 8));
+    outb(port, value);
+}
+
+fn inb(port: u16) -> u8 {
+    let result: u8;
+    unsafe {
+        asm!("in al, dx", in("dx") port, out("al") result);
+    }
+    result
+}
+
 fn outb(port: u16, value: u8) {
     unsafe {
         asm!("out dx, al", in("dx") port, in("al") value);
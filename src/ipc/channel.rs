@@ -0,0 +1,259 @@
+use super::poll::{PollQueue, POLLERR, POLLHUP, POLLIN, POLLOUT};
+use crate::process::files::{FileDescription, FileKind};
+use crate::process::signal;
+use crate::smp::synchronization::{SpinLock, WaitQueue};
+use crate::syscalls::errno::Errno;
+use crate::vga;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 64;
+pub const MAX_CHANNEL_CAPACITY: usize = 1024;
+pub const MAX_MESSAGE_SIZE: usize = 65536;
+pub const MAX_PORT_NAME_LENGTH: usize = 255;
+
+static PORTS: SpinLock<BTreeMap<String, Weak<Channel>>> = SpinLock::new(BTreeMap::new());
+
+struct ChannelState {
+    messages: VecDeque<Vec<u8>>,
+    capacity: usize,
+    senders: usize,
+    receivers: usize,
+    named: bool,
+}
+
+pub struct Channel {
+    state: SpinLock<ChannelState>,
+    readable: WaitQueue,
+    writable: WaitQueue,
+    read_pollers: PollQueue,
+    write_pollers: PollQueue,
+}
+
+impl Channel {
+    fn new(capacity: usize, named: bool) -> Result<Arc<Self>, Errno> {
+        let capacity = match capacity {
+            0 => DEFAULT_CHANNEL_CAPACITY,
+            capacity if capacity <= MAX_CHANNEL_CAPACITY => capacity,
+            _ => return Err(Errno::EINVAL),
+        };
+        Ok(Arc::new(Self {
+            state: SpinLock::new(ChannelState {
+                messages: VecDeque::new(),
+                capacity,
+                senders: 0,
+                receivers: 0,
+                named,
+            }),
+            readable: WaitQueue::new(),
+            writable: WaitQueue::new(),
+            read_pollers: PollQueue::new(),
+            write_pollers: PollQueue::new(),
+        }))
+    }
+
+    fn open_sender(self: &Arc<Self>) -> ChannelSender {
+        self.state.lock().senders += 1;
+        self.read_pollers.notify();
+        ChannelSender { channel: self.clone() }
+    }
+
+    fn open_receiver(self: &Arc<Self>) -> ChannelReceiver {
+        self.state.lock().receivers += 1;
+        self.write_pollers.notify();
+        ChannelReceiver { channel: self.clone() }
+    }
+
+    fn send(&self, data: &[u8]) -> Result<usize, Errno> {
+        if data.len() > MAX_MESSAGE_SIZE {
+            return Err(Errno::EMSGSIZE);
+        }
+
+        loop {
+            {
+                let mut state = self.state.lock();
+                if state.receivers == 0 {
+                    return Err(Errno::EPIPE);
+                }
+                if state.messages.len() < state.capacity {
+                    state.messages.push_back(data.to_vec());
+                    drop(state);
+                    self.readable.wake_one();
+                    self.read_pollers.notify();
+                    return Ok(data.len());
+                }
+            }
+            let waited = self.writable.wait_if(|| {
+                let state = self.state.lock();
+                state.messages.len() >= state.capacity && state.receivers > 0
+            });
+            if waited && signal::pending() {
+                self.writable.wake_one();
+                return Err(Errno::EINTR);
+            }
+        }
+    }
+
+    fn receive(&self, buffer: &mut [u8]) -> Result<usize, Errno> {
+        loop {
+            {
+                let mut state = self.state.lock();
+                // A message is never split; a short buffer leaves it queued.
+                if state.messages.front().map_or(false, |message| message.len() > buffer.len()) {
+                    return Err(Errno::EMSGSIZE);
+                }
+                if let Some(message) = state.messages.pop_front() {
+                    drop(state);
+                    buffer[..message.len()].copy_from_slice(&message);
+                    self.writable.wake_one();
+                    self.write_pollers.notify();
+                    return Ok(message.len());
+                }
+                if state.senders == 0 && !state.named {
+                    return Ok(0);
+                }
+            }
+            let waited = self.readable.wait_if(|| {
+                let state = self.state.lock();
+                state.messages.is_empty() && (state.senders > 0 || state.named)
+            });
+            if waited && signal::pending() {
+                self.readable.wake_one();
+                return Err(Errno::EINTR);
+            }
+        }
+    }
+}
+
+pub struct ChannelSender {
+    channel: Arc<Channel>,
+}
+
+impl FileDescription for ChannelSender {
+    fn kind(&self) -> FileKind {
+        FileKind::Channel
+    }
+
+    fn read(&self, _buffer: &mut [u8]) -> Result<usize, Errno> {
+        Err(Errno::EBADF)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, Errno> {
+        self.channel.send(data)
+    }
+
+    fn poll(&self) -> u16 {
+        let state = self.channel.state.lock();
+        if state.receivers == 0 {
+            POLLERR
+        } else if state.messages.len() < state.capacity {
+            POLLOUT
+        } else {
+            0
+        }
+    }
+
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        Some(&self.channel.write_pollers)
+    }
+}
+
+impl Drop for ChannelSender {
+    fn drop(&mut self) {
+        self.channel.state.lock().senders -= 1;
+        self.channel.readable.wake_all();
+        self.channel.read_pollers.notify();
+    }
+}
+
+pub struct ChannelReceiver {
+    channel: Arc<Channel>,
+}
+
+impl FileDescription for ChannelReceiver {
+    fn kind(&self) -> FileKind {
+        FileKind::Channel
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, Errno> {
+        self.channel.receive(buffer)
+    }
+
+    fn write(&self, _data: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::EBADF)
+    }
+
+    fn poll(&self) -> u16 {
+        let state = self.channel.state.lock();
+        let mut events = 0;
+        if !state.messages.is_empty() {
+            events |= POLLIN;
+        }
+        if state.senders == 0 && !state.named {
+            events |= POLLHUP;
+        }
+        events
+    }
+
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        Some(&self.channel.read_pollers)
+    }
+}
+
+impl Drop for ChannelReceiver {
+    fn drop(&mut self) {
+        self.channel.state.lock().receivers -= 1;
+        self.channel.writable.wake_all();
+        self.channel.write_pollers.notify();
+    }
+}
+
+pub fn init() {
+    vga::print!("Message channels initialized ({} message default capacity)\n", DEFAULT_CHANNEL_CAPACITY);
+}
+
+pub fn channel(capacity: usize) -> Result<(ChannelSender, ChannelReceiver), Errno> {
+    let channel = Channel::new(capacity, false)?;
+    Ok((channel.open_sender(), channel.open_receiver()))
+}
+
+pub fn create_port(name: &str, capacity: usize) -> Result<ChannelReceiver, Errno> {
+    validate_port_name(name)?;
+    let mut ports = PORTS.lock();
+    ports.retain(|_, port| port.upgrade().map_or(false, |channel| channel.state.lock().receivers > 0));
+    if ports.contains_key(name) {
+        return Err(Errno::EEXIST);
+    }
+
+    let channel = Channel::new(capacity, true)?;
+    let receiver = channel.open_receiver();
+    ports.insert(name.to_string(), Arc::downgrade(&channel));
+    Ok(receiver)
+}
+
+pub fn connect_port(name: &str) -> Result<ChannelSender, Errno> {
+    validate_port_name(name)?;
+    let channel = PORTS
+        .lock()
+        .get(name)
+        .and_then(|port| port.upgrade())
+        .filter(|channel| channel.state.lock().receivers > 0)
+        .ok_or(Errno::ENOENT)?;
+    Ok(channel.open_sender())
+}
+
+pub fn port_count() -> usize {
+    PORTS.lock().values().filter(|port| port.strong_count() > 0).count()
+}
+
+fn validate_port_name(name: &str) -> Result<(), Errno> {
+    if name.is_empty() {
+        return Err(Errno::EINVAL);
+    }
+    if name.len() > MAX_PORT_NAME_LENGTH {
+        return Err(Errno::ENAMETOOLONG);
+    }
+    Ok(())
+}
@@ -0,0 +1,20 @@
+use crate::vga;
+
+pub mod pipe;
+pub mod channel;
+pub mod poll;
+
+pub fn init() {
+    vga::print!("Initializing IPC subsystem...\n");
+
+    pipe::init();
+    channel::init();
+    poll::init();
+
+    vga::print!("IPC subsystem initialized\n");
+}
+
+pub fn print_stats() {
+    vga::print!("IPC: {} named pipes, {} ports, {} poll waiters\n",
+        pipe::named_count(), channel::port_count(), poll::waiting_count());
+}
@@ -0,0 +1,267 @@
+use super::poll::{PollQueue, POLLERR, POLLHUP, POLLIN, POLLOUT};
+use crate::process::files::{FileDescription, FileKind};
+use crate::process::signal;
+use crate::smp::synchronization::{SpinLock, WaitQueue};
+use crate::syscalls::errno::Errno;
+use crate::vga;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+
+pub const PIPE_CAPACITY: usize = 65536;
+
+pub const O_ACCMODE: u64 = 3;
+pub const O_RDONLY: u64 = 0;
+pub const O_WRONLY: u64 = 1;
+
+static NAMED_PIPES: SpinLock<BTreeMap<String, Arc<Pipe>>> = SpinLock::new(BTreeMap::new());
+
+struct PipeState {
+    buffer: VecDeque<u8>,
+    readers: usize,
+    writers: usize,
+}
+
+pub struct Pipe {
+    state: SpinLock<PipeState>,
+    readable: WaitQueue,
+    writable: WaitQueue,
+    opened: WaitQueue,
+    read_pollers: PollQueue,
+    write_pollers: PollQueue,
+}
+
+impl Pipe {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: SpinLock::new(PipeState {
+                buffer: VecDeque::new(),
+                readers: 0,
+                writers: 0,
+            }),
+            readable: WaitQueue::new(),
+            writable: WaitQueue::new(),
+            opened: WaitQueue::new(),
+            read_pollers: PollQueue::new(),
+            write_pollers: PollQueue::new(),
+        })
+    }
+
+    pub fn open_reader(self: &Arc<Self>) -> PipeReader {
+        self.state.lock().readers += 1;
+        self.opened.wake_all();
+        self.write_pollers.notify();
+        PipeReader { pipe: self.clone() }
+    }
+
+    pub fn open_writer(self: &Arc<Self>) -> PipeWriter {
+        self.state.lock().writers += 1;
+        self.opened.wake_all();
+        self.read_pollers.notify();
+        PipeWriter { pipe: self.clone() }
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, Errno> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            {
+                let mut state = self.state.lock();
+                if !state.buffer.is_empty() {
+                    let count = buffer.len().min(state.buffer.len());
+                    for (slot, byte) in buffer.iter_mut().zip(state.buffer.drain(..count)) {
+                        *slot = byte;
+                    }
+                    drop(state);
+                    self.writable.wake_all();
+                    self.write_pollers.notify();
+                    return Ok(count);
+                }
+                if state.writers == 0 {
+                    return Ok(0);
+                }
+            }
+            let waited = self.readable.wait_if(|| {
+                let state = self.state.lock();
+                state.buffer.is_empty() && state.writers > 0
+            });
+            if waited && signal::pending() {
+                return Err(Errno::EINTR);
+            }
+        }
+    }
+
+    fn wait_for_peer(&self, missing: impl Fn(&PipeState) -> bool) -> Result<(), Errno> {
+        while self.opened.wait_if(|| missing(&self.state.lock())) {
+            if signal::pending() {
+                return Err(Errno::EINTR);
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, Errno> {
+        let mut written = 0;
+        while written < data.len() {
+            {
+                let mut state = self.state.lock();
+                if state.readers == 0 {
+                    return if written > 0 { Ok(written) } else { Err(Errno::EPIPE) };
+                }
+                let space = PIPE_CAPACITY - state.buffer.len();
+                if space > 0 {
+                    let count = space.min(data.len() - written);
+                    state.buffer.extend(&data[written..written + count]);
+                    written += count;
+                    drop(state);
+                    self.readable.wake_all();
+                    self.read_pollers.notify();
+                    continue;
+                }
+            }
+            let waited = self.writable.wait_if(|| {
+                let state = self.state.lock();
+                state.buffer.len() >= PIPE_CAPACITY && state.readers > 0
+            });
+            if waited && signal::pending() {
+                return if written > 0 { Ok(written) } else { Err(Errno::EINTR) };
+            }
+        }
+        Ok(written)
+    }
+}
+
+pub struct PipeReader {
+    pipe: Arc<Pipe>,
+}
+
+impl FileDescription for PipeReader {
+    fn kind(&self) -> FileKind {
+        FileKind::Pipe
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, Errno> {
+        self.pipe.read(buffer)
+    }
+
+    fn write(&self, _data: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::EBADF)
+    }
+
+    fn poll(&self) -> u16 {
+        let state = self.pipe.state.lock();
+        let mut events = 0;
+        if !state.buffer.is_empty() {
+            events |= POLLIN;
+        }
+        if state.writers == 0 {
+            events |= POLLHUP;
+        }
+        events
+    }
+
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        Some(&self.pipe.read_pollers)
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.pipe.state.lock().readers -= 1;
+        self.pipe.writable.wake_all();
+        self.pipe.write_pollers.notify();
+    }
+}
+
+pub struct PipeWriter {
+    pipe: Arc<Pipe>,
+}
+
+impl FileDescription for PipeWriter {
+    fn kind(&self) -> FileKind {
+        FileKind::Pipe
+    }
+
+    fn read(&self, _buffer: &mut [u8]) -> Result<usize, Errno> {
+        Err(Errno::EBADF)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, Errno> {
+        self.pipe.write(data)
+    }
+
+    fn poll(&self) -> u16 {
+        let state = self.pipe.state.lock();
+        if state.readers == 0 {
+            POLLERR
+        } else if state.buffer.len() < PIPE_CAPACITY {
+            POLLOUT
+        } else {
+            0
+        }
+    }
+
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        Some(&self.pipe.write_pollers)
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.pipe.state.lock().writers -= 1;
+        self.pipe.readable.wake_all();
+        self.pipe.read_pollers.notify();
+    }
+}
+
+pub fn init() {
+    vga::print!("Pipes initialized ({} byte buffers)\n", PIPE_CAPACITY);
+}
+
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    let pipe = Pipe::new();
+    (pipe.open_reader(), pipe.open_writer())
+}
+
+pub fn mkfifo(path: &str) -> Result<(), Errno> {
+    if !path.starts_with('/') {
+        return Err(Errno::EINVAL);
+    }
+    let mut pipes = NAMED_PIPES.lock();
+    if pipes.contains_key(path) {
+        return Err(Errno::EEXIST);
+    }
+    pipes.insert(path.to_string(), Pipe::new());
+    Ok(())
+}
+
+pub fn is_named(path: &str) -> bool {
+    NAMED_PIPES.lock().contains_key(path)
+}
+
+pub fn open_named(path: &str, flags: u64) -> Result<Arc<dyn FileDescription>, Errno> {
+    let pipe = NAMED_PIPES.lock().get(path).cloned().ok_or(Errno::ENOENT)?;
+    match flags & O_ACCMODE {
+        O_RDONLY => {
+            let reader = pipe.open_reader();
+            pipe.wait_for_peer(|state| state.writers == 0)?;
+            Ok(Arc::new(reader))
+        }
+        O_WRONLY => {
+            let writer = pipe.open_writer();
+            pipe.wait_for_peer(|state| state.readers == 0)?;
+            Ok(Arc::new(writer))
+        }
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+pub fn unlink(path: &str) -> Result<(), Errno> {
+    NAMED_PIPES.lock().remove(path).map(|_| ()).ok_or(Errno::ENOENT)
+}
+
+pub fn named_count() -> usize {
+    NAMED_PIPES.lock().len()
+}
@@ -0,0 +1,162 @@
+use crate::process::files::FileDescription;
+use crate::process::signal;
+use crate::smp::synchronization::{SpinLock, WaitQueue};
+use crate::syscalls::errno::Errno;
+use crate::time;
+use crate::vga;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+pub const POLLIN: u16 = 0x001;
+pub const POLLOUT: u16 = 0x004;
+pub const POLLERR: u16 = 0x008;
+pub const POLLHUP: u16 = 0x010;
+pub const POLLNVAL: u16 = 0x020;
+
+pub const MAX_POLL_FDS: usize = 1024;
+
+static POLL_WAITERS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: u16,
+    pub revents: u16,
+}
+
+pub struct PollQueue {
+    pollers: SpinLock<Vec<Arc<Poller>>>,
+}
+
+impl PollQueue {
+    pub const fn new() -> Self {
+        Self {
+            pollers: SpinLock::new(Vec::new()),
+        }
+    }
+
+    pub fn notify(&self) {
+        for poller in self.pollers.lock().iter() {
+            poller.wake();
+        }
+    }
+
+    fn register(&self, poller: &Arc<Poller>) {
+        self.pollers.lock().push(poller.clone());
+    }
+
+    fn unregister(&self, poller: &Arc<Poller>) {
+        self.pollers.lock().retain(|registered| !Arc::ptr_eq(registered, poller));
+    }
+}
+
+struct Poller {
+    woken: AtomicBool,
+    queue: WaitQueue,
+}
+
+impl Poller {
+    fn wake(&self) {
+        self.woken.store(true, Ordering::Release);
+        self.queue.wake_all();
+    }
+
+    fn wait(&self) -> bool {
+        self.queue.wait_if(|| !self.woken.load(Ordering::Acquire))
+    }
+}
+
+pub fn init() {
+    vga::print!("Poll wait queue initialized\n");
+}
+
+pub fn poll(fds: &mut [PollFd], timeout_ms: i64) -> Result<usize, Errno> {
+    if fds.len() > MAX_POLL_FDS {
+        return Err(Errno::EINVAL);
+    }
+    let deadline = (timeout_ms > 0).then(|| time::monotonic_ns() + timeout_ms as u64 * time::NANOS_PER_MILLI);
+
+    let ready = scan(fds);
+    if ready > 0 || timeout_ms == 0 {
+        return Ok(ready);
+    }
+
+    let files: Vec<Arc<dyn FileDescription>> = fds
+        .iter()
+        .filter(|entry| entry.fd >= 0)
+        .filter_map(|entry| crate::process::file(entry.fd as usize).ok())
+        .collect();
+    let poller = Arc::new(Poller {
+        woken: AtomicBool::new(false),
+        queue: WaitQueue::new(),
+    });
+    for queue in files.iter().filter_map(|file| file.poll_queue()) {
+        queue.register(&poller);
+    }
+
+    POLL_WAITERS.fetch_add(1, Ordering::Relaxed);
+    let result = wait(&poller, fds, deadline);
+    POLL_WAITERS.fetch_sub(1, Ordering::Relaxed);
+
+    for queue in files.iter().filter_map(|file| file.poll_queue()) {
+        queue.unregister(&poller);
+    }
+    result
+}
+
+pub fn waiting_count() -> usize {
+    POLL_WAITERS.load(Ordering::Relaxed)
+}
+
+fn wait(poller: &Arc<Poller>, fds: &mut [PollFd], deadline: Option<u64>) -> Result<usize, Errno> {
+    loop {
+        poller.woken.store(false, Ordering::Release);
+        let ready = scan(fds);
+        if ready > 0 {
+            return Ok(ready);
+        }
+
+        let timer = match deadline {
+            Some(deadline) if time::monotonic_ns() >= deadline => return Ok(0),
+            Some(deadline) => {
+                let data = Arc::into_raw(poller.clone()) as usize;
+                Some((time::add_timer_at(deadline, expire, data), data))
+            }
+            None => None,
+        };
+        let waited = poller.wait();
+        if let Some((timer, data)) = timer {
+            if time::cancel_timer(timer) {
+                drop(unsafe { Arc::from_raw(data as *const Poller) });
+            }
+        }
+        if waited && signal::pending() {
+            return Err(Errno::EINTR);
+        }
+    }
+}
+
+fn scan(fds: &mut [PollFd]) -> usize {
+    let mut ready = 0;
+    for entry in fds.iter_mut() {
+        entry.revents = if entry.fd < 0 {
+            0
+        } else {
+            match crate::process::file(entry.fd as usize) {
+                Ok(file) => file.poll() & (entry.events | POLLERR | POLLHUP),
+                Err(_) => POLLNVAL,
+            }
+        };
+        if entry.revents != 0 {
+            ready += 1;
+        }
+    }
+    ready
+}
+
+fn expire(data: usize) {
+    let poller = unsafe { Arc::from_raw(data as *const Poller) };
+    poller.wake();
+}
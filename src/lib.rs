@@ -1,7 +1,11 @@
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 
+extern crate alloc;
+
+#[cfg(not(test))]
 use core::panic::PanicInfo;
+#[cfg(not(test))]
 use core::arch::global_asm;
 
 mod memory;
@@ -10,8 +14,12 @@ mod smbios;
 mod apic;
 mod vga;
 mod pci;
+mod gdt;
 mod interrupts;
+mod time;
 mod scheduler;
+mod loader;
+mod process;
 mod filesystem;
 mod graphics;
 mod networking;
@@ -21,6 +29,7 @@ mod performance;
 mod vm;
 mod smp;
 mod syscalls;
+mod ipc;
 mod security;
 mod graphics3d;
 mod networking_advanced;
@@ -29,7 +38,8 @@ mod power;
 mod virtualization;
 mod debugging_advanced;
 
-global_asm!(include_str!("start.s"));
+#[cfg(not(test))]
+global_asm!(include_str!("start.s"), options(att_syntax));
 
 #[repr(C)]
 pub struct MemoryMapEntry {
@@ -57,7 +67,7 @@ pub struct HandoffData {
 }
 
 #[no_mangle]
-pub extern "C" fn _start(handoff_ptr: *const HandoffData) -> ! {
+pub extern "C" fn kernel_main(handoff_ptr: *const HandoffData) -> ! {
     unsafe {
         vga::init();
         vga::print!("Kernel started\n");
@@ -65,25 +75,37 @@ pub extern "C" fn _start(handoff_ptr: *const HandoffData) -> ! {
         memory::init(handoff_ptr);
         vga::print!("Memory initialized\n");
         
-        acpi::init(handoff_ptr.acpi_rsdp);
+        let handoff = &*(memory::paging::phys_to_virt(handoff_ptr as u64) as *const HandoffData);
+        
+        acpi::init(handoff.acpi_rsdp);
         vga::print!("ACPI initialized\n");
         
-        smbios::init(handoff_ptr.smbios_entry);
+        smbios::init(handoff.smbios_entry);
         vga::print!("SMBIOS initialized\n");
         
         apic::init();
         vga::print!("APIC initialized\n");
         
+        gdt::init();
+        vga::print!("GDT initialized\n");
+        
         interrupts::init();
         vga::print!("Interrupt system initialized\n");
         
+        time::init();
+        vga::print!("Timekeeping initialized\n");
+        
         pci::init(0);
         vga::print!("PCI system initialized\n");
         
         scheduler::init();
         vga::print!("Task scheduler initialized\n");
         
+        interrupts::enable_interrupts();
+        vga::print!("Preemptive scheduling enabled\n");
+        
         filesystem::init();
+        filesystem::mount(0, "/");
         vga::print!("Filesystem initialized\n");
         
         graphics::init();
@@ -102,6 +124,9 @@ pub extern "C" fn _start(handoff_ptr: *const HandoffData) -> ! {
         vga::print!("Performance monitoring initialized\n");
         
         vm::init();
+        if let Err(err) = vm::swapping::enable(vm::swapping::DEFAULT_SWAP_DEVICE, 0) {
+            vga::print!("Swap not enabled: {:?}\n", err);
+        }
         vga::print!("Virtual memory system initialized\n");
         
         smp::init();
@@ -110,6 +135,9 @@ pub extern "C" fn _start(handoff_ptr: *const HandoffData) -> ! {
         syscalls::init();
         vga::print!("System call interface initialized\n");
         
+        ipc::init();
+        vga::print!("Inter-process communication initialized\n");
+        
         security::init();
         vga::print!("Security system initialized\n");
         
@@ -150,6 +178,11 @@ fn run_advanced_demo() {
         graphics::draw_string(10, 70, "🛡️ Security: ASLR, Stack Canaries, Capabilities, Sandboxing 🛡️", 0xFF00FF, 0x000000);
         graphics::draw_string(10, 90, "🔧 Debugging: Profiling, Tracing, Analysis, Hotpatching 🔧", 0xFFFF00, 0x000000);
         
+        match loader::spawn("/init", &["/init"], &["PATH=/"]) {
+            Ok(pid) => vga::print!("Started init process with PID: {}\n", pid),
+            Err(err) => vga::print!("No user init program loaded: {:?}\n", err),
+        }
+        
         let task_id = scheduler::create_task(advanced_demo_task, 8192);
         vga::print!("Created advanced demo task with ID: {}\n", task_id);
         
@@ -160,11 +193,23 @@ fn run_advanced_demo() {
             graphics::draw_line(10, 180, 10 + i, 200, 0x0000FF);
         }
         
+        if let Some(exit_code) = scheduler::join(task_id) {
+            vga::print!("Advanced demo task exited with code {}\n", exit_code);
+        }
+        scheduler::print_policy_stats();
+        smp::scheduler::print_stats();
+        time::print_stats();
+        interrupts::diagnostics::print_nmi_stats();
+        process::print_processes();
+        syscalls::trace::print_stats();
+        vm::print_stats();
+        ipc::print_stats();
+        
         vga::print!("🎉 Demo completed successfully! System is running at peak performance! 🎉\n");
     }
 }
 
-fn advanced_demo_task() -> ! {
+fn advanced_demo_task() {
     unsafe {
         for i in 0..5000 {
             vga::print!("🚀 Advanced demo task running: {} - CPU: {}, Memory: {}MB\n", 
@@ -179,9 +224,9 @@ fn advanced_demo_task() -> ! {
             scheduler::yield_cpu();
         }
     }
-    loop {}
 }
 
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     unsafe {
@@ -0,0 +1,105 @@
+use super::LoadError;
+
+pub const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+pub const ELF_CLASS_64: u8 = 2;
+pub const ELF_DATA_LSB: u8 = 1;
+pub const ELF_VERSION_CURRENT: u8 = 1;
+
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+pub const EM_X86_64: u16 = 0x3E;
+
+pub const PT_LOAD: u32 = 1;
+pub const PT_INTERP: u32 = 3;
+pub const PT_PHDR: u32 = 6;
+
+pub const PF_X: u32 = 1 << 0;
+pub const PF_W: u32 = 1 << 1;
+pub const PF_R: u32 = 1 << 2;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Elf64Header {
+    pub ident: [u8; 16],
+    pub file_type: u16,
+    pub machine: u16,
+    pub version: u32,
+    pub entry: u64,
+    pub phoff: u64,
+    pub shoff: u64,
+    pub flags: u32,
+    pub ehsize: u16,
+    pub phentsize: u16,
+    pub phnum: u16,
+    pub shentsize: u16,
+    pub shnum: u16,
+    pub shstrndx: u16,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct ProgramHeader {
+    pub segment_type: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub paddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+    pub align: u64,
+}
+
+pub struct ElfFile<'a> {
+    pub data: &'a [u8],
+    pub header: Elf64Header,
+}
+
+impl<'a> ElfFile<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, LoadError> {
+        if data.len() < core::mem::size_of::<Elf64Header>() {
+            return Err(LoadError::Truncated);
+        }
+
+        let header = unsafe { core::ptr::read_unaligned(data.as_ptr() as *const Elf64Header) };
+
+        if header.ident[0..4] != ELF_MAGIC {
+            return Err(LoadError::InvalidMagic);
+        }
+        if header.ident[4] != ELF_CLASS_64 || header.ident[5] != ELF_DATA_LSB {
+            return Err(LoadError::UnsupportedClass);
+        }
+        if header.ident[6] != ELF_VERSION_CURRENT || header.version != ELF_VERSION_CURRENT as u32 {
+            return Err(LoadError::UnsupportedVersion);
+        }
+        if header.machine != EM_X86_64 {
+            return Err(LoadError::UnsupportedMachine);
+        }
+        if header.file_type != ET_EXEC && header.file_type != ET_DYN {
+            return Err(LoadError::UnsupportedType);
+        }
+        if header.phentsize as usize != core::mem::size_of::<ProgramHeader>() {
+            return Err(LoadError::InvalidProgramHeader);
+        }
+
+        let table_size = header.phnum as u64 * header.phentsize as u64;
+        match header.phoff.checked_add(table_size) {
+            Some(end) if end <= data.len() as u64 => {}
+            _ => return Err(LoadError::Truncated),
+        }
+
+        Ok(Self { data, header })
+    }
+
+    pub fn program_header(&self, index: usize) -> ProgramHeader {
+        let offset = self.header.phoff as usize + index * core::mem::size_of::<ProgramHeader>();
+        unsafe { core::ptr::read_unaligned(self.data[offset..].as_ptr() as *const ProgramHeader) }
+    }
+
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + '_ {
+        (0..self.header.phnum as usize).map(move |index| self.program_header(index))
+    }
+
+    pub fn is_position_independent(&self) -> bool {
+        self.header.file_type == ET_DYN
+    }
+}
@@ -0,0 +1,367 @@
+use crate::memory::frame_allocator;
+use crate::memory::paging::{self, MapError, PageMapper, PAGE_NX, PAGE_SIZE, PAGE_USER, PAGE_WRITABLE};
+use crate::process::Personality;
+use crate::scheduler::user_stack::{DEFAULT_USER_STACK_PAGES, USER_STACK_TOP};
+use crate::syscalls::errno::Errno;
+use crate::vga;
+use crate::vm::mmap::{MAP_ANONYMOUS, MAP_PRIVATE, PROT_EXEC, PROT_READ, PROT_WRITE};
+use crate::vm::region::VirtualMemoryRegion;
+use crate::vm::FaultError;
+use alloc::vec::Vec;
+
+pub mod elf;
+
+use elf::{ElfFile, ProgramHeader, PF_W, PF_X, PT_INTERP, PT_LOAD, PT_PHDR};
+
+pub const USER_SPACE_END: u64 = 0x0000_8000_0000_0000;
+pub const PIE_LOAD_BASE: u64 = 0x0000_5555_5555_4000;
+
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+const AT_UID: u64 = 11;
+const AT_EUID: u64 = 12;
+const AT_GID: u64 = 13;
+const AT_EGID: u64 = 14;
+const AT_SECURE: u64 = 23;
+const AT_RANDOM: u64 = 25;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LoadError {
+    NotFound,
+    Truncated,
+    InvalidMagic,
+    UnsupportedClass,
+    UnsupportedVersion,
+    UnsupportedMachine,
+    UnsupportedType,
+    InvalidProgramHeader,
+    InterpreterRequired,
+    SegmentOutOfRange,
+    NoLoadableSegments,
+    StackOverflow,
+    NotUserTask,
+    Map(MapError),
+    Process(crate::process::ProcessError),
+}
+
+impl LoadError {
+    pub fn errno(self) -> Errno {
+        match self {
+            LoadError::NotFound => Errno::ENOENT,
+            LoadError::StackOverflow => Errno::E2BIG,
+            LoadError::NotUserTask => Errno::EPERM,
+            LoadError::Map(MapError::OutOfMemory) => Errno::ENOMEM,
+            LoadError::Map(MapError::WriteExecute) => Errno::EACCES,
+            LoadError::Map(_) => Errno::EFAULT,
+            LoadError::Process(err) => err.errno(),
+            _ => Errno::ENOEXEC,
+        }
+    }
+}
+
+impl From<MapError> for LoadError {
+    fn from(err: MapError) -> Self {
+        LoadError::Map(err)
+    }
+}
+
+pub struct LoadedImage {
+    pub address_space: PageMapper,
+    pub entry: u64,
+    pub stack_top: u64,
+    pub program_break: u64,
+}
+
+pub fn load(data: &[u8], argv: &[&str], envp: &[&str]) -> Result<LoadedImage, LoadError> {
+    let elf = ElfFile::parse(data)?;
+    let mut address_space = PageMapper::new()?;
+
+    match populate(&elf, &mut address_space, argv, envp) {
+        Ok(image) => Ok(image),
+        Err(err) => {
+            crate::vm::destroy_address_space(address_space);
+            Err(err)
+        }
+    }
+}
+
+pub fn load_file(path: &str, argv: &[&str], envp: &[&str]) -> Result<LoadedImage, LoadError> {
+    let data = crate::filesystem::read_entire_file(path).ok_or(LoadError::NotFound)?;
+    load(&data, argv, envp)
+}
+
+pub fn spawn(path: &str, argv: &[&str], envp: &[&str]) -> Result<usize, LoadError> {
+    spawn_with_personality(path, argv, envp, Personality::Native)
+}
+
+pub fn spawn_with_personality(path: &str, argv: &[&str], envp: &[&str], personality: Personality) -> Result<usize, LoadError> {
+    let image = load_file(path, argv, envp)?;
+    let entry = image.entry;
+    let pid = crate::process::spawn(path, image, personality).map_err(LoadError::Process)?;
+    vga::print!("Started {} as process {} (entry 0x{:x})\n", path, pid, entry);
+    Ok(pid)
+}
+
+pub fn exec(path: &str, argv: &[&str], envp: &[&str]) -> Result<(), LoadError> {
+    let image = load_file(path, argv, envp)?;
+
+    match crate::scheduler::exec_current(image.entry, image.stack_top, image.address_space) {
+        Ok(Some(previous)) => {
+            crate::process::replace_image(image.address_space, image.program_break);
+            crate::vm::destroy_address_space(previous);
+            Ok(())
+        }
+        Ok(None) => {
+            crate::process::replace_image(image.address_space, image.program_break);
+            Ok(())
+        }
+        Err(address_space) => {
+            crate::vm::destroy_address_space(address_space);
+            Err(LoadError::NotUserTask)
+        }
+    }
+}
+
+fn populate(elf: &ElfFile, address_space: &mut PageMapper, argv: &[&str], envp: &[&str]) -> Result<LoadedImage, LoadError> {
+    let bias = if elf.is_position_independent() { PIE_LOAD_BASE } else { 0 };
+    let mut program_break = 0;
+    let mut loaded = 0;
+
+    for segment in elf.program_headers() {
+        match segment.segment_type {
+            PT_INTERP => return Err(LoadError::InterpreterRequired),
+            PT_LOAD => {
+                let end = map_segment(elf, &segment, bias, address_space)?;
+                program_break = program_break.max(page_align_up(end));
+                loaded += 1;
+            }
+            _ => {}
+        }
+    }
+
+    if loaded == 0 {
+        return Err(LoadError::NoLoadableSegments);
+    }
+
+    let entry = elf.header.entry + bias;
+    if entry >= USER_SPACE_END {
+        return Err(LoadError::SegmentOutOfRange);
+    }
+
+    register_region(address_space, USER_STACK_TOP - DEFAULT_USER_STACK_PAGES * PAGE_SIZE, USER_STACK_TOP, PROT_READ | PROT_WRITE);
+    let auxv = [
+        (AT_PHDR, program_headers_address(elf, bias)),
+        (AT_PHENT, elf.header.phentsize as u64),
+        (AT_PHNUM, elf.header.phnum as u64),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_ENTRY, entry),
+        (AT_UID, 0),
+        (AT_EUID, 0),
+        (AT_GID, 0),
+        (AT_EGID, 0),
+        (AT_SECURE, 0),
+    ];
+    let stack_top = build_initial_stack(address_space, argv, envp, &auxv)?;
+
+    Ok(LoadedImage {
+        address_space: *address_space,
+        entry,
+        stack_top,
+        program_break,
+    })
+}
+
+fn map_segment(elf: &ElfFile, segment: &ProgramHeader, bias: u64, address_space: &mut PageMapper) -> Result<u64, LoadError> {
+    if segment.filesz > segment.memsz {
+        return Err(LoadError::InvalidProgramHeader);
+    }
+    match segment.offset.checked_add(segment.filesz) {
+        Some(end) if end <= elf.data.len() as u64 => {}
+        _ => return Err(LoadError::Truncated),
+    }
+
+    let start = segment.vaddr.checked_add(bias).ok_or(LoadError::SegmentOutOfRange)?;
+    let end = start.checked_add(segment.memsz).ok_or(LoadError::SegmentOutOfRange)?;
+    if start < PAGE_SIZE || end > USER_SPACE_END {
+        return Err(LoadError::SegmentOutOfRange);
+    }
+
+    let flags = segment_flags(segment.flags);
+    let file_end = page_align_up(start + segment.filesz);
+    let mut page = start & !(PAGE_SIZE - 1);
+    while page < file_end {
+        match address_space.flags(page) {
+            Some(existing) => {
+                let merged = ((existing | flags) & !PAGE_NX) | (existing & flags & PAGE_NX);
+                address_space.protect(page, merged)?;
+            }
+            None => {
+                let frame = frame_allocator::allocate_frame().ok_or(MapError::OutOfMemory)?;
+                unsafe {
+                    core::ptr::write_bytes(paging::phys_to_virt(frame) as *mut u8, 0, PAGE_SIZE as usize);
+                }
+                address_space.map(page, frame, flags)?;
+            }
+        }
+        page += PAGE_SIZE;
+    }
+    register_region(address_space, start & !(PAGE_SIZE - 1), page_align_up(end), segment_prot(segment.flags));
+
+    let file_data = &elf.data[segment.offset as usize..(segment.offset + segment.filesz) as usize];
+    write_user(address_space, start, file_data)?;
+
+    let bss_start = start + segment.filesz;
+    let bss_page_end = page_align_up(bss_start).min(end);
+    if bss_page_end > bss_start {
+        let zeroes = [0u8; PAGE_SIZE as usize];
+        write_user(address_space, bss_start, &zeroes[..(bss_page_end - bss_start) as usize])?;
+    }
+
+    Ok(end)
+}
+
+fn segment_flags(elf_flags: u32) -> u64 {
+    let mut flags = PAGE_USER;
+    if elf_flags & PF_W != 0 {
+        flags |= PAGE_WRITABLE;
+    }
+    if elf_flags & PF_X == 0 {
+        flags |= PAGE_NX;
+    }
+    flags
+}
+
+fn segment_prot(elf_flags: u32) -> u64 {
+    let mut prot = PROT_READ;
+    if elf_flags & PF_W != 0 {
+        prot |= PROT_WRITE;
+    }
+    if elf_flags & PF_X != 0 {
+        prot |= PROT_EXEC;
+    }
+    prot
+}
+
+fn register_region(address_space: &PageMapper, start: u64, end: u64, prot: u64) {
+    let mut manager = crate::vm::VM_MANAGER.lock();
+    let regions = manager.space_mut(address_space.root());
+    // Segments may share a boundary page with an earlier one; that overlap
+    // gains the extra permissions and only the rest becomes a new region.
+    let free = regions.free_ranges(start, end);
+    regions.update_range(start, end, |region| region.prot |= prot);
+    for (free_start, free_end) in free {
+        regions.insert(VirtualMemoryRegion::anonymous(free_start, free_end, prot, MAP_PRIVATE | MAP_ANONYMOUS));
+    }
+}
+
+fn program_headers_address(elf: &ElfFile, bias: u64) -> u64 {
+    let phoff = elf.header.phoff;
+    for segment in elf.program_headers() {
+        if segment.segment_type == PT_PHDR {
+            return segment.vaddr + bias;
+        }
+    }
+    for segment in elf.program_headers() {
+        if segment.segment_type == PT_LOAD && phoff >= segment.offset && phoff < segment.offset + segment.filesz {
+            return segment.vaddr + bias + (phoff - segment.offset);
+        }
+    }
+    0
+}
+
+fn build_initial_stack(address_space: &PageMapper, argv: &[&str], envp: &[&str], auxv: &[(u64, u64)]) -> Result<u64, LoadError> {
+    let limit = USER_STACK_TOP - DEFAULT_USER_STACK_PAGES * PAGE_SIZE;
+    let mut sp = USER_STACK_TOP;
+
+    let push_bytes = |bytes: &[u8], sp: &mut u64| -> Result<u64, LoadError> {
+        if *sp - limit < bytes.len() as u64 {
+            return Err(LoadError::StackOverflow);
+        }
+        *sp -= bytes.len() as u64;
+        write_user(address_space, *sp, bytes)?;
+        Ok(*sp)
+    };
+
+    let push_string = |string: &str, sp: &mut u64| -> Result<u64, LoadError> {
+        push_bytes(&[0], sp)?;
+        push_bytes(string.as_bytes(), sp)
+    };
+
+    let mut envp_pointers = Vec::with_capacity(envp.len());
+    for variable in envp.iter() {
+        envp_pointers.push(push_string(variable, &mut sp)?);
+    }
+    let mut argv_pointers = Vec::with_capacity(argv.len());
+    for argument in argv.iter() {
+        argv_pointers.push(push_string(argument, &mut sp)?);
+    }
+
+    let mut random = [0u8; 16];
+    random[..8].copy_from_slice(&crate::time::tsc::read().to_le_bytes());
+    random[8..].copy_from_slice(&crate::time::monotonic_ns().to_le_bytes());
+    sp &= !0xF;
+    let random_address = push_bytes(&random, &mut sp)?;
+
+    let mut words: Vec<u64> = Vec::new();
+    words.push(argv.len() as u64);
+    words.extend_from_slice(&argv_pointers);
+    words.push(0);
+    words.extend_from_slice(&envp_pointers);
+    words.push(0);
+    for &(key, value) in auxv.iter() {
+        words.push(key);
+        words.push(value);
+    }
+    words.push(AT_RANDOM);
+    words.push(random_address);
+    words.push(AT_NULL);
+    words.push(0);
+
+    let mut table = Vec::with_capacity(words.len() * 8);
+    for word in words.iter() {
+        table.extend_from_slice(&word.to_le_bytes());
+    }
+
+    sp = (sp - table.len() as u64) & !0xF;
+    if sp < limit {
+        return Err(LoadError::StackOverflow);
+    }
+    write_user(address_space, sp, &table)?;
+    Ok(sp)
+}
+
+fn write_user(address_space: &PageMapper, address: u64, bytes: &[u8]) -> Result<(), LoadError> {
+    let mut written = 0;
+    while written < bytes.len() {
+        let virtual_addr = address + written as u64;
+        let physical_addr = match address_space.translate(virtual_addr) {
+            Some(physical_addr) => physical_addr,
+            None => {
+                let mut mapper = *address_space;
+                if let Err(FaultError::OutOfMemory) = crate::vm::demand_paging::populate_address(&mut mapper, virtual_addr) {
+                    return Err(LoadError::Map(MapError::OutOfMemory));
+                }
+                address_space.translate(virtual_addr).ok_or(MapError::NotMapped)?
+            }
+        };
+        let chunk = core::cmp::min((PAGE_SIZE - virtual_addr % PAGE_SIZE) as usize, bytes.len() - written);
+
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                bytes[written..].as_ptr(),
+                paging::phys_to_virt(physical_addr) as *mut u8,
+                chunk,
+            );
+        }
+        written += chunk;
+    }
+    Ok(())
+}
+
+fn page_align_up(address: u64) -> u64 {
+    (address + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
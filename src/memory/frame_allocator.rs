@@ -0,0 +1,392 @@
+use crate::smp::synchronization::SpinLock;
+use crate::vga;
+use crate::MemoryMapEntry;
+use alloc::collections::BTreeMap;
+
+pub const FRAME_SIZE: u64 = 4096;
+pub const HUGE_FRAME_SIZE: u64 = 0x200000;
+const FRAMES_PER_HUGE_FRAME: usize = (HUGE_FRAME_SIZE / FRAME_SIZE) as usize;
+
+const MAX_PHYSICAL_MEMORY: u64 = 0x4_0000_0000;
+const MAX_FRAMES: usize = (MAX_PHYSICAL_MEMORY / FRAME_SIZE) as usize;
+const BITMAP_WORDS: usize = MAX_FRAMES / 64;
+
+const MEMORY_TYPE_LOADER_CODE: u32 = 1;
+const MEMORY_TYPE_LOADER_DATA: u32 = 2;
+const MEMORY_TYPE_BOOT_SERVICES_CODE: u32 = 3;
+const MEMORY_TYPE_BOOT_SERVICES_DATA: u32 = 4;
+const MEMORY_TYPE_CONVENTIONAL: u32 = 7;
+
+const LOW_MEMORY_LIMIT: u64 = 0x100000;
+
+#[derive(Clone, Copy)]
+pub struct ReservedRegion {
+    pub base: u64,
+    pub length: u64,
+}
+
+pub struct FrameAllocator {
+    bitmap: [u64; BITMAP_WORDS],
+    highest_frame: usize,
+    next_hint: usize,
+    total_frames: usize,
+    free_frames: usize,
+}
+
+impl FrameAllocator {
+    pub const fn new() -> Self {
+        Self {
+            bitmap: [u64::MAX; BITMAP_WORDS],
+            highest_frame: 0,
+            next_hint: 0,
+            total_frames: 0,
+            free_frames: 0,
+        }
+    }
+
+    pub fn init(&mut self, memory_map: &[MemoryMapEntry], reserved: &[ReservedRegion]) {
+        vga::print!("Initializing physical frame allocator...\n");
+
+        for entry in memory_map {
+            if entry.length == 0 || !is_usable(entry.type_) {
+                continue;
+            }
+
+            let start = align_up(entry.base, FRAME_SIZE);
+            let end = align_down(entry.base + entry.length, FRAME_SIZE);
+            let mut addr = start;
+            while addr < end {
+                self.release(addr);
+                addr += FRAME_SIZE;
+            }
+        }
+
+        self.total_frames = self.free_frames;
+
+        self.reserve_range(0, LOW_MEMORY_LIMIT);
+        for region in reserved {
+            self.reserve_range(region.base, region.length);
+        }
+
+        vga::print!("Physical memory: {} KiB usable, {} KiB free\n",
+            self.total_frames as u64 * FRAME_SIZE / 1024,
+            self.free_frames as u64 * FRAME_SIZE / 1024);
+    }
+
+    pub fn reserve_range(&mut self, base: u64, length: u64) {
+        if length == 0 {
+            return;
+        }
+
+        let start = align_down(base, FRAME_SIZE);
+        let end = align_up(base + length, FRAME_SIZE);
+        let mut addr = start;
+        while addr < end {
+            let frame = (addr / FRAME_SIZE) as usize;
+            if frame >= MAX_FRAMES {
+                break;
+            }
+            if !self.is_used(frame) {
+                self.set_used(frame);
+                self.free_frames -= 1;
+            }
+            addr += FRAME_SIZE;
+        }
+    }
+
+    pub fn allocate_frame(&mut self) -> Option<u64> {
+        let limit = self.highest_frame + 1;
+        let mut word = self.next_hint / 64;
+
+        for _ in 0..(limit + 63) / 64 {
+            if word * 64 >= limit {
+                word = 0;
+            }
+
+            if self.bitmap[word] != u64::MAX {
+                let bit = (!self.bitmap[word]).trailing_zeros() as usize;
+                let frame = word * 64 + bit;
+                if frame < limit {
+                    self.set_used(frame);
+                    self.free_frames -= 1;
+                    self.next_hint = frame + 1;
+                    return Some(frame as u64 * FRAME_SIZE);
+                }
+            }
+
+            word += 1;
+        }
+
+        None
+    }
+
+    pub fn allocate_huge_frame(&mut self) -> Option<u64> {
+        let mut frame = 0;
+
+        while frame + FRAMES_PER_HUGE_FRAME <= self.highest_frame + 1 {
+            let words = FRAMES_PER_HUGE_FRAME / 64;
+            let first_word = frame / 64;
+            let mut all_free = true;
+
+            for w in first_word..first_word + words {
+                if self.bitmap[w] != 0 {
+                    all_free = false;
+                    break;
+                }
+            }
+
+            if all_free {
+                for w in first_word..first_word + words {
+                    self.bitmap[w] = u64::MAX;
+                }
+                self.free_frames -= FRAMES_PER_HUGE_FRAME;
+                return Some(frame as u64 * FRAME_SIZE);
+            }
+
+            frame += FRAMES_PER_HUGE_FRAME;
+        }
+
+        None
+    }
+
+    pub fn free_frame(&mut self, addr: u64) {
+        let frame = (addr / FRAME_SIZE) as usize;
+        if addr % FRAME_SIZE != 0 || frame >= MAX_FRAMES || !self.is_used(frame) {
+            vga::print!("Invalid frame free at 0x{:x}\n", addr);
+            return;
+        }
+
+        self.release(addr);
+        if frame < self.next_hint {
+            self.next_hint = frame;
+        }
+    }
+
+    pub fn free_huge_frame(&mut self, addr: u64) {
+        if addr % HUGE_FRAME_SIZE != 0 {
+            vga::print!("Invalid huge frame free at 0x{:x}\n", addr);
+            return;
+        }
+
+        for i in 0..FRAMES_PER_HUGE_FRAME as u64 {
+            self.free_frame(addr + i * FRAME_SIZE);
+        }
+    }
+
+    pub fn total_frames(&self) -> usize {
+        self.total_frames
+    }
+
+    pub fn free_frames(&self) -> usize {
+        self.free_frames
+    }
+
+    fn release(&mut self, addr: u64) {
+        let frame = (addr / FRAME_SIZE) as usize;
+        if frame >= MAX_FRAMES || !self.is_used(frame) {
+            return;
+        }
+
+        self.bitmap[frame / 64] &= !(1 << (frame % 64));
+        self.free_frames += 1;
+        if frame > self.highest_frame {
+            self.highest_frame = frame;
+        }
+    }
+
+    fn is_used(&self, frame: usize) -> bool {
+        self.bitmap[frame / 64] & (1 << (frame % 64)) != 0
+    }
+
+    fn set_used(&mut self, frame: usize) {
+        self.bitmap[frame / 64] |= 1 << (frame % 64);
+    }
+}
+
+fn is_usable(memory_type: u32) -> bool {
+    matches!(
+        memory_type,
+        MEMORY_TYPE_LOADER_CODE
+            | MEMORY_TYPE_LOADER_DATA
+            | MEMORY_TYPE_BOOT_SERVICES_CODE
+            | MEMORY_TYPE_BOOT_SERVICES_DATA
+            | MEMORY_TYPE_CONVENTIONAL
+    )
+}
+
+fn align_up(addr: u64, align: u64) -> u64 {
+    (addr + align - 1) & !(align - 1)
+}
+
+fn align_down(addr: u64, align: u64) -> u64 {
+    addr & !(align - 1)
+}
+
+// Frames mapped once are not tracked; an entry exists only while a frame has
+// two or more references.
+pub struct FrameReferences {
+    shared: BTreeMap<u64, usize>,
+}
+
+impl FrameReferences {
+    pub const fn new() -> Self {
+        Self {
+            shared: BTreeMap::new(),
+        }
+    }
+
+    pub fn share(&mut self, addr: u64) {
+        *self.shared.entry(addr).or_insert(1) += 1;
+    }
+
+    pub fn count(&self, addr: u64) -> usize {
+        self.shared.get(&addr).copied().unwrap_or(1)
+    }
+
+    pub fn release(&mut self, addr: u64) -> bool {
+        match self.shared.get_mut(&addr) {
+            Some(count) => {
+                *count -= 1;
+                if *count == 1 {
+                    self.shared.remove(&addr);
+                }
+                false
+            }
+            None => true,
+        }
+    }
+}
+
+pub static FRAME_ALLOCATOR: SpinLock<FrameAllocator> = SpinLock::new(FrameAllocator::new());
+static SHARED_FRAMES: SpinLock<FrameReferences> = SpinLock::new(FrameReferences::new());
+
+pub fn init(memory_map: &[MemoryMapEntry], reserved: &[ReservedRegion]) {
+    FRAME_ALLOCATOR.lock().init(memory_map, reserved);
+}
+
+pub fn allocate_frame() -> Option<u64> {
+    FRAME_ALLOCATOR.lock().allocate_frame()
+}
+
+pub fn allocate_huge_frame() -> Option<u64> {
+    FRAME_ALLOCATOR.lock().allocate_huge_frame()
+}
+
+pub fn free_frame(addr: u64) {
+    FRAME_ALLOCATOR.lock().free_frame(addr);
+}
+
+pub fn free_huge_frame(addr: u64) {
+    FRAME_ALLOCATOR.lock().free_huge_frame(addr);
+}
+
+pub fn share_frame(addr: u64) {
+    SHARED_FRAMES.lock().share(addr);
+}
+
+pub fn frame_references(addr: u64) -> usize {
+    SHARED_FRAMES.lock().count(addr)
+}
+
+pub fn release_frame(addr: u64) {
+    let last_reference = SHARED_FRAMES.lock().release(addr);
+    if last_reference {
+        free_frame(addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+
+    const BASE: u64 = 0x4000_0000;
+
+    fn allocator_with_free(base: u64, frames: u64) -> Box<FrameAllocator> {
+        let mut allocator: Box<FrameAllocator> = unsafe { Box::new_zeroed().assume_init() };
+        allocator.bitmap.fill(u64::MAX);
+        for frame in 0..frames {
+            allocator.release(base + frame * FRAME_SIZE);
+        }
+        allocator.total_frames = allocator.free_frames;
+        allocator
+    }
+
+    #[test]
+    fn allocates_distinct_frames_until_exhausted() {
+        let mut allocator = allocator_with_free(BASE, 8);
+        let mut frames = Vec::new();
+        while let Some(frame) = allocator.allocate_frame() {
+            assert!(frame >= BASE && frame < BASE + 8 * FRAME_SIZE);
+            assert_eq!(frame % FRAME_SIZE, 0);
+            assert!(!frames.contains(&frame));
+            frames.push(frame);
+        }
+
+        assert_eq!(frames.len(), 8);
+        assert_eq!(allocator.free_frames(), 0);
+        assert_eq!(allocator.total_frames(), 8);
+    }
+
+    #[test]
+    fn freed_frame_is_reused() {
+        let mut allocator = allocator_with_free(BASE, 4);
+        let first = allocator.allocate_frame().unwrap();
+        let second = allocator.allocate_frame().unwrap();
+        allocator.free_frame(first);
+
+        assert_eq!(allocator.free_frames(), 3);
+        assert_eq!(allocator.allocate_frame(), Some(first));
+        assert_ne!(allocator.allocate_frame(), Some(second));
+    }
+
+    #[test]
+    fn reserved_ranges_cover_partial_frames() {
+        let mut allocator = allocator_with_free(BASE, 4);
+        allocator.reserve_range(BASE + FRAME_SIZE + 0x10, FRAME_SIZE);
+
+        assert_eq!(allocator.free_frames(), 2);
+        let mut frames = Vec::new();
+        while let Some(frame) = allocator.allocate_frame() {
+            frames.push(frame);
+        }
+        assert_eq!(frames, [BASE, BASE + 3 * FRAME_SIZE]);
+    }
+
+    #[test]
+    fn huge_frames_need_an_aligned_free_run() {
+        let mut allocator = allocator_with_free(BASE, FRAMES_PER_HUGE_FRAME as u64 + 1);
+        let huge = allocator.allocate_huge_frame().unwrap();
+
+        assert_eq!(huge, BASE);
+        assert_eq!(allocator.free_frames(), 1);
+        assert_eq!(allocator.allocate_huge_frame(), None);
+
+        allocator.free_huge_frame(huge);
+        assert_eq!(allocator.free_frames(), FRAMES_PER_HUGE_FRAME + 1);
+    }
+
+    #[test]
+    fn huge_frame_is_refused_when_one_frame_is_used() {
+        let mut allocator = allocator_with_free(BASE, FRAMES_PER_HUGE_FRAME as u64);
+        allocator.reserve_range(BASE + 17 * FRAME_SIZE, FRAME_SIZE);
+
+        assert_eq!(allocator.allocate_huge_frame(), None);
+        assert!(allocator.allocate_frame().is_some());
+    }
+
+    #[test]
+    fn shared_frame_is_released_on_last_reference() {
+        let mut references = FrameReferences::new();
+        assert_eq!(references.count(BASE), 1);
+
+        references.share(BASE);
+        references.share(BASE);
+        assert_eq!(references.count(BASE), 3);
+
+        assert!(!references.release(BASE));
+        assert!(!references.release(BASE));
+        assert_eq!(references.count(BASE), 1);
+        assert!(references.release(BASE));
+    }
+}
@@ -1,55 +1,355 @@
+use crate::smp::synchronization::SpinLock;
 use core::alloc::{GlobalAlloc, Layout};
-use core::ptr::NonNull;
+use core::mem;
+use core::ptr;
 
-pub struct BumpAllocator {
-    heap_start: usize,
-    heap_end: usize,
-    next: usize,
+pub const HEAP_START: usize = 0xFFFF_C000_0000_0000;
+pub const HEAP_MAX_SIZE: usize = 0x4000000;
+const HEAP_INITIAL_SIZE: usize = 0x100000;
+const HEAP_GROW_SIZE: usize = 0x10000;
+const PAGE_SIZE: usize = 4096;
+
+const BLOCK_ALIGN: usize = 16;
+
+#[repr(C, align(16))]
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+const MIN_BLOCK_SIZE: usize = mem::size_of::<FreeBlock>();
+
+pub struct Heap {
+    head: *mut FreeBlock,
+    size: usize,
+    used: usize,
+}
+
+unsafe impl Send for Heap {}
+
+impl Heap {
+    pub const fn empty() -> Self {
+        Self {
+            head: ptr::null_mut(),
+            size: 0,
+            used: 0,
+        }
+    }
+
+    pub unsafe fn add_region(&mut self, start: usize, size: usize) {
+        let aligned_start = align_up(start, BLOCK_ALIGN);
+        if aligned_start >= start + size {
+            return;
+        }
+
+        let aligned_size = (start + size - aligned_start) & !(BLOCK_ALIGN - 1);
+        if aligned_size < MIN_BLOCK_SIZE {
+            return;
+        }
+
+        self.size += aligned_size;
+        self.insert_free(aligned_start, aligned_size);
+    }
+
+    pub fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let (size, align) = block_layout(layout);
+
+        let mut prev: *mut FreeBlock = ptr::null_mut();
+        let mut current = self.head;
+
+        unsafe {
+            while !current.is_null() {
+                let block_start = current as usize;
+                let block_end = block_start + (*current).size;
+                let alloc_start = align_up(block_start, align);
+                let alloc_end = alloc_start + size;
+
+                if alloc_end <= block_end {
+                    let front = alloc_start - block_start;
+                    let back = block_end - alloc_end;
+                    let next = (*current).next;
+
+                    if front == 0 {
+                        if prev.is_null() {
+                            self.head = next;
+                        } else {
+                            (*prev).next = next;
+                        }
+                    } else {
+                        (*current).size = front;
+                    }
+
+                    if back > 0 {
+                        let tail = alloc_end as *mut FreeBlock;
+                        (*tail).size = back;
+                        if front == 0 {
+                            (*tail).next = next;
+                            if prev.is_null() {
+                                self.head = tail;
+                            } else {
+                                (*prev).next = tail;
+                            }
+                        } else {
+                            (*tail).next = next;
+                            (*current).next = tail;
+                        }
+                    }
+
+                    self.used += size;
+                    return alloc_start as *mut u8;
+                }
+
+                prev = current;
+                current = (*current).next;
+            }
+        }
+
+        ptr::null_mut()
+    }
+
+    pub unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = block_layout(layout);
+        self.used -= size;
+        self.insert_free(ptr as usize, size);
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn free(&self) -> usize {
+        self.size - self.used
+    }
+
+    unsafe fn insert_free(&mut self, addr: usize, size: usize) {
+        let mut prev: *mut FreeBlock = ptr::null_mut();
+        let mut current = self.head;
+
+        while !current.is_null() && (current as usize) < addr {
+            prev = current;
+            current = (*current).next;
+        }
+
+        let block = addr as *mut FreeBlock;
+        (*block).size = size;
+        (*block).next = current;
+
+        if !current.is_null() && addr + size == current as usize {
+            (*block).size += (*current).size;
+            (*block).next = (*current).next;
+        }
+
+        if prev.is_null() {
+            self.head = block;
+        } else if prev as usize + (*prev).size == addr {
+            (*prev).size += (*block).size;
+            (*prev).next = (*block).next;
+        } else {
+            (*prev).next = block;
+        }
+    }
+}
+
+fn block_layout(layout: Layout) -> (usize, usize) {
+    let align = layout.align().max(BLOCK_ALIGN);
+    let size = align_up(layout.size().max(MIN_BLOCK_SIZE), BLOCK_ALIGN);
+    (size, align)
 }
 
-impl BumpAllocator {
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+struct HeapState {
+    heap: Heap,
+    end: usize,
+}
+
+pub struct KernelHeap {
+    state: SpinLock<HeapState>,
+}
+
+impl KernelHeap {
     pub const fn new() -> Self {
         Self {
-            heap_start: 0,
-            heap_end: 0,
-            next: 0,
+            state: SpinLock::new(HeapState {
+                heap: Heap::empty(),
+                end: HEAP_START,
+            }),
         }
     }
-    
-    pub fn init(&mut self, heap_start: usize, heap_size: usize) {
-        self.heap_start = heap_start;
-        self.heap_end = heap_start + heap_size;
-        self.next = heap_start;
+
+    pub fn init(&self) {
+        self.with_heap(|heap, end| unsafe {
+            grow(heap, end, HEAP_INITIAL_SIZE);
+        });
+    }
+
+    pub fn stats(&self) -> (usize, usize) {
+        self.with_heap(|heap, _| (heap.size(), heap.used()))
+    }
+
+    fn with_heap<R>(&self, f: impl FnOnce(&mut Heap, &mut usize) -> R) -> R {
+        let mut state = self.state.lock();
+        let HeapState { heap, end } = &mut *state;
+        f(heap, end)
     }
 }
 
-unsafe impl GlobalAlloc for BumpAllocator {
+unsafe impl GlobalAlloc for KernelHeap {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let alloc_start = align_up(self.next, layout.align());
-        let alloc_end = alloc_start + layout.size();
-        
-        if alloc_end > self.heap_end {
-            return core::ptr::null_mut();
+        self.with_heap(|heap, end| {
+            let mut ptr = heap.allocate(layout);
+
+            if ptr.is_null() {
+                let needed = layout.size() + layout.align();
+                if grow(heap, end, needed.max(HEAP_GROW_SIZE)) {
+                    ptr = heap.allocate(layout);
+                }
+            }
+
+            report_usage(heap);
+            ptr
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.with_heap(|heap, _| {
+            heap.deallocate(ptr, layout);
+            report_usage(heap);
+        });
+    }
+}
+
+unsafe fn grow(heap: &mut Heap, end: &mut usize, size: usize) -> bool {
+    let size = align_up(size, PAGE_SIZE);
+    if *end + size > HEAP_START + HEAP_MAX_SIZE {
+        return false;
+    }
+
+    let start = *end;
+    let mut mapped = 0;
+    while mapped < size {
+        let frame = match super::frame_allocator::allocate_frame() {
+            Some(frame) => frame,
+            None => break,
+        };
+
+        let mapping = super::paging::PageMapper::kernel().map(
+            (start + mapped) as u64,
+            frame,
+            super::paging::PAGE_WRITABLE | super::paging::PAGE_GLOBAL | super::paging::PAGE_NX,
+        );
+        if mapping.is_err() {
+            super::frame_allocator::free_frame(frame);
+            break;
         }
-        
-        (alloc_start as *mut u8)
+        mapped += PAGE_SIZE;
     }
-    
-    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+
+    if mapped == 0 {
+        return false;
     }
+
+    heap.add_region(start, mapped);
+    *end = start + mapped;
+    true
 }
 
-fn align_up(addr: usize, align: usize) -> usize {
-    (addr + align - 1) & !(align - 1)
+fn report_usage(heap: &Heap) {
+    crate::performance::PERFORMANCE.update_memory_usage(heap.used() as u64);
 }
 
-pub static mut ALLOCATOR: BumpAllocator = BumpAllocator::new();
+#[cfg_attr(not(test), global_allocator)]
+pub static ALLOCATOR: KernelHeap = KernelHeap::new();
 
 pub fn init() {
-    unsafe {
-        ALLOCATOR.init(0x2000000, 0x1000000);
-    }
+    ALLOCATOR.init();
 }
 
-#[global_allocator]
-static ALLOCATOR_REF: BumpAllocator = unsafe { core::mem::transmute(ALLOCATOR) };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(align(4096))]
+    struct Arena([u8; 4096]);
+
+    fn heap_with_arena(arena: &mut Arena) -> Heap {
+        let mut heap = Heap::empty();
+        unsafe {
+            heap.add_region(arena.0.as_mut_ptr() as usize, arena.0.len());
+        }
+        heap
+    }
+
+    #[test]
+    fn allocations_do_not_overlap() {
+        let mut arena = Arena([0; 4096]);
+        let mut heap = heap_with_arena(&mut arena);
+        let layout = Layout::from_size_align(100, 8).unwrap();
+
+        let a = heap.allocate(layout) as usize;
+        let b = heap.allocate(layout) as usize;
+
+        assert_ne!(a, 0);
+        assert_ne!(b, 0);
+        assert!(a + 100 <= b || b + 100 <= a);
+    }
+
+    #[test]
+    fn respects_alignment() {
+        let mut arena = Arena([0; 4096]);
+        let mut heap = heap_with_arena(&mut arena);
+
+        heap.allocate(Layout::from_size_align(24, 8).unwrap());
+        let ptr = heap.allocate(Layout::from_size_align(64, 256).unwrap()) as usize;
+
+        assert_ne!(ptr, 0);
+        assert_eq!(ptr % 256, 0);
+    }
+
+    #[test]
+    fn freed_memory_is_reused_and_coalesced() {
+        let mut arena = Arena([0; 4096]);
+        let mut heap = heap_with_arena(&mut arena);
+        let layout = Layout::from_size_align(1024, 16).unwrap();
+
+        let blocks = [
+            heap.allocate(layout),
+            heap.allocate(layout),
+            heap.allocate(layout),
+            heap.allocate(layout),
+        ];
+        assert!(blocks.iter().all(|b| !b.is_null()));
+        assert!(heap.allocate(layout).is_null());
+
+        unsafe {
+            heap.deallocate(blocks[1], layout);
+            heap.deallocate(blocks[0], layout);
+            heap.deallocate(blocks[2], layout);
+        }
+
+        let big = heap.allocate(Layout::from_size_align(3072, 16).unwrap());
+        assert_eq!(big, blocks[0]);
+    }
+
+    #[test]
+    fn tracks_usage() {
+        let mut arena = Arena([0; 4096]);
+        let mut heap = heap_with_arena(&mut arena);
+        let layout = Layout::from_size_align(10, 1).unwrap();
+
+        let ptr = heap.allocate(layout);
+        assert_eq!(heap.used(), 16);
+
+        unsafe {
+            heap.deallocate(ptr, layout);
+        }
+        assert_eq!(heap.used(), 0);
+        assert_eq!(heap.free(), heap.size());
+    }
+}
@@ -1,18 +1,60 @@
 use crate::HandoffData;
+use crate::vga;
 
 pub mod paging;
 pub mod heap;
+pub mod frame_allocator;
+
+use frame_allocator::ReservedRegion;
 
 static mut MEMORY_MAP: [crate::MemoryMapEntry; 256] = [crate::MemoryMapEntry { base: 0, length: 0, type_: 0 }; 256];
 
+extern "C" {
+    static __kernel_start: u8;
+    static __kernel_end: u8;
+}
+
 pub fn init(handoff: *const HandoffData) {
     unsafe {
-        MEMORY_MAP = handoff.memory_map;
-        paging::init();
+        let handoff_data = &*handoff;
+        MEMORY_MAP = core::ptr::read(&handoff_data.memory_map);
+
+        let kernel_size = &__kernel_end as *const u8 as u64 - &__kernel_start as *const u8 as u64;
+        let framebuffer = &handoff_data.framebuffer;
+
+        let reserved = [
+            ReservedRegion { base: handoff_data.kernel_physical, length: kernel_size },
+            ReservedRegion { base: handoff as u64, length: core::mem::size_of::<HandoffData>() as u64 },
+            ReservedRegion { base: framebuffer.base, length: framebuffer.pitch as u64 * framebuffer.height as u64 },
+        ];
+
+        frame_allocator::init(get_memory_map(), &reserved);
+        reserve_firmware_tables(handoff_data);
+        vga::print!("Reserved kernel image: 0x{:x} ({} bytes)\n", handoff_data.kernel_physical, kernel_size);
+
+        paging::init(handoff_data.kernel_physical, physical_memory_end());
         heap::init();
     }
 }
 
+// The firmware tables are read again once ACPI and SMBIOS initialize, and
+// they can span several frames or sit in memory the map reports as usable.
+fn reserve_firmware_tables(handoff_data: &HandoffData) {
+    let mut allocator = frame_allocator::FRAME_ALLOCATOR.lock();
+    crate::acpi::reserve_tables(handoff_data.acpi_rsdp, |base, length| allocator.reserve_range(base, length));
+    crate::smbios::reserve_tables(handoff_data.smbios_entry, |base, length| allocator.reserve_range(base, length));
+}
+
+fn physical_memory_end() -> u64 {
+    let mut end = 0;
+    for entry in get_memory_map() {
+        if entry.length != 0 && entry.base + entry.length > end {
+            end = entry.base + entry.length;
+        }
+    }
+    end
+}
+
 pub fn get_memory_map() -> &'static [crate::MemoryMapEntry] {
     unsafe { &MEMORY_MAP }
 }
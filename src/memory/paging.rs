@@ -1,14 +1,42 @@
+use crate::smp::synchronization::SpinLock;
+use crate::vga;
 use core::arch::asm;
+use core::sync::atomic::{AtomicU64, Ordering};
 
-const PAGE_SIZE: u64 = 4096;
-const PAGE_PRESENT: u64 = 1 << 0;
-const PAGE_WRITABLE: u64 = 1 << 1;
-const PAGE_USER: u64 = 1 << 2;
-const PAGE_SIZE_2MB: u64 = 1 << 7;
-const PAGE_NX: u64 = 1 << 63;
+pub const PAGE_SIZE: u64 = 4096;
+pub const HUGE_PAGE_SIZE: u64 = 0x200000;
+pub const PAGE_PRESENT: u64 = 1 << 0;
+pub const PAGE_WRITABLE: u64 = 1 << 1;
+pub const PAGE_USER: u64 = 1 << 2;
+pub const PAGE_WRITE_THROUGH: u64 = 1 << 3;
+pub const PAGE_NO_CACHE: u64 = 1 << 4;
+pub const PAGE_ACCESSED: u64 = 1 << 5;
+pub const PAGE_DIRTY: u64 = 1 << 6;
+pub const PAGE_SIZE_2MB: u64 = 1 << 7;
+pub const PAGE_GLOBAL: u64 = 1 << 8;
+pub const PAGE_COW: u64 = 1 << 9;
+pub const PAGE_SHARED: u64 = 1 << 10;
+pub const PAGE_PKEY_SHIFT: u64 = 59;
+pub const PAGE_PKEY_MASK: u64 = 0xF << PAGE_PKEY_SHIFT;
+pub const PAGE_NX: u64 = 1 << 63;
 
+pub const PAGE_FLAGS_MASK: u64 = PAGE_WRITABLE | PAGE_USER | PAGE_WRITE_THROUGH | PAGE_NO_CACHE | PAGE_GLOBAL | PAGE_COW | PAGE_SHARED | PAGE_PKEY_MASK | PAGE_NX;
+const ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+const HUGE_ADDRESS_MASK: u64 = 0x000F_FFFF_FFE0_0000;
+const TABLE_FLAGS: u64 = PAGE_PRESENT | PAGE_WRITABLE | PAGE_USER;
+
+pub const PHYSICAL_MEMORY_OFFSET: u64 = 0xFFFF_8000_0000_0000;
+const MIN_DIRECT_MAP_SIZE: u64 = 0x1_0000_0000;
+
+const EFER_MSR: u32 = 0xC000_0080;
+const EFER_NXE: u64 = 1 << 11;
+const CR0_WP: u64 = 1 << 16;
+
+pub const TLB_SHOOTDOWN_VECTOR: u8 = 0xFD;
+
+#[repr(C, align(4096))]
 pub struct PageTable {
-    entries: [u64; 512],
+    pub entries: [u64; 512],
 }
 
 impl PageTable {
@@ -17,59 +45,668 @@ impl PageTable {
     }
 }
 
-static mut PML4: PageTable = PageTable::new();
-static mut PDPT: PageTable = PageTable::new();
-static mut PD: [PageTable; 4] = [PageTable::new(); 4];
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MapError {
+    AlreadyMapped,
+    NotMapped,
+    OutOfMemory,
+    Misaligned,
+    WriteExecute,
+}
 
-pub fn init() {
-    unsafe {
-        PML4.entries[0] = (&PDPT as *const PageTable as u64) | PAGE_PRESENT | PAGE_WRITABLE;
-        
-        for i in 0..4 {
-            PD[i].entries[0] = 0x00000000 | PAGE_PRESENT | PAGE_WRITABLE | PAGE_SIZE_2MB;
-            PD[i].entries[1] = 0x20000000 | PAGE_PRESENT | PAGE_WRITABLE | PAGE_SIZE_2MB;
-            PD[i].entries[2] = 0x40000000 | PAGE_PRESENT | PAGE_WRITABLE | PAGE_SIZE_2MB;
-            PD[i].entries[3] = 0x60000000 | PAGE_PRESENT | PAGE_WRITABLE | PAGE_SIZE_2MB;
-            
-            PDPT.entries[i] = (&PD[i] as *const PageTable as u64) | PAGE_PRESENT | PAGE_WRITABLE;
+#[derive(Clone, Copy)]
+pub struct PageMapper {
+    root: u64,
+}
+
+impl PageMapper {
+    pub fn new() -> Result<Self, MapError> {
+        let root = allocate_table()?;
+
+        unsafe {
+            let kernel = table_at(KERNEL_ROOT.load(Ordering::Relaxed));
+            let table = table_at(root);
+            for i in 256..512 {
+                table.entries[i] = kernel.entries[i];
+            }
+        }
+
+        Ok(Self { root })
+    }
+
+    pub const fn from_root(root: u64) -> Self {
+        Self { root }
+    }
+
+    pub fn kernel() -> Self {
+        Self { root: KERNEL_ROOT.load(Ordering::Relaxed) }
+    }
+
+    pub fn current() -> Self {
+        Self { root: read_cr3() & ADDRESS_MASK }
+    }
+
+    pub fn root(&self) -> u64 {
+        self.root
+    }
+
+    pub fn is_active(&self) -> bool {
+        read_cr3() & ADDRESS_MASK == self.root
+    }
+
+    pub fn activate(&self) {
+        load_cr3(self.root);
+    }
+
+    pub fn map(&mut self, virtual_addr: u64, physical_addr: u64, flags: u64) -> Result<(), MapError> {
+        if virtual_addr % PAGE_SIZE != 0 || physical_addr % PAGE_SIZE != 0 {
+            return Err(MapError::Misaligned);
+        }
+        if !crate::vm::protection::check_mapping(virtual_addr, flags) {
+            return Err(MapError::WriteExecute);
+        }
+
+        let _tables = PAGE_TABLE_LOCK.lock();
+        self.map_unlocked(virtual_addr, physical_addr, flags)
+    }
+
+    fn map_unlocked(&mut self, virtual_addr: u64, physical_addr: u64, flags: u64) -> Result<(), MapError> {
+        let pd = self.walk_create(virtual_addr, 2)?;
+        let pd_index = table_index(virtual_addr, 1);
+        if pd.entries[pd_index] & PAGE_SIZE_2MB != 0 {
+            split_huge_page(&mut pd.entries[pd_index])?;
+        }
+
+        let pt = next_table_create(&mut pd.entries[pd_index])?;
+        let entry = &mut pt.entries[table_index(virtual_addr, 0)];
+        if *entry & PAGE_PRESENT != 0 {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        *entry = physical_addr | (flags & PAGE_FLAGS_MASK) | PAGE_PRESENT;
+        Ok(())
+    }
+
+    pub fn map_huge(&mut self, virtual_addr: u64, physical_addr: u64, flags: u64) -> Result<(), MapError> {
+        if virtual_addr % HUGE_PAGE_SIZE != 0 || physical_addr % HUGE_PAGE_SIZE != 0 {
+            return Err(MapError::Misaligned);
+        }
+        if !crate::vm::protection::check_mapping(virtual_addr, flags) {
+            return Err(MapError::WriteExecute);
+        }
+
+        let _tables = PAGE_TABLE_LOCK.lock();
+        let pd = self.walk_create(virtual_addr, 2)?;
+        let entry = &mut pd.entries[table_index(virtual_addr, 1)];
+        if *entry & PAGE_PRESENT != 0 {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        *entry = physical_addr | (flags & PAGE_FLAGS_MASK) | PAGE_PRESENT | PAGE_SIZE_2MB;
+        Ok(())
+    }
+
+    pub fn unmap(&mut self, virtual_addr: u64) -> Result<u64, MapError> {
+        let physical_addr = self.unmap_no_flush(virtual_addr)?;
+        self.flush(virtual_addr);
+        Ok(physical_addr)
+    }
+
+    // The caller must flush_range() the page before its frame is reused.
+    pub fn unmap_no_flush(&mut self, virtual_addr: u64) -> Result<u64, MapError> {
+        let _tables = PAGE_TABLE_LOCK.lock();
+        let entry = self.leaf_entry_split(virtual_addr)?;
+        let physical_addr = *entry & ADDRESS_MASK;
+        *entry = 0;
+        Ok(physical_addr)
+    }
+
+    pub fn unmap_huge(&mut self, virtual_addr: u64) -> Result<u64, MapError> {
+        let _tables = PAGE_TABLE_LOCK.lock();
+        let pd = self.walk(virtual_addr, 2).ok_or(MapError::NotMapped)?;
+        let entry = &mut pd.entries[table_index(virtual_addr, 1)];
+        if *entry & PAGE_PRESENT == 0 || *entry & PAGE_SIZE_2MB == 0 {
+            return Err(MapError::NotMapped);
+        }
+
+        let physical_addr = *entry & HUGE_ADDRESS_MASK;
+        *entry = 0;
+
+        self.flush(virtual_addr);
+        Ok(physical_addr)
+    }
+
+    pub fn protect(&mut self, virtual_addr: u64, flags: u64) -> Result<(), MapError> {
+        self.protect_no_flush(virtual_addr, flags)?;
+        self.flush(virtual_addr);
+        Ok(())
+    }
+
+    pub fn protect_no_flush(&mut self, virtual_addr: u64, flags: u64) -> Result<(), MapError> {
+        if !crate::vm::protection::check_mapping(virtual_addr, flags) {
+            return Err(MapError::WriteExecute);
+        }
+        let _tables = PAGE_TABLE_LOCK.lock();
+        let entry = self.leaf_entry_split(virtual_addr)?;
+        *entry = (*entry & !PAGE_FLAGS_MASK) | (flags & PAGE_FLAGS_MASK);
+        Ok(())
+    }
+
+    pub fn remap(&mut self, virtual_addr: u64, physical_addr: u64, flags: u64) -> Result<u64, MapError> {
+        if !crate::vm::protection::check_mapping(virtual_addr, flags) {
+            return Err(MapError::WriteExecute);
+        }
+        let _tables = PAGE_TABLE_LOCK.lock();
+        let entry = self.leaf_entry_split(virtual_addr)?;
+        let previous = *entry & ADDRESS_MASK;
+        *entry = physical_addr | (flags & PAGE_FLAGS_MASK) | PAGE_PRESENT;
+
+        self.flush(virtual_addr);
+        Ok(previous)
+    }
+
+    pub fn clone_cow(&mut self) -> Result<PageMapper, MapError> {
+        let mut child = PageMapper::new()?;
+        if let Err(err) = self.share_user_pages(&mut child) {
+            child.destroy();
+            return Err(err);
+        }
+
+        if self.is_active() {
+            self.activate();
+        }
+        Ok(child)
+    }
+
+    fn share_user_pages(&mut self, child: &mut PageMapper) -> Result<(), MapError> {
+        let pml4 = unsafe { table_at(self.root) };
+        for (i4, pml4_entry) in pml4.entries[..256].iter().enumerate() {
+            if *pml4_entry & PAGE_PRESENT == 0 {
+                continue;
+            }
+
+            let pdpt = unsafe { table_at(*pml4_entry & ADDRESS_MASK) };
+            for (i3, pdpt_entry) in pdpt.entries.iter().enumerate() {
+                if *pdpt_entry & PAGE_PRESENT == 0 {
+                    continue;
+                }
+
+                let pd = unsafe { table_at(*pdpt_entry & ADDRESS_MASK) };
+                for (i2, pd_entry) in pd.entries.iter_mut().enumerate() {
+                    {
+                        let _tables = PAGE_TABLE_LOCK.lock();
+                        if *pd_entry & PAGE_PRESENT == 0 {
+                            continue;
+                        }
+                        if *pd_entry & PAGE_SIZE_2MB != 0 {
+                            split_huge_page(pd_entry)?;
+                        }
+                    }
+
+                    let pt = unsafe { table_at(*pd_entry & ADDRESS_MASK) };
+                    for (i1, entry) in pt.entries.iter_mut().enumerate() {
+                        let virtual_addr = (i4 as u64) << 39 | (i3 as u64) << 30 | (i2 as u64) << 21 | (i1 as u64) << 12;
+                        let shared = {
+                            let _tables = PAGE_TABLE_LOCK.lock();
+                            if *entry == 0 {
+                                continue;
+                            }
+                            if *entry & PAGE_PRESENT == 0 {
+                                child.walk_create(virtual_addr, 3)?.entries[i1] = *entry;
+                            } else {
+                                if *entry & PAGE_SHARED == 0 && *entry & (PAGE_WRITABLE | PAGE_COW) != 0 {
+                                    *entry = (*entry & !PAGE_WRITABLE) | PAGE_COW;
+                                }
+                                child.map_unlocked(virtual_addr, *entry & ADDRESS_MASK, *entry)?;
+                            }
+                            *entry
+                        };
+
+                        if shared & PAGE_PRESENT == 0 {
+                            crate::vm::swapping::share_entry(shared);
+                        } else {
+                            super::frame_allocator::share_frame(shared & ADDRESS_MASK);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn translate(&self, virtual_addr: u64) -> Option<u64> {
+        let (entry, huge) = self.leaf_entry(virtual_addr)?;
+        if huge {
+            Some((entry & HUGE_ADDRESS_MASK) + (virtual_addr % HUGE_PAGE_SIZE))
+        } else {
+            Some((entry & ADDRESS_MASK) + (virtual_addr % PAGE_SIZE))
+        }
+    }
+
+    pub fn flags(&self, virtual_addr: u64) -> Option<u64> {
+        self.leaf_entry(virtual_addr).map(|(entry, _)| entry & !ADDRESS_MASK)
+    }
+
+    pub fn entry(&self, virtual_addr: u64) -> Option<u64> {
+        let pt = self.walk(virtual_addr, 3)?;
+        Some(pt.entries[table_index(virtual_addr, 0)])
+    }
+
+    pub fn set_entry(&mut self, virtual_addr: u64, value: u64) -> Result<u64, MapError> {
+        if value & PAGE_PRESENT != 0 && !crate::vm::protection::check_mapping(virtual_addr, value) {
+            return Err(MapError::WriteExecute);
+        }
+        let _tables = PAGE_TABLE_LOCK.lock();
+        let pt = self.walk(virtual_addr, 3).ok_or(MapError::NotMapped)?;
+        let entry = &mut pt.entries[table_index(virtual_addr, 0)];
+        let previous = *entry;
+        *entry = value;
+
+        invlpg(virtual_addr);
+        shootdown(virtual_addr);
+        Ok(previous)
+    }
+
+    pub fn compare_and_set(&mut self, virtual_addr: u64, expected: u64, value: u64) -> Result<bool, MapError> {
+        if value & PAGE_PRESENT != 0 && !crate::vm::protection::check_mapping(virtual_addr, value) {
+            return Err(MapError::WriteExecute);
+        }
+        let _tables = PAGE_TABLE_LOCK.lock();
+        let pt = self.walk(virtual_addr, 3).ok_or(MapError::NotMapped)?;
+        let entry = unsafe { &*(&mut pt.entries[table_index(virtual_addr, 0)] as *mut u64 as *const AtomicU64) };
+        if entry.compare_exchange(expected, value, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return Ok(false);
+        }
+
+        invlpg(virtual_addr);
+        shootdown(virtual_addr);
+        Ok(true)
+    }
+
+    pub fn move_entry(&mut self, from: u64, to: u64) -> Result<u64, MapError> {
+        let _tables = PAGE_TABLE_LOCK.lock();
+        let source = self.walk(from, 3).ok_or(MapError::NotMapped)?;
+        let entry = core::mem::replace(&mut source.entries[table_index(from, 0)], 0);
+        let target = match self.walk_create(to, 3) {
+            Ok(target) => target,
+            Err(err) => {
+                source.entries[table_index(from, 0)] = entry;
+                return Err(err);
+            }
+        };
+        target.entries[table_index(to, 0)] = entry;
+
+        invlpg(from);
+        shootdown(from);
+        Ok(entry)
+    }
+
+    pub fn clear_accessed(&mut self, virtual_addr: u64) -> bool {
+        let _tables = PAGE_TABLE_LOCK.lock();
+        let pt = match self.walk(virtual_addr, 3) {
+            Some(pt) => pt,
+            None => return false,
+        };
+        let entry = &mut pt.entries[table_index(virtual_addr, 0)];
+        if *entry & (PAGE_PRESENT | PAGE_ACCESSED) != PAGE_PRESENT | PAGE_ACCESSED {
+            return false;
+        }
+        *entry &= !PAGE_ACCESSED;
+
+        invlpg(virtual_addr);
+        shootdown(virtual_addr);
+        true
+    }
+
+    pub fn flush(&self, virtual_addr: u64) {
+        self.flush_range(virtual_addr, PAGE_SIZE);
+    }
+
+    pub fn flush_range(&self, start: u64, length: u64) {
+        if length == 0 {
+            return;
+        }
+        if self.is_active() || start >= KERNEL_HALF_START {
+            invalidate(start, start + length);
+            shootdown_range(start, start + length);
+        }
+    }
+
+    pub fn destroy(self) {
+        if self.root == KERNEL_ROOT.load(Ordering::Relaxed) || self.is_active() {
+            return;
+        }
+
+        let pml4 = unsafe { table_at(self.root) };
+        for entry in pml4.entries[..256].iter_mut() {
+            if *entry & PAGE_PRESENT != 0 {
+                release_table(*entry & ADDRESS_MASK, 2);
+                *entry = 0;
+            }
+        }
+        super::frame_allocator::free_frame(self.root);
+    }
+
+    fn leaf_entry(&self, virtual_addr: u64) -> Option<(u64, bool)> {
+        let pd = self.walk(virtual_addr, 2)?;
+        let pd_entry = pd.entries[table_index(virtual_addr, 1)];
+        if pd_entry & PAGE_PRESENT == 0 {
+            return None;
+        }
+        if pd_entry & PAGE_SIZE_2MB != 0 {
+            return Some((pd_entry, true));
+        }
+
+        let pt = unsafe { table_at(pd_entry & ADDRESS_MASK) };
+        let entry = pt.entries[table_index(virtual_addr, 0)];
+        if entry & PAGE_PRESENT == 0 {
+            return None;
+        }
+        Some((entry, false))
+    }
+
+    fn leaf_entry_split(&mut self, virtual_addr: u64) -> Result<&'static mut u64, MapError> {
+        let pd = self.walk(virtual_addr, 2).ok_or(MapError::NotMapped)?;
+        let pd_entry = &mut pd.entries[table_index(virtual_addr, 1)];
+        if *pd_entry & PAGE_PRESENT == 0 {
+            return Err(MapError::NotMapped);
         }
-        
-        load_cr3(&PML4 as *const PageTable as u64);
+        if *pd_entry & PAGE_SIZE_2MB != 0 {
+            split_huge_page(pd_entry)?;
+        }
+
+        let pt = unsafe { table_at(*pd_entry & ADDRESS_MASK) };
+        let entry = &mut pt.entries[table_index(virtual_addr, 0)];
+        if *entry & PAGE_PRESENT == 0 {
+            return Err(MapError::NotMapped);
+        }
+        Ok(entry)
+    }
+
+    fn walk(&self, virtual_addr: u64, depth: usize) -> Option<&'static mut PageTable> {
+        let mut table = unsafe { table_at(self.root) };
+        for level in (4 - depth..4).rev() {
+            let entry = table.entries[table_index(virtual_addr, level)];
+            if entry & PAGE_PRESENT == 0 || entry & PAGE_SIZE_2MB != 0 {
+                return None;
+            }
+            table = unsafe { table_at(entry & ADDRESS_MASK) };
+        }
+        Some(table)
+    }
+
+    fn walk_create(&mut self, virtual_addr: u64, depth: usize) -> Result<&'static mut PageTable, MapError> {
+        let mut table = unsafe { table_at(self.root) };
+        for level in (4 - depth..4).rev() {
+            let entry = &mut table.entries[table_index(virtual_addr, level)];
+            if *entry & PAGE_SIZE_2MB != 0 {
+                return Err(MapError::AlreadyMapped);
+            }
+            table = next_table_create(entry)?;
+        }
+        Ok(table)
     }
 }
 
-fn load_cr3(addr: u64) {
-    unsafe {
-        asm!("mov {}, %cr3", in(reg) addr);
+fn table_index(virtual_addr: u64, level: usize) -> usize {
+    ((virtual_addr >> (12 + level * 9)) & 0x1FF) as usize
+}
+
+fn next_table_create(entry: &mut u64) -> Result<&'static mut PageTable, MapError> {
+    if *entry & PAGE_PRESENT == 0 {
+        *entry = allocate_table()? | TABLE_FLAGS;
     }
+    Ok(unsafe { table_at(*entry & ADDRESS_MASK) })
 }
 
-pub fn map_page(virtual_addr: u64, physical_addr: u64, flags: u64) {
-    let pml4_index = (virtual_addr >> 39) & 0x1FF;
-    let pdpt_index = (virtual_addr >> 30) & 0x1FF;
-    let pd_index = (virtual_addr >> 21) & 0x1FF;
-    let pt_index = (virtual_addr >> 12) & 0x1FF;
-    
+fn split_huge_page(entry: &mut u64) -> Result<(), MapError> {
+    let table = allocate_table()?;
+    let base = *entry & HUGE_ADDRESS_MASK;
+    let flags = *entry & (PAGE_FLAGS_MASK | PAGE_ACCESSED | PAGE_DIRTY) | PAGE_PRESENT;
+
+    let pt = unsafe { table_at(table) };
+    for i in 0..512 {
+        pt.entries[i] = (base + i as u64 * PAGE_SIZE) | flags;
+    }
+
+    *entry = table | TABLE_FLAGS;
+    Ok(())
+}
+
+fn release_table(table: u64, level: usize) {
+    let entries = unsafe { table_at(table) };
+    for entry in entries.entries.iter_mut() {
+        if *entry & PAGE_PRESENT == 0 {
+            if level == 0 && *entry != 0 {
+                crate::vm::swapping::release_entry(*entry);
+                *entry = 0;
+            }
+            continue;
+        }
+
+        if level == 0 {
+            super::frame_allocator::release_frame(*entry & ADDRESS_MASK);
+        } else if level == 1 && *entry & PAGE_SIZE_2MB != 0 {
+            super::frame_allocator::free_huge_frame(*entry & HUGE_ADDRESS_MASK);
+        } else {
+            release_table(*entry & ADDRESS_MASK, level - 1);
+        }
+        *entry = 0;
+    }
+    super::frame_allocator::free_frame(table);
+}
+
+fn allocate_table() -> Result<u64, MapError> {
+    let frame = super::frame_allocator::allocate_frame().ok_or(MapError::OutOfMemory)?;
     unsafe {
-        if PML4.entries[pml4_index as usize] == 0 {
-            PML4.entries[pml4_index as usize] = allocate_page() | PAGE_PRESENT | PAGE_WRITABLE;
+        core::ptr::write_bytes(phys_to_virt(frame) as *mut u8, 0, PAGE_SIZE as usize);
+    }
+    Ok(frame)
+}
+
+unsafe fn table_at(physical_addr: u64) -> &'static mut PageTable {
+    &mut *(phys_to_virt(physical_addr) as *mut PageTable)
+}
+
+pub fn phys_to_virt(physical_addr: u64) -> u64 {
+    physical_addr + DIRECT_MAP_BASE.load(Ordering::Relaxed)
+}
+
+pub fn virt_to_phys(virtual_addr: u64) -> Option<u64> {
+    let base = DIRECT_MAP_BASE.load(Ordering::Relaxed);
+    if base != 0 && virtual_addr >= base && virtual_addr < base + DIRECT_MAP_SIZE.load(Ordering::Relaxed) {
+        return Some(virtual_addr - base);
+    }
+    PageMapper::kernel().translate(virtual_addr)
+}
+
+const KERNEL_HALF_START: u64 = 0xFFFF_8000_0000_0000;
+
+static DIRECT_MAP_BASE: AtomicU64 = AtomicU64::new(0);
+static DIRECT_MAP_SIZE: AtomicU64 = AtomicU64::new(0);
+static KERNEL_ROOT: AtomicU64 = AtomicU64::new(0);
+static SHOOTDOWN_START: AtomicU64 = AtomicU64::new(0);
+static SHOOTDOWN_END: AtomicU64 = AtomicU64::new(0);
+static SHOOTDOWN_TARGETS: AtomicU64 = AtomicU64::new(0);
+static SHOOTDOWN_LOCK: SpinLock<()> = SpinLock::new(());
+static PAGE_TABLE_LOCK: SpinLock<()> = SpinLock::new(());
+
+// Past this many pages a user range is dropped with a CR3 reload instead of
+// one invlpg per page.
+const FULL_FLUSH_PAGES: u64 = 32;
+
+fn invalidate(start: u64, end: u64) {
+    if start < KERNEL_HALF_START && (end - start) / PAGE_SIZE > FULL_FLUSH_PAGES {
+        load_cr3(read_cr3());
+        return;
+    }
+
+    let mut page = start & !(PAGE_SIZE - 1);
+    while page < end {
+        invlpg(page);
+        page += PAGE_SIZE;
+    }
+}
+
+fn shootdown(virtual_addr: u64) {
+    shootdown_range(virtual_addr, virtual_addr + PAGE_SIZE);
+}
+
+fn shootdown_range(start: u64, end: u64) {
+    if crate::smp::online_cpu_mask().count_ones() <= 1 {
+        return;
+    }
+
+    let _shootdown = SHOOTDOWN_LOCK.lock();
+    let targets = crate::smp::online_cpu_mask() & !(1 << crate::smp::current_cpu_id());
+    if targets == 0 {
+        return;
+    }
+
+    SHOOTDOWN_START.store(start, Ordering::Release);
+    SHOOTDOWN_END.store(end, Ordering::Release);
+    SHOOTDOWN_TARGETS.store(targets, Ordering::Release);
+    for cpu in 0..crate::smp::MAX_CPUS as u32 {
+        if targets & (1 << cpu) != 0 {
+            crate::interrupts::apic::send_ipi(crate::smp::apic_id(cpu) as u8, TLB_SHOOTDOWN_VECTOR);
         }
-        
-        let pdpt = &mut *((PML4.entries[pml4_index as usize] & !0xFFF) as *mut PageTable);
-        if pdpt.entries[pdpt_index as usize] == 0 {
-            pdpt.entries[pdpt_index as usize] = allocate_page() | PAGE_PRESENT | PAGE_WRITABLE;
+    }
+
+    while SHOOTDOWN_TARGETS.load(Ordering::Acquire) != 0 {
+        core::hint::spin_loop();
+    }
+}
+
+pub fn handle_tlb_shootdown() {
+    if SHOOTDOWN_TARGETS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+
+    let cpu = 1 << crate::smp::current_cpu_id();
+    if SHOOTDOWN_TARGETS.load(Ordering::Acquire) & cpu != 0 {
+        invalidate(SHOOTDOWN_START.load(Ordering::Acquire), SHOOTDOWN_END.load(Ordering::Acquire));
+        SHOOTDOWN_TARGETS.fetch_and(!cpu, Ordering::AcqRel);
+    }
+}
+
+extern "C" {
+    static __kernel_start: u8;
+    static __text_start: u8;
+    static __text_end: u8;
+    static __rodata_start: u8;
+    static __rodata_end: u8;
+    static __data_start: u8;
+    static __data_end: u8;
+    static __boot_stack_bottom: u8;
+    static __boot_stack_top: u8;
+}
+
+pub fn init(kernel_physical: u64, physical_memory_end: u64) {
+    vga::print!("Initializing page tables...\n");
+
+    let root = match allocate_table() {
+        Ok(root) => root,
+        Err(_) => panic!("Failed to allocate kernel PML4"),
+    };
+    KERNEL_ROOT.store(root, Ordering::Relaxed);
+
+    let mut mapper = PageMapper::from_root(root);
+    let kernel_pml4 = unsafe { table_at(root) };
+    for i in 256..512 {
+        if next_table_create(&mut kernel_pml4.entries[i]).is_err() {
+            panic!("Failed to allocate kernel PDPT {}", i);
         }
-        
-        let pd = &mut *((pdpt.entries[pdpt_index as usize] & !0xFFF) as *mut PageTable);
-        if pd.entries[pd_index as usize] == 0 {
-            pd.entries[pd_index as usize] = allocate_page() | PAGE_PRESENT | PAGE_WRITABLE;
+    }
+
+    let direct_map_size = align_up(physical_memory_end.max(MIN_DIRECT_MAP_SIZE), HUGE_PAGE_SIZE);
+    let mut addr = 0;
+    while addr < direct_map_size {
+        if mapper.map_huge(PHYSICAL_MEMORY_OFFSET + addr, addr, PAGE_WRITABLE | PAGE_GLOBAL | PAGE_NX).is_err() {
+            panic!("Failed to direct map 0x{:x}", addr);
         }
-        
-        let pt = &mut *((pd.entries[pd_index as usize] & !0xFFF) as *mut PageTable);
-        pt.entries[pt_index as usize] = physical_addr | flags | PAGE_PRESENT;
+        addr += HUGE_PAGE_SIZE;
     }
+
+    unsafe {
+        let kernel_virtual = &__kernel_start as *const u8 as u64;
+        let sections = [
+            (&__text_start as *const u8 as u64, &__text_end as *const u8 as u64, PAGE_GLOBAL),
+            (&__rodata_start as *const u8 as u64, &__rodata_end as *const u8 as u64, PAGE_GLOBAL | PAGE_NX),
+            (&__data_start as *const u8 as u64, &__data_end as *const u8 as u64, PAGE_WRITABLE | PAGE_GLOBAL | PAGE_NX),
+            (&__boot_stack_bottom as *const u8 as u64, &__boot_stack_top as *const u8 as u64, PAGE_WRITABLE | PAGE_GLOBAL | PAGE_NX),
+        ];
+
+        for &(start, end, flags) in sections.iter() {
+            let mut page = start & !(PAGE_SIZE - 1);
+            while page < end {
+                let physical = kernel_physical + (page - kernel_virtual);
+                if mapper.map(page, physical, flags).is_err() {
+                    panic!("Failed to map kernel page 0x{:x}", page);
+                }
+                page += PAGE_SIZE;
+            }
+        }
+    }
+
+    enable_nx_and_write_protect();
+    load_cr3(root);
+
+    DIRECT_MAP_SIZE.store(direct_map_size, Ordering::Relaxed);
+    DIRECT_MAP_BASE.store(PHYSICAL_MEMORY_OFFSET, Ordering::Relaxed);
+    vga::relocate(PHYSICAL_MEMORY_OFFSET);
+
+    vga::print!("Kernel page tables loaded at 0x{:x}\n", root);
+    vga::print!("Direct map: {} MiB at 0x{:x}\n", direct_map_size / 0x100000, PHYSICAL_MEMORY_OFFSET);
+}
+
+fn enable_nx_and_write_protect() {
+    unsafe {
+        let (low, high): (u32, u32);
+        asm!("rdmsr", in("ecx") EFER_MSR, out("eax") low, out("edx") high);
+        let efer = ((high as u64) << 32 | low as u64) | EFER_NXE;
+        asm!("wrmsr", in("ecx") EFER_MSR, in("eax") efer as u32, in("edx") (efer >> 32) as u32);
+
+        let mut cr0: u64;
+        asm!("mov {}, cr0", out(reg) cr0);
+        cr0 |= CR0_WP;
+        asm!("mov cr0, {}", in(reg) cr0);
+    }
+}
+
+fn align_up(addr: u64, align: u64) -> u64 {
+    (addr + align - 1) & !(align - 1)
+}
+
+fn read_cr3() -> u64 {
+    let value: u64;
+    unsafe {
+        asm!("mov {}, cr3", out(reg) value);
+    }
+    value
+}
+
+fn load_cr3(addr: u64) {
+    unsafe {
+        asm!("mov cr3, {}", in(reg) addr);
+    }
+}
+
+fn invlpg(addr: u64) {
+    unsafe {
+        asm!("invlpg [{}]", in(reg) addr);
+    }
+}
+
+pub fn map_page(virtual_addr: u64, physical_addr: u64, flags: u64) {
+    if let Err(err) = PageMapper::kernel().map(virtual_addr, physical_addr, flags) {
+        vga::print!("Failed to map 0x{:x} -> 0x{:x}: {:?}\n", virtual_addr, physical_addr, err);
+    }
+}
+
+pub fn unmap_page(virtual_addr: u64) -> Option<u64> {
+    PageMapper::kernel().unmap(virtual_addr).ok()
 }
 
-fn allocate_page() -> u64 {
-    0x1000000
+pub fn translate(virtual_addr: u64) -> Option<u64> {
+    PageMapper::kernel().translate(virtual_addr)
 }
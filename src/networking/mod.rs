@@ -1,9 +1,11 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod ethernet;
 pub mod ip;
 pub mod tcp;
 pub mod udp;
+pub mod socket;
 
 pub struct NetworkStack {
     pub mac_address: [u8; 6],
@@ -13,7 +15,7 @@ pub struct NetworkStack {
 }
 
 impl NetworkStack {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
             ip_address: [192, 168, 1, 100],
@@ -47,10 +49,8 @@ impl NetworkStack {
     }
 }
 
-pub static mut NETWORK: NetworkStack = NetworkStack::new();
+pub static NETWORK: SpinLock<NetworkStack> = SpinLock::new(NetworkStack::new());
 
 pub fn init() {
-    unsafe {
-        NETWORK.init();
-    }
+    NETWORK.lock().init();
 }
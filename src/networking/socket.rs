@@ -0,0 +1,43 @@
+use crate::process::files::{FileDescription, FileKind};
+use crate::smp::synchronization::SpinLock;
+use crate::syscalls::errno::Errno;
+
+pub const AF_INET: u64 = 2;
+pub const SOCK_DGRAM: u64 = 2;
+pub const IPPROTO_UDP: u64 = 17;
+
+pub struct UdpSocket {
+    destination: SpinLock<Option<([u8; 4], u16)>>,
+}
+
+impl UdpSocket {
+    pub fn new() -> Self {
+        Self {
+            destination: SpinLock::new(None),
+        }
+    }
+}
+
+impl FileDescription for UdpSocket {
+    fn kind(&self) -> FileKind {
+        FileKind::Socket
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, Errno> {
+        Ok(super::udp::receive_packet(buffer))
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, Errno> {
+        let (address, port) = (*self.destination.lock()).ok_or(Errno::EDESTADDRREQ)?;
+        if super::udp::send_packet(address, port, data) {
+            Ok(data.len())
+        } else {
+            Err(Errno::EIO)
+        }
+    }
+
+    fn connect(&self, address: [u8; 4], port: u16) -> Result<(), Errno> {
+        *self.destination.lock() = Some((address, port));
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod ipv6;
@@ -13,7 +14,7 @@ pub struct AdvancedNetworking {
 }
 
 impl AdvancedNetworking {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             ipv6_enabled: false,
             tls_enabled: false,
@@ -39,10 +40,8 @@ impl AdvancedNetworking {
     }
 }
 
-pub static mut ADVANCED_NETWORKING: AdvancedNetworking = AdvancedNetworking::new();
+pub static ADVANCED_NETWORKING: SpinLock<AdvancedNetworking> = SpinLock::new(AdvancedNetworking::new());
 
 pub fn init() {
-    unsafe {
-        ADVANCED_NETWORKING.init();
-    }
+    ADVANCED_NETWORKING.lock().init();
 }
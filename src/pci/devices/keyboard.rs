@@ -1,9 +1,17 @@
 use crate::vga;
-use core::sync::atomic::{AtomicU8, Ordering};
+use crate::smp::synchronization::SpinLock;
 
-static mut KEYBOARD_BUFFER: [u8; 256] = [0; 256];
-static mut KEYBOARD_HEAD: AtomicU8 = AtomicU8::new(0);
-static mut KEYBOARD_TAIL: AtomicU8 = AtomicU8::new(0);
+struct KeyboardBuffer {
+    data: [u8; 256],
+    head: u8,
+    tail: u8,
+}
+
+static KEYBOARD_BUFFER: SpinLock<KeyboardBuffer> = SpinLock::new(KeyboardBuffer {
+    data: [0; 256],
+    head: 0,
+    tail: 0,
+});
 
 const KEYBOARD_DATA_PORT: u16 = 0x60;
 const KEYBOARD_STATUS_PORT: u16 = 0x64;
@@ -27,35 +35,33 @@ unsafe fn enable_keyboard() {
 }
 
 pub fn handle_interrupt() {
-    unsafe {
-        if (inb(KEYBOARD_STATUS_PORT) & 0x01) != 0 {
-            let scancode = inb(KEYBOARD_DATA_PORT);
-            process_scancode(scancode);
-        }
+    if (inb(KEYBOARD_STATUS_PORT) & 0x01) != 0 {
+        let scancode = inb(KEYBOARD_DATA_PORT);
+        process_scancode(scancode);
     }
 }
 
-unsafe fn process_scancode(scancode: u8) {
-    let head = KEYBOARD_HEAD.load(Ordering::Relaxed);
-    let next_head = (head + 1) % 256;
-    
-    if next_head != KEYBOARD_TAIL.load(Ordering::Relaxed) {
-        KEYBOARD_BUFFER[head as usize] = scancode;
-        KEYBOARD_HEAD.store(next_head, Ordering::Relaxed);
+fn process_scancode(scancode: u8) {
+    let mut buffer = KEYBOARD_BUFFER.lock();
+    let head = buffer.head;
+    let next_head = head.wrapping_add(1);
+
+    if next_head != buffer.tail {
+        buffer.data[head as usize] = scancode;
+        buffer.head = next_head;
     }
 }
 
 pub fn read_key() -> Option<u8> {
-    unsafe {
-        let tail = KEYBOARD_TAIL.load(Ordering::Relaxed);
-        
-        if tail != KEYBOARD_HEAD.load(Ordering::Relaxed) {
-            let key = KEYBOARD_BUFFER[tail as usize];
-            KEYBOARD_TAIL.store((tail + 1) % 256, Ordering::Relaxed);
-            Some(key)
-        } else {
-            None
-        }
+    let mut buffer = KEYBOARD_BUFFER.lock();
+    let tail = buffer.tail;
+
+    if tail != buffer.head {
+        let key = buffer.data[tail as usize];
+        buffer.tail = tail.wrapping_add(1);
+        Some(key)
+    } else {
+        None
     }
 }
 
@@ -1,23 +1,32 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 const MOUSE_DATA_PORT: u16 = 0x60;
 const MOUSE_STATUS_PORT: u16 = 0x64;
 const MOUSE_COMMAND_PORT: u16 = 0x64;
 
+#[derive(Clone, Copy)]
 pub struct MouseState {
     pub x: i32,
     pub y: i32,
     pub buttons: u8,
 }
 
-static mut MOUSE_STATE: MouseState = MouseState {
-    x: 0,
-    y: 0,
-    buttons: 0,
-};
+struct MouseDriver {
+    state: MouseState,
+    packet: [u8; 3],
+    packet_index: usize,
+}
 
-static mut MOUSE_PACKET: [u8; 3] = [0; 3];
-static mut MOUSE_PACKET_INDEX: usize = 0;
+static MOUSE: SpinLock<MouseDriver> = SpinLock::new(MouseDriver {
+    state: MouseState {
+        x: 0,
+        y: 0,
+        buttons: 0,
+    },
+    packet: [0; 3],
+    packet_index: 0,
+});
 
 pub fn init() {
     vga::print!("Initializing PS/2 mouse...\n");
@@ -38,38 +47,39 @@ unsafe fn enable_mouse() {
 }
 
 pub fn handle_interrupt() {
-    unsafe {
-        if (inb(MOUSE_STATUS_PORT) & 0x01) != 0 {
-            let data = inb(MOUSE_DATA_PORT);
-            process_mouse_data(data);
-        }
+    if (inb(MOUSE_STATUS_PORT) & 0x01) != 0 {
+        let data = inb(MOUSE_DATA_PORT);
+        MOUSE.lock().process_data(data);
     }
 }
 
-unsafe fn process_mouse_data(data: u8) {
-    MOUSE_PACKET[MOUSE_PACKET_INDEX] = data;
-    MOUSE_PACKET_INDEX += 1;
-    
-    if MOUSE_PACKET_INDEX == 3 {
-        MOUSE_PACKET_INDEX = 0;
+impl MouseDriver {
+    fn process_data(&mut self, data: u8) {
+        self.packet[self.packet_index] = data;
+        self.packet_index += 1;
         
-        let buttons = MOUSE_PACKET[0] & 0x07;
-        let x_delta = ((MOUSE_PACKET[0] & 0x10) as i8 as i32) << 4 | (MOUSE_PACKET[1] as i32);
-        let y_delta = ((MOUSE_PACKET[0] & 0x20) as i8 as i32) << 3 | (MOUSE_PACKET[2] as i32);
-        
-        MOUSE_STATE.buttons = buttons;
-        MOUSE_STATE.x += x_delta;
-        MOUSE_STATE.y -= y_delta;
-        
-        if MOUSE_STATE.x < 0 { MOUSE_STATE.x = 0; }
-        if MOUSE_STATE.y < 0 { MOUSE_STATE.y = 0; }
-        if MOUSE_STATE.x >= 80 { MOUSE_STATE.x = 79; }
-        if MOUSE_STATE.y >= 25 { MOUSE_STATE.y = 24; }
+        if self.packet_index == 3 {
+            self.packet_index = 0;
+            
+            let buttons = self.packet[0] & 0x07;
+            let x_delta = ((self.packet[0] & 0x10) as i8 as i32) << 4 | (self.packet[1] as i32);
+            let y_delta = ((self.packet[0] & 0x20) as i8 as i32) << 3 | (self.packet[2] as i32);
+            
+            let state = &mut self.state;
+            state.buttons = buttons;
+            state.x += x_delta;
+            state.y -= y_delta;
+            
+            if state.x < 0 { state.x = 0; }
+            if state.y < 0 { state.y = 0; }
+            if state.x >= 80 { state.x = 79; }
+            if state.y >= 25 { state.y = 24; }
+        }
     }
 }
 
 pub fn get_mouse_state() -> MouseState {
-    unsafe { MOUSE_STATE }
+    MOUSE.lock().state
 }
 
 fn inb(port: u16) -> u8 {
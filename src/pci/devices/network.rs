@@ -1,4 +1,6 @@
 use crate::vga;
+use crate::smp::synchronization::SpinLock;
+use alloc::vec::Vec;
 
 pub struct NetworkDevice {
     pub mac_address: [u8; 6],
@@ -7,8 +9,7 @@ pub struct NetworkDevice {
     pub gateway: [u8; 4],
 }
 
-static mut NETWORK_DEVICES: [Option<NetworkDevice>; 4] = [None; 4];
-static mut NETWORK_COUNT: usize = 0;
+static NETWORK_DEVICES: SpinLock<Vec<NetworkDevice>> = SpinLock::new(Vec::new());
 
 pub fn init() {
     vga::print!("Initializing network devices...\n");
@@ -27,22 +28,18 @@ pub fn handle_interrupt() {
 }
 
 pub fn send_packet(device_id: usize, data: &[u8]) -> bool {
-    unsafe {
-        if device_id >= NETWORK_COUNT {
-            return false;
-        }
-        
-        vga::print!("Sending {} bytes on network device {}\n", data.len(), device_id);
-        true
+    if device_id >= NETWORK_DEVICES.lock().len() {
+        return false;
     }
+
+    vga::print!("Sending {} bytes on network device {}\n", data.len(), device_id);
+    true
 }
 
 pub fn receive_packet(device_id: usize, buffer: &mut [u8]) -> usize {
-    unsafe {
-        if device_id >= NETWORK_COUNT {
-            return 0;
-        }
-        
-        0
+    if device_id >= NETWORK_DEVICES.lock().len() {
+        return 0;
     }
+
+    0
 }
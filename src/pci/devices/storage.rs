@@ -1,5 +1,7 @@
 use crate::vga;
 use crate::pci::PCIDevice;
+use crate::smp::synchronization::SpinLock;
+use alloc::vec::Vec;
 
 pub struct StorageDevice {
     pub device_type: StorageType,
@@ -15,8 +17,9 @@ pub enum StorageType {
     SCSI,
 }
 
-static mut STORAGE_DEVICES: [Option<StorageDevice>; 8] = [None; 8];
-static mut STORAGE_COUNT: usize = 0;
+const MAX_STORAGE_DEVICES: usize = 8;
+
+static STORAGE_DEVICES: SpinLock<Vec<StorageDevice>> = SpinLock::new(Vec::new());
 
 pub fn init() {
     vga::print!("Initializing storage devices...\n");
@@ -28,16 +31,17 @@ pub fn init() {
 }
 
 unsafe fn init_ata_devices() {
+    let mut devices = STORAGE_DEVICES.lock();
     for i in 0..4 {
-        let device = StorageDevice {
+        if devices.len() >= MAX_STORAGE_DEVICES {
+            break;
+        }
+        devices.push(StorageDevice {
             device_type: StorageType::ATA,
             capacity: 0,
             sector_size: 512,
             lba_count: 0,
-        };
-        
-        STORAGE_DEVICES[STORAGE_COUNT] = Some(device);
-        STORAGE_COUNT += 1;
+        });
     }
     
     vga::print!("Found {} ATA devices\n", 4);
@@ -52,19 +56,35 @@ pub fn handle_interrupt() {
 }
 
 pub fn read_sector(device_id: usize, lba: u64, buffer: &mut [u8]) -> bool {
+    // The lock is held across the transfer so that two CPUs never
+    // interleave commands on the same controller ports.
+    let devices = STORAGE_DEVICES.lock();
+    let Some(device) = devices.get(device_id) else {
+        return false;
+    };
+
     unsafe {
-        if device_id >= STORAGE_COUNT {
-            return false;
+        match device.device_type {
+            StorageType::ATA => read_ata_sector(device_id, lba, buffer),
+            StorageType::NVMe => read_nvme_sector(device_id, lba, buffer),
+            _ => false,
         }
-        
-        if let Some(device) = &STORAGE_DEVICES[device_id] {
-            match device.device_type {
-                StorageType::ATA => read_ata_sector(device_id, lba, buffer),
-                StorageType::NVMe => read_nvme_sector(device_id, lba, buffer),
-                _ => false,
-            }
-        } else {
-            false
+    }
+}
+
+pub fn write_sector(device_id: usize, lba: u64, buffer: &[u8]) -> bool {
+    // The lock is held across the transfer so that two CPUs never
+    // interleave commands on the same controller ports.
+    let devices = STORAGE_DEVICES.lock();
+    let Some(device) = devices.get(device_id) else {
+        return false;
+    };
+
+    unsafe {
+        match device.device_type {
+            StorageType::ATA => write_ata_sector(device_id, lba, buffer),
+            StorageType::NVMe => write_nvme_sector(device_id, lba, buffer),
+            _ => false,
         }
     }
 }
@@ -90,11 +110,38 @@ unsafe fn read_ata_sector(device_id: usize, lba: u64, buffer: &mut [u8]) -> bool
     true
 }
 
+unsafe fn write_ata_sector(device_id: usize, lba: u64, buffer: &[u8]) -> bool {
+    let base_port = 0x1F0 + (device_id as u16 * 0x10);
+    
+    outb(base_port + 6, 0x40 | ((device_id as u8) << 4));
+    outb(base_port + 2, 1);
+    outb(base_port + 3, (lba & 0xFF) as u8);
+    outb(base_port + 4, ((lba >> 8) & 0xFF) as u8);
+    outb(base_port + 5, ((lba >> 16) & 0xFF) as u8);
+    outb(base_port + 7, 0x30);
+    
+    while (inb(base_port + 7) & 0x80) != 0 {}
+    
+    for i in 0..256 {
+        outw(base_port, buffer[i * 2] as u16 | ((buffer[i * 2 + 1] as u16) << 8));
+    }
+    
+    outb(base_port + 7, 0xE7);
+    while (inb(base_port + 7) & 0x80) != 0 {}
+    
+    true
+}
+
 unsafe fn read_nvme_sector(device_id: usize, lba: u64, buffer: &mut [u8]) -> bool {
     vga::print!("NVMe read not implemented\n");
     false
 }
 
+unsafe fn write_nvme_sector(device_id: usize, lba: u64, buffer: &[u8]) -> bool {
+    vga::print!("NVMe write not implemented\n");
+    false
+}
+
 fn inb(port: u16) -> u8 {
     let result: u8;
     unsafe {
@@ -116,3 +163,9 @@ fn inw(port: u16) -> u16 {
     }
     result
 }
+
+fn outw(port: u16, value: u16) {
+    unsafe {
+        asm!("out dx, ax", in("dx") port, in("ax") value);
+    }
+}
@@ -18,7 +18,7 @@ pub struct MCFGTable {
 
 pub fn parse_mcfg(mcfg_addr: u64) -> Option<u64> {
     unsafe {
-        let mcfg = &*(mcfg_addr as *const MCFGTable);
+        let mcfg = &*(crate::memory::paging::phys_to_virt(mcfg_addr) as *const MCFGTable);
         
         if &mcfg.header.signature != b"MCFG" {
             vga::print!("Invalid MCFG signature\n");
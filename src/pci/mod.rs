@@ -1,9 +1,11 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 use core::ptr;
 
 pub mod mcfg;
 pub mod devices;
 
+#[derive(Clone, Copy)]
 #[repr(C, packed)]
 pub struct PCIConfigHeader {
     pub vendor_id: u16,
@@ -20,6 +22,7 @@ pub struct PCIConfigHeader {
     pub bist: u8,
 }
 
+#[derive(Clone, Copy)]
 #[repr(C, packed)]
 pub struct PCIDevice {
     pub bus: u8,
@@ -36,7 +39,7 @@ pub struct PCIManager {
 }
 
 impl PCIManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             devices: [None; 256],
             device_count: 0,
@@ -106,7 +109,7 @@ impl PCIManager {
         if self.mcfg_base == 0 {
             self.legacy_config_address(bus, device, function)
         } else {
-            self.mcfg_base + ((bus as u64) << 20) + ((device as u64) << 15) + ((function as u64) << 12)
+            crate::memory::paging::phys_to_virt(self.mcfg_base) + ((bus as u64) << 20) + ((device as u64) << 15) + ((function as u64) << 12)
         }
     }
 
@@ -149,10 +152,8 @@ impl PCIManager {
     }
 }
 
-pub static mut PCI_MANAGER: PCIManager = PCIManager::new();
+pub static PCI_MANAGER: SpinLock<PCIManager> = SpinLock::new(PCIManager::new());
 
 pub fn init(mcfg_base: u64) {
-    unsafe {
-        PCI_MANAGER.init(mcfg_base);
-    }
+    PCI_MANAGER.lock().init(mcfg_base);
 }
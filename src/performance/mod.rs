@@ -1,28 +1,37 @@
 use crate::vga;
 use core::sync::atomic::{AtomicU64, Ordering};
 
+const SAMPLE_INTERVAL_NS: u64 = crate::time::NANOS_PER_SECOND;
+
 pub struct PerformanceMonitor {
     pub cpu_usage: AtomicU64,
     pub memory_usage: AtomicU64,
     pub task_count: AtomicU64,
     pub interrupt_count: AtomicU64,
     pub context_switches: AtomicU64,
+    last_sample_ticks: AtomicU64,
+    last_idle_ticks: AtomicU64,
 }
 
 impl PerformanceMonitor {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             cpu_usage: AtomicU64::new(0),
             memory_usage: AtomicU64::new(0),
             task_count: AtomicU64::new(0),
             interrupt_count: AtomicU64::new(0),
             context_switches: AtomicU64::new(0),
+            last_sample_ticks: AtomicU64::new(0),
+            last_idle_ticks: AtomicU64::new(0),
         }
     }
     
-    pub fn init(&mut self) {
+    pub fn init(&self) {
         vga::print!("Initializing performance monitoring...\n");
         
+        self.last_sample_ticks.store(crate::time::ticks(), Ordering::Relaxed);
+        crate::time::add_timer(SAMPLE_INTERVAL_NS, sample_cpu_usage, 0);
+        
         vga::print!("Performance monitoring initialized\n");
     }
     
@@ -42,8 +51,23 @@ impl PerformanceMonitor {
         self.context_switches.fetch_add(1, Ordering::Relaxed);
     }
     
+    pub fn sample_cpu_usage(&self) {
+        let ticks = crate::time::ticks();
+        let cpus = crate::smp::get_online_cpu_count().max(1) as u64;
+        let idle = idle_ticks();
+        
+        let elapsed = (ticks - self.last_sample_ticks.swap(ticks, Ordering::Relaxed)) * cpus;
+        let idle_elapsed = idle - self.last_idle_ticks.swap(idle, Ordering::Relaxed);
+        
+        if elapsed > 0 {
+            let busy = elapsed.saturating_sub(idle_elapsed);
+            self.update_cpu_usage(busy * 100 / elapsed);
+        }
+    }
+    
     pub fn get_stats(&self) -> PerformanceStats {
         PerformanceStats {
+            uptime_ms: crate::time::monotonic_ns() / crate::time::NANOS_PER_MILLI,
             cpu_usage: self.cpu_usage.load(Ordering::Relaxed),
             memory_usage: self.memory_usage.load(Ordering::Relaxed),
             task_count: self.task_count.load(Ordering::Relaxed),
@@ -54,6 +78,7 @@ impl PerformanceMonitor {
 }
 
 pub struct PerformanceStats {
+    pub uptime_ms: u64,
     pub cpu_usage: u64,
     pub memory_usage: u64,
     pub task_count: u64,
@@ -61,22 +86,35 @@ pub struct PerformanceStats {
     pub context_switches: u64,
 }
 
-pub static mut PERFORMANCE: PerformanceMonitor = PerformanceMonitor::new();
+fn sample_cpu_usage(_: usize) {
+    PERFORMANCE.sample_cpu_usage();
+    crate::time::add_timer(SAMPLE_INTERVAL_NS, sample_cpu_usage, 0);
+}
 
-pub fn init() {
-    unsafe {
-        PERFORMANCE.init();
+fn idle_ticks() -> u64 {
+    let smp = crate::smp::SMP_MANAGER.lock();
+    let mut idle = 0;
+    for cpu in 0..smp.get_cpu_count() {
+        if let Some(info) = smp.get_cpu_info(cpu) {
+            idle += info.idle_time;
+        }
     }
+    idle
+}
+
+pub static PERFORMANCE: PerformanceMonitor = PerformanceMonitor::new();
+
+pub fn init() {
+    PERFORMANCE.init();
 }
 
 pub fn print_stats() {
-    unsafe {
-        let stats = PERFORMANCE.get_stats();
-        vga::print!("Performance Stats:\n");
-        vga::print!("  CPU Usage: {}%\n", stats.cpu_usage);
-        vga::print!("  Memory Usage: {} bytes\n", stats.memory_usage);
-        vga::print!("  Tasks: {}\n", stats.task_count);
-        vga::print!("  Interrupts: {}\n", stats.interrupt_count);
-        vga::print!("  Context Switches: {}\n", stats.context_switches);
-    }
+    let stats = PERFORMANCE.get_stats();
+    vga::print!("Performance Stats:\n");
+    vga::print!("  Uptime: {} ms\n", stats.uptime_ms);
+    vga::print!("  CPU Usage: {}%\n", stats.cpu_usage);
+    vga::print!("  Memory Usage: {} bytes\n", stats.memory_usage);
+    vga::print!("  Tasks: {}\n", stats.task_count);
+    vga::print!("  Interrupts: {}\n", stats.interrupt_count);
+    vga::print!("  Context Switches: {}\n", stats.context_switches);
 }
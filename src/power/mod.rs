@@ -1,3 +1,4 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod acpi_power;
@@ -13,7 +14,7 @@ pub struct PowerManager {
 }
 
 impl PowerManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             acpi_power_enabled: false,
             cpu_freq_scaling: false,
@@ -39,10 +40,8 @@ impl PowerManager {
     }
 }
 
-pub static mut POWER_MANAGER: PowerManager = PowerManager::new();
+pub static POWER_MANAGER: SpinLock<PowerManager> = SpinLock::new(PowerManager::new());
 
 pub fn init() {
-    unsafe {
-        POWER_MANAGER.init();
-    }
+    POWER_MANAGER.lock().init();
 }
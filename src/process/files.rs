@@ -0,0 +1,202 @@
+use crate::ipc::poll::{PollQueue, POLLIN, POLLOUT};
+use crate::syscalls::errno::Errno;
+use crate::vm::object::MemoryObject;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub const MAX_FILE_DESCRIPTORS: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FileKind {
+    Console,
+    File,
+    Socket,
+    SharedMemory,
+    Pipe,
+    Channel,
+}
+
+pub trait FileDescription: Send + Sync {
+    fn kind(&self) -> FileKind;
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, Errno>;
+
+    fn write(&self, data: &[u8]) -> Result<usize, Errno>;
+
+    fn seek(&self, _position: u64) -> Result<u64, Errno> {
+        Err(Errno::ESPIPE)
+    }
+
+    fn connect(&self, _address: [u8; 4], _port: u16) -> Result<(), Errno> {
+        Err(Errno::ENOTSOCK)
+    }
+
+    fn path(&self) -> Option<&str> {
+        None
+    }
+
+    fn truncate(&self, _length: u64) -> Result<(), Errno> {
+        Err(Errno::EINVAL)
+    }
+
+    fn memory_object(&self) -> Option<Arc<MemoryObject>> {
+        None
+    }
+
+    fn poll(&self) -> u16 {
+        POLLIN | POLLOUT
+    }
+
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        None
+    }
+}
+
+pub struct ConsoleFile;
+
+impl FileDescription for ConsoleFile {
+    fn kind(&self) -> FileKind {
+        FileKind::Console
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, Errno> {
+        let mut count = 0;
+        while count < buffer.len() {
+            match crate::pci::devices::keyboard::read_key() {
+                Some(key) => {
+                    buffer[count] = key;
+                    count += 1;
+                    if key == b'\n' {
+                        break;
+                    }
+                }
+                None if count > 0 => break,
+                None => crate::scheduler::yield_cpu(),
+            }
+        }
+        Ok(count)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, Errno> {
+        match core::str::from_utf8(data) {
+            Ok(text) => crate::vga::print!("{}", text),
+            Err(_) => {
+                for &byte in data {
+                    crate::vga::print!("{}", byte as char);
+                }
+            }
+        }
+        Ok(data.len())
+    }
+}
+
+pub struct VfsFile {
+    file_id: usize,
+    path: String,
+}
+
+impl VfsFile {
+    pub fn open(path: &str) -> Result<Self, Errno> {
+        let file_id = crate::filesystem::open_file(path).ok_or(Errno::ENOENT)?;
+        Ok(Self {
+            file_id,
+            path: path.to_string(),
+        })
+    }
+
+    pub fn size(&self) -> Result<u64, Errno> {
+        crate::filesystem::file_size(self.file_id).map(|size| size as u64).ok_or(Errno::EBADF)
+    }
+
+    pub fn write_through(&self, data: &[u8]) -> Result<usize, Errno> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        match crate::filesystem::write_file(self.file_id, data) {
+            0 => Err(Errno::EROFS),
+            written => Ok(written),
+        }
+    }
+}
+
+impl FileDescription for VfsFile {
+    fn kind(&self) -> FileKind {
+        FileKind::File
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, Errno> {
+        Ok(crate::filesystem::read_file(self.file_id, buffer))
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, Errno> {
+        let offset = crate::filesystem::file_position(self.file_id).ok_or(Errno::EBADF)? as u64;
+        let written = self.write_through(data)?;
+        crate::vm::object::file_written(&self.path, offset, &data[..written]);
+        Ok(written)
+    }
+
+    fn seek(&self, position: u64) -> Result<u64, Errno> {
+        let size = self.size()?;
+        let position = position.min(size);
+        crate::filesystem::seek_file(self.file_id, position as u32);
+        Ok(position)
+    }
+
+    fn path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+}
+
+impl Drop for VfsFile {
+    fn drop(&mut self) {
+        crate::filesystem::close_file(self.file_id);
+    }
+}
+
+#[derive(Clone)]
+pub struct FileTable {
+    descriptors: Vec<Option<Arc<dyn FileDescription>>>,
+}
+
+impl FileTable {
+    pub const fn new() -> Self {
+        Self {
+            descriptors: Vec::new(),
+        }
+    }
+
+    pub fn with_console() -> Self {
+        let console: Arc<dyn FileDescription> = Arc::new(ConsoleFile);
+        let mut table = Self::new();
+        for _ in 0..3 {
+            table.descriptors.push(Some(console.clone()));
+        }
+        table
+    }
+
+    pub fn get(&self, fd: usize) -> Result<Arc<dyn FileDescription>, Errno> {
+        self.descriptors.get(fd).and_then(|file| file.clone()).ok_or(Errno::EBADF)
+    }
+
+    pub fn insert(&mut self, file: Arc<dyn FileDescription>) -> Result<usize, Errno> {
+        if let Some(fd) = self.descriptors.iter().position(|slot| slot.is_none()) {
+            self.descriptors[fd] = Some(file);
+            return Ok(fd);
+        }
+
+        if self.descriptors.len() >= MAX_FILE_DESCRIPTORS {
+            return Err(Errno::EMFILE);
+        }
+        self.descriptors.push(Some(file));
+        Ok(self.descriptors.len() - 1)
+    }
+
+    pub fn remove(&mut self, fd: usize) -> Result<Arc<dyn FileDescription>, Errno> {
+        self.descriptors.get_mut(fd).and_then(|slot| slot.take()).ok_or(Errno::EBADF)
+    }
+
+    pub fn open_count(&self) -> usize {
+        self.descriptors.iter().filter(|slot| slot.is_some()).count()
+    }
+}
@@ -0,0 +1,143 @@
+use super::{current_pid, PROCESS_TABLE};
+use crate::memory::paging::{PageMapper, PAGE_SIZE};
+use crate::syscalls::errno::Errno;
+use crate::syscalls::user;
+use crate::time;
+use crate::vm::mmap::{self, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE};
+use crate::vm::futex;
+use crate::vm::protection;
+use crate::vm::region::VirtualMemoryRegion;
+use crate::vm::VM_MANAGER;
+
+pub const MAX_BREAK_SIZE: u64 = 0x4000_0000;
+
+pub fn brk(address: u64) -> u64 {
+    let pid = match current_pid() {
+        Some(pid) => pid,
+        None => return 0,
+    };
+    let (address_space, start, current) = match PROCESS_TABLE.lock().get(pid) {
+        Some(process) => (process.address_space, process.break_start, process.program_break),
+        None => return 0,
+    };
+
+    if address < start || address > start + MAX_BREAK_SIZE {
+        return current;
+    }
+
+    let old_end = page_align_up(current);
+    let new_end = page_align_up(address);
+    if new_end > old_end {
+        let mut manager = VM_MANAGER.lock();
+        let regions = manager.space_mut(address_space.root());
+        if !regions.is_free(old_end, new_end) {
+            return current;
+        }
+        regions.insert(VirtualMemoryRegion::anonymous(old_end, new_end, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS));
+    } else if new_end < old_end && mmap::munmap(&address_space, new_end, old_end - new_end).is_err() {
+        return current;
+    }
+
+    if let Some(process) = PROCESS_TABLE.lock().get_mut(pid) {
+        process.program_break = address;
+    }
+    address
+}
+
+pub fn map(address: u64, length: u64, prot: u64, flags: u64, fd: u64, offset: u64) -> Result<u64, Errno> {
+    let file = if flags & MAP_ANONYMOUS == 0 {
+        Some((super::file(fd as usize)?, offset))
+    } else {
+        None
+    };
+    mmap::mmap(&current_address_space()?, address, length, prot, flags, file)
+}
+
+pub fn unmap(address: u64, length: u64) -> Result<(), Errno> {
+    mmap::munmap(&current_address_space()?, address, length)
+}
+
+pub fn protect(address: u64, length: u64, prot: u64) -> Result<(), Errno> {
+    mmap::mprotect(&current_address_space()?, address, length, prot)
+}
+
+pub fn remap(address: u64, old_length: u64, new_length: u64, flags: u64) -> Result<u64, Errno> {
+    mmap::mremap(&current_address_space()?, address, old_length, new_length, flags)
+}
+
+pub fn pkey_alloc(flags: u64, rights: u64) -> Result<u64, Errno> {
+    if flags != 0 || rights & !(protection::PKEY_RIGHTS_MASK as u64) != 0 {
+        return Err(Errno::EINVAL);
+    }
+    if !protection::pku_enabled() {
+        return Err(Errno::ENOSPC);
+    }
+
+    let pid = current_pid().ok_or(Errno::ESRCH)?;
+    let mut table = PROCESS_TABLE.lock();
+    let process = table.get_mut(pid).ok_or(Errno::ESRCH)?;
+    let pkey = (1..protection::PKEY_COUNT as u8)
+        .find(|pkey| process.pkeys & (1 << pkey) == 0)
+        .ok_or(Errno::ENOSPC)?;
+    process.pkeys |= 1 << pkey;
+    protection::set_pkey_rights(pkey, rights as u32);
+    Ok(pkey as u64)
+}
+
+pub fn pkey_free(pkey: u64) -> Result<(), Errno> {
+    let pid = current_pid().ok_or(Errno::ESRCH)?;
+    let mut table = PROCESS_TABLE.lock();
+    let process = table.get_mut(pid).ok_or(Errno::ESRCH)?;
+    if pkey == 0 || pkey >= protection::PKEY_COUNT as u64 || process.pkeys & (1 << pkey) == 0 {
+        return Err(Errno::EINVAL);
+    }
+    process.pkeys &= !(1 << pkey);
+    Ok(())
+}
+
+pub fn pkey_protect(address: u64, length: u64, prot: u64, pkey: u64) -> Result<(), Errno> {
+    let pid = current_pid().ok_or(Errno::ESRCH)?;
+    let address_space = {
+        let table = PROCESS_TABLE.lock();
+        let process = table.get(pid).ok_or(Errno::ESRCH)?;
+        if pkey >= protection::PKEY_COUNT as u64 || process.pkeys & (1 << pkey) == 0 {
+            return Err(Errno::EINVAL);
+        }
+        process.address_space
+    };
+    mmap::pkey_mprotect(&address_space, address, length, prot, pkey as u8)
+}
+
+pub fn futex(address: u64, op: u64, value: u64, timeout: u64) -> Result<u64, Errno> {
+    let address_space = current_address_space()?;
+    match op & !(futex::FUTEX_PRIVATE_FLAG | futex::FUTEX_CLOCK_REALTIME) {
+        futex::FUTEX_WAIT => {
+            let deadline = match timeout {
+                0 => None,
+                timeout => Some(futex_deadline(timeout)?),
+            };
+            futex::wait(&address_space, address, value as u32, deadline).map(|_| 0)
+        }
+        futex::FUTEX_WAKE => futex::wake(&address_space, address, value),
+        _ => Err(Errno::ENOSYS),
+    }
+}
+
+fn futex_deadline(timeout: u64) -> Result<u64, Errno> {
+    let [seconds, nanoseconds] = user::read_value::<[i64; 2]>(timeout)?;
+    if seconds < 0 || !(0..time::NANOS_PER_SECOND as i64).contains(&nanoseconds) {
+        return Err(Errno::EINVAL);
+    }
+    let relative = (seconds as u64).saturating_mul(time::NANOS_PER_SECOND).saturating_add(nanoseconds as u64);
+    Ok(time::monotonic_ns().saturating_add(relative))
+}
+
+fn current_address_space() -> Result<PageMapper, Errno> {
+    let pid = current_pid().ok_or(Errno::ESRCH)?;
+    let table = PROCESS_TABLE.lock();
+    Ok(table.get(pid).ok_or(Errno::ESRCH)?.address_space)
+}
+
+fn page_align_up(address: u64) -> u64 {
+    (address + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
@@ -0,0 +1,426 @@
+use crate::loader::LoadedImage;
+use crate::memory::paging::PageMapper;
+use crate::smp::synchronization::{SpinLock, WaitQueue};
+use crate::syscalls::errno::Errno;
+use crate::vga;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub mod files;
+pub mod memory;
+pub mod signal;
+
+use files::{FileDescription, FileTable};
+use signal::SignalState;
+
+pub const INIT_PID: usize = 1;
+pub const WAIT_ANY: i64 = -1;
+pub const WNOHANG: u64 = 1;
+pub const DEFAULT_PKEYS: u16 = 1;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProcessState {
+    Running,
+    Zombie,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Personality {
+    Native,
+    Linux,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ProcessError {
+    NotAProcess,
+    NoChildren,
+    OutOfMemory,
+    TaskLimit,
+    Interrupted,
+    PermissionDenied,
+}
+
+impl ProcessError {
+    pub fn errno(self) -> Errno {
+        match self {
+            ProcessError::NotAProcess => Errno::ESRCH,
+            ProcessError::NoChildren => Errno::ECHILD,
+            ProcessError::OutOfMemory => Errno::ENOMEM,
+            ProcessError::TaskLimit => Errno::EAGAIN,
+            ProcessError::Interrupted => Errno::EINTR,
+            ProcessError::PermissionDenied => Errno::EPERM,
+        }
+    }
+}
+
+pub struct Process {
+    pub pid: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub name: String,
+    pub address_space: PageMapper,
+    pub files: FileTable,
+    pub threads: Vec<usize>,
+    pub state: ProcessState,
+    pub exit_status: i32,
+    pub exit_signal: u8,
+    pub personality: Personality,
+    pub traced: bool,
+    pub break_start: u64,
+    pub program_break: u64,
+    pub pkeys: u16,
+    pub signals: SignalState,
+}
+
+impl Process {
+    fn reset_memory_layout(&mut self, program_break: u64) {
+        self.break_start = program_break;
+        self.program_break = program_break;
+        self.pkeys = DEFAULT_PKEYS;
+    }
+
+    pub fn wait_status(&self) -> i32 {
+        match self.exit_signal {
+            0 => (self.exit_status & 0xFF) << 8,
+            signo => signo as i32 & 0x7F,
+        }
+    }
+}
+
+pub struct ProcessTable {
+    processes: BTreeMap<usize, Process>,
+    next_pid: usize,
+}
+
+impl ProcessTable {
+    pub const fn new() -> Self {
+        Self {
+            processes: BTreeMap::new(),
+            next_pid: INIT_PID,
+        }
+    }
+
+    pub fn create(&mut self, name: &str, parent: Option<usize>, address_space: PageMapper, files: FileTable) -> usize {
+        let pid = self.next_pid;
+        self.next_pid += 1;
+
+        self.processes.insert(pid, Process {
+            pid,
+            parent,
+            children: Vec::new(),
+            name: name.to_string(),
+            address_space,
+            files,
+            threads: Vec::new(),
+            state: ProcessState::Running,
+            exit_status: 0,
+            exit_signal: 0,
+            personality: Personality::Native,
+            traced: false,
+            break_start: 0,
+            program_break: 0,
+            pkeys: DEFAULT_PKEYS,
+            signals: SignalState::new(),
+        });
+
+        if let Some(parent) = parent.and_then(|parent| self.processes.get_mut(&parent)) {
+            parent.children.push(pid);
+        }
+        pid
+    }
+
+    pub fn get(&self, pid: usize) -> Option<&Process> {
+        self.processes.get(&pid)
+    }
+
+    pub fn get_mut(&mut self, pid: usize) -> Option<&mut Process> {
+        self.processes.get_mut(&pid)
+    }
+
+    pub fn remove(&mut self, pid: usize) -> Option<Process> {
+        let process = self.processes.remove(&pid)?;
+        if let Some(parent) = process.parent.and_then(|parent| self.processes.get_mut(&parent)) {
+            parent.children.retain(|&child| child != pid);
+        }
+        Some(process)
+    }
+
+    pub fn reparent_children(&mut self, pid: usize) {
+        let children = match self.processes.get_mut(&pid) {
+            Some(process) => core::mem::take(&mut process.children),
+            None => return,
+        };
+
+        let new_parent = if pid != INIT_PID && self.processes.contains_key(&INIT_PID) {
+            Some(INIT_PID)
+        } else {
+            None
+        };
+
+        for child in children.iter() {
+            if let Some(process) = self.processes.get_mut(child) {
+                process.parent = new_parent;
+            }
+        }
+        if let Some(init) = new_parent.and_then(|init| self.processes.get_mut(&init)) {
+            init.children.extend_from_slice(&children);
+        }
+    }
+
+    pub fn reap_child(&mut self, parent: usize, pid: i64) -> Result<Option<(usize, i32)>, ProcessError> {
+        let children = match self.processes.get(&parent) {
+            Some(process) => &process.children,
+            None => return Err(ProcessError::NotAProcess),
+        };
+
+        let mut matched = false;
+        let mut zombie = None;
+        for &child in children.iter() {
+            if pid != WAIT_ANY && child as i64 != pid {
+                continue;
+            }
+            matched = true;
+            if let Some(process) = self.processes.get(&child) {
+                if process.state == ProcessState::Zombie {
+                    zombie = Some((child, process.wait_status()));
+                    break;
+                }
+            }
+        }
+
+        if !matched {
+            return Err(ProcessError::NoChildren);
+        }
+        if let Some((child, _)) = zombie {
+            self.remove(child);
+        }
+        Ok(zombie)
+    }
+
+    pub fn count(&self) -> usize {
+        self.processes.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Process> {
+        self.processes.values()
+    }
+}
+
+pub static PROCESS_TABLE: SpinLock<ProcessTable> = SpinLock::new(ProcessTable::new());
+static CHILD_EXITED: WaitQueue = WaitQueue::new();
+
+pub fn current_pid() -> Option<usize> {
+    crate::scheduler::current_process_id()
+}
+
+pub fn current_syscall_profile() -> (Option<usize>, Personality, bool) {
+    let pid = current_pid();
+    let (personality, traced) = pid
+        .and_then(|pid| PROCESS_TABLE.lock().get(pid).map(|process| (process.personality, process.traced)))
+        .unwrap_or((Personality::Native, false));
+    (pid, personality, traced)
+}
+
+pub fn set_traced(pid: usize, traced: bool) -> Result<(), ProcessError> {
+    let caller = current_pid();
+    let mut table = PROCESS_TABLE.lock();
+    let process = table.get_mut(pid).ok_or(ProcessError::NotAProcess)?;
+    let permitted = match caller {
+        None | Some(INIT_PID) => true,
+        Some(caller) => process.pid == caller || process.parent == Some(caller),
+    };
+    if !permitted {
+        return Err(ProcessError::PermissionDenied);
+    }
+    process.traced = traced;
+    Ok(())
+}
+
+pub fn may_trace_all() -> bool {
+    matches!(current_pid(), None | Some(INIT_PID))
+}
+
+pub fn set_personality(personality: Personality) -> Result<(), ProcessError> {
+    let pid = current_pid().ok_or(ProcessError::NotAProcess)?;
+    let mut table = PROCESS_TABLE.lock();
+    table.get_mut(pid).ok_or(ProcessError::NotAProcess)?.personality = personality;
+    Ok(())
+}
+
+pub fn spawn(name: &str, image: LoadedImage, personality: Personality) -> Result<usize, ProcessError> {
+    let parent = current_pid();
+    let pid = {
+        let mut table = PROCESS_TABLE.lock();
+        let pid = table.create(name, parent, image.address_space, FileTable::with_console());
+        if let Some(process) = table.get_mut(pid) {
+            process.personality = personality;
+            process.reset_memory_layout(image.program_break);
+        }
+        pid
+    };
+    crate::vm::enable_reclaim(&image.address_space);
+
+    let task_id = crate::scheduler::create_user_task(image.entry, image.stack_top, Some(image.address_space), Some(pid));
+    if task_id == 0 {
+        PROCESS_TABLE.lock().remove(pid);
+        crate::vm::destroy_address_space(image.address_space);
+        return Err(ProcessError::TaskLimit);
+    }
+
+    if let Some(process) = PROCESS_TABLE.lock().get_mut(pid) {
+        process.threads.push(task_id);
+    }
+    vga::print!("Created process {} ({}) with task {}\n", pid, name, task_id);
+    Ok(pid)
+}
+
+pub fn fork() -> Result<usize, ProcessError> {
+    let parent = current_pid().ok_or(ProcessError::NotAProcess)?;
+    let (mut address_space, files, name, personality, traced, break_start, program_break, pkeys, signals) = {
+        let table = PROCESS_TABLE.lock();
+        let process = table.get(parent).ok_or(ProcessError::NotAProcess)?;
+        (process.address_space, process.files.clone(), process.name.clone(), process.personality,
+            process.traced, process.break_start, process.program_break, process.pkeys, process.signals.inherit())
+    };
+
+    let child_space = address_space.clone_cow().map_err(|_| ProcessError::OutOfMemory)?;
+    crate::vm::clone_regions(&address_space, &child_space);
+    let child = {
+        let mut table = PROCESS_TABLE.lock();
+        let child = table.create(&name, Some(parent), child_space, files);
+        if let Some(process) = table.get_mut(child) {
+            process.personality = personality;
+            process.traced = traced;
+            process.break_start = break_start;
+            process.program_break = program_break;
+            process.pkeys = pkeys;
+            process.signals = signals;
+        }
+        child
+    };
+
+    match crate::scheduler::fork_current(child_space, child) {
+        Some(task_id) => {
+            if let Some(process) = PROCESS_TABLE.lock().get_mut(child) {
+                process.threads.push(task_id);
+            }
+            Ok(child)
+        }
+        None => {
+            let process = PROCESS_TABLE.lock().remove(child);
+            drop(process);
+            crate::vm::destroy_address_space(child_space);
+            Err(ProcessError::TaskLimit)
+        }
+    }
+}
+
+pub fn file(fd: usize) -> Result<Arc<dyn FileDescription>, Errno> {
+    let pid = current_pid().ok_or(Errno::EBADF)?;
+    let table = PROCESS_TABLE.lock();
+    table.get(pid).ok_or(Errno::EBADF)?.files.get(fd)
+}
+
+pub fn install_file(file: Arc<dyn FileDescription>) -> Result<usize, Errno> {
+    let pid = current_pid().ok_or(Errno::ESRCH)?;
+    let mut table = PROCESS_TABLE.lock();
+    table.get_mut(pid).ok_or(Errno::ESRCH)?.files.insert(file)
+}
+
+pub fn close_file(fd: usize) -> Result<(), Errno> {
+    let pid = current_pid().ok_or(Errno::EBADF)?;
+    let file = {
+        let mut table = PROCESS_TABLE.lock();
+        table.get_mut(pid).ok_or(Errno::EBADF)?.files.remove(fd)?
+    };
+    drop(file);
+    Ok(())
+}
+
+pub fn replace_image(address_space: PageMapper, program_break: u64) {
+    if let Some(pid) = current_pid() {
+        if let Some(process) = PROCESS_TABLE.lock().get_mut(pid) {
+            process.address_space = address_space;
+            process.reset_memory_layout(program_break);
+            process.signals.reset_handlers();
+        }
+    }
+    crate::vm::enable_reclaim(&address_space);
+}
+
+pub fn exit(status: i32) -> ! {
+    finish(status, 0)
+}
+
+pub fn exit_by_signal(signo: u8) -> ! {
+    finish(signal::TERMINATED_BY_SIGNAL + signo as i32, signo)
+}
+
+fn finish(status: i32, signo: u8) -> ! {
+    if let Some(pid) = current_pid() {
+        let (address_space, files, parent) = {
+            let mut table = PROCESS_TABLE.lock();
+            table.reparent_children(pid);
+            match table.get_mut(pid) {
+                Some(process) => {
+                    process.state = ProcessState::Zombie;
+                    process.exit_status = status;
+                    process.exit_signal = signo;
+                    (Some(process.address_space), core::mem::replace(&mut process.files, FileTable::new()), process.parent)
+                }
+                None => (None, FileTable::new(), None),
+            }
+        };
+        drop(files);
+
+        if let Some(address_space) = address_space {
+            crate::scheduler::release_address_space();
+            crate::vm::destroy_address_space(address_space);
+        }
+
+        vga::print!("Process {} exited with status {}\n", pid, status);
+        if let Some(parent) = parent {
+            let _ = signal::send(parent, signal::SIGCHLD, signal::CLD_EXITED, pid as u64);
+        }
+        CHILD_EXITED.wake_all();
+    }
+
+    crate::scheduler::exit(status)
+}
+
+pub fn wait(pid: i64, options: u64) -> Result<Option<(usize, i32)>, ProcessError> {
+    let parent = current_pid().ok_or(ProcessError::NotAProcess)?;
+
+    loop {
+        let mut result = Ok(None);
+        let waited = CHILD_EXITED.wait_if(|| {
+            result = PROCESS_TABLE.lock().reap_child(parent, pid);
+            matches!(result, Ok(None)) && options & WNOHANG == 0
+        });
+        if waited && signal::pending() {
+            return Err(ProcessError::Interrupted);
+        }
+
+        match result {
+            Ok(None) if options & WNOHANG == 0 => continue,
+            other => return other,
+        }
+    }
+}
+
+pub fn print_processes() {
+    let table = PROCESS_TABLE.lock();
+    vga::print!("Processes ({}):\n", table.count());
+    for process in table.iter() {
+        let state = match process.state {
+            ProcessState::Running if process.signals.stopped => "stopped",
+            ProcessState::Running => "running",
+            ProcessState::Zombie => "zombie",
+        };
+        vga::print!("  {} {} parent={:?} threads={} files={} {:?} {}\n",
+            process.pid, process.name, process.parent, process.threads.len(),
+            process.files.open_count(), process.personality, state);
+    }
+}
@@ -0,0 +1,674 @@
+use super::{current_pid, Personality, ProcessState, INIT_PID, PROCESS_TABLE};
+use crate::gdt::{USER_CODE_SELECTOR, USER_DATA_SELECTOR};
+use crate::loader::USER_SPACE_END;
+use crate::scheduler::task::{FpuState, TaskContext};
+use crate::smp::synchronization::WaitQueue;
+use crate::syscalls::errno::Errno;
+use crate::syscalls::user;
+use crate::vga;
+use alloc::vec::Vec;
+
+pub const NSIG: usize = 64;
+
+pub const SIGHUP: u8 = 1;
+pub const SIGINT: u8 = 2;
+pub const SIGQUIT: u8 = 3;
+pub const SIGILL: u8 = 4;
+pub const SIGTRAP: u8 = 5;
+pub const SIGABRT: u8 = 6;
+pub const SIGBUS: u8 = 7;
+pub const SIGFPE: u8 = 8;
+pub const SIGKILL: u8 = 9;
+pub const SIGUSR1: u8 = 10;
+pub const SIGSEGV: u8 = 11;
+pub const SIGUSR2: u8 = 12;
+pub const SIGPIPE: u8 = 13;
+pub const SIGALRM: u8 = 14;
+pub const SIGTERM: u8 = 15;
+pub const SIGCHLD: u8 = 17;
+pub const SIGCONT: u8 = 18;
+pub const SIGSTOP: u8 = 19;
+pub const SIGTSTP: u8 = 20;
+pub const SIGTTIN: u8 = 21;
+pub const SIGTTOU: u8 = 22;
+pub const SIGURG: u8 = 23;
+pub const SIGWINCH: u8 = 28;
+
+pub const SIG_DFL: u64 = 0;
+pub const SIG_IGN: u64 = 1;
+
+pub const SA_SIGINFO: u64 = 0x0000_0004;
+pub const SA_RESTORER: u64 = 0x0400_0000;
+pub const SA_NODEFER: u64 = 0x4000_0000;
+pub const SA_RESETHAND: u64 = 0x8000_0000;
+
+pub const SIG_BLOCK: u64 = 0;
+pub const SIG_UNBLOCK: u64 = 1;
+pub const SIG_SETMASK: u64 = 2;
+
+pub const SI_USER: i32 = 0;
+pub const SI_KERNEL: i32 = 0x80;
+pub const ILL_ILLOPN: i32 = 2;
+pub const FPE_INTDIV: i32 = 1;
+pub const SEGV_MAPERR: i32 = 1;
+pub const SEGV_ACCERR: i32 = 2;
+pub const SEGV_PKUERR: i32 = 4;
+pub const BUS_ADRALN: i32 = 1;
+pub const BUS_ADRERR: i32 = 2;
+pub const TRAP_BRKPT: i32 = 1;
+pub const TRAP_TRACE: i32 = 2;
+pub const CLD_EXITED: i32 = 1;
+
+pub const TERMINATED_BY_SIGNAL: i32 = 128;
+
+const UNBLOCKABLE: u64 = bit(SIGKILL) | bit(SIGSTOP);
+const STOP_SIGNALS: u64 = bit(SIGSTOP) | bit(SIGTSTP) | bit(SIGTTIN) | bit(SIGTTOU);
+const SYNCHRONOUS_SIGNALS: u64 = bit(SIGILL) | bit(SIGTRAP) | bit(SIGBUS) | bit(SIGFPE) | bit(SIGSEGV);
+
+const RED_ZONE_SIZE: u64 = 128;
+const RFLAGS_RESERVED: u64 = 1 << 1;
+const RFLAGS_TF: u64 = 1 << 8;
+const RFLAGS_IF: u64 = 1 << 9;
+const RFLAGS_DF: u64 = 1 << 10;
+const RFLAGS_USER_MASK: u64 = 0x0005_0DD5;
+const SS_DISABLE: u64 = 2;
+const FPU_STATE_ALIGN: u64 = 64;
+const MXCSR_OFFSET: usize = 24;
+const MXCSR_RESERVED: u32 = 0xFFFF_0000;
+
+const DEFAULT_ACTION: SignalAction = SignalAction {
+    handler: SIG_DFL,
+    flags: 0,
+    restorer: 0,
+    mask: 0,
+};
+
+static STOPPED: WaitQueue = WaitQueue::new();
+
+#[derive(Clone, Copy, PartialEq)]
+enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct SignalAction {
+    pub handler: u64,
+    pub flags: u64,
+    pub restorer: u64,
+    pub mask: u64,
+}
+
+#[derive(Clone, Copy)]
+struct PendingSignal {
+    code: i32,
+    data: u64,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct SignalInfo {
+    signo: i32,
+    errno: i32,
+    code: i32,
+    reserved: i32,
+    data: u64,
+    padding: [u64; 13],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct SignalFrame {
+    restorer: u64,
+    info: SignalInfo,
+    context: TaskContext,
+    mask: u64,
+    fpstate: u64,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct LinuxSignalContext {
+    r8: u64,
+    r9: u64,
+    r10: u64,
+    r11: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    rdi: u64,
+    rsi: u64,
+    rbp: u64,
+    rbx: u64,
+    rdx: u64,
+    rax: u64,
+    rcx: u64,
+    rsp: u64,
+    rip: u64,
+    rflags: u64,
+    cs: u16,
+    gs: u16,
+    fs: u16,
+    ss: u16,
+    err: u64,
+    trapno: u64,
+    oldmask: u64,
+    cr2: u64,
+    fpstate: u64,
+    reserved: [u64; 8],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct LinuxUserContext {
+    flags: u64,
+    link: u64,
+    stack_pointer: u64,
+    stack_flags: u64,
+    stack_size: u64,
+    machine: LinuxSignalContext,
+    mask: u64,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct LinuxSignalFrame {
+    restorer: u64,
+    context: LinuxUserContext,
+    info: SignalInfo,
+}
+
+impl LinuxSignalContext {
+    fn capture(context: &TaskContext, mask: u64, fpstate: u64) -> Self {
+        Self {
+            r8: context.r8,
+            r9: context.r9,
+            r10: context.r10,
+            r11: context.r11,
+            r12: context.r12,
+            r13: context.r13,
+            r14: context.r14,
+            r15: context.r15,
+            rdi: context.rdi,
+            rsi: context.rsi,
+            rbp: context.rbp,
+            rbx: context.rbx,
+            rdx: context.rdx,
+            rax: context.rax,
+            rcx: context.rcx,
+            rsp: context.rsp,
+            rip: context.rip,
+            rflags: context.rflags,
+            cs: context.cs as u16,
+            gs: 0,
+            fs: 0,
+            ss: context.ss as u16,
+            err: 0,
+            trapno: 0,
+            oldmask: mask,
+            cr2: 0,
+            fpstate,
+            reserved: [0; 8],
+        }
+    }
+
+    fn task_context(&self) -> TaskContext {
+        TaskContext {
+            r15: self.r15,
+            r14: self.r14,
+            r13: self.r13,
+            r12: self.r12,
+            r11: self.r11,
+            r10: self.r10,
+            r9: self.r9,
+            r8: self.r8,
+            rdi: self.rdi,
+            rsi: self.rsi,
+            rbp: self.rbp,
+            rdx: self.rdx,
+            rcx: self.rcx,
+            rbx: self.rbx,
+            rax: self.rax,
+            rip: self.rip,
+            cs: self.cs as u64,
+            rflags: self.rflags,
+            rsp: self.rsp,
+            ss: self.ss as u64,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SignalState {
+    actions: [SignalAction; NSIG],
+    info: [PendingSignal; NSIG],
+    pub mask: u64,
+    pub pending: u64,
+    pub stopped: bool,
+}
+
+impl SignalState {
+    pub const fn new() -> Self {
+        Self {
+            actions: [DEFAULT_ACTION; NSIG],
+            info: [PendingSignal { code: SI_USER, data: 0 }; NSIG],
+            mask: 0,
+            pending: 0,
+            stopped: false,
+        }
+    }
+
+    pub fn inherit(&self) -> Self {
+        Self {
+            actions: self.actions,
+            mask: self.mask,
+            ..Self::new()
+        }
+    }
+
+    pub fn reset_handlers(&mut self) {
+        for action in self.actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = DEFAULT_ACTION;
+            }
+        }
+    }
+
+    fn is_ignored(&self, signo: u8) -> bool {
+        match self.actions[signo as usize - 1].handler {
+            SIG_IGN => true,
+            SIG_DFL => default_action(signo) == DefaultAction::Ignore,
+            _ => false,
+        }
+    }
+
+    fn queue(&mut self, signo: u8, info: PendingSignal) -> bool {
+        match signo {
+            SIGCONT | SIGKILL => {
+                self.pending &= !STOP_SIGNALS;
+                self.stopped = false;
+            }
+            SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => self.pending &= !bit(SIGCONT),
+            _ => {}
+        }
+        if self.is_ignored(signo) {
+            return false;
+        }
+
+        if self.pending & bit(signo) == 0 {
+            self.info[signo as usize - 1] = info;
+        }
+        self.pending |= bit(signo);
+        true
+    }
+
+    fn next(&self) -> Option<u8> {
+        let deliverable = self.pending & !(self.mask & !UNBLOCKABLE);
+        let synchronous = deliverable & SYNCHRONOUS_SIGNALS;
+        let set = if synchronous != 0 { synchronous } else { deliverable };
+        (set != 0).then(|| set.trailing_zeros() as u8 + 1)
+    }
+}
+
+const fn bit(signo: u8) -> u64 {
+    1 << (signo - 1)
+}
+
+fn default_action(signo: u8) -> DefaultAction {
+    match signo {
+        SIGCHLD | SIGCONT | SIGURG | SIGWINCH => DefaultAction::Ignore,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        _ => DefaultAction::Terminate,
+    }
+}
+
+fn validate(signo: u64) -> Result<u8, Errno> {
+    if signo == 0 || signo > NSIG as u64 {
+        return Err(Errno::EINVAL);
+    }
+    Ok(signo as u8)
+}
+
+pub fn send(pid: usize, signo: u8, code: i32, data: u64) -> Result<(), Errno> {
+    let (queued, resumed, threads) = {
+        let mut table = PROCESS_TABLE.lock();
+        let process = table.get_mut(pid).ok_or(Errno::ESRCH)?;
+        if signo == 0 || process.state == ProcessState::Zombie {
+            return Ok(());
+        }
+        if pid == INIT_PID && process.signals.actions[signo as usize - 1].handler == SIG_DFL {
+            return Ok(());
+        }
+
+        let was_stopped = process.signals.stopped;
+        let queued = process.signals.queue(signo, PendingSignal { code, data });
+        (queued, was_stopped && !process.signals.stopped, process.threads.clone())
+    };
+
+    if resumed {
+        STOPPED.wake_all();
+    }
+    if queued {
+        for task in threads {
+            crate::scheduler::unblock_task(task);
+        }
+    }
+    Ok(())
+}
+
+pub fn raise(signo: u8) {
+    if let Some(pid) = current_pid() {
+        let _ = send(pid, signo, SI_KERNEL, 0);
+    }
+}
+
+pub fn force(signo: u8, code: i32, data: u64) -> bool {
+    let pid = match current_pid() {
+        Some(pid) => pid,
+        None => return false,
+    };
+    let mut table = PROCESS_TABLE.lock();
+    let signals = match table.get_mut(pid) {
+        Some(process) => &mut process.signals,
+        None => return false,
+    };
+
+    let index = signo as usize - 1;
+    if signals.actions[index].handler == SIG_IGN || signals.mask & bit(signo) != 0 {
+        signals.actions[index] = DEFAULT_ACTION;
+        signals.mask &= !bit(signo);
+    }
+    signals.info[index] = PendingSignal { code, data };
+    signals.pending |= bit(signo);
+    signals.actions[index].handler > SIG_IGN
+}
+
+pub fn pending() -> bool {
+    current_pid()
+        .and_then(|pid| PROCESS_TABLE.lock().get(pid).map(|process| process.signals.next().is_some()))
+        .unwrap_or(false)
+}
+
+pub fn kill(pid: i64, signo: u64) -> Result<(), Errno> {
+    if signo > NSIG as u64 {
+        return Err(Errno::EINVAL);
+    }
+    let signo = signo as u8;
+    let sender = current_pid().unwrap_or(0);
+
+    match pid {
+        0 => send(sender, signo, SI_USER, sender as u64),
+        -1 => {
+            let targets: Vec<usize> = PROCESS_TABLE
+                .lock()
+                .iter()
+                .map(|process| process.pid)
+                .filter(|&target| target != INIT_PID && target != sender)
+                .collect();
+            if targets.is_empty() {
+                return Err(Errno::ESRCH);
+            }
+            for target in targets {
+                let _ = send(target, signo, SI_USER, sender as u64);
+            }
+            Ok(())
+        }
+        pid => send(pid.unsigned_abs() as usize, signo, SI_USER, sender as u64),
+    }
+}
+
+pub fn sigaction(signo: u64, action: u64, old_action: u64) -> Result<(), Errno> {
+    let signo = validate(signo)?;
+    let new_action = if action != 0 {
+        let new_action: SignalAction = user::read_value(action)?;
+        if bit(signo) & UNBLOCKABLE != 0 {
+            return Err(Errno::EINVAL);
+        }
+        if new_action.handler > SIG_IGN
+            && (new_action.handler >= USER_SPACE_END
+                || new_action.flags & SA_RESTORER == 0
+                || !user::access_ok(new_action.restorer, 1))
+        {
+            return Err(Errno::EINVAL);
+        }
+        Some(new_action)
+    } else {
+        None
+    };
+
+    let pid = current_pid().ok_or(Errno::ESRCH)?;
+    let previous = {
+        let mut table = PROCESS_TABLE.lock();
+        let signals = &mut table.get_mut(pid).ok_or(Errno::ESRCH)?.signals;
+        let index = signo as usize - 1;
+        let previous = signals.actions[index];
+        if let Some(mut new_action) = new_action {
+            new_action.mask &= !UNBLOCKABLE;
+            signals.actions[index] = new_action;
+            if signals.is_ignored(signo) {
+                signals.pending &= !bit(signo);
+            }
+        }
+        previous
+    };
+
+    if old_action != 0 {
+        user::write_value(old_action, previous)?;
+    }
+    Ok(())
+}
+
+pub fn sigprocmask(how: u64, set: u64, old_set: u64) -> Result<(), Errno> {
+    let set = if set != 0 { Some(user::read_value::<u64>(set)?) } else { None };
+
+    let pid = current_pid().ok_or(Errno::ESRCH)?;
+    let previous = {
+        let mut table = PROCESS_TABLE.lock();
+        let signals = &mut table.get_mut(pid).ok_or(Errno::ESRCH)?.signals;
+        let previous = signals.mask;
+        if let Some(set) = set {
+            let mask = match how {
+                SIG_BLOCK => previous | set,
+                SIG_UNBLOCK => previous & !set,
+                SIG_SETMASK => set,
+                _ => return Err(Errno::EINVAL),
+            };
+            signals.mask = mask & !UNBLOCKABLE;
+        }
+        previous
+    };
+
+    if old_set != 0 {
+        user::write_value(old_set, previous)?;
+    }
+    Ok(())
+}
+
+pub fn deliver_pending(context: &mut TaskContext) {
+    if context.cs & 3 != 3 {
+        return;
+    }
+    let pid = match current_pid() {
+        Some(pid) => pid,
+        None => return,
+    };
+
+    loop {
+        let (signo, action, info, mask) = {
+            let mut table = PROCESS_TABLE.lock();
+            let signals = match table.get_mut(pid) {
+                Some(process) => &mut process.signals,
+                None => return,
+            };
+            let signo = match signals.next() {
+                Some(signo) => signo,
+                None => return,
+            };
+
+            let index = signo as usize - 1;
+            let action = signals.actions[index];
+            let mask = signals.mask;
+            signals.pending &= !bit(signo);
+            if action.handler > SIG_IGN {
+                signals.mask |= action.mask & !UNBLOCKABLE;
+                if action.flags & SA_NODEFER == 0 {
+                    signals.mask |= bit(signo);
+                }
+                if action.flags & SA_RESETHAND != 0 {
+                    signals.actions[index] = DEFAULT_ACTION;
+                }
+            } else if action.handler == SIG_DFL && default_action(signo) == DefaultAction::Stop {
+                signals.stopped = true;
+            }
+            (signo, action, signals.info[index], mask)
+        };
+
+        match action.handler {
+            SIG_IGN => continue,
+            SIG_DFL => match default_action(signo) {
+                DefaultAction::Ignore => continue,
+                DefaultAction::Stop => stop(pid),
+                DefaultAction::Terminate => terminate(pid, signo),
+            },
+            _ => {
+                if setup_frame(context, signo, &action, info, mask).is_err() {
+                    vga::print!("Cannot build frame for signal {} in process {}\n", signo, pid);
+                    terminate(pid, SIGSEGV);
+                }
+                return;
+            }
+        }
+    }
+}
+
+pub fn sigreturn(context: &mut TaskContext) {
+    if restore_frame(context).is_err() {
+        vga::print!("Bad signal frame in process {:?}\n", current_pid());
+        force(SIGSEGV, SI_KERNEL, 0);
+    }
+}
+
+fn setup_frame(context: &mut TaskContext, signo: u8, action: &SignalAction, info: PendingSignal, mask: u64) -> Result<(), Errno> {
+    let info = SignalInfo {
+        signo: signo as i32,
+        errno: 0,
+        code: info.code,
+        reserved: 0,
+        data: info.data,
+        padding: [0; 13],
+    };
+
+    let fpstate = context
+        .rsp
+        .checked_sub(RED_ZONE_SIZE + core::mem::size_of::<FpuState>() as u64)
+        .map(|address| address & !(FPU_STATE_ALIGN - 1))
+        .ok_or(Errno::EFAULT)?;
+    user::write_value(fpstate, crate::scheduler::save_fpu())?;
+
+    match super::current_syscall_profile().1 {
+        Personality::Native => {
+            let frame = SignalFrame {
+                restorer: action.restorer,
+                info,
+                context: *context,
+                mask,
+                fpstate,
+            };
+            let context_offset = 8 + core::mem::size_of::<SignalInfo>() as u64;
+            push_frame(context, fpstate, action, signo, frame, 8, context_offset)
+        }
+        Personality::Linux => {
+            let frame = LinuxSignalFrame {
+                restorer: action.restorer,
+                context: LinuxUserContext {
+                    flags: 0,
+                    link: 0,
+                    stack_pointer: 0,
+                    stack_flags: SS_DISABLE,
+                    stack_size: 0,
+                    machine: LinuxSignalContext::capture(context, mask, fpstate),
+                    mask,
+                },
+                info,
+            };
+            let info_offset = 8 + core::mem::size_of::<LinuxUserContext>() as u64;
+            push_frame(context, fpstate, action, signo, frame, info_offset, 8)
+        }
+    }
+}
+
+fn push_frame<T: Copy>(context: &mut TaskContext, top: u64, action: &SignalAction, signo: u8, frame: T, info_offset: u64, context_offset: u64) -> Result<(), Errno> {
+    let size = core::mem::size_of::<T>() as u64;
+    let frame_address = top
+        .checked_sub(size)
+        .and_then(|address| (address & !0xF).checked_sub(8))
+        .ok_or(Errno::EFAULT)?;
+    user::write_value(frame_address, frame)?;
+
+    context.rsp = frame_address;
+    context.rip = action.handler;
+    context.rdi = signo as u64;
+    context.rsi = frame_address + info_offset;
+    context.rdx = frame_address + context_offset;
+    context.rax = 0;
+    context.rflags &= !(RFLAGS_TF | RFLAGS_DF);
+    Ok(())
+}
+
+fn restore_frame(context: &mut TaskContext) -> Result<(), Errno> {
+    let (saved, mask, fpstate) = match super::current_syscall_profile().1 {
+        Personality::Native => {
+            let frame: SignalFrame = user::read_value(context.rsp.wrapping_sub(8))?;
+            (frame.context, frame.mask, frame.fpstate)
+        }
+        Personality::Linux => {
+            let frame: LinuxUserContext = user::read_value(context.rsp)?;
+            (frame.machine.task_context(), frame.mask, frame.machine.fpstate)
+        }
+    };
+    if saved.rip >= USER_SPACE_END {
+        return Err(Errno::EFAULT);
+    }
+    let fpu = match fpstate {
+        0 => None,
+        address => {
+            let fpu: FpuState = user::read_value(address)?;
+            let mut mxcsr = [0u8; 4];
+            mxcsr.copy_from_slice(&fpu.data[MXCSR_OFFSET..MXCSR_OFFSET + 4]);
+            if u32::from_le_bytes(mxcsr) & MXCSR_RESERVED != 0 {
+                return Err(Errno::EINVAL);
+            }
+            Some(fpu)
+        }
+    };
+
+    let pid = current_pid().ok_or(Errno::ESRCH)?;
+    PROCESS_TABLE.lock().get_mut(pid).ok_or(Errno::ESRCH)?.signals.mask = mask & !UNBLOCKABLE;
+    if let Some(fpu) = fpu {
+        crate::scheduler::load_fpu(&fpu);
+    }
+
+    *context = saved;
+    context.cs = USER_CODE_SELECTOR as u64;
+    context.ss = USER_DATA_SELECTOR as u64;
+    context.rflags = (saved.rflags & RFLAGS_USER_MASK) | RFLAGS_IF | RFLAGS_RESERVED;
+    Ok(())
+}
+
+fn stop(pid: usize) {
+    vga::print!("Process {} stopped\n", pid);
+    while STOPPED.wait_if(|| is_stopped(pid)) {}
+    vga::print!("Process {} continued\n", pid);
+}
+
+fn is_stopped(pid: usize) -> bool {
+    PROCESS_TABLE.lock().get(pid).map_or(false, |process| process.signals.stopped)
+}
+
+fn terminate(pid: usize, signo: u8) -> ! {
+    vga::print!("Process {} terminated by signal {}\n", pid, signo);
+    super::exit_by_signal(signo)
+}
@@ -0,0 +1,111 @@
+use crate::scheduler::policy::{PolicyStats, SchedulingPolicy};
+use crate::scheduler::task_table::TaskTable;
+use alloc::collections::VecDeque;
+
+const NICE_0_WEIGHT: u64 = 1024;
+const TICK_VRUNTIME: u64 = 1_000_000;
+const SCHED_LATENCY_TICKS: u64 = 20;
+const MIN_GRANULARITY_TICKS: u64 = 2;
+
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291,
+    29154, 23254, 18705, 14949, 11916,
+    9548, 7620, 6100, 4904, 3906,
+    3121, 2501, 1991, 1586, 1277,
+    1024, 820, 655, 526, 423,
+    335, 272, 215, 172, 137,
+    110, 87, 70, 56, 45,
+    36, 29, 23, 18, 15,
+];
+
+pub fn nice_to_weight(nice: i8) -> u64 {
+    let index = (nice.clamp(-20, 19) + 20) as usize;
+    NICE_TO_WEIGHT[index]
+}
+
+pub struct FairScheduler {
+    min_vruntime: u64,
+    stats: PolicyStats,
+}
+
+impl FairScheduler {
+    pub fn new() -> Self {
+        Self {
+            min_vruntime: 0,
+            stats: PolicyStats::new("fair"),
+        }
+    }
+
+    fn runnable_weight(&self, tasks: &TaskTable, ready: &VecDeque<usize>) -> u64 {
+        ready.iter()
+            .filter_map(|&task_id| tasks.get_task(task_id))
+            .map(|task| nice_to_weight(task.nice))
+            .sum()
+    }
+}
+
+impl SchedulingPolicy for FairScheduler {
+    fn name(&self) -> &'static str {
+        "fair"
+    }
+
+    fn enqueue(&mut self, tasks: &mut TaskTable, task_id: usize) {
+        let task = match tasks.get_task_mut(task_id) {
+            Some(task) => task,
+            None => return,
+        };
+        let floor = self.min_vruntime.saturating_sub(SCHED_LATENCY_TICKS * TICK_VRUNTIME / 2);
+        if task.vruntime < floor {
+            task.vruntime = floor;
+        }
+        self.stats.wakeups += 1;
+    }
+
+    fn pick_next(&mut self, tasks: &mut TaskTable, ready: &VecDeque<usize>) -> Option<usize> {
+        let mut best: Option<(usize, u64)> = None;
+
+        for &task_id in ready {
+            if let Some(task) = tasks.get_task(task_id) {
+                match best {
+                    Some((_, vruntime)) if vruntime <= task.vruntime => {}
+                    _ => best = Some((task_id, task.vruntime)),
+                }
+            }
+        }
+
+        let (task_id, vruntime) = best?;
+        if vruntime > self.min_vruntime {
+            self.min_vruntime = vruntime;
+        }
+
+        let task = tasks.get_task_mut(task_id)?;
+        task.slice_runtime = 0;
+        self.stats.picks += 1;
+        Some(task.id)
+    }
+
+    fn tick(&mut self, tasks: &mut TaskTable, ready: &VecDeque<usize>, task_id: usize) -> bool {
+        let runnable_weight = self.runnable_weight(tasks, ready);
+        let task = match tasks.get_task_mut(task_id) {
+            Some(task) => task,
+            None => return true,
+        };
+
+        let weight = nice_to_weight(task.nice);
+        task.vruntime += TICK_VRUNTIME * NICE_0_WEIGHT / weight;
+        task.slice_runtime += 1;
+
+        let total_weight = runnable_weight + weight;
+        let slice = (SCHED_LATENCY_TICKS * weight / total_weight).max(MIN_GRANULARITY_TICKS);
+
+        if task.slice_runtime >= slice && runnable_weight > 0 {
+            self.stats.preemptions += 1;
+            return true;
+        }
+        false
+    }
+
+    fn stats(&self) -> PolicyStats {
+        self.stats
+    }
+}
@@ -0,0 +1,94 @@
+use crate::scheduler::policy::{PolicyStats, SchedulingPolicy};
+use crate::scheduler::task_table::{TaskTable, MAX_TASKS};
+use alloc::collections::VecDeque;
+
+pub const MLFQ_LEVELS: usize = 8;
+const BOOST_INTERVAL_TICKS: u64 = 1000;
+
+pub struct MultilevelFeedbackScheduler {
+    cpu: u32,
+    ticks_since_boost: u64,
+    stats: PolicyStats,
+}
+
+impl MultilevelFeedbackScheduler {
+    pub fn new(cpu: u32) -> Self {
+        Self {
+            cpu,
+            ticks_since_boost: 0,
+            stats: PolicyStats::new("mlfq"),
+        }
+    }
+
+    fn level_quantum(level: u8) -> u32 {
+        1 << level.min(MLFQ_LEVELS as u8 - 1)
+    }
+}
+
+impl SchedulingPolicy for MultilevelFeedbackScheduler {
+    fn name(&self) -> &'static str {
+        "mlfq"
+    }
+
+    fn enqueue(&mut self, tasks: &mut TaskTable, task_id: usize) {
+        let task = match tasks.get_task_mut(task_id) {
+            Some(task) => task,
+            None => return,
+        };
+        if task.ticks_left == 0 {
+            task.ticks_left = Self::level_quantum(task.mlfq_level);
+        }
+        self.stats.wakeups += 1;
+    }
+
+    fn pick_next(&mut self, tasks: &mut TaskTable, ready: &VecDeque<usize>) -> Option<usize> {
+        if self.ticks_since_boost >= BOOST_INTERVAL_TICKS {
+            self.ticks_since_boost = 0;
+            self.stats.boosts += 1;
+            for slot in 0..MAX_TASKS {
+                if let Some(task) = tasks.slot_mut(slot) {
+                    if task.cpu != self.cpu {
+                        continue;
+                    }
+                    task.mlfq_level = 0;
+                    task.ticks_left = Self::level_quantum(0);
+                }
+            }
+        }
+
+        let task_id = ready.iter()
+            .filter_map(|&task_id| tasks.get_task(task_id))
+            .min_by_key(|task| task.mlfq_level)?
+            .id;
+
+        let task = tasks.get_task_mut(task_id)?;
+        if task.ticks_left == 0 {
+            task.ticks_left = Self::level_quantum(task.mlfq_level);
+        }
+        self.stats.picks += 1;
+        Some(task_id)
+    }
+
+    fn tick(&mut self, tasks: &mut TaskTable, _ready: &VecDeque<usize>, task_id: usize) -> bool {
+        self.ticks_since_boost += 1;
+        let task = match tasks.get_task_mut(task_id) {
+            Some(task) => task,
+            None => return true,
+        };
+        task.ticks_left = task.ticks_left.saturating_sub(1);
+
+        if task.ticks_left == 0 {
+            if (task.mlfq_level as usize) < MLFQ_LEVELS - 1 {
+                task.mlfq_level += 1;
+                self.stats.demotions += 1;
+            }
+            self.stats.preemptions += 1;
+            return true;
+        }
+        false
+    }
+
+    fn stats(&self) -> PolicyStats {
+        self.stats
+    }
+}
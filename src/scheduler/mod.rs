@@ -1,100 +1,788 @@
+use crate::memory::paging::PageMapper;
+use crate::smp::synchronization::TicketLock;
 use crate::vga;
+use alloc::boxed::Box;
+use core::arch::asm;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 pub mod task;
+pub mod task_table;
+pub mod policy;
 pub mod round_robin;
+pub mod fair;
+pub mod mlfq;
+pub mod realtime;
+pub mod run_queue;
+pub mod user_stack;
 
-use task::Task;
+use task::{FpuState, Task, TaskContext, TaskState};
+use task_table::{TaskTable, MAX_CPUS};
+use policy::{PolicyKind, PolicyStats, SchedulingPolicy};
 use round_robin::RoundRobinScheduler;
+use fair::FairScheduler;
+use mlfq::MultilevelFeedbackScheduler;
+use realtime::RealtimeScheduler;
+use run_queue::{RunQueue, RUN_QUEUES};
+
+pub const YIELD_VECTOR: u8 = 0x81;
+pub const KERNEL_STACK_SIZE: usize = 0x4000;
+
+const CR0_MP: u64 = 1 << 1;
+const CR0_EM: u64 = 1 << 2;
+const CR0_TS: u64 = 1 << 3;
+const CR4_OSFXSR: u64 = 1 << 9;
+const CR4_OSXMMEXCPT: u64 = 1 << 10;
+const MSR_FS_BASE: u32 = 0xC000_0100;
 
 pub struct Scheduler {
-    current_task: AtomicUsize,
-    scheduler: RoundRobinScheduler,
+    current_task: [AtomicUsize; MAX_CPUS],
+    tasks: TaskTable,
+    policy_kind: PolicyKind,
+    fpu_owner: [Option<usize>; MAX_CPUS],
+    retiring: [Option<usize>; MAX_CPUS],
 }
 
+const NO_TASK: AtomicUsize = AtomicUsize::new(0);
+
 impl Scheduler {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
-            current_task: AtomicUsize::new(0),
-            scheduler: RoundRobinScheduler::new(),
+            current_task: [NO_TASK; MAX_CPUS],
+            tasks: TaskTable::new(),
+            policy_kind: PolicyKind::Fair,
+            fpu_owner: [None; MAX_CPUS],
+            retiring: [None; MAX_CPUS],
         }
     }
 
-    pub fn init(&mut self) {
+    pub fn init(&mut self, kind: PolicyKind) {
         vga::print!("Initializing task scheduler...\n");
         
-        self.scheduler.init();
-        self.create_idle_task();
+        self.policy_kind = kind;
+        self.init_cpu(0);
+        self.create_boot_task();
         
         vga::print!("Task scheduler initialized\n");
     }
 
-    fn create_idle_task(&mut self) {
-        let idle_task = Task::new_idle();
-        self.scheduler.add_task(idle_task);
+    pub fn init_cpu(&mut self, cpu: u32) {
+        enable_fpu();
+        
+        let policy: Box<dyn SchedulingPolicy> = match self.policy_kind {
+            PolicyKind::RoundRobin => {
+                let mut round_robin = RoundRobinScheduler::new();
+                round_robin.init();
+                Box::new(round_robin)
+            }
+            PolicyKind::Fair => Box::new(FairScheduler::new()),
+            PolicyKind::MultilevelFeedback => Box::new(MultilevelFeedbackScheduler::new(cpu)),
+            PolicyKind::Realtime => Box::new(RealtimeScheduler::new()),
+        };
+        vga::print!("CPU {} run queue: {} policy\n", cpu, policy.name());
+        *RUN_QUEUES[cpu as usize].lock() = Some(RunQueue::new(cpu, policy));
+        
+        self.create_idle_task(cpu);
+    }
+
+    fn create_boot_task(&mut self) {
+        let boot_task = Task::new_boot();
+        let task_id = self.tasks.add_task(boot_task);
+        self.current_task[0].store(task_id, Ordering::Relaxed);
+    }
+
+    fn create_idle_task(&mut self, cpu: u32) {
+        let mut idle_task = Task::new_idle();
+        idle_task.detached = true;
+        idle_task.cpu = cpu;
+        idle_task.affinity = 1 << cpu;
+        let task_id = self.tasks.add_task(idle_task);
+        self.tasks.set_idle_task(cpu, task_id);
+    }
+
+    pub fn start_cpu(&mut self, cpu: u32) {
+        let idle_task = self.tasks.idle_task(cpu);
+        if let Some(task) = self.tasks.get_task_mut(idle_task) {
+            task.state = TaskState::Running;
+        }
+        self.current_task[cpu as usize].store(idle_task, Ordering::Relaxed);
     }
 
     pub fn create_task(&mut self, entry_point: fn(), stack_size: usize) -> usize {
         let task = Task::new(entry_point, stack_size);
-        let task_id = self.scheduler.add_task(task);
+        let task_id = self.tasks.add_task(task);
+        if task_id != 0 {
+            self.place_task(task_id);
+        }
         vga::print!("Created task with ID: {}\n", task_id);
         task_id
     }
 
-    pub fn schedule(&mut self) {
-        let next_task = self.scheduler.get_next_task();
-        if let Some(task_id) = next_task {
-            self.current_task.store(task_id, Ordering::Relaxed);
-            self.scheduler.switch_to_task(task_id);
+    pub fn create_user_task(&mut self, entry_point: u64, user_stack_top: u64, address_space: Option<PageMapper>, process: Option<usize>) -> usize {
+        let mut task = Task::new_user(entry_point, user_stack_top, KERNEL_STACK_SIZE);
+        task.address_space = address_space;
+        task.process = process;
+        task.detached = process.is_some();
+        let task_id = self.tasks.add_task(task);
+        if task_id != 0 {
+            self.place_task(task_id);
+        }
+        vga::print!("Created user task with ID: {}\n", task_id);
+        task_id
+    }
+
+    pub fn exec_current(&mut self, entry_point: u64, user_stack_top: u64, address_space: PageMapper) -> Result<Option<PageMapper>, PageMapper> {
+        let current = self.get_current_task_id();
+        let task = match self.tasks.get_task_mut(current) {
+            Some(task) if task.user_mode => task,
+            _ => return Err(address_space),
+        };
+        
+        let previous = task.address_space.replace(address_space);
+        task.fs_base = 0;
+        write_fs_base(0);
+        task.pkru = crate::vm::protection::DEFAULT_PKRU;
+        crate::vm::protection::write_pkru(task.pkru);
+        if let Some(context) = task.user_context_mut() {
+            *context = TaskContext {
+                r15: 0, r14: 0, r13: 0, r12: 0, r11: 0, r10: 0, r9: 0, r8: 0,
+                rdi: 0, rsi: 0, rbp: 0, rdx: 0, rcx: 0, rbx: 0, rax: 0,
+                rip: entry_point,
+                cs: crate::gdt::USER_CODE_SELECTOR as u64,
+                rflags: 0x202,
+                rsp: user_stack_top,
+                ss: crate::gdt::USER_DATA_SELECTOR as u64,
+            };
+        }
+        
+        address_space.activate();
+        Ok(previous)
+    }
+
+    pub fn fork_current(&mut self, address_space: PageMapper, process: usize) -> Option<usize> {
+        let current = self.get_current_task_id();
+        let cpu = crate::smp::current_cpu_id() as usize;
+        let owns_fpu = self.fpu_owner[cpu] == Some(current);
+        
+        let parent = self.tasks.get_task_mut(current)?;
+        if owns_fpu {
+            clear_task_switched();
+            parent.fpu_state.save();
+        }
+        
+        let mut context = *parent.user_context_mut()?;
+        context.rax = 0;
+        
+        let mut child = Task::new_user(context.rip, context.rsp, KERNEL_STACK_SIZE);
+        child.context = context;
+        child.priority = parent.priority;
+        child.nice = parent.nice;
+        child.affinity = parent.affinity;
+        child.fpu_state = parent.fpu_state;
+        child.fpu_initialized = parent.fpu_initialized;
+        child.address_space = Some(address_space);
+        child.process = Some(process);
+        child.fs_base = parent.fs_base;
+        child.pkru = crate::vm::protection::read_pkru();
+        child.detached = true;
+        
+        let task_id = self.tasks.add_task(child);
+        if task_id == 0 {
+            return None;
+        }
+        self.place_task(task_id);
+        Some(task_id)
+    }
+
+    pub fn current_process_id(&self) -> Option<usize> {
+        self.tasks.get_task(self.get_current_task_id()).and_then(|task| task.process)
+    }
+
+    pub fn set_fs_base(&mut self, base: u64) {
+        let current = self.get_current_task_id();
+        if let Some(task) = self.tasks.get_task_mut(current) {
+            task.fs_base = base;
+        }
+        write_fs_base(base);
+    }
+
+    pub fn fs_base(&self) -> u64 {
+        self.tasks.get_task(self.get_current_task_id()).map_or(0, |task| task.fs_base)
+    }
+
+    pub fn release_address_space(&mut self) {
+        let current = self.get_current_task_id();
+        if let Some(task) = self.tasks.get_task_mut(current) {
+            task.address_space = None;
+        }
+        PageMapper::kernel().activate();
+    }
+
+    fn place_task(&mut self, task_id: usize) {
+        let (affinity, previous) = match self.tasks.get_task(task_id) {
+            Some(task) => (task.affinity, task.cpu),
+            None => return,
+        };
+        if !self.release_fpu_on(task_id, previous) {
+            return;
+        }
+        
+        if let Some(queue) = RUN_QUEUES[previous as usize].lock().as_mut() {
+            queue.remove(task_id);
+        }
+        
+        let cpu = crate::smp::scheduler::select_cpu(&self.tasks, affinity);
+        if let Some(task) = self.tasks.get_task_mut(task_id) {
+            task.cpu = cpu;
+        }
+        
+        self.enqueue(task_id);
+        if cpu != crate::smp::current_cpu_id() {
+            crate::smp::scheduler::send_reschedule(cpu);
+        }
+    }
+
+    fn enqueue(&mut self, task_id: usize) {
+        let cpu = match self.tasks.get_task(task_id) {
+            Some(task) => task.cpu,
+            None => return,
+        };
+        
+        if let Some(queue) = RUN_QUEUES[cpu as usize].lock().as_mut() {
+            queue.enqueue(&mut self.tasks, task_id);
+        }
+    }
+
+    fn ready_count(&self, cpu: u32) -> usize {
+        RUN_QUEUES[cpu as usize].lock().as_ref().map_or(0, |queue| queue.ready_count(&self.tasks))
+    }
+
+    pub fn cpu_load(&self, cpu: u32) -> usize {
+        let running = match self.tasks.get_task(self.current_task[cpu as usize].load(Ordering::Relaxed)) {
+            Some(task) if !self.tasks.is_idle_task(task.id) && task.state == TaskState::Running => 1,
+            _ => 0,
+        };
+        self.ready_count(cpu) + running
+    }
+
+    pub fn migrate_one(&mut self, from_cpu: u32, to_cpu: u32) -> Option<usize> {
+        let running = self.current_task[from_cpu as usize].load(Ordering::Relaxed);
+        let fpu_owner = self.fpu_owner[from_cpu as usize].filter(|_| from_cpu != crate::smp::current_cpu_id());
+        let task_id = RUN_QUEUES[from_cpu as usize]
+            .lock()
+            .as_mut()?
+            .steal(&self.tasks, to_cpu, |id| id != running && Some(id) != fpu_owner)?;
+        self.release_fpu_on(task_id, from_cpu);
+        if let Some(task) = self.tasks.get_task_mut(task_id) {
+            task.cpu = to_cpu;
+        }
+        self.enqueue(task_id);
+        Some(task_id)
+    }
+
+    pub fn set_affinity(&mut self, task_id: usize, affinity: u64) -> bool {
+        if affinity == 0 {
+            return false;
+        }
+        
+        let (cpu, ready) = match self.tasks.get_task_mut(task_id) {
+            Some(task) => {
+                task.affinity = affinity;
+                (task.cpu, task.is_ready())
+            }
+            None => return false,
+        };
+        
+        if affinity & (1 << cpu) == 0 && ready {
+            self.place_task(task_id);
+        }
+        true
+    }
+
+    pub fn timer_tick(&mut self, context: &mut TaskContext) {
+        let cpu = crate::smp::current_cpu_id();
+        self.retire_previous(cpu);
+        
+        let current = self.get_current_task_id();
+        match self.tasks.get_task_mut(current) {
+            Some(task) => task.total_runtime += 1,
+            None => {
+                self.switch_context(context);
+                return;
+            }
+        }
+        
+        if cpu == 0 {
+            crate::smp::scheduler::balance_tick(self);
+        }
+        
+        let preempt = if current == self.tasks.idle_task(cpu) {
+            crate::smp::record_idle_tick(cpu);
+            self.ready_count(cpu) > 0
+        } else {
+            match RUN_QUEUES[cpu as usize].lock().as_mut() {
+                Some(queue) => queue.tick(&mut self.tasks, current),
+                None => false,
+            }
+        };
+        
+        if preempt {
+            self.switch_context(context);
+        }
+    }
+
+    pub fn switch_context(&mut self, context: &mut TaskContext) {
+        let cpu = crate::smp::current_cpu_id();
+        self.retire_previous(cpu);
+        
+        let current = self.get_current_task_id();
+        let idle_task = self.tasks.idle_task(cpu);
+        let preempted = match self.tasks.get_task_mut(current) {
+            Some(task) if task.state == TaskState::Running => {
+                task.state = TaskState::Ready;
+                Some(task.affinity & (1 << cpu) != 0)
+            }
+            _ => None,
+        };
+        match preempted {
+            Some(true) if current != idle_task => {
+                if let Some(queue) = RUN_QUEUES[cpu as usize].lock().as_mut() {
+                    queue.requeue(current);
+                }
+            }
+            Some(false) => self.place_task(current),
+            _ => {}
+        }
+        
+        if self.ready_count(cpu) == 0 {
+            crate::smp::scheduler::steal_work(self, cpu);
+        }
+        
+        let picked = match RUN_QUEUES[cpu as usize].lock().as_mut() {
+            Some(queue) => queue.pick_next(&mut self.tasks),
+            None => None,
+        };
+        let next = picked.unwrap_or(idle_task);
+        
+        if next == current {
+            if let Some(task) = self.tasks.get_task_mut(current) {
+                task.state = TaskState::Running;
+            }
+            return;
+        }
+        
+        if let Some(task) = self.tasks.get_task_mut(current) {
+            task.context = *context;
+            task.pkru = crate::vm::protection::read_pkru();
+            if task.is_terminated() {
+                self.retiring[cpu as usize] = Some(current);
+            }
+        }
+        
+        if let Some(task) = self.tasks.get_task_mut(next) {
+            task.state = TaskState::Running;
+            *context = task.context;
+            if let Some(stack_top) = task.kernel_stack_top() {
+                crate::gdt::set_kernel_stack(cpu, stack_top);
+            }
+            let address_space = task.address_space.unwrap_or_else(PageMapper::kernel);
+            if !address_space.is_active() {
+                address_space.activate();
+            }
+            write_fs_base(task.fs_base);
+            crate::vm::protection::write_pkru(task.pkru);
+        }
+        
+        self.current_task[cpu as usize].store(next, Ordering::Relaxed);
+        crate::smp::set_current_task(cpu, next);
+        set_task_switched();
+        
+        crate::performance::PERFORMANCE.increment_context_switches();
+    }
+
+    pub fn handle_fpu_trap(&mut self) {
+        clear_task_switched();
+        
+        let cpu = crate::smp::current_cpu_id() as usize;
+        let current = self.get_current_task_id();
+        if self.fpu_owner[cpu] == Some(current) {
+            return;
+        }
+        
+        if let Some(owner) = self.fpu_owner[cpu] {
+            if let Some(task) = self.tasks.get_task_mut(owner) {
+                task.fpu_state.save();
+            }
+        }
+        
+        if let Some(task) = self.tasks.get_task_mut(current) {
+            if task.fpu_initialized {
+                task.fpu_state.restore();
+            } else {
+                FpuState::reset();
+                task.fpu_initialized = true;
+            }
+        }
+        
+        self.fpu_owner[cpu] = Some(current);
+    }
+
+    pub fn save_fpu(&mut self) -> FpuState {
+        self.handle_fpu_trap();
+        let mut state = FpuState::new();
+        state.save();
+        state
+    }
+
+    pub fn load_fpu(&mut self, state: &FpuState) {
+        self.handle_fpu_trap();
+        state.restore();
+        let current = self.get_current_task_id();
+        if let Some(task) = self.tasks.get_task_mut(current) {
+            task.fpu_state = *state;
+        }
+    }
+
+    fn release_fpu_on(&mut self, task_id: usize, cpu: u32) -> bool {
+        if self.fpu_owner[cpu as usize] != Some(task_id) {
+            return true;
+        }
+        if cpu != crate::smp::current_cpu_id() {
+            return false;
+        }
+        
+        if let Some(task) = self.tasks.get_task_mut(task_id) {
+            clear_task_switched();
+            task.fpu_state.save();
+            set_task_switched();
+        }
+        self.fpu_owner[cpu as usize] = None;
+        true
+    }
+
+    fn release_fpu(&mut self, task_id: usize) {
+        for owner in self.fpu_owner.iter_mut() {
+            if *owner == Some(task_id) {
+                *owner = None;
+            }
         }
     }
 
-    pub fn yield_cpu(&mut self) {
-        self.scheduler.yield_current_task();
-        self.schedule();
+    pub fn exit_current(&mut self, exit_code: i32) {
+        let current = self.get_current_task_id();
+        self.tasks.terminate_task(current, exit_code);
+        self.release_fpu(current);
+    }
+
+    pub fn kill_task(&mut self, task_id: usize, exit_code: i32) -> bool {
+        if task_id == self.get_current_task_id() {
+            return false;
+        }
+        
+        if self.tasks.terminate_task(task_id, exit_code) {
+            self.release_fpu(task_id);
+            match self.running_cpu(task_id) {
+                Some(cpu) => crate::smp::scheduler::send_reschedule(cpu),
+                None => self.retire(task_id),
+            }
+        }
+        
+        self.tasks.get_task(task_id).is_some()
+    }
+
+    fn running_cpu(&self, task_id: usize) -> Option<u32> {
+        (0..MAX_CPUS)
+            .find(|&cpu| self.current_task[cpu].load(Ordering::Relaxed) == task_id)
+            .map(|cpu| cpu as u32)
+    }
+
+    fn retire(&mut self, task_id: usize) {
+        if let Some(joiner) = self.tasks.mark_dead(task_id) {
+            self.unblock_task(joiner);
+        }
+    }
+
+    fn retire_previous(&mut self, cpu: u32) {
+        if let Some(task_id) = self.retiring[cpu as usize].take() {
+            self.retire(task_id);
+        }
+    }
+
+    pub fn try_join(&mut self, task_id: usize) -> JoinStatus {
+        let current = self.get_current_task_id();
+        let task = match self.tasks.get_task_mut(task_id) {
+            Some(task) if task_id != current => task,
+            _ => return JoinStatus::Invalid,
+        };
+        
+        if task.is_dead() {
+            let exit_code = task.exit_code;
+            self.reap(task_id);
+            return JoinStatus::Exited(exit_code);
+        }
+        
+        match task.joiner {
+            Some(joiner) if joiner != current => JoinStatus::Invalid,
+            _ => {
+                task.joiner = Some(current);
+                self.tasks.block_task(current);
+                JoinStatus::Waiting
+            }
+        }
+    }
+
+    pub fn detach(&mut self, task_id: usize) -> bool {
+        match self.tasks.get_task_mut(task_id) {
+            Some(task) => {
+                task.detached = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn reap(&mut self, task_id: usize) {
+        if let Some(task) = self.tasks.remove_task(task_id) {
+            vga::print!("Reaped task {} ({} bytes of stack freed)\n", task_id, task.stack_size);
+        }
+    }
+
+    pub fn reap_terminated(&mut self) {
+        self.retire_previous(crate::smp::current_cpu_id());
+        while let Some(task_id) = self.tasks.find_reapable_task() {
+            self.reap(task_id);
+        }
     }
 
     pub fn block_task(&mut self, task_id: usize) {
-        self.scheduler.block_task(task_id);
+        self.tasks.block_task(task_id);
+    }
+
+    pub fn unblock_task(&mut self, task_id: usize) -> bool {
+        if self.tasks.unblock_task(task_id) {
+            self.enqueue(task_id);
+            return true;
+        }
+        false
+    }
+
+    pub fn set_nice(&mut self, task_id: usize, nice: i8) -> bool {
+        match self.tasks.get_task_mut(task_id) {
+            Some(task) => {
+                task.nice = nice.clamp(-20, 19);
+                true
+            }
+            None => false,
+        }
     }
 
-    pub fn unblock_task(&mut self, task_id: usize) {
-        self.scheduler.unblock_task(task_id);
+    pub fn set_priority(&mut self, task_id: usize, priority: u8) -> bool {
+        match self.tasks.get_task_mut(task_id) {
+            Some(task) => {
+                task.priority = priority;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn policy_stats(&self, cpu: u32) -> Option<PolicyStats> {
+        RUN_QUEUES[cpu as usize].lock().as_ref().map(|queue| queue.stats())
     }
 
     pub fn get_current_task_id(&self) -> usize {
-        self.current_task.load(Ordering::Relaxed)
+        self.current_task[crate::smp::current_cpu_id() as usize].load(Ordering::Relaxed)
     }
 }
 
-pub static mut SCHEDULER: Scheduler = Scheduler::new();
+pub enum JoinStatus {
+    Exited(i32),
+    Waiting,
+    Invalid,
+}
 
-pub fn init() {
+fn enable_fpu() {
     unsafe {
-        SCHEDULER.init();
+        let mut cr0: u64;
+        asm!("mov {}, cr0", out(reg) cr0);
+        cr0 = (cr0 & !CR0_EM) | CR0_MP;
+        asm!("mov cr0, {}", in(reg) cr0);
+        
+        let mut cr4: u64;
+        asm!("mov {}, cr4", out(reg) cr4);
+        cr4 |= CR4_OSFXSR | CR4_OSXMMEXCPT;
+        asm!("mov cr4, {}", in(reg) cr4);
     }
 }
 
-pub fn create_task(entry_point: fn(), stack_size: usize) -> usize {
+fn set_task_switched() {
     unsafe {
-        SCHEDULER.create_task(entry_point, stack_size)
+        let mut cr0: u64;
+        asm!("mov {}, cr0", out(reg) cr0);
+        cr0 |= CR0_TS;
+        asm!("mov cr0, {}", in(reg) cr0);
     }
 }
 
-pub fn yield_cpu() {
+fn clear_task_switched() {
     unsafe {
-        SCHEDULER.yield_cpu();
+        asm!("clts");
     }
 }
 
-pub fn block_task(task_id: usize) {
+fn write_fs_base(base: u64) {
     unsafe {
-        SCHEDULER.block_task(task_id);
+        asm!("wrmsr", in("ecx") MSR_FS_BASE, in("eax") base as u32, in("edx") (base >> 32) as u32);
     }
 }
 
-pub fn unblock_task(task_id: usize) {
+pub static SCHEDULER: TicketLock<Scheduler> = TicketLock::new(Scheduler::new());
+
+pub fn init() {
+    init_with_policy(PolicyKind::Fair);
+}
+
+pub fn init_with_policy(kind: PolicyKind) {
+    SCHEDULER.lock().init(kind);
+}
+
+pub fn create_task(entry_point: fn(), stack_size: usize) -> usize {
+    SCHEDULER.lock().create_task(entry_point, stack_size)
+}
+
+pub fn create_user_task(entry_point: u64, user_stack_top: u64, address_space: Option<PageMapper>, process: Option<usize>) -> usize {
+    SCHEDULER.lock().create_user_task(entry_point, user_stack_top, address_space, process)
+}
+
+pub fn fork_current(address_space: PageMapper, process: usize) -> Option<usize> {
+    SCHEDULER.lock().fork_current(address_space, process)
+}
+
+pub fn current_process_id() -> Option<usize> {
+    SCHEDULER.lock().current_process_id()
+}
+
+pub fn release_address_space() {
+    SCHEDULER.lock().release_address_space();
+}
+
+pub fn set_fs_base(base: u64) {
+    SCHEDULER.lock().set_fs_base(base);
+}
+
+pub fn fs_base() -> u64 {
+    SCHEDULER.lock().fs_base()
+}
+
+pub fn exec_current(entry_point: u64, user_stack_top: u64, address_space: PageMapper) -> Result<Option<PageMapper>, PageMapper> {
+    SCHEDULER.lock().exec_current(entry_point, user_stack_top, address_space)
+}
+
+pub fn schedule() {
     unsafe {
-        SCHEDULER.unblock_task(task_id);
+        asm!("int 0x81");
     }
 }
+
+pub fn yield_cpu() {
+    schedule();
+}
+
+pub fn block_task(task_id: usize) {
+    SCHEDULER.lock().block_task(task_id);
+}
+
+pub fn unblock_task(task_id: usize) -> bool {
+    SCHEDULER.lock().unblock_task(task_id)
+}
+
+pub fn set_nice(task_id: usize, nice: i8) -> bool {
+    SCHEDULER.lock().set_nice(task_id, nice)
+}
+
+pub fn set_priority(task_id: usize, priority: u8) -> bool {
+    SCHEDULER.lock().set_priority(task_id, priority)
+}
+
+pub fn set_affinity(task_id: usize, affinity: u64) -> bool {
+    SCHEDULER.lock().set_affinity(task_id, affinity)
+}
+
+pub fn print_policy_stats() {
+    let cpu = crate::smp::current_cpu_id();
+    if let Some(stats) = SCHEDULER.lock().policy_stats(cpu) {
+        vga::print!("Scheduler Stats (CPU {}, {}):\n", cpu, stats.name);
+        vga::print!("  Picks: {}\n", stats.picks);
+        vga::print!("  Preemptions: {}\n", stats.preemptions);
+        vga::print!("  Wakeups: {}\n", stats.wakeups);
+        vga::print!("  Demotions: {}\n", stats.demotions);
+        vga::print!("  Boosts: {}\n", stats.boosts);
+    }
+}
+
+pub fn exit(exit_code: i32) -> ! {
+    crate::interrupts::disable_interrupts();
+    SCHEDULER.lock().exit_current(exit_code);
+    schedule();
+    
+    loop {
+        crate::interrupts::halt();
+    }
+}
+
+pub fn join(task_id: usize) -> Option<i32> {
+    loop {
+        let interrupts_enabled = crate::interrupts::save_and_disable();
+        let status = SCHEDULER.lock().try_join(task_id);
+        
+        match status {
+            JoinStatus::Exited(exit_code) => {
+                crate::interrupts::restore(interrupts_enabled);
+                return Some(exit_code);
+            }
+            JoinStatus::Invalid => {
+                crate::interrupts::restore(interrupts_enabled);
+                return None;
+            }
+            JoinStatus::Waiting => {
+                schedule();
+                crate::interrupts::restore(interrupts_enabled);
+            }
+        }
+    }
+}
+
+pub fn detach(task_id: usize) -> bool {
+    SCHEDULER.lock().detach(task_id)
+}
+
+pub fn kill_task(task_id: usize, exit_code: i32) -> bool {
+    SCHEDULER.lock().kill_task(task_id, exit_code)
+}
+
+pub fn reap_terminated() {
+    SCHEDULER.lock().reap_terminated();
+}
+
+pub fn timer_tick(context: &mut TaskContext) {
+    SCHEDULER.lock().timer_tick(context);
+}
+
+pub fn switch_context(context: &mut TaskContext) {
+    SCHEDULER.lock().switch_context(context);
+}
+
+pub fn handle_fpu_trap() {
+    SCHEDULER.lock().handle_fpu_trap();
+}
+
+pub fn save_fpu() -> FpuState {
+    SCHEDULER.lock().save_fpu()
+}
+
+pub fn load_fpu(state: &FpuState) {
+    SCHEDULER.lock().load_fpu(state);
+}
+
+pub fn current_task_id() -> usize {
+    SCHEDULER.lock().get_current_task_id()
+}
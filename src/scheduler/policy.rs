@@ -0,0 +1,45 @@
+use crate::scheduler::task_table::TaskTable;
+use alloc::collections::VecDeque;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PolicyKind {
+    RoundRobin,
+    Fair,
+    MultilevelFeedback,
+    Realtime,
+}
+
+#[derive(Clone, Copy)]
+pub struct PolicyStats {
+    pub name: &'static str,
+    pub picks: u64,
+    pub preemptions: u64,
+    pub wakeups: u64,
+    pub demotions: u64,
+    pub boosts: u64,
+}
+
+impl PolicyStats {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            picks: 0,
+            preemptions: 0,
+            wakeups: 0,
+            demotions: 0,
+            boosts: 0,
+        }
+    }
+}
+
+pub trait SchedulingPolicy: Send {
+    fn name(&self) -> &'static str;
+
+    fn enqueue(&mut self, tasks: &mut TaskTable, task_id: usize);
+
+    fn pick_next(&mut self, tasks: &mut TaskTable, ready: &VecDeque<usize>) -> Option<usize>;
+
+    fn tick(&mut self, tasks: &mut TaskTable, ready: &VecDeque<usize>, task_id: usize) -> bool;
+
+    fn stats(&self) -> PolicyStats;
+}
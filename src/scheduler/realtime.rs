@@ -0,0 +1,79 @@
+use crate::scheduler::policy::{PolicyStats, SchedulingPolicy};
+use crate::scheduler::task_table::TaskTable;
+use alloc::collections::VecDeque;
+
+pub struct RealtimeScheduler {
+    stats: PolicyStats,
+}
+
+impl RealtimeScheduler {
+    pub fn new() -> Self {
+        Self {
+            stats: PolicyStats::new("realtime"),
+        }
+    }
+
+    fn highest_ready_priority(&self, tasks: &TaskTable, ready: &VecDeque<usize>) -> Option<u8> {
+        ready.iter()
+            .filter_map(|&task_id| tasks.get_task(task_id))
+            .map(|task| task.priority)
+            .max()
+    }
+}
+
+impl SchedulingPolicy for RealtimeScheduler {
+    fn name(&self) -> &'static str {
+        "realtime"
+    }
+
+    fn enqueue(&mut self, tasks: &mut TaskTable, task_id: usize) {
+        let task = match tasks.get_task_mut(task_id) {
+            Some(task) => task,
+            None => return,
+        };
+        task.ticks_left = task.quantum;
+        self.stats.wakeups += 1;
+    }
+
+    fn pick_next(&mut self, tasks: &mut TaskTable, ready: &VecDeque<usize>) -> Option<usize> {
+        let priority = self.highest_ready_priority(tasks, ready)?;
+
+        for &task_id in ready {
+            if let Some(task) = tasks.get_task_mut(task_id) {
+                if task.priority == priority {
+                    task.ticks_left = task.quantum;
+                    self.stats.picks += 1;
+                    return Some(task_id);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn tick(&mut self, tasks: &mut TaskTable, ready: &VecDeque<usize>, task_id: usize) -> bool {
+        let highest = self.highest_ready_priority(tasks, ready);
+        let task = match tasks.get_task_mut(task_id) {
+            Some(task) => task,
+            None => return true,
+        };
+
+        let preempt = match highest {
+            Some(priority) if priority > task.priority => true,
+            Some(priority) if priority == task.priority => {
+                task.ticks_left = task.ticks_left.saturating_sub(1);
+                task.ticks_left == 0
+            }
+            _ => false,
+        };
+
+        if preempt {
+            self.stats.preemptions += 1;
+        }
+        preempt
+    }
+
+    fn stats(&self) -> PolicyStats {
+        self.stats
+    }
+}
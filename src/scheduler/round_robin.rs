@@ -1,135 +1,59 @@
-use crate::scheduler::task::{Task, TaskState};
+use crate::scheduler::policy::{PolicyStats, SchedulingPolicy};
+use crate::scheduler::task_table::TaskTable;
 use crate::vga;
+use alloc::collections::VecDeque;
 
 pub struct RoundRobinScheduler {
-    tasks: [Option<Task>; 64],
-    task_count: usize,
-    current_index: usize,
-    next_id: usize,
+    stats: PolicyStats,
 }
 
 impl RoundRobinScheduler {
     pub fn new() -> Self {
         Self {
-            tasks: [None; 64],
-            task_count: 0,
-            current_index: 0,
-            next_id: 1,
+            stats: PolicyStats::new("round-robin"),
         }
     }
-    
+
     pub fn init(&mut self) {
         vga::print!("Round-robin scheduler initialized\n");
     }
-    
-    pub fn add_task(&mut self, mut task: Task) -> usize {
-        if self.task_count >= 64 {
-            vga::print!("Maximum number of tasks reached\n");
-            return 0;
-        }
-        
-        let task_id = self.next_id;
-        task.set_id(task_id);
-        self.next_id += 1;
-        
-        for i in 0..64 {
-            if self.tasks[i].is_none() {
-                self.tasks[i] = Some(task);
-                self.task_count += 1;
-                vga::print!("Added task {} to scheduler\n", task_id);
-                return task_id;
-            }
-        }
-        
-        0
-    }
-    
-    pub fn get_next_task(&mut self) -> Option<usize> {
-        if self.task_count == 0 {
-            return None;
-        }
-        
-        let start_index = self.current_index;
-        
-        loop {
-            if let Some(task) = &self.tasks[self.current_index] {
-                if task.is_ready() {
-                    let task_id = task.id;
-                    self.current_index = (self.current_index + 1) % 64;
-                    return Some(task_id);
-                }
-            }
-            
-            self.current_index = (self.current_index + 1) % 64;
-            
-            if self.current_index == start_index {
-                break;
-            }
-        }
-        
-        None
-    }
-    
-    pub fn switch_to_task(&mut self, task_id: usize) {
-        for i in 0..64 {
-            if let Some(task) = &mut self.tasks[i] {
-                if task.id == task_id {
-                    task.state = TaskState::Running;
-                    return;
-                }
-            }
-        }
-    }
-    
-    pub fn yield_current_task(&mut self) {
-        for i in 0..64 {
-            if let Some(task) = &mut self.tasks[i] {
-                if task.state == TaskState::Running {
-                    task.state = TaskState::Ready;
-                    break;
-                }
-            }
-        }
+}
+
+impl SchedulingPolicy for RoundRobinScheduler {
+    fn name(&self) -> &'static str {
+        "round-robin"
     }
-    
-    pub fn block_task(&mut self, task_id: usize) {
-        for i in 0..64 {
-            if let Some(task) = &mut self.tasks[i] {
-                if task.id == task_id {
-                    task.state = TaskState::Blocked;
-                    vga::print!("Task {} blocked\n", task_id);
-                    break;
-                }
-            }
-        }
+
+    fn enqueue(&mut self, tasks: &mut TaskTable, task_id: usize) {
+        let task = match tasks.get_task_mut(task_id) {
+            Some(task) => task,
+            None => return,
+        };
+        task.ticks_left = task.quantum;
+        self.stats.wakeups += 1;
     }
-    
-    pub fn unblock_task(&mut self, task_id: usize) {
-        for i in 0..64 {
-            if let Some(task) = &mut self.tasks[i] {
-                if task.id == task_id {
-                    task.state = TaskState::Ready;
-                    vga::print!("Task {} unblocked\n", task_id);
-                    break;
-                }
-            }
-        }
+
+    fn pick_next(&mut self, tasks: &mut TaskTable, ready: &VecDeque<usize>) -> Option<usize> {
+        let task = tasks.get_task_mut(*ready.front()?)?;
+        task.ticks_left = task.quantum;
+        self.stats.picks += 1;
+        Some(task.id)
     }
-    
-    pub fn terminate_task(&mut self, task_id: usize) {
-        for i in 0..64 {
-            if let Some(task) = &mut self.tasks[i] {
-                if task.id == task_id {
-                    task.state = TaskState::Terminated;
-                    self.task_count -= 1;
-                    vga::print!("Task {} terminated\n", task_id);
-                    break;
-                }
-            }
-        }
+
+    fn tick(&mut self, tasks: &mut TaskTable, _ready: &VecDeque<usize>, task_id: usize) -> bool {
+        let task = match tasks.get_task_mut(task_id) {
+            Some(task) => task,
+            None => return true,
+        };
+        task.ticks_left = task.ticks_left.saturating_sub(1);
+        if task.ticks_left == 0 {
+            self.stats.preemptions += 1;
+            return true;
+        }
+        false
     }
-    
-    pub fn get_task_count(&self) -> usize {
-        self.task_count
+
+    fn stats(&self) -> PolicyStats {
+        self.stats
     }
 }
@@ -0,0 +1,84 @@
+use crate::scheduler::policy::{PolicyStats, SchedulingPolicy};
+use crate::scheduler::task_table::{TaskTable, MAX_CPUS};
+use crate::smp::synchronization::TicketLock;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+
+pub struct RunQueue {
+    cpu: u32,
+    ready: VecDeque<usize>,
+    policy: Box<dyn SchedulingPolicy>,
+}
+
+impl RunQueue {
+    pub fn new(cpu: u32, policy: Box<dyn SchedulingPolicy>) -> Self {
+        Self {
+            cpu,
+            ready: VecDeque::new(),
+            policy,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.policy.name()
+    }
+
+    pub fn enqueue(&mut self, tasks: &mut TaskTable, task_id: usize) {
+        self.requeue(task_id);
+        self.policy.enqueue(tasks, task_id);
+    }
+
+    pub fn requeue(&mut self, task_id: usize) {
+        if !self.ready.contains(&task_id) {
+            self.ready.push_back(task_id);
+        }
+    }
+
+    pub fn remove(&mut self, task_id: usize) {
+        self.ready.retain(|&id| id != task_id);
+    }
+
+    pub fn pick_next(&mut self, tasks: &mut TaskTable) -> Option<usize> {
+        self.prune(tasks);
+        let task_id = self.policy.pick_next(tasks, &self.ready)?;
+        self.remove(task_id);
+        Some(task_id)
+    }
+
+    pub fn tick(&mut self, tasks: &mut TaskTable, task_id: usize) -> bool {
+        self.prune(tasks);
+        self.policy.tick(tasks, &self.ready, task_id)
+    }
+
+    pub fn ready_count(&self, tasks: &TaskTable) -> usize {
+        self.ready.iter().filter(|&&id| is_queued(tasks, self.cpu, id)).count()
+    }
+
+    pub fn steal(&mut self, tasks: &TaskTable, to_cpu: u32, movable: impl Fn(usize) -> bool) -> Option<usize> {
+        let index = self.ready.iter().position(|&id| {
+            movable(id)
+                && is_queued(tasks, self.cpu, id)
+                && tasks.get_task(id).map_or(false, |task| task.affinity & (1 << to_cpu) != 0)
+        })?;
+        self.ready.remove(index)
+    }
+
+    pub fn stats(&self) -> PolicyStats {
+        self.policy.stats()
+    }
+
+    fn prune(&mut self, tasks: &TaskTable) {
+        let cpu = self.cpu;
+        self.ready.retain(|&id| is_queued(tasks, cpu, id));
+    }
+}
+
+fn is_queued(tasks: &TaskTable, cpu: u32, task_id: usize) -> bool {
+    match tasks.get_task(task_id) {
+        Some(task) => task.is_ready() && task.cpu == cpu && !tasks.is_idle_task(task_id),
+        None => false,
+    }
+}
+
+const NO_RUN_QUEUE: TicketLock<Option<RunQueue>> = TicketLock::new(None);
+pub static RUN_QUEUES: [TicketLock<Option<RunQueue>>; MAX_CPUS] = [NO_RUN_QUEUE; MAX_CPUS];
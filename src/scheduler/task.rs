@@ -1,5 +1,7 @@
 use core::arch::asm;
+use crate::memory::paging::PageMapper;
 
+#[derive(Clone, Copy)]
 #[repr(C, packed)]
 pub struct TaskContext {
     pub r15: u64,
@@ -30,6 +32,38 @@ pub enum TaskState {
     Running,
     Blocked,
     Terminated,
+    Dead,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+pub struct FpuState {
+    pub data: [u8; 512],
+}
+
+impl FpuState {
+    pub const fn new() -> Self {
+        Self { data: [0; 512] }
+    }
+
+    pub fn save(&mut self) {
+        unsafe {
+            asm!("fxsave64 [{}]", in(reg) self.data.as_mut_ptr());
+        }
+    }
+
+    pub fn restore(&self) {
+        unsafe {
+            asm!("fxrstor64 [{}]", in(reg) self.data.as_ptr());
+        }
+    }
+
+    pub fn reset() {
+        let mxcsr: u32 = 0x1F80;
+        unsafe {
+            asm!("fninit", "ldmxcsr [{}]", in(reg) &mxcsr);
+        }
+    }
 }
 
 pub struct Task {
@@ -40,37 +74,48 @@ pub struct Task {
     pub stack_size: usize,
     pub priority: u8,
     pub quantum: u32,
+    pub ticks_left: u32,
+    pub nice: i8,
+    pub vruntime: u64,
+    pub slice_runtime: u64,
+    pub mlfq_level: u8,
+    pub cpu: u32,
+    pub affinity: u64,
     pub total_runtime: u64,
+    pub fpu_state: FpuState,
+    pub fpu_initialized: bool,
+    pub exit_code: i32,
+    pub joiner: Option<usize>,
+    pub detached: bool,
+    pub user_mode: bool,
+    pub address_space: Option<PageMapper>,
+    pub process: Option<usize>,
+    pub fs_base: u64,
+    pub pkru: u32,
 }
 
 impl Task {
     pub fn new(entry_point: fn(), stack_size: usize) -> Self {
-        let stack = unsafe {
-            crate::memory::heap::ALLOCATOR.alloc(
-                core::alloc::Layout::from_size_align(stack_size, 16).unwrap()
-            )
-        };
+        let stack = crate::vm::protection::allocate_stack(stack_size);
         
         if stack.is_null() {
             panic!("Failed to allocate stack for task");
         }
         
-        let stack_top = unsafe { stack.add(stack_size) };
+        let stack_top = unsafe { stack.add(stack_size) } as u64 & !0xF;
         
-        let mut context = TaskContext {
+        let context = TaskContext {
             r15: 0, r14: 0, r13: 0, r12: 0, r11: 0, r10: 0, r9: 0, r8: 0,
             rdi: 0, rsi: 0, rbp: 0, rdx: 0, rcx: 0, rbx: 0, rax: 0,
             rip: entry_point as u64,
             cs: 0x08,
             rflags: 0x202,
-            rsp: stack_top as u64,
+            rsp: stack_top - 8,
             ss: 0x10,
         };
         
         unsafe {
-            *(stack_top as *mut u64).offset(-1) = 0;
-            *(stack_top as *mut u64).offset(-2) = entry_point as u64;
-            context.rsp = (stack_top as *mut u64).offset(-2) as u64;
+            *((stack_top - 8) as *mut u64) = task_exit_trampoline as u64;
         }
         
         Self {
@@ -81,23 +126,82 @@ impl Task {
             stack_size,
             priority: 5,
             quantum: 10,
+            ticks_left: 10,
+            nice: 0,
+            vruntime: 0,
+            slice_runtime: 0,
+            mlfq_level: 0,
+            cpu: 0,
+            affinity: u64::MAX,
+            total_runtime: 0,
+            fpu_state: FpuState::new(),
+            fpu_initialized: false,
+            exit_code: 0,
+            joiner: None,
+            detached: false,
+            user_mode: false,
+            address_space: None,
+            process: None,
+            fs_base: 0,
+            pkru: crate::vm::protection::DEFAULT_PKRU,
+        }
+    }
+    
+    pub fn new_user(entry_point: u64, user_stack_top: u64, stack_size: usize) -> Self {
+        let stack = crate::vm::protection::allocate_stack(stack_size);
+        
+        if stack.is_null() {
+            panic!("Failed to allocate kernel stack for user task");
+        }
+        
+        let context = TaskContext {
+            r15: 0, r14: 0, r13: 0, r12: 0, r11: 0, r10: 0, r9: 0, r8: 0,
+            rdi: 0, rsi: 0, rbp: 0, rdx: 0, rcx: 0, rbx: 0, rax: 0,
+            rip: entry_point,
+            cs: crate::gdt::USER_CODE_SELECTOR as u64,
+            rflags: 0x202,
+            rsp: user_stack_top,
+            ss: crate::gdt::USER_DATA_SELECTOR as u64,
+        };
+        
+        Self {
+            id: 0,
+            context,
+            state: TaskState::Ready,
+            stack,
+            stack_size,
+            priority: 5,
+            quantum: 10,
+            ticks_left: 10,
+            nice: 0,
+            vruntime: 0,
+            slice_runtime: 0,
+            mlfq_level: 0,
+            cpu: 0,
+            affinity: u64::MAX,
             total_runtime: 0,
+            fpu_state: FpuState::new(),
+            fpu_initialized: false,
+            exit_code: 0,
+            joiner: None,
+            detached: false,
+            user_mode: true,
+            address_space: None,
+            process: None,
+            fs_base: 0,
+            pkru: crate::vm::protection::DEFAULT_PKRU,
         }
     }
     
     pub fn new_idle() -> Self {
         let stack_size = 4096;
-        let stack = unsafe {
-            crate::memory::heap::ALLOCATOR.alloc(
-                core::alloc::Layout::from_size_align(stack_size, 16).unwrap()
-            )
-        };
+        let stack = crate::vm::protection::allocate_stack(stack_size);
         
         if stack.is_null() {
             panic!("Failed to allocate stack for idle task");
         }
         
-        let stack_top = unsafe { stack.add(stack_size) };
+        let stack_top = unsafe { stack.add(stack_size) } as u64 & !0xF;
         
         let context = TaskContext {
             r15: 0, r14: 0, r13: 0, r12: 0, r11: 0, r10: 0, r9: 0, r8: 0,
@@ -105,61 +209,76 @@ impl Task {
             rip: idle_loop as u64,
             cs: 0x08,
             rflags: 0x202,
-            rsp: stack_top as u64,
+            rsp: stack_top - 8,
             ss: 0x10,
         };
         
         Self {
             id: 0,
             context,
-            state: TaskState::Running,
+            state: TaskState::Ready,
             stack,
             stack_size,
             priority: 0,
-            quantum: 0,
+            quantum: 1,
+            ticks_left: 1,
+            nice: 0,
+            vruntime: 0,
+            slice_runtime: 0,
+            mlfq_level: 0,
+            cpu: 0,
+            affinity: u64::MAX,
             total_runtime: 0,
+            fpu_state: FpuState::new(),
+            fpu_initialized: false,
+            exit_code: 0,
+            joiner: None,
+            detached: false,
+            user_mode: false,
+            address_space: None,
+            process: None,
+            fs_base: 0,
+            pkru: crate::vm::protection::DEFAULT_PKRU,
         }
     }
     
-    pub fn switch_to(&mut self, other: &mut Task) {
-        unsafe {
-            asm!(
-                "push rbp",
-                "mov rbp, rsp",
-                "push rax",
-                "push rbx",
-                "push rcx",
-                "push rdx",
-                "push rsi",
-                "push rdi",
-                "push r8",
-                "push r9",
-                "push r10",
-                "push r11",
-                "push r12",
-                "push r13",
-                "push r14",
-                "push r15",
-                "mov [rdi], rsp",
-                "mov rsp, [rsi]",
-                "pop r15",
-                "pop r14",
-                "pop r13",
-                "pop r12",
-                "pop r11",
-                "pop r10",
-                "pop r9",
-                "pop r8",
-                "pop rdi",
-                "pop rsi",
-                "pop rdx",
-                "pop rcx",
-                "pop rbx",
-                "pop rax",
-                "pop rbp",
-                in("rdi") &mut self.context.rsp,
-                in("rsi") &other.context.rsp,
-            );
+    pub fn new_boot() -> Self {
+        let context = TaskContext {
+            r15: 0, r14: 0, r13: 0, r12: 0, r11: 0, r10: 0, r9: 0, r8: 0,
+            rdi: 0, rsi: 0, rbp: 0, rdx: 0, rcx: 0, rbx: 0, rax: 0,
+            rip: 0,
+            cs: 0x08,
+            rflags: 0x202,
+            rsp: 0,
+            ss: 0x10,
+        };
+        
+        Self {
+            id: 0,
+            context,
+            state: TaskState::Running,
+            stack: core::ptr::null_mut(),
+            stack_size: 0,
+            priority: 5,
+            quantum: 10,
+            ticks_left: 10,
+            nice: 0,
+            vruntime: 0,
+            slice_runtime: 0,
+            mlfq_level: 0,
+            cpu: 0,
+            affinity: u64::MAX,
+            total_runtime: 0,
+            fpu_state: FpuState::new(),
+            fpu_initialized: false,
+            exit_code: 0,
+            joiner: None,
+            detached: false,
+            user_mode: false,
+            address_space: None,
+            process: None,
+            fs_base: 0,
+            pkru: crate::vm::protection::DEFAULT_PKRU,
         }
     }
     
@@ -178,10 +297,47 @@ impl Task {
     pub fn is_terminated(&self) -> bool {
         self.state == TaskState::Terminated
     }
+    
+    pub fn is_dead(&self) -> bool {
+        self.state == TaskState::Dead
+    }
+    
+    pub fn kernel_stack_top(&self) -> Option<u64> {
+        if self.stack.is_null() {
+            None
+        } else {
+            Some((self.stack as u64 + self.stack_size as u64) & !0xF)
+        }
+    }
+    
+    pub fn user_context_mut(&mut self) -> Option<&mut TaskContext> {
+        if !self.user_mode {
+            return None;
+        }
+        
+        let frame = self.kernel_stack_top()? - core::mem::size_of::<TaskContext>() as u64;
+        unsafe { Some(&mut *(frame as *mut TaskContext)) }
+    }
+}
+
+unsafe impl Send for Task {}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        if !self.stack.is_null() {
+            crate::vm::protection::free_stack(self.stack, self.stack_size);
+            self.stack = core::ptr::null_mut();
+        }
+    }
+}
+
+extern "C" fn task_exit_trampoline() -> ! {
+    crate::scheduler::exit(0);
 }
 
 fn idle_loop() -> ! {
     loop {
+        crate::scheduler::reap_terminated();
         unsafe {
             asm!("hlt");
         }
@@ -0,0 +1,147 @@
+use crate::scheduler::task::{Task, TaskState};
+use crate::vga;
+
+pub const MAX_TASKS: usize = 64;
+pub const MAX_CPUS: usize = 64;
+const SLOT_BITS: usize = 6;
+const SLOT_MASK: usize = (1 << SLOT_BITS) - 1;
+const NO_TASK: Option<Task> = None;
+
+pub struct TaskTable {
+    tasks: [Option<Task>; MAX_TASKS],
+    generations: [usize; MAX_TASKS],
+    task_count: usize,
+    idle_tasks: [usize; MAX_CPUS],
+}
+
+impl TaskTable {
+    pub const fn new() -> Self {
+        Self {
+            tasks: [NO_TASK; MAX_TASKS],
+            generations: [0; MAX_TASKS],
+            task_count: 0,
+            idle_tasks: [0; MAX_CPUS],
+        }
+    }
+
+    pub fn add_task(&mut self, mut task: Task) -> usize {
+        if self.task_count >= MAX_TASKS {
+            vga::print!("Maximum number of tasks reached\n");
+            return 0;
+        }
+
+        for i in 0..MAX_TASKS {
+            if self.tasks[i].is_none() {
+                self.generations[i] += 1;
+                let task_id = (self.generations[i] << SLOT_BITS) | i;
+                task.set_id(task_id);
+
+                self.tasks[i] = Some(task);
+                self.task_count += 1;
+                vga::print!("Added task {} to scheduler\n", task_id);
+                return task_id;
+            }
+        }
+
+        0
+    }
+
+    pub fn remove_task(&mut self, task_id: usize) -> Option<Task> {
+        let slot = self.slot_of(task_id)?;
+        let task = self.tasks[slot].take()?;
+        self.task_count -= 1;
+        Some(task)
+    }
+
+    pub fn set_idle_task(&mut self, cpu: u32, task_id: usize) {
+        self.idle_tasks[cpu as usize] = task_id;
+    }
+
+    pub fn idle_task(&self, cpu: u32) -> usize {
+        self.idle_tasks[cpu as usize]
+    }
+
+    pub fn is_idle_task(&self, task_id: usize) -> bool {
+        task_id != 0 && self.idle_tasks.contains(&task_id)
+    }
+
+    pub fn get_task(&self, task_id: usize) -> Option<&Task> {
+        let slot = self.slot_of(task_id)?;
+        self.tasks[slot].as_ref()
+    }
+
+    pub fn get_task_mut(&mut self, task_id: usize) -> Option<&mut Task> {
+        let slot = self.slot_of(task_id)?;
+        self.tasks[slot].as_mut()
+    }
+
+    pub fn slot_mut(&mut self, slot: usize) -> Option<&mut Task> {
+        self.tasks[slot].as_mut()
+    }
+
+    pub fn find_reapable_task(&self) -> Option<usize> {
+        for i in 0..MAX_TASKS {
+            if let Some(task) = &self.tasks[i] {
+                if task.is_dead() && task.detached {
+                    return Some(task.id);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn block_task(&mut self, task_id: usize) {
+        if let Some(task) = self.get_task_mut(task_id) {
+            if task.state != TaskState::Terminated && task.state != TaskState::Dead {
+                task.state = TaskState::Blocked;
+            }
+        }
+    }
+
+    pub fn unblock_task(&mut self, task_id: usize) -> bool {
+        if let Some(task) = self.get_task_mut(task_id) {
+            if task.state == TaskState::Blocked {
+                task.state = TaskState::Ready;
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn terminate_task(&mut self, task_id: usize, exit_code: i32) -> bool {
+        let task = match self.get_task_mut(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        if task.state == TaskState::Terminated || task.state == TaskState::Dead {
+            return false;
+        }
+
+        task.state = TaskState::Terminated;
+        task.exit_code = exit_code;
+        vga::print!("Task {} terminated with code {}\n", task_id, exit_code);
+        true
+    }
+
+    pub fn mark_dead(&mut self, task_id: usize) -> Option<usize> {
+        let task = self.get_task_mut(task_id)?;
+        if task.state != TaskState::Terminated {
+            return None;
+        }
+
+        task.state = TaskState::Dead;
+        task.joiner.take()
+    }
+
+    pub fn get_task_count(&self) -> usize {
+        self.task_count
+    }
+
+    fn slot_of(&self, task_id: usize) -> Option<usize> {
+        let slot = task_id & SLOT_MASK;
+        if task_id >> SLOT_BITS != self.generations[slot] {
+            return None;
+        }
+        Some(slot)
+    }
+}
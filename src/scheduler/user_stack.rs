@@ -0,0 +1,2 @@
+pub const USER_STACK_TOP: u64 = 0x0000_7FFF_FFFF_F000;
+pub const DEFAULT_USER_STACK_PAGES: u64 = 16;
@@ -1,3 +1,4 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod aslr;
@@ -13,7 +14,7 @@ pub struct SecurityManager {
 }
 
 impl SecurityManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             aslr_enabled: false,
             stack_canaries_enabled: false,
@@ -39,10 +40,8 @@ impl SecurityManager {
     }
 }
 
-pub static mut SECURITY_MANAGER: SecurityManager = SecurityManager::new();
+pub static SECURITY_MANAGER: SpinLock<SecurityManager> = SpinLock::new(SecurityManager::new());
 
 pub fn init() {
-    unsafe {
-        SECURITY_MANAGER.init();
-    }
+    SECURITY_MANAGER.lock().init();
 }
@@ -1,4 +1,5 @@
 use crate::vga;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 #[repr(C, packed)]
 pub struct SMBIOSEntryPoint {
@@ -18,30 +19,39 @@ pub struct SMBIOSEntryPoint {
     pub bcd_revision: u8,
 }
 
-static mut SMBIOS_ENTRY: u64 = 0;
+static SMBIOS_ENTRY: AtomicU64 = AtomicU64::new(0);
 
 pub fn init(entry_addr: u64) {
-    unsafe {
-        SMBIOS_ENTRY = entry_addr;
-        
-        if entry_addr == 0 {
-            vga::print!("SMBIOS entry point not found\n");
-            return;
-        }
-        
-        vga::print!("SMBIOS entry point at: 0x{:x}\n", entry_addr);
+    SMBIOS_ENTRY.store(entry_addr, Ordering::Release);
+    
+    if entry_addr == 0 {
+        vga::print!("SMBIOS entry point not found\n");
+        return;
+    }
+    
+    vga::print!("SMBIOS entry point at: 0x{:x}\n", entry_addr);
+    
+    if let Some(entry) = parse_smbios_entry(entry_addr) {
+        vga::print!("SMBIOS version: {}.{}\n", entry.major_version, entry.minor_version);
+        vga::print!("SMBIOS structures: {}\n", entry.number_of_structures);
         
-        if let Some(entry) = parse_smbios_entry(entry_addr) {
-            vga::print!("SMBIOS version: {}.{}\n", entry.major_version, entry.minor_version);
-            vga::print!("SMBIOS structures: {}\n", entry.number_of_structures);
-            
-            parse_smbios_tables(entry);
-        }
+        parse_smbios_tables(entry);
+    }
+}
+
+pub fn reserve_tables(entry_addr: u64, mut reserve: impl FnMut(u64, u64)) {
+    if entry_addr == 0 {
+        return;
+    }
+    if let Some(entry) = parse_smbios_entry(entry_addr) {
+        reserve(entry_addr, entry.length as u64);
+        reserve(entry.structure_table_address as u64, entry.structure_table_length as u64);
     }
 }
 
 fn parse_smbios_entry(addr: u64) -> Option<&'static SMBIOSEntryPoint> {
     unsafe {
+        let addr = crate::memory::paging::phys_to_virt(addr);
         let entry = &*(addr as *const SMBIOSEntryPoint);
         
         if &entry.anchor_string != b"_SM_" {
@@ -63,7 +73,7 @@ fn parse_smbios_entry(addr: u64) -> Option<&'static SMBIOSEntryPoint> {
 }
 
 fn parse_smbios_tables(entry: &SMBIOSEntryPoint) {
-    let table_addr = entry.structure_table_address as u64;
+    let table_addr = crate::memory::paging::phys_to_virt(entry.structure_table_address as u64);
     let mut current_addr = table_addr;
     
     for _ in 0..entry.number_of_structures {
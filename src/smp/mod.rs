@@ -1,10 +1,42 @@
+use crate::interrupts::apic;
+use crate::memory::paging::{phys_to_virt, PageMapper, PageTable, PAGE_PRESENT, PAGE_SIZE_2MB, PAGE_WRITABLE};
+use crate::scheduler::KERNEL_STACK_SIZE;
 use crate::vga;
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::arch::global_asm;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use synchronization::SpinLock;
 
 pub mod cpu;
 pub mod scheduler;
 pub mod synchronization;
 
+global_asm!(include_str!("trampoline.s"), options(att_syntax));
+
+pub const MAX_CPUS: usize = 64;
+
+const AP_TRAMPOLINE_BASE: u64 = 0x8000;
+const AP_PML4: u64 = 0x9000;
+const AP_PDPT: u64 = 0xA000;
+const AP_PD: u64 = 0xB000;
+const INIT_DELAY_NS: u64 = 10_000_000;
+const STARTUP_DELAY_NS: u64 = 200_000;
+const AP_BOOT_POLLS: u32 = 500;
+
+const NO_APIC_ID: AtomicU32 = AtomicU32::new(0);
+static CPU_APIC_IDS: [AtomicU32; MAX_CPUS] = [NO_APIC_ID; MAX_CPUS];
+static REGISTERED_CPUS: AtomicU32 = AtomicU32::new(0);
+static ONLINE_CPU_MASK: AtomicU64 = AtomicU64::new(1);
+
+extern "C" {
+    static ap_trampoline_start: u8;
+    static ap_trampoline_end: u8;
+    static ap_trampoline_cr3: u8;
+    static ap_trampoline_cpu: u8;
+    static ap_trampoline_stack: u8;
+    static ap_trampoline_entry: u8;
+}
+
+#[derive(Clone, Copy)]
 #[repr(C, packed)]
 pub struct CPUInfo {
     pub id: u32,
@@ -17,14 +49,14 @@ pub struct CPUInfo {
 }
 
 pub struct SMPManager {
-    pub cpus: [CPUInfo; 64],
+    pub cpus: [CPUInfo; MAX_CPUS],
     pub cpu_count: u32,
     pub online_cpus: AtomicU32,
     pub bsp_id: u32,
 }
 
 impl SMPManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             cpus: [CPUInfo {
                 id: 0,
@@ -34,64 +66,67 @@ impl SMPManager {
                 current_task: None,
                 idle_time: 0,
                 interrupt_count: 0,
-            }; 64],
+            }; MAX_CPUS],
             cpu_count: 0,
             online_cpus: AtomicU32::new(0),
             bsp_id: 0,
         }
     }
     
-    pub fn init(&mut self) {
-        vga::print!("Initializing Symmetric Multiprocessing...\n");
+    fn detect_cpus(&mut self) {
+        vga::print!("Detecting CPUs...\n");
         
-        cpu::init();
-        scheduler::init();
-        synchronization::init();
+        let bsp_apic_id = apic::current_apic_id() as u32;
+        self.register_cpu(bsp_apic_id, true);
+        self.bsp_id = bsp_apic_id;
+        self.online_cpus.store(1, Ordering::Relaxed);
         
-        self.detect_cpus();
-        self.start_ap_cpus();
+        for apic_id in crate::acpi::madt::local_apic_ids() {
+            if apic_id == bsp_apic_id {
+                continue;
+            }
+            if self.cpu_count as usize >= MAX_CPUS {
+                vga::print!("Ignoring CPU with APIC ID {}: too many CPUs\n", apic_id);
+                continue;
+            }
+            self.register_cpu(apic_id, false);
+        }
+        REGISTERED_CPUS.store(self.cpu_count, Ordering::Release);
         
-        vga::print!("SMP initialized with {} CPUs\n", self.cpu_count);
+        vga::print!("Found {} CPUs\n", self.cpu_count);
     }
     
-    fn detect_cpus(&mut self) {
-        vga::print!("Detecting CPUs...\n");
-        
-        self.cpus[0] = CPUInfo {
-            id: 0,
-            apic_id: 0,
-            is_bsp: true,
-            online: true,
+    fn register_cpu(&mut self, apic_id: u32, is_bsp: bool) {
+        let id = self.cpu_count;
+        self.cpus[id as usize] = CPUInfo {
+            id,
+            apic_id,
+            is_bsp,
+            online: is_bsp,
             current_task: None,
             idle_time: 0,
             interrupt_count: 0,
         };
-        
-        self.cpu_count = 1;
-        self.bsp_id = 0;
-        self.online_cpus.store(1, Ordering::Relaxed);
-        
-        vga::print!("Found {} CPUs\n", self.cpu_count);
+        CPU_APIC_IDS[id as usize].store(apic_id, Ordering::Relaxed);
+        self.cpu_count += 1;
     }
     
-    fn start_ap_cpus(&mut self) {
-        vga::print!("Starting AP CPUs...\n");
-        
-        for i in 1..4 {
-            self.cpus[i as usize] = CPUInfo {
-                id: i,
-                apic_id: i,
-                is_bsp: false,
-                online: true,
-                current_task: None,
-                idle_time: 0,
-                interrupt_count: 0,
-            };
-            self.cpu_count += 1;
+    pub fn cpu_online(&mut self, cpu_id: u32) {
+        if cpu_id >= self.cpu_count || self.cpus[cpu_id as usize].online {
+            return;
         }
         
-        self.online_cpus.store(self.cpu_count, Ordering::Relaxed);
-        vga::print!("Started {} AP CPUs\n", self.cpu_count - 1);
+        self.cpus[cpu_id as usize].online = true;
+        self.online_cpus.fetch_add(1, Ordering::Relaxed);
+        ONLINE_CPU_MASK.fetch_or(1 << cpu_id, Ordering::AcqRel);
+        vga::print!("CPU {} online\n", cpu_id);
+    }
+    
+    pub fn is_online(&self, cpu_id: u32) -> bool {
+        if self.cpu_count == 0 {
+            return cpu_id == 0;
+        }
+        cpu_id < self.cpu_count && self.cpus[cpu_id as usize].online
     }
     
     pub fn get_cpu_count(&self) -> u32 {
@@ -111,22 +146,174 @@ impl SMPManager {
     }
 }
 
-pub static mut SMP_MANAGER: SMPManager = SMPManager::new();
+pub static SMP_MANAGER: SpinLock<SMPManager> = SpinLock::new(SMPManager::new());
 
 pub fn init() {
+    vga::print!("Initializing Symmetric Multiprocessing...\n");
+    
+    cpu::init();
+    scheduler::init();
+    synchronization::init();
+    
+    SMP_MANAGER.lock().detect_cpus();
+    start_ap_cpus();
+    
+    vga::print!("SMP initialized with {} of {} CPUs online\n", get_online_cpu_count(), get_cpu_count());
+}
+
+fn start_ap_cpus() {
+    let count = REGISTERED_CPUS.load(Ordering::Acquire);
+    if count <= 1 {
+        return;
+    }
+    
+    vga::print!("Starting {} AP CPUs...\n", count - 1);
+    install_trampoline();
+    for cpu in 1..count {
+        if !start_ap(cpu) {
+            vga::print!("CPU {} (APIC ID {}) did not come online\n", cpu, apic_id(cpu));
+        }
+    }
+}
+
+fn install_trampoline() {
     unsafe {
-        SMP_MANAGER.init();
+        let start = &ap_trampoline_start as *const u8;
+        let length = &ap_trampoline_end as *const u8 as usize - start as usize;
+        core::ptr::copy_nonoverlapping(start, phys_to_virt(AP_TRAMPOLINE_BASE) as *mut u8, length);
+        
+        let pml4 = &mut *(phys_to_virt(AP_PML4) as *mut PageTable);
+        let pdpt = &mut *(phys_to_virt(AP_PDPT) as *mut PageTable);
+        let pd = &mut *(phys_to_virt(AP_PD) as *mut PageTable);
+        let kernel = &*(phys_to_virt(PageMapper::kernel().root()) as *const PageTable);
+        *pml4 = PageTable::new();
+        *pdpt = PageTable::new();
+        *pd = PageTable::new();
+        pml4.entries[256..].copy_from_slice(&kernel.entries[256..]);
+        pml4.entries[0] = AP_PDPT | PAGE_PRESENT | PAGE_WRITABLE;
+        pdpt.entries[0] = AP_PD | PAGE_PRESENT | PAGE_WRITABLE;
+        pd.entries[0] = PAGE_PRESENT | PAGE_WRITABLE | PAGE_SIZE_2MB;
+        
+        core::ptr::write_volatile(trampoline_field::<u32>(&ap_trampoline_cr3), AP_PML4 as u32);
+        core::ptr::write_volatile(trampoline_field::<u64>(&ap_trampoline_entry), ap_main as usize as u64);
     }
 }
 
-pub fn get_cpu_count() -> u32 {
+fn start_ap(cpu: u32) -> bool {
+    let stack = crate::vm::protection::allocate_stack(KERNEL_STACK_SIZE);
+    if stack.is_null() {
+        return false;
+    }
+    
     unsafe {
-        SMP_MANAGER.get_cpu_count()
+        core::ptr::write_volatile(trampoline_field::<u32>(&ap_trampoline_cpu), cpu);
+        core::ptr::write_volatile(trampoline_field::<u64>(&ap_trampoline_stack), stack as u64 + KERNEL_STACK_SIZE as u64);
+    }
+    
+    let target = apic_id(cpu) as u8;
+    apic::send_init(target);
+    crate::time::busy_wait_ns(INIT_DELAY_NS);
+    for _ in 0..2 {
+        if is_started(cpu) {
+            break;
+        }
+        apic::send_startup(target, (AP_TRAMPOLINE_BASE >> 12) as u8);
+        crate::time::busy_wait_ns(STARTUP_DELAY_NS);
+    }
+    
+    for _ in 0..AP_BOOT_POLLS {
+        if is_started(cpu) {
+            return true;
+        }
+        crate::time::busy_wait_ns(STARTUP_DELAY_NS);
     }
+    false
+}
+
+fn is_started(cpu: u32) -> bool {
+    online_cpu_mask() & (1 << cpu) != 0
+}
+
+unsafe fn trampoline_field<T>(symbol: &u8) -> *mut T {
+    let offset = symbol as *const u8 as u64 - &ap_trampoline_start as *const u8 as u64;
+    phys_to_virt(AP_TRAMPOLINE_BASE + offset) as *mut T
+}
+
+pub fn get_cpu_count() -> u32 {
+    SMP_MANAGER.lock().get_cpu_count()
 }
 
 pub fn get_online_cpu_count() -> u32 {
-    unsafe {
-        SMP_MANAGER.get_online_cpu_count()
+    SMP_MANAGER.lock().get_online_cpu_count()
+}
+
+pub fn is_cpu_online(cpu_id: u32) -> bool {
+    SMP_MANAGER.lock().is_online(cpu_id)
+}
+
+pub fn current_cpu_id() -> u32 {
+    let count = REGISTERED_CPUS.load(Ordering::Acquire);
+    if count <= 1 {
+        return 0;
+    }
+    
+    let apic_id = crate::interrupts::apic::current_apic_id() as u32;
+    for i in 0..count {
+        if CPU_APIC_IDS[i as usize].load(Ordering::Relaxed) == apic_id {
+            return i;
+        }
+    }
+    0
+}
+
+pub fn online_cpu_mask() -> u64 {
+    ONLINE_CPU_MASK.load(Ordering::Acquire)
+}
+
+pub fn apic_id(cpu_id: u32) -> u32 {
+    CPU_APIC_IDS[cpu_id as usize].load(Ordering::Relaxed)
+}
+
+pub fn set_current_task(cpu_id: u32, task_id: usize) {
+    let mut smp = SMP_MANAGER.lock();
+    if cpu_id < smp.cpu_count {
+        smp.cpus[cpu_id as usize].current_task = Some(task_id as u32);
+    }
+}
+
+pub fn record_idle_tick(cpu_id: u32) {
+    let mut smp = SMP_MANAGER.lock();
+    if cpu_id < smp.cpu_count {
+        smp.cpus[cpu_id as usize].idle_time += 1;
+    }
+}
+
+pub fn record_interrupt(cpu_id: u32) {
+    let mut smp = SMP_MANAGER.lock();
+    if cpu_id < smp.cpu_count {
+        smp.cpus[cpu_id as usize].interrupt_count += 1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ap_main(cpu_id: u32) -> ! {
+    crate::interrupts::disable_interrupts();
+    PageMapper::kernel().activate();
+    apic::init_cpu();
+    crate::gdt::init_cpu(cpu_id);
+    crate::interrupts::idt::load();
+    crate::syscalls::interface::init_cpu(cpu_id);
+    crate::vm::protection::init_cpu();
+    {
+        let mut scheduler = crate::scheduler::SCHEDULER.lock();
+        scheduler.init_cpu(cpu_id);
+        scheduler.start_cpu(cpu_id);
+    }
+    SMP_MANAGER.lock().cpu_online(cpu_id);
+    crate::time::init_cpu();
+    crate::interrupts::enable_interrupts();
+    
+    loop {
+        crate::interrupts::halt();
     }
 }
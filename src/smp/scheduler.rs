@@ -1,5 +1,152 @@
+use crate::scheduler::run_queue::RUN_QUEUES;
+use crate::scheduler::task_table::TaskTable;
+use crate::scheduler::{Scheduler, SCHEDULER};
 use crate::vga;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub const RESCHEDULE_VECTOR: u8 = 0xFC;
+const BALANCE_INTERVAL: u64 = 100;
+const IMBALANCE_THRESHOLD: usize = 2;
+
+static BALANCE_TICKS: AtomicU64 = AtomicU64::new(0);
+static MIGRATIONS: AtomicU64 = AtomicU64::new(0);
+static STEALS: AtomicU64 = AtomicU64::new(0);
 
 pub fn init() {
     vga::print!("SMP scheduler initialized\n");
 }
+
+pub fn select_cpu(tasks: &TaskTable, affinity: u64) -> u32 {
+    let mut best_cpu = None;
+    let mut best_load = usize::MAX;
+
+    for cpu in online_cpus() {
+        if affinity & (1 << cpu) == 0 {
+            continue;
+        }
+
+        let load = match RUN_QUEUES[cpu as usize].lock().as_ref() {
+            Some(queue) => queue.ready_count(tasks),
+            None => continue,
+        };
+        if load < best_load {
+            best_cpu = Some(cpu);
+            best_load = load;
+        }
+    }
+
+    best_cpu.unwrap_or_else(|| affinity.trailing_zeros().min(super::get_cpu_count().saturating_sub(1)))
+}
+
+pub fn steal_work(scheduler: &mut Scheduler, cpu: u32) -> bool {
+    let mut busiest = None;
+    let mut busiest_load = 1;
+
+    for other in online_cpus() {
+        if other == cpu {
+            continue;
+        }
+
+        let load = scheduler.cpu_load(other);
+        if load > busiest_load {
+            busiest = Some(other);
+            busiest_load = load;
+        }
+    }
+
+    let from_cpu = match busiest {
+        Some(from_cpu) => from_cpu,
+        None => return false,
+    };
+
+    match scheduler.migrate_one(from_cpu, cpu) {
+        Some(_) => {
+            STEALS.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn balance_tick(scheduler: &mut Scheduler) {
+    if (BALANCE_TICKS.fetch_add(1, Ordering::Relaxed) + 1) % BALANCE_INTERVAL != 0 {
+        return;
+    }
+
+    balance(scheduler);
+}
+
+pub fn balance(scheduler: &mut Scheduler) {
+    let mut busiest = (0, 0);
+    let mut idlest = (0, usize::MAX);
+
+    for cpu in online_cpus() {
+        let load = scheduler.cpu_load(cpu);
+        if load > busiest.1 {
+            busiest = (cpu, load);
+        }
+        if load < idlest.1 {
+            idlest = (cpu, load);
+        }
+    }
+
+    if busiest.0 == idlest.0 || busiest.1 < idlest.1 + IMBALANCE_THRESHOLD {
+        return;
+    }
+
+    let moves = (busiest.1 - idlest.1) / 2;
+    let mut moved = 0;
+    for _ in 0..moves {
+        if scheduler.migrate_one(busiest.0, idlest.0).is_none() {
+            break;
+        }
+        moved += 1;
+    }
+
+    if moved > 0 {
+        MIGRATIONS.fetch_add(moved, Ordering::Relaxed);
+        if idlest.0 != super::current_cpu_id() {
+            send_reschedule(idlest.0);
+        }
+    }
+}
+
+pub fn send_reschedule(cpu: u32) {
+    if super::online_cpu_mask() & (1 << cpu) == 0 {
+        return;
+    }
+
+    crate::interrupts::apic::send_ipi(super::apic_id(cpu) as u8, RESCHEDULE_VECTOR);
+}
+
+pub fn handle_reschedule(context: &mut crate::scheduler::task::TaskContext) {
+    crate::interrupts::apic::send_eoi();
+    crate::scheduler::switch_context(context);
+}
+
+pub fn get_migration_count() -> u64 {
+    MIGRATIONS.load(Ordering::Relaxed)
+}
+
+pub fn get_steal_count() -> u64 {
+    STEALS.load(Ordering::Relaxed)
+}
+
+pub fn print_stats() {
+    vga::print!("SMP Scheduler Stats:\n");
+    for cpu in online_cpus() {
+        let load = SCHEDULER.lock().cpu_load(cpu);
+        let idle_time = match super::SMP_MANAGER.lock().get_cpu_info(cpu) {
+            Some(info) => info.idle_time,
+            None => 0,
+        };
+        vga::print!("  CPU {}: load {}, idle ticks {}\n", cpu, load, idle_time);
+    }
+    vga::print!("  Migrations: {}\n", get_migration_count());
+    vga::print!("  Steals: {}\n", get_steal_count());
+}
+
+fn online_cpus() -> impl Iterator<Item = u32> {
+    let mask = super::online_cpu_mask();
+    (0..super::MAX_CPUS as u32).filter(move |&cpu| mask & (1 << cpu) != 0)
+}
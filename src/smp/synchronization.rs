@@ -1,5 +0,0 @@
-use crate::vga;
-
-pub fn init() {
-    vga::print!("SMP synchronization initialized\n");
-}
@@ -0,0 +1,35 @@
+use super::mutex::MutexGuard;
+use super::wait_queue::WaitQueue;
+
+pub struct Condvar {
+    waiters: WaitQueue,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = guard.mutex();
+        self.waiters.sleep_with(|| drop(guard));
+        mutex.lock()
+    }
+
+    pub fn wait_while<'a, T>(&self, mut guard: MutexGuard<'a, T>, mut condition: impl FnMut(&mut T) -> bool) -> MutexGuard<'a, T> {
+        while condition(&mut guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    pub fn notify_one(&self) -> bool {
+        self.waiters.wake_one()
+    }
+
+    pub fn notify_all(&self) -> usize {
+        self.waiters.wake_all()
+    }
+}
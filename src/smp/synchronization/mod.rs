@@ -0,0 +1,18 @@
+use crate::vga;
+
+pub mod spinlock;
+pub mod ticket_lock;
+pub mod wait_queue;
+pub mod mutex;
+pub mod rwlock;
+pub mod semaphore;
+pub mod condvar;
+
+pub use spinlock::SpinLock;
+pub use ticket_lock::TicketLock;
+pub use wait_queue::WaitQueue;
+pub use mutex::Mutex;
+
+pub fn init() {
+    vga::print!("SMP synchronization initialized\n");
+}
@@ -0,0 +1,84 @@
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+pub struct TicketLock<T> {
+    next_ticket: AtomicU32,
+    now_serving: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for TicketLock<T> {}
+unsafe impl<T: Send> Send for TicketLock<T> {}
+
+impl<T> TicketLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            next_ticket: AtomicU32::new(0),
+            now_serving: AtomicU32::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> TicketLockGuard<'_, T> {
+        crate::interrupts::push_disable();
+
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            crate::memory::paging::handle_tlb_shootdown();
+            core::hint::spin_loop();
+        }
+
+        TicketLockGuard {
+            lock: self,
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<TicketLockGuard<'_, T>> {
+        crate::interrupts::push_disable();
+
+        let ticket = self.now_serving.load(Ordering::Relaxed);
+        if self.next_ticket.compare_exchange(ticket, ticket.wrapping_add(1), Ordering::Acquire, Ordering::Relaxed).is_ok() {
+            Some(TicketLockGuard {
+                lock: self,
+            })
+        } else {
+            crate::interrupts::pop_disable();
+            None
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.next_ticket.load(Ordering::Relaxed) != self.now_serving.load(Ordering::Relaxed)
+    }
+
+    pub fn waiters(&self) -> u32 {
+        let next = self.next_ticket.load(Ordering::Relaxed);
+        next.wrapping_sub(self.now_serving.load(Ordering::Relaxed)).saturating_sub(1)
+    }
+}
+
+pub struct TicketLockGuard<'a, T> {
+    lock: &'a TicketLock<T>,
+}
+
+impl<'a, T> Deref for TicketLockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for TicketLockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for TicketLockGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.now_serving.fetch_add(1, Ordering::Release);
+        crate::interrupts::pop_disable();
+    }
+}
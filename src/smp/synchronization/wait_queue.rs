@@ -0,0 +1,93 @@
+use super::spinlock::SpinLock;
+use crate::scheduler;
+use alloc::collections::VecDeque;
+
+pub struct WaitQueue {
+    waiters: SpinLock<VecDeque<usize>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: SpinLock::new(VecDeque::new()),
+        }
+    }
+
+    pub fn wait(&self) {
+        self.wait_if(|| true);
+    }
+
+    pub fn wait_if(&self, condition: impl FnOnce() -> bool) -> bool {
+        let task_id = scheduler::current_task_id();
+        if task_id == 0 {
+            let should_wait = condition();
+            if should_wait {
+                core::hint::spin_loop();
+            }
+            return should_wait;
+        }
+
+        {
+            let mut waiters = self.waiters.lock();
+            if !condition() {
+                return false;
+            }
+            waiters.push_back(task_id);
+            scheduler::block_task(task_id);
+        }
+
+        scheduler::schedule();
+        true
+    }
+
+    pub fn sleep_with(&self, release: impl FnOnce()) {
+        let task_id = scheduler::current_task_id();
+        if task_id == 0 {
+            release();
+            core::hint::spin_loop();
+            return;
+        }
+
+        {
+            let mut waiters = self.waiters.lock();
+            waiters.push_back(task_id);
+            scheduler::block_task(task_id);
+            release();
+        }
+
+        scheduler::schedule();
+    }
+
+    pub fn wake_one(&self) -> bool {
+        let mut waiters = self.waiters.lock();
+        while let Some(task_id) = waiters.pop_front() {
+            if scheduler::unblock_task(task_id) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn wake_all(&self) -> usize {
+        let mut waiters = self.waiters.lock();
+        let mut woken = 0;
+        while let Some(task_id) = waiters.pop_front() {
+            if scheduler::unblock_task(task_id) {
+                woken += 1;
+            }
+        }
+        woken
+    }
+
+    pub fn remove(&self, task_id: usize) {
+        self.waiters.lock().retain(|&waiter| waiter != task_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiters.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,84 @@
+.set AP_TRAMPOLINE_BASE, 0x8000
+
+.section .rodata
+.balign 4096
+.global ap_trampoline_start
+.global ap_trampoline_end
+.global ap_trampoline_cr3
+.global ap_trampoline_cpu
+.global ap_trampoline_stack
+.global ap_trampoline_entry
+
+.code16
+ap_trampoline_start:
+    cli
+    cld
+    xorw %ax, %ax
+    movw %ax, %ds
+    lgdtl AP_TRAMPOLINE_BASE + (ap_trampoline_gdt_pointer - ap_trampoline_start)
+    movl %cr0, %eax
+    orl $0x1, %eax
+    movl %eax, %cr0
+    ljmpl $0x08, $(AP_TRAMPOLINE_BASE + (ap_trampoline_protected - ap_trampoline_start))
+
+.code32
+ap_trampoline_protected:
+    movw $0x10, %ax
+    movw %ax, %ds
+    movw %ax, %es
+    movw %ax, %fs
+    movw %ax, %gs
+    movw %ax, %ss
+
+    movl %cr4, %eax
+    orl $0x20, %eax
+    movl %eax, %cr4
+
+    movl AP_TRAMPOLINE_BASE + (ap_trampoline_cr3 - ap_trampoline_start), %eax
+    movl %eax, %cr3
+
+    movl $0xC0000080, %ecx
+    rdmsr
+    orl $0x900, %eax
+    wrmsr
+
+    movl %cr0, %eax
+    orl $0x80010001, %eax
+    movl %eax, %cr0
+
+    ljmpl $0x18, $(AP_TRAMPOLINE_BASE + (ap_trampoline_long - ap_trampoline_start))
+
+.code64
+ap_trampoline_long:
+    movq AP_TRAMPOLINE_BASE + (ap_trampoline_stack - ap_trampoline_start), %rsp
+    movl AP_TRAMPOLINE_BASE + (ap_trampoline_cpu - ap_trampoline_start), %edi
+    movq AP_TRAMPOLINE_BASE + (ap_trampoline_entry - ap_trampoline_start), %rax
+    xorl %ebp, %ebp
+    callq *%rax
+1:
+    cli
+    hlt
+    jmp 1b
+
+.balign 8
+ap_trampoline_gdt:
+    .quad 0
+    .quad 0x00CF9A000000FFFF
+    .quad 0x00CF92000000FFFF
+    .quad 0x00AF9A000000FFFF
+ap_trampoline_gdt_pointer:
+    .word ap_trampoline_gdt_pointer - ap_trampoline_gdt - 1
+    .long AP_TRAMPOLINE_BASE + (ap_trampoline_gdt - ap_trampoline_start)
+
+.balign 8
+ap_trampoline_cr3:
+    .long 0
+ap_trampoline_cpu:
+    .long 0
+ap_trampoline_stack:
+    .quad 0
+ap_trampoline_entry:
+    .quad 0
+ap_trampoline_end:
+
+.text
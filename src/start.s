@@ -1,7 +1,93 @@
+.set PAGE_TABLE_FLAGS, 0x3
+.set HUGE_PAGE_FLAGS, 0x83
+.set HUGE_PAGE_SIZE, 0x200000
+.set BOOT_IDENTITY_GIB, 4
+
 .section .text
 .global _start
 _start:
-    mov %rdi, %rsp
-    sub $0x1000, %rsp
+    cli
+    mov %rdi, %r12
+
+    lea boot_pdpt_low(%rip), %rbx
+    lea boot_pd_low(%rip), %rdx
+    or $PAGE_TABLE_FLAGS, %rdx
+    xor %ecx, %ecx
+1:
+    mov %rdx, (%rbx,%rcx,8)
+    add $0x1000, %rdx
+    inc %rcx
+    cmp $BOOT_IDENTITY_GIB, %rcx
+    jb 1b
+
+    lea boot_pd_low(%rip), %rbx
+    mov $HUGE_PAGE_FLAGS, %rdx
+    xor %ecx, %ecx
+2:
+    mov %rdx, (%rbx,%rcx,8)
+    add $HUGE_PAGE_SIZE, %rdx
+    inc %rcx
+    cmp $(BOOT_IDENTITY_GIB * 512), %rcx
+    jb 2b
+
+    movabs $KERNEL_VIRTUAL_BASE, %rsi
+    movabs $__kernel_start, %rdx
+    sub %rsi, %rdx
+    lea __kernel_start(%rip), %rbx
+    sub %rdx, %rbx
+    test $(HUGE_PAGE_SIZE - 1), %rbx
+    jnz 5f
+    or $HUGE_PAGE_FLAGS, %rbx
+
+    lea boot_pd_high(%rip), %rdx
+    xor %ecx, %ecx
+3:
+    mov %rbx, (%rdx,%rcx,8)
+    add $HUGE_PAGE_SIZE, %rbx
+    inc %rcx
+    cmp $512, %rcx
+    jb 3b
+
+    lea boot_pdpt_high(%rip), %rbx
+    mov %rsi, %rcx
+    shr $30, %rcx
+    and $0x1FF, %rcx
+    or $PAGE_TABLE_FLAGS, %rdx
+    mov %rdx, (%rbx,%rcx,8)
+
+    lea boot_pml4(%rip), %rdx
+    lea boot_pdpt_low(%rip), %rax
+    or $PAGE_TABLE_FLAGS, %rax
+    mov %rax, (%rdx)
+    or $PAGE_TABLE_FLAGS, %rbx
+    mov %rsi, %rcx
+    shr $39, %rcx
+    and $0x1FF, %rcx
+    mov %rbx, (%rdx,%rcx,8)
+
+    mov %rdx, %cr3
+    movabs $higher_half_start, %rax
+    jmp *%rax
+
+higher_half_start:
+    movabs $__boot_stack_top, %rsp
+    mov %r12, %rdi
+    xor %rbp, %rbp
     call kernel_main
+5:
+    cli
     hlt
+    jmp 5b
+
+.section .bss
+.align 4096
+boot_pml4:
+    .skip 4096
+boot_pdpt_low:
+    .skip 4096
+boot_pdpt_high:
+    .skip 4096
+boot_pd_high:
+    .skip 4096
+boot_pd_low:
+    .skip 4096 * BOOT_IDENTITY_GIB
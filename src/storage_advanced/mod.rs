@@ -1,3 +1,4 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod raid;
@@ -13,7 +14,7 @@ pub struct AdvancedStorage {
 }
 
 impl AdvancedStorage {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             raid_enabled: false,
             journaling_enabled: false,
@@ -39,10 +40,8 @@ impl AdvancedStorage {
     }
 }
 
-pub static mut ADVANCED_STORAGE: AdvancedStorage = AdvancedStorage::new();
+pub static ADVANCED_STORAGE: SpinLock<AdvancedStorage> = SpinLock::new(AdvancedStorage::new());
 
 pub fn init() {
-    unsafe {
-        ADVANCED_STORAGE.init();
-    }
+    ADVANCED_STORAGE.lock().init();
 }
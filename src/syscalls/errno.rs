@@ -0,0 +1,126 @@
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(i32)]
+pub enum Errno {
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EINTR = 4,
+    EIO = 5,
+    E2BIG = 7,
+    ENOEXEC = 8,
+    EBADF = 9,
+    ECHILD = 10,
+    EAGAIN = 11,
+    ENOMEM = 12,
+    EACCES = 13,
+    EFAULT = 14,
+    EEXIST = 17,
+    ENODEV = 19,
+    ENOTDIR = 20,
+    EISDIR = 21,
+    EINVAL = 22,
+    EMFILE = 24,
+    ENOTTY = 25,
+    ENOSPC = 28,
+    ESPIPE = 29,
+    EROFS = 30,
+    EPIPE = 32,
+    ENAMETOOLONG = 36,
+    ENOSYS = 38,
+    ETIMEDOUT = 110,
+    ENOTSOCK = 88,
+    EAFNOSUPPORT = 97,
+    EDESTADDRREQ = 89,
+    EMSGSIZE = 90,
+    EPROTONOSUPPORT = 93,
+}
+
+impl Errno {
+    pub fn to_return_value(self) -> u64 {
+        (-(self as i64)) as u64
+    }
+
+    pub fn from_code(code: i32) -> Option<Errno> {
+        const ERRNOS: [Errno; 32] = [
+            Errno::EPERM,
+            Errno::ENOENT,
+            Errno::ESRCH,
+            Errno::EINTR,
+            Errno::EIO,
+            Errno::E2BIG,
+            Errno::ENOEXEC,
+            Errno::EBADF,
+            Errno::ECHILD,
+            Errno::EAGAIN,
+            Errno::ENOMEM,
+            Errno::EACCES,
+            Errno::EFAULT,
+            Errno::EEXIST,
+            Errno::ENODEV,
+            Errno::ENOTDIR,
+            Errno::EISDIR,
+            Errno::EINVAL,
+            Errno::EMFILE,
+            Errno::ENOTTY,
+            Errno::ENOSPC,
+            Errno::ESPIPE,
+            Errno::EROFS,
+            Errno::EPIPE,
+            Errno::ENAMETOOLONG,
+            Errno::ENOSYS,
+            Errno::ETIMEDOUT,
+            Errno::ENOTSOCK,
+            Errno::EAFNOSUPPORT,
+            Errno::EDESTADDRREQ,
+            Errno::EMSGSIZE,
+            Errno::EPROTONOSUPPORT,
+        ];
+        ERRNOS.iter().copied().find(|&errno| errno as i32 == code)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Errno::EPERM => "EPERM",
+            Errno::ENOENT => "ENOENT",
+            Errno::ESRCH => "ESRCH",
+            Errno::EINTR => "EINTR",
+            Errno::EIO => "EIO",
+            Errno::E2BIG => "E2BIG",
+            Errno::ENOEXEC => "ENOEXEC",
+            Errno::EBADF => "EBADF",
+            Errno::ECHILD => "ECHILD",
+            Errno::EAGAIN => "EAGAIN",
+            Errno::ENOMEM => "ENOMEM",
+            Errno::EACCES => "EACCES",
+            Errno::EFAULT => "EFAULT",
+            Errno::EEXIST => "EEXIST",
+            Errno::ENODEV => "ENODEV",
+            Errno::ENOTDIR => "ENOTDIR",
+            Errno::EISDIR => "EISDIR",
+            Errno::EINVAL => "EINVAL",
+            Errno::EMFILE => "EMFILE",
+            Errno::ENOTTY => "ENOTTY",
+            Errno::ENOSPC => "ENOSPC",
+            Errno::ESPIPE => "ESPIPE",
+            Errno::EROFS => "EROFS",
+            Errno::EPIPE => "EPIPE",
+            Errno::ENAMETOOLONG => "ENAMETOOLONG",
+            Errno::ENOSYS => "ENOSYS",
+            Errno::ETIMEDOUT => "ETIMEDOUT",
+            Errno::ENOTSOCK => "ENOTSOCK",
+            Errno::EAFNOSUPPORT => "EAFNOSUPPORT",
+            Errno::EDESTADDRREQ => "EDESTADDRREQ",
+            Errno::EMSGSIZE => "EMSGSIZE",
+            Errno::EPROTONOSUPPORT => "EPROTONOSUPPORT",
+        }
+    }
+}
+
+pub type SyscallResult = Result<u64, Errno>;
+
+pub fn encode(result: SyscallResult) -> u64 {
+    match result {
+        Ok(value) => value,
+        Err(errno) => errno.to_return_value(),
+    }
+}
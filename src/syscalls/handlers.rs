@@ -1,56 +1,318 @@
+use crate::networking::socket::{UdpSocket, AF_INET, IPPROTO_UDP, SOCK_DGRAM};
+use crate::process::files::{ConsoleFile, FileDescription, VfsFile};
+use crate::ipc::poll::PollFd;
+use crate::process::Personality;
 use crate::vga;
+use super::errno::{encode, Errno, SyscallResult};
+use super::user;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const MAX_IO_SIZE: usize = 0x10000;
+const O_ACCMODE: u64 = 3;
+const O_RDONLY: u64 = 0;
+const CONSOLE_PATH: &str = "/dev/console";
+
+pub const PERSONALITY_NATIVE: u64 = 0;
+pub const PERSONALITY_LINUX: u64 = 1;
+pub const TRACE_ALL: u64 = 0;
 
 pub fn init() {
     vga::print!("System call handlers initialized\n");
 }
 
 pub fn sys_exit(status: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
-    vga::print!("Process exit with status: {}\n", status);
-    0
+    crate::process::exit(status as i32)
 }
 
 pub fn sys_read(fd: u64, buf: u64, count: u64, _arg4: u64, _arg5: u64) -> u64 {
-    vga::print!("Read from fd {}: {} bytes to 0x{:x}\n", fd, count, buf);
-    count
+    encode(read(fd, buf, count))
 }
 
 pub fn sys_write(fd: u64, buf: u64, count: u64, _arg4: u64, _arg5: u64) -> u64 {
-    vga::print!("Write to fd {}: {} bytes from 0x{:x}\n", fd, count, buf);
-    count
+    encode(write(fd, buf, count))
 }
 
-pub fn sys_open(pathname: u64, flags: u64, mode: u64, _arg4: u64, _arg5: u64) -> u64 {
-    vga::print!("Open file at 0x{:x}, flags: 0x{:x}, mode: 0x{:x}\n", pathname, flags, mode);
-    1
+pub fn sys_open(pathname: u64, flags: u64, _mode: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(open(pathname, flags))
 }
 
 pub fn sys_close(fd: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
-    vga::print!("Close fd {}\n", fd);
-    0
+    encode(crate::process::close_file(fd as usize).map(|_| 0))
 }
 
 pub fn sys_mmap(addr: u64, length: u64, prot: u64, flags: u64, fd: u64) -> u64 {
-    vga::print!("Mmap: addr=0x{:x}, len={}, prot=0x{:x}, flags=0x{:x}, fd={}\n", 
-        addr, length, prot, flags, fd);
-    addr
+    encode(crate::process::memory::map(addr, length, prot, flags, fd, 0))
 }
 
 pub fn sys_munmap(addr: u64, length: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
-    vga::print!("Munmap: addr=0x{:x}, len={}\n", addr, length);
-    0
+    encode(crate::process::memory::unmap(addr, length).map(|_| 0))
 }
 
 pub fn sys_fork(_arg1: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
-    vga::print!("Fork system call\n");
-    0
+    encode(crate::process::fork().map(|pid| pid as u64).map_err(|err| err.errno()))
 }
 
 pub fn sys_exec(pathname: u64, argv: u64, envp: u64, _arg4: u64, _arg5: u64) -> u64 {
-    vga::print!("Exec: path=0x{:x}, argv=0x{:x}, envp=0x{:x}\n", pathname, argv, envp);
-    0
+    encode(exec(pathname, argv, envp))
 }
 
 pub fn sys_wait(pid: u64, status: u64, options: u64, _arg4: u64, _arg5: u64) -> u64 {
-    vga::print!("Wait: pid={}, status=0x{:x}, options=0x{:x}\n", pid, status, options);
-    pid
+    encode(wait(pid, status, options))
+}
+
+pub fn sys_socket(domain: u64, socket_type: u64, protocol: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(socket(domain, socket_type, protocol))
+}
+
+pub fn sys_connect(fd: u64, address: u64, length: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(connect(fd, address, length))
+}
+
+pub fn sys_personality(personality: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    let personality = match personality {
+        PERSONALITY_NATIVE => Personality::Native,
+        PERSONALITY_LINUX => Personality::Linux,
+        _ => return Errno::EINVAL.to_return_value(),
+    };
+    encode(crate::process::set_personality(personality).map(|_| 0).map_err(|err| err.errno()))
+}
+
+pub fn sys_trace(pid: u64, enabled: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    if pid == TRACE_ALL {
+        if !crate::process::may_trace_all() {
+            return Errno::EPERM.to_return_value();
+        }
+        super::trace::set_global_tracing(enabled != 0);
+        return 0;
+    }
+    encode(crate::process::set_traced(pid as usize, enabled != 0).map(|_| 0).map_err(|err| err.errno()))
+}
+
+pub fn sys_mprotect(addr: u64, length: u64, prot: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(crate::process::memory::protect(addr, length, prot).map(|_| 0))
+}
+
+pub fn sys_mremap(addr: u64, old_length: u64, new_length: u64, flags: u64, _arg5: u64) -> u64 {
+    encode(crate::process::memory::remap(addr, old_length, new_length, flags))
+}
+
+pub fn sys_pkey_alloc(flags: u64, rights: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(crate::process::memory::pkey_alloc(flags, rights))
+}
+
+pub fn sys_pkey_free(pkey: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(crate::process::memory::pkey_free(pkey).map(|_| 0))
+}
+
+pub fn sys_pkey_mprotect(addr: u64, length: u64, prot: u64, pkey: u64, _arg5: u64) -> u64 {
+    encode(crate::process::memory::pkey_protect(addr, length, prot, pkey).map(|_| 0))
+}
+
+pub fn sys_shm_open(name: u64, flags: u64, _mode: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(shm_open(name, flags))
+}
+
+pub fn sys_shm_unlink(name: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(user::read_string(name).and_then(|name| crate::vm::shm::unlink(&name)).map(|_| 0))
+}
+
+pub fn sys_ftruncate(fd: u64, length: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(crate::process::file(fd as usize).and_then(|file| file.truncate(length)).map(|_| 0))
+}
+
+pub fn sys_futex(addr: u64, op: u64, value: u64, timeout: u64, _arg5: u64) -> u64 {
+    encode(crate::process::memory::futex(addr, op, value, timeout))
+}
+
+pub fn sys_pipe(fds: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    let (reader, writer) = crate::ipc::pipe::pipe();
+    encode(install_pair(fds, Arc::new(reader), Arc::new(writer)))
+}
+
+pub fn sys_mkfifo(pathname: u64, _mode: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(user::read_string(pathname).and_then(|path| crate::ipc::pipe::mkfifo(&path)).map(|_| 0))
+}
+
+pub fn sys_channel(fds: u64, capacity: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(crate::ipc::channel::channel(capacity as usize)
+        .and_then(|(sender, receiver)| install_pair(fds, Arc::new(receiver), Arc::new(sender))))
+}
+
+pub fn sys_port_create(name: u64, capacity: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(port_create(name, capacity))
+}
+
+pub fn sys_port_connect(name: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(port_connect(name))
+}
+
+pub fn sys_poll(fds: u64, nfds: u64, timeout: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(poll(fds, nfds, timeout as i32 as i64))
+}
+
+pub fn sys_kill(pid: u64, signo: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(crate::process::signal::kill(pid as i64, signo).map(|_| 0))
+}
+
+pub fn sys_sigaction(signo: u64, action: u64, old_action: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(crate::process::signal::sigaction(signo, action, old_action).map(|_| 0))
+}
+
+pub fn sys_sigprocmask(how: u64, set: u64, old_set: u64, _arg4: u64, _arg5: u64) -> u64 {
+    encode(crate::process::signal::sigprocmask(how, set, old_set).map(|_| 0))
+}
+
+fn read(fd: u64, buf: u64, count: u64) -> SyscallResult {
+    let file = crate::process::file(fd as usize)?;
+    let count = (count as usize).min(MAX_IO_SIZE);
+    let mut buffer = vec![0u8; count];
+    let bytes_read = file.read(&mut buffer)?;
+    user::write_bytes(buf, &buffer[..bytes_read])?;
+    Ok(bytes_read as u64)
+}
+
+pub fn write(fd: u64, buf: u64, count: u64) -> SyscallResult {
+    let file = crate::process::file(fd as usize)?;
+    let data = user::read_bytes(buf, (count as usize).min(MAX_IO_SIZE))?;
+    let written = file.write(&data);
+    if written == Err(Errno::EPIPE) {
+        crate::process::signal::raise(crate::process::signal::SIGPIPE);
+    }
+    Ok(written? as u64)
+}
+
+fn open(pathname: u64, flags: u64) -> SyscallResult {
+    open_path(&user::read_string(pathname)?, flags)
+}
+
+fn shm_open(name: u64, flags: u64) -> SyscallResult {
+    let file = crate::vm::shm::open(&user::read_string(name)?, flags)?;
+    Ok(crate::process::install_file(file)? as u64)
+}
+
+pub fn open_path(path: &str, flags: u64) -> SyscallResult {
+    let file: Arc<dyn FileDescription> = if path == CONSOLE_PATH {
+        Arc::new(ConsoleFile)
+    } else if let Some(name) = path.strip_prefix(crate::vm::shm::SHM_PREFIX) {
+        crate::vm::shm::open(name, flags)?
+    } else if crate::ipc::pipe::is_named(path) {
+        crate::ipc::pipe::open_named(path, flags)?
+    } else {
+        if flags & O_ACCMODE != O_RDONLY {
+            return Err(Errno::EROFS);
+        }
+        Arc::new(VfsFile::open(path)?)
+    };
+
+    Ok(crate::process::install_file(file)? as u64)
+}
+
+pub fn unlink_path(path: &str) -> SyscallResult {
+    match path.strip_prefix(crate::vm::shm::SHM_PREFIX) {
+        Some(name) => crate::vm::shm::unlink(name).map(|_| 0),
+        None if crate::ipc::pipe::is_named(path) => crate::ipc::pipe::unlink(path).map(|_| 0),
+        None => Err(Errno::EROFS),
+    }
+}
+
+fn install_pair(fds: u64, first: Arc<dyn FileDescription>, second: Arc<dyn FileDescription>) -> SyscallResult {
+    let first = crate::process::install_file(first)?;
+    let second = match crate::process::install_file(second) {
+        Ok(fd) => fd,
+        Err(err) => {
+            let _ = crate::process::close_file(first);
+            return Err(err);
+        }
+    };
+
+    if let Err(err) = user::write_value(fds, [first as i32, second as i32]) {
+        let _ = crate::process::close_file(first);
+        let _ = crate::process::close_file(second);
+        return Err(err);
+    }
+    Ok(0)
+}
+
+fn port_create(name: u64, capacity: u64) -> SyscallResult {
+    let receiver = crate::ipc::channel::create_port(&user::read_string(name)?, capacity as usize)?;
+    Ok(crate::process::install_file(Arc::new(receiver))? as u64)
+}
+
+fn port_connect(name: u64) -> SyscallResult {
+    let sender = crate::ipc::channel::connect_port(&user::read_string(name)?)?;
+    Ok(crate::process::install_file(Arc::new(sender))? as u64)
+}
+
+fn poll(fds: u64, nfds: u64, timeout: i64) -> SyscallResult {
+    if nfds as usize > crate::ipc::poll::MAX_POLL_FDS {
+        return Err(Errno::EINVAL);
+    }
+    let size = core::mem::size_of::<PollFd>() as u64;
+    let mut entries = Vec::with_capacity(nfds as usize);
+    for index in 0..nfds {
+        entries.push(user::read_value::<PollFd>(fds + index * size)?);
+    }
+
+    let ready = crate::ipc::poll::poll(&mut entries, timeout)?;
+    for (index, entry) in entries.iter().enumerate() {
+        user::write_value(fds + index as u64 * size, *entry)?;
+    }
+    Ok(ready as u64)
+}
+
+fn exec(pathname: u64, argv: u64, envp: u64) -> SyscallResult {
+    let path = user::read_string(pathname)?;
+    let arguments = user::read_string_array(argv)?;
+    let environment = user::read_string_array(envp)?;
+
+    let argument_refs: Vec<&str> = arguments.iter().map(|argument| argument.as_str()).collect();
+    let environment_refs: Vec<&str> = environment.iter().map(|variable| variable.as_str()).collect();
+
+    crate::loader::exec(&path, &argument_refs, &environment_refs).map_err(|err| {
+        vga::print!("Exec of {} failed: {:?}\n", path, err);
+        err.errno()
+    })?;
+    Ok(0)
+}
+
+fn wait(pid: u64, status: u64, options: u64) -> SyscallResult {
+    match crate::process::wait(pid as i64, options).map_err(|err| err.errno())? {
+        Some((child, wait_status)) => {
+            if status != 0 {
+                user::write_value(status, wait_status)?;
+            }
+            Ok(child as u64)
+        }
+        None => Ok(0),
+    }
+}
+
+fn socket(domain: u64, socket_type: u64, protocol: u64) -> SyscallResult {
+    if domain != AF_INET {
+        return Err(Errno::EAFNOSUPPORT);
+    }
+    if socket_type != SOCK_DGRAM || (protocol != 0 && protocol != IPPROTO_UDP) {
+        return Err(Errno::EPROTONOSUPPORT);
+    }
+
+    Ok(crate::process::install_file(Arc::new(UdpSocket::new()))? as u64)
+}
+
+fn connect(fd: u64, address: u64, length: u64) -> SyscallResult {
+    let file = crate::process::file(fd as usize)?;
+    if (length as usize) < 8 {
+        return Err(Errno::EINVAL);
+    }
+
+    let sockaddr = user::read_bytes(address, 8)?;
+    if u16::from_le_bytes([sockaddr[0], sockaddr[1]]) as u64 != AF_INET {
+        return Err(Errno::EAFNOSUPPORT);
+    }
+    let port = u16::from_be_bytes([sockaddr[2], sockaddr[3]]);
+    let ip = [sockaddr[4], sockaddr[5], sockaddr[6], sockaddr[7]];
+
+    file.connect(ip, port)?;
+    Ok(0)
 }
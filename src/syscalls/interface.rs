@@ -1,5 +1,85 @@
+use crate::scheduler::task::TaskContext;
 use crate::vga;
+use core::arch::asm;
+
+pub const SYSCALL_VECTOR: u8 = 0x80;
+
+const MSR_EFER: u32 = 0xC000_0080;
+const MSR_STAR: u32 = 0xC000_0081;
+const MSR_LSTAR: u32 = 0xC000_0082;
+const MSR_SFMASK: u32 = 0xC000_0084;
+const MSR_KERNEL_GS_BASE: u32 = 0xC000_0102;
+
+const EFER_SCE: u64 = 1 << 0;
+const RFLAGS_TF: u64 = 1 << 8;
+const RFLAGS_IF: u64 = 1 << 9;
+const RFLAGS_DF: u64 = 1 << 10;
+const RFLAGS_AC: u64 = 1 << 18;
+
+extern "C" {
+    fn syscall_entry();
+}
 
 pub fn init() {
-    vga::print!("System call interface initialized\n");
+    init_cpu(0);
+    vga::print!("System call interface initialized (SYSCALL and int 0x{:x})\n", SYSCALL_VECTOR);
+    vga::print!("SMEP {}, SMAP {}\n",
+        if super::user::smep_enabled() { "enabled" } else { "unsupported" },
+        if super::user::smap_enabled() { "enabled" } else { "unsupported" });
+}
+
+pub fn init_cpu(cpu: u32) {
+    let star = ((crate::gdt::KERNEL_DATA_SELECTOR as u64 | 3) << 48)
+        | ((crate::gdt::KERNEL_CODE_SELECTOR as u64) << 32);
+
+    write_msr(MSR_EFER, read_msr(MSR_EFER) | EFER_SCE);
+    write_msr(MSR_STAR, star);
+    write_msr(MSR_LSTAR, syscall_entry as u64);
+    write_msr(MSR_SFMASK, RFLAGS_TF | RFLAGS_IF | RFLAGS_DF | RFLAGS_AC);
+    write_msr(MSR_KERNEL_GS_BASE, crate::gdt::cpu_local(cpu));
+    super::user::init_cpu();
+}
+
+#[no_mangle]
+extern "C" fn syscall_dispatch(context: &mut TaskContext) -> bool {
+    let (number, arg1, arg2, arg3, arg4, arg5, arg6) =
+        (context.rax, context.rdi, context.rsi, context.rdx, context.r10, context.r8, context.r9);
+
+    crate::interrupts::enable_interrupts();
+    let sigreturn = super::is_sigreturn(number);
+    if sigreturn {
+        crate::process::signal::sigreturn(context);
+    } else {
+        context.rax = super::handle_syscall(number, arg1, arg2, arg3, arg4, arg5, arg6);
+    }
+    crate::process::signal::deliver_pending(context);
+    crate::interrupts::disable_interrupts();
+
+    sigreturn
+}
+
+fn read_msr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    unsafe {
+        asm!(
+            "rdmsr",
+            in("ecx") msr,
+            out("eax") low,
+            out("edx") high,
+        );
+    }
+    ((high as u64) << 32) | (low as u64)
+}
+
+fn write_msr(msr: u32, value: u64) {
+    let low = value as u32;
+    let high = (value >> 32) as u32;
+    unsafe {
+        asm!(
+            "wrmsr",
+            in("ecx") msr,
+            in("eax") low,
+            in("edx") high,
+        );
+    }
 }
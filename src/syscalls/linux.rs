@@ -0,0 +1,421 @@
+use super::errno::{encode, Errno, SyscallResult};
+use super::{handlers, user};
+use crate::loader::USER_SPACE_END;
+use crate::process::files::FileKind;
+use crate::vga;
+use alloc::format;
+
+pub const MAX_SYSCALLS: usize = 512;
+
+pub const SYS_READ: usize = 0;
+pub const SYS_WRITE: usize = 1;
+pub const SYS_CLOSE: usize = 3;
+pub const SYS_POLL: usize = 7;
+pub const SYS_MMAP: usize = 9;
+pub const SYS_MPROTECT: usize = 10;
+pub const SYS_MUNMAP: usize = 11;
+pub const SYS_BRK: usize = 12;
+pub const SYS_RT_SIGACTION: usize = 13;
+pub const SYS_RT_SIGPROCMASK: usize = 14;
+pub const SYS_RT_SIGRETURN: usize = 15;
+pub const SYS_IOCTL: usize = 16;
+pub const SYS_WRITEV: usize = 20;
+pub const SYS_PIPE: usize = 22;
+pub const SYS_MREMAP: usize = 25;
+pub const SYS_GETPID: usize = 39;
+pub const SYS_EXIT: usize = 60;
+pub const SYS_KILL: usize = 62;
+pub const SYS_UNAME: usize = 63;
+pub const SYS_FTRUNCATE: usize = 77;
+pub const SYS_UNLINK: usize = 87;
+pub const SYS_MKNOD: usize = 133;
+pub const SYS_ARCH_PRCTL: usize = 158;
+pub const SYS_TKILL: usize = 200;
+pub const SYS_FUTEX: usize = 202;
+pub const SYS_SET_TID_ADDRESS: usize = 218;
+pub const SYS_CLOCK_GETTIME: usize = 228;
+pub const SYS_EXIT_GROUP: usize = 231;
+pub const SYS_TGKILL: usize = 234;
+pub const SYS_OPENAT: usize = 257;
+pub const SYS_PIPE2: usize = 293;
+pub const SYS_PKEY_MPROTECT: usize = 329;
+pub const SYS_PKEY_ALLOC: usize = 330;
+pub const SYS_PKEY_FREE: usize = 331;
+
+pub const AT_FDCWD: i64 = -100;
+pub const IOV_MAX: u64 = 1024;
+
+const TCGETS: u64 = 0x5401;
+const TIOCGWINSZ: u64 = 0x5413;
+
+const ARCH_SET_FS: u64 = 0x1002;
+const ARCH_GET_FS: u64 = 0x1003;
+
+const CLOCK_REALTIME: u64 = 0;
+const CLOCK_MONOTONIC: u64 = 1;
+const CLOCK_MONOTONIC_RAW: u64 = 4;
+const CLOCK_REALTIME_COARSE: u64 = 5;
+const CLOCK_MONOTONIC_COARSE: u64 = 6;
+const CLOCK_BOOTTIME: u64 = 7;
+
+const UTSNAME_LENGTH: usize = 65;
+
+const S_IFMT: u64 = 0o170000;
+const S_IFIFO: u64 = 0o010000;
+const O_CLOEXEC: u64 = 0x80000;
+
+const SIGSET_SIZE: u64 = 8;
+
+pub type LinuxSystemCallHandler = fn(u64, u64, u64, u64, u64, u64) -> u64;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Timespec {
+    seconds: i64,
+    nanoseconds: i64,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct IoVec {
+    base: u64,
+    length: u64,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Termios {
+    input_flags: u32,
+    output_flags: u32,
+    control_flags: u32,
+    local_flags: u32,
+    line: u8,
+    control_characters: [u8; 19],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct WindowSize {
+    rows: u16,
+    columns: u16,
+    x_pixels: u16,
+    y_pixels: u16,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Utsname {
+    sysname: [u8; UTSNAME_LENGTH],
+    nodename: [u8; UTSNAME_LENGTH],
+    release: [u8; UTSNAME_LENGTH],
+    version: [u8; UTSNAME_LENGTH],
+    machine: [u8; UTSNAME_LENGTH],
+    domainname: [u8; UTSNAME_LENGTH],
+}
+
+pub fn register_handlers(table: &mut [Option<LinuxSystemCallHandler>; MAX_SYSCALLS]) {
+    table[SYS_READ] = Some(linux_read);
+    table[SYS_WRITE] = Some(linux_write);
+    table[SYS_CLOSE] = Some(linux_close);
+    table[SYS_POLL] = Some(linux_poll);
+    table[SYS_MMAP] = Some(linux_mmap);
+    table[SYS_MPROTECT] = Some(linux_mprotect);
+    table[SYS_MUNMAP] = Some(linux_munmap);
+    table[SYS_BRK] = Some(linux_brk);
+    table[SYS_RT_SIGACTION] = Some(linux_rt_sigaction);
+    table[SYS_RT_SIGPROCMASK] = Some(linux_rt_sigprocmask);
+    table[SYS_IOCTL] = Some(linux_ioctl);
+    table[SYS_WRITEV] = Some(linux_writev);
+    table[SYS_PIPE] = Some(linux_pipe);
+    table[SYS_MREMAP] = Some(linux_mremap);
+    table[SYS_GETPID] = Some(linux_getpid);
+    table[SYS_EXIT] = Some(linux_exit);
+    table[SYS_KILL] = Some(linux_kill);
+    table[SYS_UNAME] = Some(linux_uname);
+    table[SYS_FTRUNCATE] = Some(linux_ftruncate);
+    table[SYS_UNLINK] = Some(linux_unlink);
+    table[SYS_MKNOD] = Some(linux_mknod);
+    table[SYS_ARCH_PRCTL] = Some(linux_arch_prctl);
+    table[SYS_TKILL] = Some(linux_tkill);
+    table[SYS_FUTEX] = Some(linux_futex);
+    table[SYS_SET_TID_ADDRESS] = Some(linux_set_tid_address);
+    table[SYS_CLOCK_GETTIME] = Some(linux_clock_gettime);
+    table[SYS_EXIT_GROUP] = Some(linux_exit);
+    table[SYS_TGKILL] = Some(linux_tgkill);
+    table[SYS_OPENAT] = Some(linux_openat);
+    table[SYS_PIPE2] = Some(linux_pipe2);
+    table[SYS_PKEY_MPROTECT] = Some(linux_pkey_mprotect);
+    table[SYS_PKEY_ALLOC] = Some(linux_pkey_alloc);
+    table[SYS_PKEY_FREE] = Some(linux_pkey_free);
+
+    let count = table.iter().filter(|handler| handler.is_some()).count();
+    vga::print!("Registered {} Linux system call handlers\n", count);
+}
+
+fn linux_read(fd: u64, buf: u64, count: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_read(fd, buf, count, 0, 0)
+}
+
+fn linux_write(fd: u64, buf: u64, count: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_write(fd, buf, count, 0, 0)
+}
+
+fn linux_close(fd: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_close(fd, 0, 0, 0, 0)
+}
+
+fn linux_poll(fds: u64, nfds: u64, timeout: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_poll(fds, nfds, timeout, 0, 0)
+}
+
+fn linux_mmap(addr: u64, length: u64, prot: u64, flags: u64, fd: u64, offset: u64) -> u64 {
+    encode(crate::process::memory::map(addr, length, prot, flags, fd, offset))
+}
+
+fn linux_mprotect(addr: u64, length: u64, prot: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(crate::process::memory::protect(addr, length, prot).map(|_| 0))
+}
+
+fn linux_munmap(addr: u64, length: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(crate::process::memory::unmap(addr, length).map(|_| 0))
+}
+
+fn linux_brk(address: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    crate::process::memory::brk(address)
+}
+
+fn linux_rt_sigaction(signo: u64, action: u64, old_action: u64, sigsetsize: u64, _arg5: u64, _arg6: u64) -> u64 {
+    if sigsetsize != SIGSET_SIZE {
+        return Errno::EINVAL.to_return_value();
+    }
+    handlers::sys_sigaction(signo, action, old_action, 0, 0)
+}
+
+fn linux_rt_sigprocmask(how: u64, set: u64, old_set: u64, sigsetsize: u64, _arg5: u64, _arg6: u64) -> u64 {
+    if sigsetsize != SIGSET_SIZE {
+        return Errno::EINVAL.to_return_value();
+    }
+    handlers::sys_sigprocmask(how, set, old_set, 0, 0)
+}
+
+fn linux_ioctl(fd: u64, request: u64, argument: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(ioctl(fd, request, argument))
+}
+
+fn linux_writev(fd: u64, iov: u64, iovcnt: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(writev(fd, iov, iovcnt))
+}
+
+fn linux_pipe(fds: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_pipe(fds, 0, 0, 0, 0)
+}
+
+fn linux_mremap(addr: u64, old_length: u64, new_length: u64, flags: u64, _new_address: u64, _arg6: u64) -> u64 {
+    encode(crate::process::memory::remap(addr, old_length, new_length, flags))
+}
+
+fn linux_getpid(_arg1: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(crate::process::current_pid().map(|pid| pid as u64).ok_or(Errno::ESRCH))
+}
+
+fn linux_exit(status: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    crate::process::exit((status & 0xFF) as i32)
+}
+
+fn linux_kill(pid: u64, signo: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_kill(pid, signo, 0, 0, 0)
+}
+
+fn linux_uname(buf: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(uname(buf))
+}
+
+fn linux_ftruncate(fd: u64, length: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_ftruncate(fd, length, 0, 0, 0)
+}
+
+fn linux_unlink(pathname: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(user::read_string(pathname).and_then(|path| handlers::unlink_path(&path)))
+}
+
+fn linux_mknod(pathname: u64, mode: u64, _dev: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    if mode & S_IFMT != S_IFIFO {
+        return Errno::EPERM.to_return_value();
+    }
+    handlers::sys_mkfifo(pathname, mode, 0, 0, 0)
+}
+
+fn linux_arch_prctl(code: u64, address: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(arch_prctl(code, address))
+}
+
+fn linux_tkill(tid: u64, signo: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    if tid as i64 <= 0 {
+        return Errno::EINVAL.to_return_value();
+    }
+    handlers::sys_kill(tid, signo, 0, 0, 0)
+}
+
+fn linux_set_tid_address(_tidptr: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    linux_getpid(0, 0, 0, 0, 0, 0)
+}
+
+fn linux_clock_gettime(clock: u64, timespec: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(clock_gettime(clock, timespec))
+}
+
+fn linux_futex(addr: u64, op: u64, value: u64, timeout: u64, _addr2: u64, _value3: u64) -> u64 {
+    handlers::sys_futex(addr, op, value, timeout, 0)
+}
+
+fn linux_tgkill(tgid: u64, tid: u64, signo: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    if tgid != tid {
+        return Errno::ESRCH.to_return_value();
+    }
+    linux_tkill(tid, signo, 0, 0, 0, 0)
+}
+
+fn linux_openat(dirfd: u64, pathname: u64, flags: u64, _mode: u64, _arg5: u64, _arg6: u64) -> u64 {
+    encode(openat(dirfd as i64, pathname, flags))
+}
+
+fn linux_pipe2(fds: u64, flags: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    if flags & !O_CLOEXEC != 0 {
+        return Errno::EINVAL.to_return_value();
+    }
+    handlers::sys_pipe(fds, 0, 0, 0, 0)
+}
+
+fn linux_pkey_mprotect(addr: u64, length: u64, prot: u64, pkey: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_pkey_mprotect(addr, length, prot, pkey, 0)
+}
+
+fn linux_pkey_alloc(flags: u64, rights: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_pkey_alloc(flags, rights, 0, 0, 0)
+}
+
+fn linux_pkey_free(pkey: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64, _arg6: u64) -> u64 {
+    handlers::sys_pkey_free(pkey, 0, 0, 0, 0)
+}
+
+fn ioctl(fd: u64, request: u64, argument: u64) -> SyscallResult {
+    let file = crate::process::file(fd as usize)?;
+    if file.kind() != FileKind::Console {
+        return Err(Errno::ENOTTY);
+    }
+
+    match request {
+        TCGETS => {
+            let termios = Termios {
+                input_flags: 0x0100,
+                output_flags: 0x0005,
+                control_flags: 0x00BF,
+                local_flags: 0x803B,
+                line: 0,
+                control_characters: [0; 19],
+            };
+            user::write_value(argument, termios)?;
+            Ok(0)
+        }
+        TIOCGWINSZ => {
+            let size = WindowSize {
+                rows: vga::VGA_HEIGHT as u16,
+                columns: vga::VGA_WIDTH as u16,
+                x_pixels: 0,
+                y_pixels: 0,
+            };
+            user::write_value(argument, size)?;
+            Ok(0)
+        }
+        _ => Err(Errno::ENOTTY),
+    }
+}
+
+fn writev(fd: u64, iov: u64, iovcnt: u64) -> SyscallResult {
+    if iovcnt > IOV_MAX {
+        return Err(Errno::EINVAL);
+    }
+
+    let mut total = 0;
+    for index in 0..iovcnt {
+        let vector: IoVec = user::read_value(iov + index * core::mem::size_of::<IoVec>() as u64)?;
+        if vector.length == 0 {
+            continue;
+        }
+
+        match handlers::write(fd, vector.base, vector.length) {
+            Ok(written) => {
+                total += written;
+                if written < vector.length {
+                    break;
+                }
+            }
+            Err(err) if total == 0 => return Err(err),
+            Err(_) => break,
+        }
+    }
+    Ok(total)
+}
+
+fn uname(buf: u64) -> SyscallResult {
+    let field = |text: &str| {
+        let mut bytes = [0u8; UTSNAME_LENGTH];
+        let length = text.len().min(UTSNAME_LENGTH - 1);
+        bytes[..length].copy_from_slice(&text.as_bytes()[..length]);
+        bytes
+    };
+
+    let name = Utsname {
+        sysname: field("Linux"),
+        nodename: field("crate"),
+        release: field("5.15.0-crate"),
+        version: field("#1 SMP"),
+        machine: field("x86_64"),
+        domainname: field("(none)"),
+    };
+    user::write_value(buf, name)?;
+    Ok(0)
+}
+
+fn arch_prctl(code: u64, address: u64) -> SyscallResult {
+    match code {
+        ARCH_SET_FS => {
+            if address >= USER_SPACE_END {
+                return Err(Errno::EPERM);
+            }
+            crate::scheduler::set_fs_base(address);
+            Ok(0)
+        }
+        ARCH_GET_FS => {
+            user::write_value(address, crate::scheduler::fs_base())?;
+            Ok(0)
+        }
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+fn clock_gettime(clock: u64, timespec: u64) -> SyscallResult {
+    let nanoseconds = match clock {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => crate::time::wall_clock_ns(),
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => crate::time::monotonic_ns(),
+        _ => return Err(Errno::EINVAL),
+    };
+
+    let value = Timespec {
+        seconds: (nanoseconds / 1_000_000_000) as i64,
+        nanoseconds: (nanoseconds % 1_000_000_000) as i64,
+    };
+    user::write_value(timespec, value)?;
+    Ok(0)
+}
+
+fn openat(dirfd: i64, pathname: u64, flags: u64) -> SyscallResult {
+    let path = user::read_string(pathname)?;
+    if path.starts_with('/') {
+        return handlers::open_path(&path, flags);
+    }
+    if dirfd != AT_FDCWD {
+        return Err(Errno::ENOTDIR);
+    }
+    handlers::open_path(&format!("/{}", path), flags)
+}
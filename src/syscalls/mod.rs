@@ -1,16 +1,28 @@
+use crate::process::Personality;
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod interface;
 pub mod handlers;
+pub mod errno;
+pub mod user;
+pub mod linux;
+pub mod trace;
+
+pub type SystemCallHandler = fn(u64, u64, u64, u64, u64) -> u64;
+
+pub const SYS_SIGRETURN: u64 = 32;
 
 pub struct SystemCallManager {
-    pub handlers: [Option<fn(u64, u64, u64, u64, u64) -> u64>; 256],
+    pub handlers: [Option<SystemCallHandler>; 256],
+    pub linux_handlers: [Option<linux::LinuxSystemCallHandler>; linux::MAX_SYSCALLS],
 }
 
 impl SystemCallManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             handlers: [None; 256],
+            linux_handlers: [None; linux::MAX_SYSCALLS],
         }
     }
     
@@ -36,35 +48,107 @@ impl SystemCallManager {
         self.handlers[7] = Some(handlers::sys_fork);
         self.handlers[8] = Some(handlers::sys_exec);
         self.handlers[9] = Some(handlers::sys_wait);
+        self.handlers[10] = Some(handlers::sys_socket);
+        self.handlers[11] = Some(handlers::sys_connect);
+        self.handlers[12] = Some(handlers::sys_personality);
+        self.handlers[13] = Some(handlers::sys_trace);
+        self.handlers[14] = Some(handlers::sys_mprotect);
+        self.handlers[15] = Some(handlers::sys_mremap);
+        self.handlers[16] = Some(handlers::sys_pkey_alloc);
+        self.handlers[17] = Some(handlers::sys_pkey_free);
+        self.handlers[18] = Some(handlers::sys_pkey_mprotect);
+        self.handlers[19] = Some(handlers::sys_shm_open);
+        self.handlers[20] = Some(handlers::sys_shm_unlink);
+        self.handlers[21] = Some(handlers::sys_ftruncate);
+        self.handlers[22] = Some(handlers::sys_futex);
+        self.handlers[23] = Some(handlers::sys_pipe);
+        self.handlers[24] = Some(handlers::sys_mkfifo);
+        self.handlers[25] = Some(handlers::sys_channel);
+        self.handlers[26] = Some(handlers::sys_port_create);
+        self.handlers[27] = Some(handlers::sys_port_connect);
+        self.handlers[28] = Some(handlers::sys_poll);
+        self.handlers[29] = Some(handlers::sys_kill);
+        self.handlers[30] = Some(handlers::sys_sigaction);
+        self.handlers[31] = Some(handlers::sys_sigprocmask);
         
-        vga::print!("Registered {} system call handlers\n", 10);
+        vga::print!("Registered {} system call handlers\n", 32);
+        
+        linux::register_handlers(&mut self.linux_handlers);
     }
     
-    pub fn handle_syscall(&self, syscall_num: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64) -> u64 {
-        if syscall_num < 256 {
-            if let Some(handler) = self.handlers[syscall_num as usize] {
-                handler(arg1, arg2, arg3, arg4, arg5)
-            } else {
-                vga::print!("Unknown system call: {}\n", syscall_num);
-                0xFFFFFFFFFFFFFFFF
-            }
-        } else {
-            vga::print!("Invalid system call number: {}\n", syscall_num);
-            0xFFFFFFFFFFFFFFFF
+    pub fn get_handler(&self, syscall_num: u64) -> Result<SystemCallHandler, SystemCallLookupError> {
+        if syscall_num >= 256 {
+            return Err(SystemCallLookupError::Invalid);
         }
+        
+        self.handlers[syscall_num as usize].ok_or(SystemCallLookupError::Unknown)
     }
+    
+    pub fn get_linux_handler(&self, syscall_num: u64) -> Result<linux::LinuxSystemCallHandler, SystemCallLookupError> {
+        if syscall_num >= linux::MAX_SYSCALLS as u64 {
+            return Err(SystemCallLookupError::Invalid);
+        }
+        
+        self.linux_handlers[syscall_num as usize].ok_or(SystemCallLookupError::Unknown)
+    }
+}
+
+pub enum SystemCallLookupError {
+    Invalid,
+    Unknown,
 }
 
-pub static mut SYSCALL_MANAGER: SystemCallManager = SystemCallManager::new();
+pub static SYSCALL_MANAGER: SpinLock<SystemCallManager> = SpinLock::new(SystemCallManager::new());
 
 pub fn init() {
-    unsafe {
-        SYSCALL_MANAGER.init();
+    SYSCALL_MANAGER.lock().init();
+}
+
+pub fn is_sigreturn(syscall_num: u64) -> bool {
+    match crate::process::current_syscall_profile().1 {
+        Personality::Native => syscall_num == SYS_SIGRETURN,
+        Personality::Linux => syscall_num == linux::SYS_RT_SIGRETURN as u64,
     }
 }
 
-pub fn handle_syscall(syscall_num: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64) -> u64 {
-    unsafe {
-        SYSCALL_MANAGER.handle_syscall(syscall_num, arg1, arg2, arg3, arg4, arg5)
+pub fn handle_syscall(syscall_num: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64, arg6: u64) -> u64 {
+    let (pid, personality, traced) = crate::process::current_syscall_profile();
+    let start = crate::time::monotonic_ns();
+    
+    let result = match personality {
+        Personality::Native => handle_native_syscall(syscall_num, arg1, arg2, arg3, arg4, arg5),
+        Personality::Linux => handle_linux_syscall(syscall_num, arg1, arg2, arg3, arg4, arg5, arg6),
+    };
+    
+    let latency = crate::time::monotonic_ns() - start;
+    trace::record(pid, personality, traced, syscall_num, [arg1, arg2, arg3, arg4, arg5, arg6], result, latency);
+    result
+}
+
+fn handle_native_syscall(syscall_num: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64) -> u64 {
+    let handler = SYSCALL_MANAGER.lock().get_handler(syscall_num);
+    
+    match handler {
+        Ok(handler) => handler(arg1, arg2, arg3, arg4, arg5),
+        Err(SystemCallLookupError::Unknown) => {
+            vga::print!("Unknown system call: {}\n", syscall_num);
+            errno::Errno::ENOSYS.to_return_value()
+        }
+        Err(SystemCallLookupError::Invalid) => {
+            vga::print!("Invalid system call number: {}\n", syscall_num);
+            errno::Errno::ENOSYS.to_return_value()
+        }
+    }
+}
+
+fn handle_linux_syscall(syscall_num: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64, arg6: u64) -> u64 {
+    let handler = SYSCALL_MANAGER.lock().get_linux_handler(syscall_num);
+    
+    match handler {
+        Ok(handler) => handler(arg1, arg2, arg3, arg4, arg5, arg6),
+        Err(_) => {
+            vga::print!("Unsupported Linux system call: {}\n", syscall_num);
+            errno::Errno::ENOSYS.to_return_value()
+        }
     }
 }
@@ -0,0 +1,466 @@
+use super::errno::Errno;
+use super::linux;
+use crate::process::Personality;
+use crate::smp::synchronization::SpinLock;
+use crate::vga;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+pub const TRACE_BUFFER_SIZE: usize = 256;
+pub const HISTOGRAM_BUCKETS: usize = 16;
+pub const NATIVE_SYSCALLS: usize = 256;
+
+const MAX_ERRNO: i64 = 4095;
+
+static GLOBAL_TRACING: AtomicBool = AtomicBool::new(false);
+static STATISTICS: AtomicBool = AtomicBool::new(true);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArgumentKind {
+    Int,
+    Fd,
+    Pointer,
+    Size,
+    Flags,
+}
+
+#[derive(Clone, Copy)]
+pub struct TraceRecord {
+    pub timestamp_ns: u64,
+    pub pid: Option<usize>,
+    pub personality: Personality,
+    pub number: u64,
+    pub arguments: [u64; 6],
+    pub result: u64,
+    pub latency_ns: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct SyscallStats {
+    pub count: u64,
+    pub errors: u64,
+    pub total_ns: u64,
+    pub max_ns: u64,
+    pub histogram: [u64; HISTOGRAM_BUCKETS],
+}
+
+pub struct SyscallCounters {
+    count: AtomicU64,
+    errors: AtomicU64,
+    total_ns: AtomicU64,
+    max_ns: AtomicU64,
+    histogram: [AtomicU64; HISTOGRAM_BUCKETS],
+}
+
+const NO_CALLS: AtomicU64 = AtomicU64::new(0);
+const NO_COUNTERS: SyscallCounters = SyscallCounters::new();
+
+impl SyscallCounters {
+    pub const fn new() -> Self {
+        Self {
+            count: NO_CALLS,
+            errors: NO_CALLS,
+            total_ns: NO_CALLS,
+            max_ns: NO_CALLS,
+            histogram: [NO_CALLS; HISTOGRAM_BUCKETS],
+        }
+    }
+
+    fn add(&self, latency_ns: u64, failed: bool) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.total_ns.fetch_add(latency_ns, Ordering::Relaxed);
+        self.max_ns.fetch_max(latency_ns, Ordering::Relaxed);
+        self.histogram[histogram_bucket(latency_ns)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> SyscallStats {
+        let mut histogram = [0; HISTOGRAM_BUCKETS];
+        for (bucket, counter) in histogram.iter_mut().zip(&self.histogram) {
+            *bucket = counter.load(Ordering::Relaxed);
+        }
+        SyscallStats {
+            count: self.count.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            total_ns: self.total_ns.load(Ordering::Relaxed),
+            max_ns: self.max_ns.load(Ordering::Relaxed),
+            histogram,
+        }
+    }
+
+    fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+        self.total_ns.store(0, Ordering::Relaxed);
+        self.max_ns.store(0, Ordering::Relaxed);
+        for counter in &self.histogram {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+static NATIVE_STATS: [SyscallCounters; NATIVE_SYSCALLS] = [NO_COUNTERS; NATIVE_SYSCALLS];
+static LINUX_STATS: [SyscallCounters; linux::MAX_SYSCALLS] = [NO_COUNTERS; linux::MAX_SYSCALLS];
+
+pub struct SyscallTracer {
+    records: [Option<TraceRecord>; TRACE_BUFFER_SIZE],
+    next_record: usize,
+    dropped: u64,
+}
+
+impl SyscallTracer {
+    pub const fn new() -> Self {
+        Self {
+            records: [None; TRACE_BUFFER_SIZE],
+            next_record: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn push(&mut self, record: TraceRecord) {
+        let slot = self.next_record % TRACE_BUFFER_SIZE;
+        if self.records[slot].is_some() {
+            self.dropped += 1;
+        }
+        self.records[slot] = Some(record);
+        self.next_record += 1;
+    }
+
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &TraceRecord> {
+        let available = self.next_record.min(TRACE_BUFFER_SIZE);
+        let count = count.min(available);
+        let first = self.next_record - count;
+        (first..self.next_record).filter_map(move |index| self.records[index % TRACE_BUFFER_SIZE].as_ref())
+    }
+
+    pub fn clear(&mut self) {
+        self.records = [None; TRACE_BUFFER_SIZE];
+        self.next_record = 0;
+        self.dropped = 0;
+    }
+}
+
+pub static SYSCALL_TRACER: SpinLock<SyscallTracer> = SpinLock::new(SyscallTracer::new());
+
+pub fn set_global_tracing(enabled: bool) {
+    GLOBAL_TRACING.store(enabled, Ordering::Relaxed);
+    vga::print!("Global syscall tracing {}\n", if enabled { "enabled" } else { "disabled" });
+}
+
+pub fn global_tracing() -> bool {
+    GLOBAL_TRACING.load(Ordering::Relaxed)
+}
+
+pub fn set_statistics(enabled: bool) {
+    STATISTICS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn statistics() -> bool {
+    STATISTICS.load(Ordering::Relaxed)
+}
+
+pub fn record(pid: Option<usize>, personality: Personality, traced: bool, number: u64, arguments: [u64; 6], result: u64, latency_ns: u64) {
+    if statistics() {
+        account(personality, number, result, latency_ns);
+    }
+
+    // Only traced calls touch the ring buffer lock; everything else stays on
+    // the per-syscall atomic counters.
+    if traced || global_tracing() {
+        SYSCALL_TRACER.lock().push(TraceRecord {
+            timestamp_ns: crate::time::monotonic_ns(),
+            pid,
+            personality,
+            number,
+            arguments,
+            result,
+            latency_ns,
+        });
+    }
+}
+
+pub fn account(personality: Personality, number: u64, result: u64, latency_ns: u64) {
+    if let Some(counters) = counters(personality, number) {
+        counters.add(latency_ns, is_error(result));
+    }
+}
+
+pub fn stats(personality: Personality, number: u64) -> Option<SyscallStats> {
+    counters(personality, number).map(SyscallCounters::snapshot)
+}
+
+pub fn clear() {
+    SYSCALL_TRACER.lock().clear();
+    for counters in NATIVE_STATS.iter().chain(LINUX_STATS.iter()) {
+        counters.reset();
+    }
+}
+
+fn counters(personality: Personality, number: u64) -> Option<&'static SyscallCounters> {
+    match personality {
+        Personality::Native => NATIVE_STATS.get(number as usize),
+        Personality::Linux => LINUX_STATS.get(number as usize),
+    }
+}
+
+pub fn name(personality: Personality, number: u64) -> Option<&'static str> {
+    signature(personality, number).map(|(name, _)| name)
+}
+
+pub fn print_trace(count: usize) {
+    let tracer = SYSCALL_TRACER.lock();
+    vga::print!("Syscall trace (last {}, {} overwritten):\n", count.min(tracer.next_record.min(TRACE_BUFFER_SIZE)), tracer.dropped);
+    for record in tracer.recent(count) {
+        print_record(record);
+    }
+}
+
+pub fn print_stats() {
+    vga::print!("Syscall statistics:\n");
+    for (personality, table) in [(Personality::Native, &NATIVE_STATS[..]), (Personality::Linux, &LINUX_STATS[..])] {
+        for (number, counters) in table.iter().enumerate() {
+            let stats = counters.snapshot();
+            if stats.count == 0 {
+                continue;
+            }
+
+            vga::print!("  {:?} ", personality);
+            print_name(personality, number as u64);
+            vga::print!(": {} calls, {} errors, avg {} ns, max {} ns\n",
+                stats.count, stats.errors, stats.total_ns / stats.count, stats.max_ns);
+            print_histogram(&stats.histogram);
+        }
+    }
+}
+
+fn print_record(record: &TraceRecord) {
+    match record.pid {
+        Some(pid) => vga::print!("[{}] ", pid),
+        None => vga::print!("[kernel] "),
+    }
+    print_name(record.personality, record.number);
+
+    vga::print!("(");
+    let kinds = signature(record.personality, record.number).map_or(&[][..], |(_, kinds)| kinds);
+    for (index, kind) in kinds.iter().enumerate() {
+        if index > 0 {
+            vga::print!(", ");
+        }
+        let value = record.arguments[index];
+        match kind {
+            ArgumentKind::Int | ArgumentKind::Fd => vga::print!("{}", value as i64),
+            ArgumentKind::Size => vga::print!("{}", value),
+            ArgumentKind::Pointer if value == 0 => vga::print!("NULL"),
+            ArgumentKind::Pointer | ArgumentKind::Flags => vga::print!("0x{:x}", value),
+        }
+    }
+    vga::print!(") = ");
+
+    if is_error(record.result) {
+        let code = -(record.result as i64) as i32;
+        match Errno::from_code(code) {
+            Some(errno) => vga::print!("-1 {}", errno.name()),
+            None => vga::print!("-1 errno {}", code),
+        }
+    } else if record.result > u32::MAX as u64 {
+        vga::print!("0x{:x}", record.result);
+    } else {
+        vga::print!("{}", record.result);
+    }
+    vga::print!(" <{} ns>\n", record.latency_ns);
+}
+
+fn print_name(personality: Personality, number: u64) {
+    match name(personality, number) {
+        Some(name) => vga::print!("{}", name),
+        None => vga::print!("syscall_{}", number),
+    }
+}
+
+fn print_histogram(histogram: &[u64; HISTOGRAM_BUCKETS]) {
+    vga::print!("   ");
+    for (bucket, &count) in histogram.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        if bucket == 0 {
+            vga::print!(" <1us:{}", count);
+        } else {
+            vga::print!(" {}us:{}", 1u64 << (bucket - 1), count);
+        }
+    }
+    vga::print!("\n");
+}
+
+fn histogram_bucket(latency_ns: u64) -> usize {
+    let microseconds = latency_ns / 1000;
+    ((64 - microseconds.leading_zeros()) as usize).min(HISTOGRAM_BUCKETS - 1)
+}
+
+fn is_error(result: u64) -> bool {
+    let value = result as i64;
+    value < 0 && value >= -MAX_ERRNO
+}
+
+fn signature(personality: Personality, number: u64) -> Option<(&'static str, &'static [ArgumentKind])> {
+    use ArgumentKind::*;
+
+    match personality {
+        Personality::Native => match number {
+            0 => Some(("exit", &[Int])),
+            1 => Some(("read", &[Fd, Pointer, Size])),
+            2 => Some(("write", &[Fd, Pointer, Size])),
+            3 => Some(("open", &[Pointer, Flags, Flags])),
+            4 => Some(("close", &[Fd])),
+            5 => Some(("mmap", &[Pointer, Size, Flags, Flags, Fd])),
+            6 => Some(("munmap", &[Pointer, Size])),
+            7 => Some(("fork", &[])),
+            8 => Some(("exec", &[Pointer, Pointer, Pointer])),
+            9 => Some(("wait", &[Int, Pointer, Flags])),
+            10 => Some(("socket", &[Int, Int, Int])),
+            11 => Some(("connect", &[Fd, Pointer, Size])),
+            12 => Some(("personality", &[Int])),
+            13 => Some(("trace", &[Int, Int])),
+            14 => Some(("mprotect", &[Pointer, Size, Flags])),
+            15 => Some(("mremap", &[Pointer, Size, Size, Flags])),
+            16 => Some(("pkey_alloc", &[Flags, Flags])),
+            17 => Some(("pkey_free", &[Int])),
+            18 => Some(("pkey_mprotect", &[Pointer, Size, Flags, Int])),
+            19 => Some(("shm_open", &[Pointer, Flags, Flags])),
+            20 => Some(("shm_unlink", &[Pointer])),
+            21 => Some(("ftruncate", &[Fd, Size])),
+            22 => Some(("futex", &[Pointer, Flags, Int, Pointer])),
+            23 => Some(("pipe", &[Pointer])),
+            24 => Some(("mkfifo", &[Pointer, Flags])),
+            25 => Some(("channel", &[Pointer, Size])),
+            26 => Some(("port_create", &[Pointer, Size])),
+            27 => Some(("port_connect", &[Pointer])),
+            28 => Some(("poll", &[Pointer, Size, Int])),
+            29 => Some(("kill", &[Int, Int])),
+            30 => Some(("sigaction", &[Int, Pointer, Pointer])),
+            31 => Some(("sigprocmask", &[Int, Pointer, Pointer])),
+            _ => None,
+        },
+        Personality::Linux => match number as usize {
+            linux::SYS_READ => Some(("read", &[Fd, Pointer, Size])),
+            linux::SYS_WRITE => Some(("write", &[Fd, Pointer, Size])),
+            linux::SYS_CLOSE => Some(("close", &[Fd])),
+            linux::SYS_POLL => Some(("poll", &[Pointer, Size, Int])),
+            linux::SYS_MMAP => Some(("mmap", &[Pointer, Size, Flags, Flags, Fd, Size])),
+            linux::SYS_MPROTECT => Some(("mprotect", &[Pointer, Size, Flags])),
+            linux::SYS_MUNMAP => Some(("munmap", &[Pointer, Size])),
+            linux::SYS_BRK => Some(("brk", &[Pointer])),
+            linux::SYS_RT_SIGACTION => Some(("rt_sigaction", &[Int, Pointer, Pointer, Size])),
+            linux::SYS_RT_SIGPROCMASK => Some(("rt_sigprocmask", &[Int, Pointer, Pointer, Size])),
+            linux::SYS_IOCTL => Some(("ioctl", &[Fd, Flags, Pointer])),
+            linux::SYS_WRITEV => Some(("writev", &[Fd, Pointer, Int])),
+            linux::SYS_PIPE => Some(("pipe", &[Pointer])),
+            linux::SYS_MREMAP => Some(("mremap", &[Pointer, Size, Size, Flags, Pointer])),
+            linux::SYS_GETPID => Some(("getpid", &[])),
+            linux::SYS_EXIT => Some(("exit", &[Int])),
+            linux::SYS_KILL => Some(("kill", &[Int, Int])),
+            linux::SYS_UNAME => Some(("uname", &[Pointer])),
+            linux::SYS_FTRUNCATE => Some(("ftruncate", &[Fd, Size])),
+            linux::SYS_UNLINK => Some(("unlink", &[Pointer])),
+            linux::SYS_MKNOD => Some(("mknod", &[Pointer, Flags, Int])),
+            linux::SYS_ARCH_PRCTL => Some(("arch_prctl", &[Flags, Pointer])),
+            linux::SYS_TKILL => Some(("tkill", &[Int, Int])),
+            linux::SYS_FUTEX => Some(("futex", &[Pointer, Flags, Int, Pointer, Pointer, Int])),
+            linux::SYS_SET_TID_ADDRESS => Some(("set_tid_address", &[Pointer])),
+            linux::SYS_CLOCK_GETTIME => Some(("clock_gettime", &[Int, Pointer])),
+            linux::SYS_EXIT_GROUP => Some(("exit_group", &[Int])),
+            linux::SYS_TGKILL => Some(("tgkill", &[Int, Int, Int])),
+            linux::SYS_OPENAT => Some(("openat", &[Fd, Pointer, Flags, Flags])),
+            linux::SYS_PIPE2 => Some(("pipe2", &[Pointer, Flags])),
+            linux::SYS_PKEY_MPROTECT => Some(("pkey_mprotect", &[Pointer, Size, Flags, Int])),
+            linux::SYS_PKEY_ALLOC => Some(("pkey_alloc", &[Flags, Flags])),
+            linux::SYS_PKEY_FREE => Some(("pkey_free", &[Int])),
+            _ => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace_record(number: u64) -> TraceRecord {
+        TraceRecord {
+            timestamp_ns: number,
+            pid: Some(1),
+            personality: Personality::Native,
+            number,
+            arguments: [0; 6],
+            result: 0,
+            latency_ns: 0,
+        }
+    }
+
+    #[test]
+    fn recent_returns_latest_records_in_order() {
+        let mut tracer = SyscallTracer::new();
+        for number in 0..5 {
+            tracer.push(trace_record(number));
+        }
+
+        let numbers: Vec<u64> = tracer.recent(3).map(|record| record.number).collect();
+        assert_eq!(numbers, [2, 3, 4]);
+        assert_eq!(tracer.recent(100).count(), 5);
+        assert_eq!(tracer.dropped, 0);
+    }
+
+    #[test]
+    fn ring_buffer_overwrites_and_counts_dropped_records() {
+        let mut tracer = SyscallTracer::new();
+        let total = TRACE_BUFFER_SIZE as u64 + 10;
+        for number in 0..total {
+            tracer.push(trace_record(number));
+        }
+
+        assert_eq!(tracer.dropped, 10);
+        assert_eq!(tracer.recent(usize::MAX).count(), TRACE_BUFFER_SIZE);
+        assert_eq!(tracer.recent(usize::MAX).next().unwrap().number, 10);
+        assert_eq!(tracer.recent(1).next().unwrap().number, total - 1);
+
+        tracer.clear();
+        assert_eq!(tracer.recent(usize::MAX).count(), 0);
+        assert_eq!(tracer.dropped, 0);
+    }
+
+    #[test]
+    fn counters_track_calls_errors_and_latency() {
+        let counters = SyscallCounters::new();
+        counters.add(500, false);
+        counters.add(3_000, true);
+        counters.add(1_500, false);
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.total_ns, 5_000);
+        assert_eq!(stats.max_ns, 3_000);
+        assert_eq!(stats.histogram[0], 1);
+        assert_eq!(stats.histogram[1], 1);
+        assert_eq!(stats.histogram[2], 1);
+
+        counters.reset();
+        assert_eq!(counters.snapshot().count, 0);
+        assert_eq!(counters.snapshot().max_ns, 0);
+    }
+
+    #[test]
+    fn histogram_buckets_saturate() {
+        assert_eq!(histogram_bucket(0), 0);
+        assert_eq!(histogram_bucket(999), 0);
+        assert_eq!(histogram_bucket(1_000), 1);
+        assert_eq!(histogram_bucket(u64::MAX), HISTOGRAM_BUCKETS - 1);
+    }
+
+    #[test]
+    fn error_results_are_detected() {
+        assert!(is_error(Errno::EINVAL.to_return_value()));
+        assert!(!is_error(0));
+        assert!(!is_error(0xFFFF_8000_0000_0000));
+    }
+}
@@ -0,0 +1,189 @@
+use super::errno::Errno;
+use crate::loader::USER_SPACE_END;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::arch::{asm, global_asm, x86_64::{__cpuid, __cpuid_count}};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+global_asm!(include_str!("user.s"));
+
+pub const MAX_STRING_LENGTH: usize = 4096;
+pub const MAX_ARGUMENTS: usize = 256;
+
+const CPUID_SMEP: u32 = 1 << 7;
+const CPUID_SMAP: u32 = 1 << 20;
+const CR0_WP: u64 = 1 << 16;
+const CR4_SMEP: u64 = 1 << 20;
+const CR4_SMAP: u64 = 1 << 21;
+
+static SMEP_ENABLED: AtomicBool = AtomicBool::new(false);
+#[no_mangle]
+static SMAP_ENABLED: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    fn user_copy_bytes(destination: *mut u8, source: *const u8, count: usize) -> usize;
+    fn user_copy_string(destination: *mut u8, source: *const u8, limit: usize) -> isize;
+}
+
+pub fn init_cpu() {
+    unsafe {
+        let mut cr0: u64;
+        asm!("mov {}, cr0", out(reg) cr0);
+        cr0 |= CR0_WP;
+        asm!("mov cr0, {}", in(reg) cr0);
+    }
+
+    if __cpuid(0).eax < 7 {
+        return;
+    }
+    let features = __cpuid_count(7, 0).ebx;
+
+    let mut cr4: u64;
+    unsafe {
+        asm!("mov {}, cr4", out(reg) cr4);
+    }
+    if features & CPUID_SMEP != 0 {
+        cr4 |= CR4_SMEP;
+        SMEP_ENABLED.store(true, Ordering::Relaxed);
+    }
+    if features & CPUID_SMAP != 0 {
+        cr4 |= CR4_SMAP;
+        SMAP_ENABLED.store(true, Ordering::Relaxed);
+    }
+    unsafe {
+        asm!("mov cr4, {}", in(reg) cr4);
+    }
+}
+
+pub fn smep_enabled() -> bool {
+    SMEP_ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn smap_enabled() -> bool {
+    SMAP_ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn access_ok(address: u64, length: usize) -> bool {
+    match address.checked_add(length as u64) {
+        Some(end) => address != 0 && end <= USER_SPACE_END,
+        None => false,
+    }
+}
+
+pub fn copy_from_user(destination: &mut [u8], source: u64) -> Result<(), Errno> {
+    copy_in(destination.as_mut_ptr(), source, destination.len())
+}
+
+pub fn copy_to_user(destination: u64, source: &[u8]) -> Result<(), Errno> {
+    copy_out(destination, source.as_ptr(), source.len())
+}
+
+pub fn strncpy_from_user(destination: &mut [u8], source: u64) -> Result<usize, Errno> {
+    if source == 0 || source >= USER_SPACE_END {
+        return Err(Errno::EFAULT);
+    }
+    let limit = destination.len().min((USER_SPACE_END - source) as usize);
+
+    begin_user_access();
+    let copied = unsafe { user_copy_string(destination.as_mut_ptr(), source as *const u8, limit) };
+    end_user_access();
+
+    if copied < 0 {
+        Err(Errno::EFAULT)
+    } else {
+        Ok(copied as usize)
+    }
+}
+
+pub fn read_bytes(address: u64, length: usize) -> Result<Vec<u8>, Errno> {
+    let mut data = alloc::vec![0u8; length];
+    copy_from_user(&mut data, address)?;
+    Ok(data)
+}
+
+pub fn write_bytes(address: u64, data: &[u8]) -> Result<(), Errno> {
+    copy_to_user(address, data)
+}
+
+pub fn read_value<T: Copy>(address: u64) -> Result<T, Errno> {
+    let mut value = core::mem::MaybeUninit::<T>::uninit();
+    copy_in(value.as_mut_ptr() as *mut u8, address, core::mem::size_of::<T>())?;
+    Ok(unsafe { value.assume_init() })
+}
+
+pub fn write_value<T: Copy>(address: u64, value: T) -> Result<(), Errno> {
+    copy_out(address, &value as *const T as *const u8, core::mem::size_of::<T>())
+}
+
+pub fn read_string(address: u64) -> Result<String, Errno> {
+    let mut buffer = alloc::vec![0u8; MAX_STRING_LENGTH];
+    let length = strncpy_from_user(&mut buffer, address)?;
+    if length == buffer.len() {
+        return Err(Errno::ENAMETOOLONG);
+    }
+
+    buffer.truncate(length);
+    String::from_utf8(buffer).map_err(|_| Errno::EINVAL)
+}
+
+pub fn read_string_array(address: u64) -> Result<Vec<String>, Errno> {
+    let mut strings = Vec::new();
+    if address == 0 {
+        return Ok(strings);
+    }
+
+    for index in 0..MAX_ARGUMENTS as u64 {
+        let pointer: u64 = read_value(address + index * 8)?;
+        if pointer == 0 {
+            return Ok(strings);
+        }
+        strings.push(read_string(pointer)?);
+    }
+    Err(Errno::E2BIG)
+}
+
+fn copy_in(destination: *mut u8, source: u64, length: usize) -> Result<(), Errno> {
+    if length == 0 {
+        return Ok(());
+    }
+    if !access_ok(source, length) {
+        return Err(Errno::EFAULT);
+    }
+
+    begin_user_access();
+    let remaining = unsafe { user_copy_bytes(destination, source as *const u8, length) };
+    end_user_access();
+
+    if remaining == 0 { Ok(()) } else { Err(Errno::EFAULT) }
+}
+
+fn copy_out(destination: u64, source: *const u8, length: usize) -> Result<(), Errno> {
+    if length == 0 {
+        return Ok(());
+    }
+    if !access_ok(destination, length) {
+        return Err(Errno::EFAULT);
+    }
+
+    begin_user_access();
+    let remaining = unsafe { user_copy_bytes(destination as *mut u8, source, length) };
+    end_user_access();
+
+    if remaining == 0 { Ok(()) } else { Err(Errno::EFAULT) }
+}
+
+fn begin_user_access() {
+    if smap_enabled() {
+        unsafe {
+            asm!("stac", options(nostack));
+        }
+    }
+}
+
+fn end_user_access() {
+    if smap_enabled() {
+        unsafe {
+            asm!("clac", options(nostack));
+        }
+    }
+}
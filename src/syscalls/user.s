@@ -0,0 +1,37 @@
+.section .text
+.global user_copy_bytes
+user_copy_bytes:
+    mov rcx, rdx
+user_copy_bytes_access:
+    rep movsb
+    xor eax, eax
+    ret
+user_copy_bytes_fixup:
+    mov rax, rcx
+    ret
+
+.global user_copy_string
+user_copy_string:
+    xor eax, eax
+user_copy_string_loop:
+    cmp rax, rdx
+    je user_copy_string_done
+user_copy_string_access:
+    mov cl, byte ptr [rsi + rax]
+    mov byte ptr [rdi + rax], cl
+    test cl, cl
+    jz user_copy_string_done
+    inc rax
+    jmp user_copy_string_loop
+user_copy_string_done:
+    ret
+user_copy_string_fixup:
+    mov rax, -1
+    ret
+
+.section .ex_table, "a"
+.balign 8
+    .quad user_copy_bytes_access, user_copy_bytes_fixup
+    .quad user_copy_string_access, user_copy_string_fixup
+
+.section .text
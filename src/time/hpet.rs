@@ -0,0 +1,75 @@
+use core::ptr;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+const HPET_ADDRESS_OFFSET: u64 = 44;
+
+const HPET_CAPABILITIES: u64 = 0x000;
+const HPET_CONFIG: u64 = 0x010;
+const HPET_MAIN_COUNTER: u64 = 0x0F0;
+
+const HPET_ENABLE: u64 = 1 << 0;
+const FEMTOS_PER_NANO: u64 = 1_000_000;
+
+static HPET_BASE: AtomicU64 = AtomicU64::new(0);
+static HPET_PERIOD_FS: AtomicU64 = AtomicU64::new(0);
+
+pub fn init() -> bool {
+    let table = match crate::acpi::find_table(b"HPET") {
+        Some(table) => table,
+        None => return false,
+    };
+
+    let physical_base = unsafe {
+        let address = crate::memory::paging::phys_to_virt(table + HPET_ADDRESS_OFFSET);
+        ptr::read_unaligned(address as *const u64)
+    };
+    if physical_base == 0 {
+        return false;
+    }
+
+    HPET_BASE.store(crate::memory::paging::phys_to_virt(physical_base), Ordering::Relaxed);
+
+    let period = read_register(HPET_CAPABILITIES) >> 32;
+    if period == 0 || period > 100_000_000 {
+        HPET_BASE.store(0, Ordering::Relaxed);
+        return false;
+    }
+    HPET_PERIOD_FS.store(period, Ordering::Relaxed);
+
+    let config = read_register(HPET_CONFIG);
+    write_register(HPET_CONFIG, config | HPET_ENABLE);
+    true
+}
+
+pub fn is_available() -> bool {
+    HPET_PERIOD_FS.load(Ordering::Relaxed) != 0
+}
+
+pub fn counter() -> u64 {
+    read_register(HPET_MAIN_COUNTER)
+}
+
+pub fn frequency() -> u64 {
+    1_000_000_000_000_000 / HPET_PERIOD_FS.load(Ordering::Relaxed)
+}
+
+pub fn nanos() -> u64 {
+    ((counter() as u128 * HPET_PERIOD_FS.load(Ordering::Relaxed) as u128) / FEMTOS_PER_NANO as u128) as u64
+}
+
+pub fn busy_wait_ns(ns: u64) {
+    let start = nanos();
+    while nanos() - start < ns {
+        core::hint::spin_loop();
+    }
+}
+
+fn read_register(offset: u64) -> u64 {
+    unsafe { ptr::read_volatile((HPET_BASE.load(Ordering::Relaxed) + offset) as *const u64) }
+}
+
+fn write_register(offset: u64, value: u64) {
+    unsafe {
+        ptr::write_volatile((HPET_BASE.load(Ordering::Relaxed) + offset) as *mut u64, value);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::interrupts::apic::{self, TimerMode};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+
+pub const LAPIC_TIMER_VECTOR: u8 = 0x30;
+
+const CALIBRATION_NS: u64 = 10_000_000;
+const CALIBRATION_COUNT: u32 = u32::MAX;
+
+static TICKS_PER_SECOND: AtomicU64 = AtomicU64::new(0);
+static MODE: AtomicU8 = AtomicU8::new(TimerMode::Periodic as u8);
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn calibrate(busy_wait_ns: fn(u64)) -> u64 {
+    apic::configure_timer(LAPIC_TIMER_VECTOR, TimerMode::OneShot, true);
+    apic::set_timer_count(CALIBRATION_COUNT);
+    busy_wait_ns(CALIBRATION_NS);
+    let elapsed = CALIBRATION_COUNT - apic::timer_current_count();
+    apic::stop_timer();
+
+    let ticks_per_second = elapsed as u64 * (1_000_000_000 / CALIBRATION_NS);
+    TICKS_PER_SECOND.store(ticks_per_second, Ordering::Relaxed);
+    ticks_per_second
+}
+
+pub fn start(mode: TimerMode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+    apic::configure_timer(LAPIC_TIMER_VECTOR, mode, false);
+    ENABLED.store(true, Ordering::Release);
+    arm();
+}
+
+pub fn stop() {
+    ENABLED.store(false, Ordering::Release);
+    apic::stop_timer();
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+pub fn mode() -> TimerMode {
+    match MODE.load(Ordering::Relaxed) {
+        0 => TimerMode::OneShot,
+        2 => TimerMode::TscDeadline,
+        _ => TimerMode::Periodic,
+    }
+}
+
+pub fn ticks_per_second() -> u64 {
+    TICKS_PER_SECOND.load(Ordering::Relaxed)
+}
+
+pub fn handle_interrupt() {
+    apic::send_eoi();
+    if mode() != TimerMode::Periodic {
+        arm();
+    }
+}
+
+fn arm() {
+    match mode() {
+        TimerMode::TscDeadline => {
+            let interval = super::tsc::frequency() / super::TICK_HZ;
+            apic::set_tsc_deadline(super::tsc::read() + interval);
+        }
+        TimerMode::OneShot | TimerMode::Periodic => {
+            let count = (ticks_per_second() / super::TICK_HZ).clamp(1, u32::MAX as u64);
+            apic::set_timer_count(count as u32);
+        }
+    }
+}
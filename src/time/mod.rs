@@ -0,0 +1,228 @@
+use crate::interrupts::apic::TimerMode;
+use crate::smp::synchronization::SpinLock;
+use crate::vga;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+pub mod pit;
+pub mod hpet;
+pub mod tsc;
+pub mod lapic_timer;
+pub mod rtc;
+pub mod timer_wheel;
+
+use timer_wheel::{TimerCallback, TimerWheel};
+
+pub const TICK_HZ: u64 = 1000;
+pub const NANOS_PER_SECOND: u64 = 1_000_000_000;
+pub const NANOS_PER_MILLI: u64 = 1_000_000;
+pub const NANOS_PER_TICK: u64 = NANOS_PER_SECOND / TICK_HZ;
+
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum ClockSource {
+    Ticks = 0,
+    Hpet = 1,
+    Tsc = 2,
+}
+
+static CLOCK_SOURCE: AtomicU8 = AtomicU8::new(ClockSource::Ticks as u8);
+static CLOCK_OFFSET: AtomicU64 = AtomicU64::new(0);
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static BOOT_UNIX_NANOS: AtomicU64 = AtomicU64::new(0);
+static BOOT_MONOTONIC_NANOS: AtomicU64 = AtomicU64::new(0);
+
+pub static TIMER_WHEEL: SpinLock<TimerWheel> = SpinLock::new(TimerWheel::new());
+
+pub fn init() {
+    vga::print!("Initializing timekeeping...\n");
+
+    pit::set_frequency(TICK_HZ);
+
+    let busy_wait: fn(u64) = if hpet::init() {
+        vga::print!("HPET available at {} Hz\n", hpet::frequency());
+        hpet::busy_wait_ns
+    } else {
+        pit::busy_wait_ns
+    };
+
+    let tsc_hz = tsc::calibrate(busy_wait);
+    vga::print!("TSC calibrated at {} MHz\n", tsc_hz / 1_000_000);
+
+    let source = if tsc::is_invariant() && tsc_hz != 0 {
+        ClockSource::Tsc
+    } else if hpet::is_available() {
+        ClockSource::Hpet
+    } else {
+        ClockSource::Ticks
+    };
+    set_clock_source(source);
+
+    let lapic_hz = lapic_timer::calibrate(busy_wait);
+    vga::print!("LAPIC timer calibrated at {} kHz\n", lapic_hz / 1000);
+
+    let mode = if tsc::supports_deadline() && tsc::is_invariant() {
+        TimerMode::TscDeadline
+    } else {
+        TimerMode::Periodic
+    };
+    if lapic_hz != 0 {
+        start_local_timer(mode);
+        crate::interrupts::pic::mask_irq(0);
+    }
+
+    let rtc_time = rtc::read_time();
+    BOOT_UNIX_NANOS.store(rtc_time.to_unix_seconds() * NANOS_PER_SECOND, Ordering::Relaxed);
+    BOOT_MONOTONIC_NANOS.store(monotonic_ns(), Ordering::Relaxed);
+    vga::print!("Wall clock: {:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC\n",
+        rtc_time.year, rtc_time.month, rtc_time.day,
+        rtc_time.hour, rtc_time.minute, rtc_time.second);
+
+    vga::print!("Timekeeping initialized ({} clock source)\n", clock_source_name(source));
+}
+
+pub fn start_local_timer(mode: TimerMode) {
+    lapic_timer::start(mode);
+}
+
+pub fn init_cpu() {
+    if lapic_timer::ticks_per_second() != 0 {
+        lapic_timer::start(lapic_timer::mode());
+    }
+}
+
+fn set_clock_source(source: ClockSource) {
+    let now = monotonic_ns();
+    CLOCK_SOURCE.store(source as u8, Ordering::Relaxed);
+    CLOCK_OFFSET.store(0, Ordering::Relaxed);
+    let raw = raw_ns();
+    CLOCK_OFFSET.store(raw.wrapping_sub(now), Ordering::Relaxed);
+}
+
+pub fn clock_source() -> ClockSource {
+    match CLOCK_SOURCE.load(Ordering::Relaxed) {
+        1 => ClockSource::Hpet,
+        2 => ClockSource::Tsc,
+        _ => ClockSource::Ticks,
+    }
+}
+
+pub fn clock_source_name(source: ClockSource) -> &'static str {
+    match source {
+        ClockSource::Ticks => "tick",
+        ClockSource::Hpet => "hpet",
+        ClockSource::Tsc => "tsc",
+    }
+}
+
+fn raw_ns() -> u64 {
+    match clock_source() {
+        ClockSource::Tsc => tsc::nanos(),
+        ClockSource::Hpet => hpet::nanos(),
+        ClockSource::Ticks => TICKS.load(Ordering::Relaxed) * NANOS_PER_TICK,
+    }
+}
+
+pub fn monotonic_ns() -> u64 {
+    raw_ns().wrapping_sub(CLOCK_OFFSET.load(Ordering::Relaxed))
+}
+
+pub fn wall_clock_ns() -> u64 {
+    BOOT_UNIX_NANOS.load(Ordering::Relaxed) + monotonic_ns() - BOOT_MONOTONIC_NANOS.load(Ordering::Relaxed)
+}
+
+pub fn wall_clock_seconds() -> u64 {
+    wall_clock_ns() / NANOS_PER_SECOND
+}
+
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+pub fn ns_to_ticks(ns: u64) -> u64 {
+    (ns + NANOS_PER_TICK - 1) / NANOS_PER_TICK
+}
+
+pub fn handle_tick(cpu: u32) {
+    if cpu != 0 {
+        return;
+    }
+
+    let now = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+
+    let mut expired = Vec::new();
+    TIMER_WHEEL.lock().advance(now, &mut expired);
+
+    for timer in expired {
+        (timer.callback)(timer.data);
+    }
+}
+
+pub fn add_timer(delay_ns: u64, callback: TimerCallback, data: usize) -> u64 {
+    let expires = ticks() + ns_to_ticks(delay_ns);
+    TIMER_WHEEL.lock().add(expires, callback, data)
+}
+
+pub fn add_timer_at(deadline_ns: u64, callback: TimerCallback, data: usize) -> u64 {
+    add_timer(deadline_ns.saturating_sub(monotonic_ns()), callback, data)
+}
+
+pub fn cancel_timer(id: u64) -> bool {
+    TIMER_WHEEL.lock().cancel(id)
+}
+
+pub fn sleep_until(deadline_ns: u64) {
+    loop {
+        let now = monotonic_ns();
+        if now >= deadline_ns {
+            return;
+        }
+
+        let task_id = crate::scheduler::current_task_id();
+        if task_id == 0 || !crate::interrupts::interrupts_enabled() {
+            busy_wait_ns(deadline_ns - now);
+            return;
+        }
+
+        let interrupts_enabled = crate::interrupts::save_and_disable();
+        crate::scheduler::block_task(task_id);
+        let timer_id = add_timer(deadline_ns - now, wake_task, task_id);
+        crate::scheduler::schedule();
+        crate::interrupts::restore(interrupts_enabled);
+
+        cancel_timer(timer_id);
+    }
+}
+
+pub fn sleep_for(duration_ns: u64) {
+    sleep_until(monotonic_ns() + duration_ns);
+}
+
+pub fn sleep_ms(ms: u64) {
+    sleep_for(ms * NANOS_PER_MILLI);
+}
+
+pub fn busy_wait_ns(ns: u64) {
+    match clock_source() {
+        ClockSource::Tsc | ClockSource::Hpet => {
+            let start = monotonic_ns();
+            while monotonic_ns() - start < ns {
+                core::hint::spin_loop();
+            }
+        }
+        ClockSource::Ticks => pit::busy_wait_ns(ns),
+    }
+}
+
+fn wake_task(task_id: usize) {
+    crate::scheduler::unblock_task(task_id);
+}
+
+pub fn print_stats() {
+    vga::print!("Time Stats:\n");
+    vga::print!("  Clock source: {}\n", clock_source_name(clock_source()));
+    vga::print!("  Uptime: {} ms\n", monotonic_ns() / NANOS_PER_MILLI);
+    vga::print!("  Ticks: {}\n", ticks());
+    vga::print!("  Wall clock: {} s since epoch\n", wall_clock_seconds());
+    vga::print!("  Pending timers: {}\n", TIMER_WHEEL.lock().pending());
+}
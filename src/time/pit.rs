@@ -0,0 +1,61 @@
+use core::arch::asm;
+
+pub const PIT_FREQUENCY: u64 = 1_193_182;
+
+const PIT_CHANNEL0: u16 = 0x40;
+const PIT_CHANNEL2: u16 = 0x42;
+const PIT_COMMAND: u16 = 0x43;
+const PIT_GATE: u16 = 0x61;
+
+const GATE_ENABLE: u8 = 0x01;
+const SPEAKER_ENABLE: u8 = 0x02;
+const OUTPUT_HIGH: u8 = 0x20;
+
+const MAX_WAIT_NS: u64 = 50_000_000;
+
+pub fn set_frequency(hz: u64) {
+    let divisor = (PIT_FREQUENCY / hz).clamp(1, 0xFFFF) as u16;
+
+    outb(PIT_COMMAND, 0x34);
+    outb(PIT_CHANNEL0, divisor as u8);
+    outb(PIT_CHANNEL0, (divisor >> 8) as u8);
+}
+
+pub fn busy_wait_ns(ns: u64) {
+    let mut remaining = ns;
+    while remaining > 0 {
+        let chunk = remaining.min(MAX_WAIT_NS);
+        wait_chunk(chunk);
+        remaining -= chunk;
+    }
+}
+
+fn wait_chunk(ns: u64) {
+    let count = ((ns * PIT_FREQUENCY) / 1_000_000_000).clamp(1, 0xFFFF) as u16;
+
+    let gate = (inb(PIT_GATE) & !SPEAKER_ENABLE) | GATE_ENABLE;
+    outb(PIT_GATE, gate & !GATE_ENABLE);
+
+    outb(PIT_COMMAND, 0xB0);
+    outb(PIT_CHANNEL2, count as u8);
+    outb(PIT_CHANNEL2, (count >> 8) as u8);
+
+    outb(PIT_GATE, gate);
+    while inb(PIT_GATE) & OUTPUT_HIGH == 0 {
+        core::hint::spin_loop();
+    }
+}
+
+fn inb(port: u16) -> u8 {
+    let result: u8;
+    unsafe {
+        asm!("in al, dx", in("dx") port, out("al") result);
+    }
+    result
+}
+
+fn outb(port: u16, value: u8) {
+    unsafe {
+        asm!("out dx, al", in("dx") port, in("al") value);
+    }
+}
@@ -0,0 +1,126 @@
+use core::arch::asm;
+
+const CMOS_ADDRESS: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const RTC_SECONDS: u8 = 0x00;
+const RTC_MINUTES: u8 = 0x02;
+const RTC_HOURS: u8 = 0x04;
+const RTC_DAY: u8 = 0x07;
+const RTC_MONTH: u8 = 0x08;
+const RTC_YEAR: u8 = 0x09;
+const RTC_CENTURY: u8 = 0x32;
+const RTC_STATUS_A: u8 = 0x0A;
+const RTC_STATUS_B: u8 = 0x0B;
+
+const STATUS_A_UPDATING: u8 = 0x80;
+const STATUS_B_24_HOUR: u8 = 0x02;
+const STATUS_B_BINARY: u8 = 0x04;
+const HOUR_PM: u8 = 0x80;
+
+const NMI_DISABLE: u8 = 0x80;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct RtcTime {
+    pub year: u32,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl RtcTime {
+    pub fn to_unix_seconds(&self) -> u64 {
+        let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        days as u64 * 86_400 + self.hour as u64 * 3_600 + self.minute as u64 * 60 + self.second as u64
+    }
+}
+
+pub fn read_time() -> RtcTime {
+    let mut last = read_raw();
+    loop {
+        let current = read_raw();
+        if current == last {
+            return decode(current);
+        }
+        last = current;
+    }
+}
+
+fn read_raw() -> RtcTime {
+    while read_register(RTC_STATUS_A) & STATUS_A_UPDATING != 0 {
+        core::hint::spin_loop();
+    }
+
+    RtcTime {
+        year: read_register(RTC_YEAR) as u32 | (read_register(RTC_CENTURY) as u32) << 8,
+        month: read_register(RTC_MONTH),
+        day: read_register(RTC_DAY),
+        hour: read_register(RTC_HOURS),
+        minute: read_register(RTC_MINUTES),
+        second: read_register(RTC_SECONDS),
+    }
+}
+
+fn decode(raw: RtcTime) -> RtcTime {
+    let status_b = read_register(RTC_STATUS_B);
+    let binary = status_b & STATUS_B_BINARY != 0;
+    let convert = |value: u8| if binary { value } else { bcd_to_binary(value) };
+
+    let year_low = convert(raw.year as u8) as u32;
+    let century = match (raw.year >> 8) as u8 {
+        0 => 20,
+        value => convert(value) as u32,
+    };
+
+    let pm = raw.hour & HOUR_PM != 0;
+    let mut hour = convert(raw.hour & !HOUR_PM);
+    if status_b & STATUS_B_24_HOUR == 0 {
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    RtcTime {
+        year: century * 100 + year_low,
+        month: convert(raw.month),
+        day: convert(raw.day),
+        hour,
+        minute: convert(raw.minute),
+        second: convert(raw.second),
+    }
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value & 0x0F) + (value >> 4) * 10
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn read_register(register: u8) -> u8 {
+    outb(CMOS_ADDRESS, NMI_DISABLE | register);
+    inb(CMOS_DATA)
+}
+
+fn inb(port: u16) -> u8 {
+    let result: u8;
+    unsafe {
+        asm!("in al, dx", in("dx") port, out("al") result);
+    }
+    result
+}
+
+fn outb(port: u16, value: u8) {
+    unsafe {
+        asm!("out dx, al", in("dx") port, in("al") value);
+    }
+}
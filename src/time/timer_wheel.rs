@@ -0,0 +1,90 @@
+use alloc::vec::Vec;
+
+pub const WHEEL_SLOTS: usize = 256;
+
+pub type TimerCallback = fn(usize);
+
+#[derive(Clone, Copy)]
+pub struct Timer {
+    pub id: u64,
+    pub expires: u64,
+    pub callback: TimerCallback,
+    pub data: usize,
+}
+
+const EMPTY_SLOT: Vec<Timer> = Vec::new();
+
+pub struct TimerWheel {
+    slots: [Vec<Timer>; WHEEL_SLOTS],
+    current_tick: u64,
+    next_id: u64,
+    pending: usize,
+}
+
+impl TimerWheel {
+    pub const fn new() -> Self {
+        Self {
+            slots: [EMPTY_SLOT; WHEEL_SLOTS],
+            current_tick: 0,
+            next_id: 1,
+            pending: 0,
+        }
+    }
+
+    pub fn add(&mut self, expires: u64, callback: TimerCallback, data: usize) -> u64 {
+        let expires = expires.max(self.current_tick + 1);
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.slots[(expires % WHEEL_SLOTS as u64) as usize].push(Timer {
+            id,
+            expires,
+            callback,
+            data,
+        });
+        self.pending += 1;
+        id
+    }
+
+    pub fn cancel(&mut self, id: u64) -> bool {
+        for slot in self.slots.iter_mut() {
+            if let Some(index) = slot.iter().position(|timer| timer.id == id) {
+                slot.swap_remove(index);
+                self.pending -= 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn advance(&mut self, now: u64, expired: &mut Vec<Timer>) {
+        if self.pending == 0 {
+            self.current_tick = now.max(self.current_tick);
+            return;
+        }
+
+        let steps = now.saturating_sub(self.current_tick).min(WHEEL_SLOTS as u64);
+        for tick in (now - steps + 1)..=now {
+            let slot = &mut self.slots[(tick % WHEEL_SLOTS as u64) as usize];
+            let mut index = 0;
+            while index < slot.len() {
+                if slot[index].expires <= now {
+                    expired.push(slot.swap_remove(index));
+                    self.pending -= 1;
+                } else {
+                    index += 1;
+                }
+            }
+        }
+
+        self.current_tick = now.max(self.current_tick);
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
@@ -0,0 +1,49 @@
+use core::arch::x86_64::__cpuid;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+const CALIBRATION_NS: u64 = 10_000_000;
+
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+static TSC_BASE: AtomicU64 = AtomicU64::new(0);
+
+pub fn read() -> u64 {
+    unsafe {
+        core::arch::x86_64::_mm_lfence();
+        core::arch::x86_64::_rdtsc()
+    }
+}
+
+pub fn is_invariant() -> bool {
+    if __cpuid(0x8000_0000).eax < 0x8000_0007 {
+        return false;
+    }
+    __cpuid(0x8000_0007).edx & (1 << 8) != 0
+}
+
+pub fn supports_deadline() -> bool {
+    __cpuid(1).ecx & (1 << 24) != 0
+}
+
+pub fn calibrate(busy_wait_ns: fn(u64)) -> u64 {
+    let start = read();
+    busy_wait_ns(CALIBRATION_NS);
+    let elapsed = read() - start;
+
+    let hz = elapsed * (1_000_000_000 / CALIBRATION_NS);
+    TSC_HZ.store(hz, Ordering::Relaxed);
+    TSC_BASE.store(read(), Ordering::Relaxed);
+    hz
+}
+
+pub fn frequency() -> u64 {
+    TSC_HZ.load(Ordering::Relaxed)
+}
+
+pub fn nanos() -> u64 {
+    let elapsed = read().wrapping_sub(TSC_BASE.load(Ordering::Relaxed));
+    ((elapsed as u128 * 1_000_000_000) / TSC_HZ.load(Ordering::Relaxed) as u128) as u64
+}
+
+pub fn nanos_to_cycles(ns: u64) -> u64 {
+    ((ns as u128 * TSC_HZ.load(Ordering::Relaxed) as u128) / 1_000_000_000) as u64
+}
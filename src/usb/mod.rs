@@ -1,17 +1,20 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod host_controller;
 pub mod devices;
 
+const NO_CONTROLLER: Option<host_controller::USBController> = None;
+
 pub struct USBManager {
     pub controllers: [Option<host_controller::USBController>; 4],
     pub device_count: usize,
 }
 
 impl USBManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
-            controllers: [None; 4],
+            controllers: [NO_CONTROLLER; 4],
             device_count: 0,
         }
     }
@@ -26,10 +29,8 @@ impl USBManager {
     }
 }
 
-pub static mut USB: USBManager = USBManager::new();
+pub static USB: SpinLock<USBManager> = SpinLock::new(USBManager::new());
 
 pub fn init() {
-    unsafe {
-        USB.init();
-    }
+    USB.lock().init();
 }
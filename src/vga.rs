@@ -1,7 +1,8 @@
+use crate::smp::synchronization::SpinLock;
 use core::fmt;
 
-const VGA_WIDTH: usize = 80;
-const VGA_HEIGHT: usize = 25;
+pub const VGA_WIDTH: usize = 80;
+pub const VGA_HEIGHT: usize = 25;
 const VGA_ADDRESS: usize = 0xB8000;
 
 #[repr(transparent)]
@@ -108,28 +109,29 @@ impl fmt::Write for VGAWriter {
     }
 }
 
-static mut WRITER: VGAWriter = VGAWriter {
+static WRITER: SpinLock<VGAWriter> = SpinLock::new(VGAWriter {
     column_position: 0,
     color_code: VGAColor::LIGHT_GRAY,
     buffer: unsafe { &mut *(VGA_ADDRESS as *mut [VGAEntry; VGA_WIDTH * VGA_HEIGHT]) },
-};
+});
 
 pub fn init() {
-    unsafe {
-        for i in 0..VGA_WIDTH * VGA_HEIGHT {
-            WRITER.buffer[i] = VGAEntry {
-                character: b' ',
-                color: VGAColor::BLACK.0,
-            };
-        }
+    let mut writer = WRITER.lock();
+    for i in 0..VGA_WIDTH * VGA_HEIGHT {
+        writer.buffer[i] = VGAEntry {
+            character: b' ',
+            color: VGAColor::BLACK.0,
+        };
     }
 }
 
+pub fn relocate(offset: u64) {
+    WRITER.lock().buffer = unsafe { &mut *((VGA_ADDRESS as u64 + offset) as *mut [VGAEntry; VGA_WIDTH * VGA_HEIGHT]) };
+}
+
 pub fn print(args: fmt::Arguments) {
     use core::fmt::Write;
-    unsafe {
-        WRITER.write_fmt(args).unwrap();
-    }
+    WRITER.lock().write_fmt(args).unwrap();
 }
 
 #[macro_export]
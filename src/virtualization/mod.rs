@@ -1,3 +1,4 @@
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
 
 pub mod hypervisor;
@@ -11,7 +12,7 @@ pub struct VirtualizationManager {
 }
 
 impl VirtualizationManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             hypervisor_enabled: false,
             vm_count: 0,
@@ -33,10 +34,8 @@ impl VirtualizationManager {
     }
 }
 
-pub static mut VIRTUALIZATION: VirtualizationManager = VirtualizationManager::new();
+pub static VIRTUALIZATION: SpinLock<VirtualizationManager> = SpinLock::new(VirtualizationManager::new());
 
 pub fn init() {
-    unsafe {
-        VIRTUALIZATION.init();
-    }
+    VIRTUALIZATION.lock().init();
 }
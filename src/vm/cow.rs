@@ -0,0 +1,50 @@
+use crate::memory::frame_allocator;
+use crate::memory::paging::{self, PageMapper, PAGE_COW, PAGE_SIZE, PAGE_WRITABLE};
+
+pub const FAULT_PRESENT: u64 = 1 << 0;
+pub const FAULT_WRITE: u64 = 1 << 1;
+
+pub fn handle_page_fault(addr: u64, error_code: u64) -> bool {
+    if error_code & (FAULT_PRESENT | FAULT_WRITE) != FAULT_PRESENT | FAULT_WRITE {
+        return false;
+    }
+
+    let page = addr & !(PAGE_SIZE - 1);
+    let mut mapper = PageMapper::current();
+    let flags = match mapper.flags(page) {
+        Some(flags) if flags & PAGE_COW != 0 => flags,
+        _ => return false,
+    };
+    let frame = match mapper.translate(page) {
+        Some(physical_addr) => physical_addr & !(PAGE_SIZE - 1),
+        None => return false,
+    };
+
+    let writable_flags = (flags & !PAGE_COW) | PAGE_WRITABLE;
+    if frame_allocator::frame_references(frame) == 1 {
+        return mapper.protect(page, writable_flags).is_ok();
+    }
+
+    let copy = match frame_allocator::allocate_frame() {
+        Some(copy) => copy,
+        None => return false,
+    };
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            paging::phys_to_virt(frame) as *const u8,
+            paging::phys_to_virt(copy) as *mut u8,
+            PAGE_SIZE as usize,
+        );
+    }
+
+    match mapper.remap(page, copy, writable_flags) {
+        Ok(previous) => {
+            frame_allocator::release_frame(previous);
+            true
+        }
+        Err(_) => {
+            frame_allocator::free_frame(copy);
+            false
+        }
+    }
+}
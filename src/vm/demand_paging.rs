@@ -1,24 +1,124 @@
+use super::cow::{FAULT_PRESENT, FAULT_WRITE};
+use super::mmap::{MAP_SHARED, PROT_EXEC, PROT_READ, PROT_WRITE};
+use super::region::VirtualMemoryRegion;
+use super::FaultError;
+use crate::memory::frame_allocator;
+use crate::memory::paging::{self, MapError, PageMapper, PAGE_COW, PAGE_SHARED, PAGE_SIZE, PAGE_WRITABLE};
 use crate::vga;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub const FAULT_USER: u64 = 1 << 2;
+pub const FAULT_RESERVED: u64 = 1 << 3;
+pub const FAULT_INSTRUCTION: u64 = 1 << 4;
+pub const FAULT_PROTECTION_KEY: u64 = 1 << 5;
+
+static ZERO_FILL_FAULTS: AtomicU64 = AtomicU64::new(0);
+static FILE_FAULTS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FaultAccess {
+    Read,
+    Write,
+    Execute,
+}
+
+impl FaultAccess {
+    pub fn from_error_code(error_code: u64) -> Self {
+        if error_code & FAULT_INSTRUCTION != 0 {
+            FaultAccess::Execute
+        } else if error_code & FAULT_WRITE != 0 {
+            FaultAccess::Write
+        } else {
+            FaultAccess::Read
+        }
+    }
+
+    fn permitted(self, prot: u64) -> bool {
+        match self {
+            FaultAccess::Read => prot & (PROT_READ | PROT_WRITE | PROT_EXEC) != 0,
+            FaultAccess::Write => prot & PROT_WRITE != 0,
+            FaultAccess::Execute => prot & PROT_EXEC != 0,
+        }
+    }
+}
 
 pub fn init() {
     vga::print!("Demand paging initialized\n");
 }
 
-pub fn handle_page_fault(addr: u64, error_code: u64) -> bool {
-    vga::print!("Handling demand page fault at 0x{:x}\n", addr);
-    
-    let page_addr = addr & !0xFFF;
-    
-    if allocate_page(page_addr) {
-        vga::print!("Allocated page for 0x{:x}\n", page_addr);
-        true
-    } else {
-        vga::print!("Failed to allocate page for 0x{:x}\n", page_addr);
-        false
+pub fn handle_page_fault(addr: u64, error_code: u64) -> Result<(), FaultError> {
+    if error_code & FAULT_RESERVED != 0 {
+        return Err(FaultError::ReservedBit);
+    }
+
+    let mut mapper = PageMapper::current();
+    let region = super::find_region(mapper.root(), addr).ok_or(FaultError::NoRegion)?;
+    if !FaultAccess::from_error_code(error_code).permitted(region.prot) || error_code & FAULT_PRESENT != 0 {
+        return Err(FaultError::AccessViolation);
+    }
+
+    super::swapping::relieve_pressure();
+    populate(&mut mapper, &region, addr & !(PAGE_SIZE - 1))
+}
+
+pub fn populate_address(mapper: &mut PageMapper, addr: u64) -> Result<(), FaultError> {
+    let region = super::find_region(mapper.root(), addr).ok_or(FaultError::NoRegion)?;
+    populate(mapper, &region, addr & !(PAGE_SIZE - 1))
+}
+
+pub fn populate(mapper: &mut PageMapper, region: &VirtualMemoryRegion, page: u64) -> Result<(), FaultError> {
+    let flags = region.page_flags();
+
+    let (frame, flags) = match &region.object {
+        None => {
+            let frame = frame_allocator::allocate_frame().ok_or(FaultError::OutOfMemory)?;
+            unsafe {
+                core::ptr::write_bytes(paging::phys_to_virt(frame) as *mut u8, 0, PAGE_SIZE as usize);
+            }
+            ZERO_FILL_FAULTS.fetch_add(1, Ordering::Relaxed);
+            (frame, flags)
+        }
+        Some(object) => {
+            if !object.contains(region.object_index(page)) {
+                return Err(FaultError::OutOfBounds);
+            }
+            let frame = object.page(region.object_index(page)).ok_or(FaultError::OutOfMemory)?;
+            if object.is_file_backed() {
+                FILE_FAULTS.fetch_add(1, Ordering::Relaxed);
+            } else {
+                ZERO_FILL_FAULTS.fetch_add(1, Ordering::Relaxed);
+            }
+            if region.flags & MAP_SHARED != 0 {
+                (frame, flags | PAGE_SHARED)
+            } else if flags & PAGE_WRITABLE != 0 {
+                (frame, (flags & !PAGE_WRITABLE) | PAGE_COW)
+            } else {
+                (frame, flags)
+            }
+        }
+    };
+
+    match mapper.map(page, frame, flags) {
+        Ok(()) => {
+            if region.object.as_ref().map_or(false, |object| !object.contains(region.object_index(page))) {
+                if let Ok(frame) = mapper.unmap(page) {
+                    frame_allocator::release_frame(frame);
+                }
+                return Err(FaultError::OutOfBounds);
+            }
+            Ok(())
+        }
+        Err(MapError::AlreadyMapped) => {
+            frame_allocator::release_frame(frame);
+            Ok(())
+        }
+        Err(_) => {
+            frame_allocator::release_frame(frame);
+            Err(FaultError::OutOfMemory)
+        }
     }
 }
 
-fn allocate_page(addr: u64) -> bool {
-    vga::print!("Allocating page at 0x{:x}\n", addr);
-    true
+pub fn demand_faults() -> (u64, u64) {
+    (ZERO_FILL_FAULTS.load(Ordering::Relaxed), FILE_FAULTS.load(Ordering::Relaxed))
 }
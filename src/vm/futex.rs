@@ -0,0 +1,144 @@
+use super::mmap::MAP_SHARED;
+use crate::memory::paging::{phys_to_virt, PageMapper};
+use crate::smp::synchronization::{SpinLock, WaitQueue};
+use crate::syscalls::errno::Errno;
+use crate::syscalls::user;
+use crate::time;
+use crate::vga;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+
+pub const FUTEX_WAIT: u64 = 0;
+pub const FUTEX_WAKE: u64 = 1;
+pub const FUTEX_PRIVATE_FLAG: u64 = 128;
+pub const FUTEX_CLOCK_REALTIME: u64 = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum FutexKey {
+    Shared(usize, u64),
+    Private(u64, u64),
+}
+
+static FUTEXES: SpinLock<BTreeMap<FutexKey, Arc<WaitQueue>>> = SpinLock::new(BTreeMap::new());
+
+pub fn init() {
+    vga::print!("Futex wait queues initialized\n");
+}
+
+pub fn wait(address_space: &PageMapper, address: u64, expected: u32, deadline: Option<u64>) -> Result<(), Errno> {
+    if address % 4 != 0 {
+        return Err(Errno::EINVAL);
+    }
+    if read_value(address)? != expected {
+        return Err(Errno::EAGAIN);
+    }
+    let timed_out = || deadline.map_or(false, |deadline| time::monotonic_ns() >= deadline);
+    if timed_out() {
+        return Err(Errno::ETIMEDOUT);
+    }
+
+    let key = key(address_space, address)?;
+    let queue = FUTEXES.lock().entry(key).or_insert_with(|| Arc::new(WaitQueue::new())).clone();
+    let task_id = crate::scheduler::current_task_id();
+    let mut timer = None;
+    let waited = loop {
+        // The queue lock is held while the condition runs, so the value is
+        // only peeked through the page tables there. If the page went away
+        // since the read above it is faulted back in with the lock dropped.
+        let mut resident = true;
+        let waited = queue.wait_if(|| match peek_value(address_space, address) {
+            Some(value) if value == expected && !timed_out() => {
+                timer = deadline.map(|deadline| time::add_timer_at(deadline, expire, task_id));
+                true
+            }
+            Some(_) => false,
+            None => {
+                resident = false;
+                false
+            }
+        });
+        if resident {
+            break Ok(waited);
+        }
+        match read_value(address) {
+            Ok(value) if value == expected => continue,
+            Ok(_) => break Ok(false),
+            Err(error) => break Err(error),
+        }
+    };
+    if let Some(timer) = timer {
+        time::cancel_timer(timer);
+    }
+    queue.remove(task_id);
+    release(key, queue);
+
+    if !waited? {
+        if timed_out() {
+            Err(Errno::ETIMEDOUT)
+        } else {
+            Err(Errno::EAGAIN)
+        }
+    } else if crate::process::signal::pending() {
+        Err(Errno::EINTR)
+    } else if timed_out() {
+        Err(Errno::ETIMEDOUT)
+    } else {
+        Ok(())
+    }
+}
+
+pub fn wake(address_space: &PageMapper, address: u64, count: u64) -> Result<u64, Errno> {
+    if address % 4 != 0 {
+        return Err(Errno::EINVAL);
+    }
+
+    let key = key(address_space, address)?;
+    let queue = match FUTEXES.lock().get(&key) {
+        Some(queue) => queue.clone(),
+        None => return Ok(0),
+    };
+
+    let mut woken = 0;
+    while woken < count && queue.wake_one() {
+        woken += 1;
+    }
+    release(key, queue);
+    Ok(woken)
+}
+
+pub fn waiting_count() -> usize {
+    FUTEXES.lock().values().map(|queue| queue.len()).sum()
+}
+
+fn key(address_space: &PageMapper, address: u64) -> Result<FutexKey, Errno> {
+    let region = super::find_region(address_space.root(), address).ok_or(Errno::EFAULT)?;
+    match &region.object {
+        Some(object) if region.flags & MAP_SHARED != 0 => {
+            Ok(FutexKey::Shared(Arc::as_ptr(object) as usize, region.offset + (address - region.start)))
+        }
+        _ => Ok(FutexKey::Private(address_space.root(), address)),
+    }
+}
+
+fn release(key: FutexKey, queue: Arc<WaitQueue>) {
+    let mut futexes = FUTEXES.lock();
+    drop(queue);
+    if futexes.get(&key).map_or(false, |queue| queue.is_empty() && Arc::strong_count(queue) == 1) {
+        futexes.remove(&key);
+    }
+}
+
+fn expire(task_id: usize) {
+    crate::scheduler::unblock_task(task_id);
+}
+
+fn peek_value(address_space: &PageMapper, address: u64) -> Option<u32> {
+    let physical = address_space.translate(address)?;
+    Some(unsafe { core::ptr::read_volatile(phys_to_virt(physical) as *const u32) })
+}
+
+fn read_value(address: u64) -> Result<u32, Errno> {
+    let mut value = [0u8; 4];
+    user::copy_from_user(&mut value, address)?;
+    Ok(u32::from_ne_bytes(value))
+}
@@ -1,5 +1,341 @@
+use super::object::MemoryObject;
+use super::protection;
+use super::region::VirtualMemoryRegion;
+use super::swapping;
+use super::VM_MANAGER;
+use crate::loader::USER_SPACE_END;
+use crate::memory::frame_allocator;
+use crate::memory::paging::{PageMapper, PAGE_COW, PAGE_DIRTY, PAGE_NX, PAGE_PKEY_MASK, PAGE_PRESENT, PAGE_SHARED, PAGE_SIZE, PAGE_USER, PAGE_WRITABLE};
+use crate::process::files::FileDescription;
+use crate::scheduler::user_stack::{DEFAULT_USER_STACK_PAGES, USER_STACK_TOP};
+use crate::syscalls::errno::Errno;
 use crate::vga;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub const PROT_NONE: u64 = 0;
+pub const PROT_READ: u64 = 1;
+pub const PROT_WRITE: u64 = 2;
+pub const PROT_EXEC: u64 = 4;
+
+pub const MAP_SHARED: u64 = 0x01;
+pub const MAP_PRIVATE: u64 = 0x02;
+pub const MAP_FIXED: u64 = 0x10;
+pub const MAP_ANONYMOUS: u64 = 0x20;
+
+pub const MREMAP_MAYMOVE: u64 = 1;
+
+pub const MMAP_BASE: u64 = 0x0000_7000_0000_0000;
+pub const MMAP_END: u64 = USER_STACK_TOP - (DEFAULT_USER_STACK_PAGES + 1) * PAGE_SIZE;
 
 pub fn init() {
     vga::print!("Memory mapping system initialized\n");
 }
+
+pub fn page_flags(prot: u64) -> u64 {
+    if prot & (PROT_READ | PROT_WRITE | PROT_EXEC) == PROT_NONE {
+        return PAGE_NX;
+    }
+
+    let mut flags = PAGE_USER;
+    if prot & PROT_WRITE != 0 {
+        flags |= PAGE_WRITABLE;
+    }
+    if prot & PROT_EXEC == 0 {
+        flags |= PAGE_NX;
+    }
+    flags
+}
+
+pub fn mmap(
+    address_space: &PageMapper,
+    address: u64,
+    length: u64,
+    prot: u64,
+    flags: u64,
+    file: Option<(Arc<dyn FileDescription>, u64)>,
+) -> Result<u64, Errno> {
+    let sharing = flags & (MAP_SHARED | MAP_PRIVATE);
+    if length == 0 || sharing == 0 || sharing == MAP_SHARED | MAP_PRIVATE {
+        return Err(Errno::EINVAL);
+    }
+    protection::check_prot(prot)?;
+    let length = page_align_up(length).ok_or(Errno::ENOMEM)?;
+
+    let (object, offset) = match file {
+        Some((file, offset)) => {
+            if offset % PAGE_SIZE != 0 {
+                return Err(Errno::EINVAL);
+            }
+            let object = match file.memory_object() {
+                Some(object) => object,
+                None => MemoryObject::for_file(file.as_ref())?,
+            };
+            (Some(object), offset)
+        }
+        None if sharing == MAP_SHARED => (Some(Arc::new(MemoryObject::anonymous())), 0),
+        None => (None, 0),
+    };
+
+    let mut mapper = *address_space;
+    let (start, removed, dirty) = {
+        let mut manager = VM_MANAGER.lock();
+        let regions = manager.space_mut(mapper.root());
+
+        let (start, removed, dirty) = if flags & MAP_FIXED != 0 {
+            if !is_user_range(address, length) {
+                return Err(Errno::EINVAL);
+            }
+            let removed = regions.remove_range(address, address + length);
+            let dirty = collect_dirty(&mapper, removed.iter());
+            unmap(&mut mapper, address, length);
+            (address, removed, dirty)
+        } else if is_user_range(address, length) && regions.is_free(address, address + length) {
+            (address, Vec::new(), Vec::new())
+        } else {
+            let start = regions.find_gap(MMAP_BASE, length, MMAP_BASE, MMAP_END).ok_or(Errno::ENOMEM)?;
+            (start, Vec::new(), Vec::new())
+        };
+
+        regions.insert(VirtualMemoryRegion {
+            start,
+            end: start + length,
+            prot,
+            flags: flags & (MAP_SHARED | MAP_PRIVATE | MAP_ANONYMOUS),
+            object,
+            offset,
+            pkey: 0,
+        });
+        (start, removed, dirty)
+    };
+
+    write_back(dirty);
+    drop(removed);
+    Ok(start)
+}
+
+pub fn munmap(address_space: &PageMapper, address: u64, length: u64) -> Result<(), Errno> {
+    if length == 0 {
+        return Err(Errno::EINVAL);
+    }
+    let length = page_align_up(length).ok_or(Errno::EINVAL)?;
+    if !is_user_range(address, length) {
+        return Err(Errno::EINVAL);
+    }
+
+    let mut mapper = *address_space;
+    let (removed, dirty) = {
+        let mut manager = VM_MANAGER.lock();
+        let removed = manager.space_mut(mapper.root()).remove_range(address, address + length);
+        let dirty = collect_dirty(&mapper, removed.iter());
+        unmap(&mut mapper, address, length);
+        (removed, dirty)
+    };
+
+    write_back(dirty);
+    drop(removed);
+    Ok(())
+}
+
+pub fn mprotect(address_space: &PageMapper, address: u64, length: u64, prot: u64) -> Result<(), Errno> {
+    protect_range(address_space, address, length, prot, None)
+}
+
+pub fn pkey_mprotect(address_space: &PageMapper, address: u64, length: u64, prot: u64, pkey: u8) -> Result<(), Errno> {
+    protect_range(address_space, address, length, prot, Some(pkey))
+}
+
+pub fn mremap(address_space: &PageMapper, address: u64, old_length: u64, new_length: u64, flags: u64) -> Result<u64, Errno> {
+    if address % PAGE_SIZE != 0 || new_length == 0 || flags & !MREMAP_MAYMOVE != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let old_length = page_align_up(old_length).ok_or(Errno::EINVAL)?;
+    let new_length = page_align_up(new_length).ok_or(Errno::ENOMEM)?;
+    if old_length == 0 || !is_user_range(address, old_length) {
+        return Err(Errno::EINVAL);
+    }
+    let old_end = address + old_length;
+
+    let mut mapper = *address_space;
+    let mut manager = VM_MANAGER.lock();
+    let regions = manager.space_mut(mapper.root());
+    let region = match regions.find(address) {
+        Some(region) if old_end <= region.end => region.clone(),
+        _ => return Err(Errno::EFAULT),
+    };
+
+    if new_length <= old_length {
+        let removed = regions.remove_range(address + new_length, old_end);
+        let dirty = collect_dirty(&mapper, removed.iter());
+        unmap(&mut mapper, address + new_length, old_length - new_length);
+        drop(manager);
+        write_back(dirty);
+        drop(removed);
+        return Ok(address);
+    }
+
+    if is_user_range(address, new_length) && regions.is_free(old_end, address + new_length) {
+        regions.insert(extension(&region, old_end, address + new_length, old_end));
+        return Ok(address);
+    }
+
+    if flags & MREMAP_MAYMOVE == 0 {
+        return Err(Errno::ENOMEM);
+    }
+    let target = regions.find_gap(MMAP_BASE, new_length, MMAP_BASE, MMAP_END).ok_or(Errno::ENOMEM)?;
+
+    let moved_regions = regions.remove_range(address, old_end);
+    // Moving the entries drops their dirty bits, so file pages written
+    // through the old mapping are flushed once the move is done.
+    let dirty = collect_dirty(&mapper, moved_regions.iter());
+    for mut moved in moved_regions {
+        moved.start = target + (moved.start - address);
+        moved.end = target + (moved.end - address);
+        regions.insert(moved);
+    }
+    regions.insert(extension(&region, target + old_length, target + new_length, old_end));
+
+    let mut offset = 0;
+    while offset < old_length {
+        if mapper.entry(address + offset).map_or(false, swapping::is_swap_entry) {
+            if mapper.move_entry(address + offset, target + offset).is_err() {
+                swapping::discard(&mut mapper, address + offset);
+            }
+        } else if let Some(existing) = mapper.flags(address + offset) {
+            if let Ok(frame) = mapper.unmap(address + offset) {
+                if mapper.map(target + offset, frame, existing).is_err() {
+                    frame_allocator::release_frame(frame);
+                }
+            }
+        }
+        offset += PAGE_SIZE;
+    }
+    drop(manager);
+    write_back(dirty);
+    Ok(target)
+}
+
+pub fn unmap(address_space: &mut PageMapper, start: u64, length: u64) {
+    let mut frames = Vec::new();
+    let mut page = start;
+    while page < start + length {
+        match address_space.unmap_no_flush(page) {
+            Ok(frame) => frames.push(frame),
+            Err(_) => swapping::discard(address_space, page),
+        }
+        page += PAGE_SIZE;
+    }
+
+    // Other CPUs may still hold translations until the flush, so the frames
+    // are only handed back after it.
+    address_space.flush_range(start, length);
+    for frame in frames {
+        frame_allocator::release_frame(frame);
+    }
+}
+
+pub fn collect_dirty<'a>(address_space: &PageMapper, regions: impl Iterator<Item = &'a VirtualMemoryRegion>) -> Vec<Arc<MemoryObject>> {
+    let mut objects = Vec::new();
+    for region in regions {
+        let object = match &region.object {
+            Some(object) if object.is_file_backed() && region.flags & MAP_SHARED != 0 => object,
+            _ => continue,
+        };
+
+        let mut page = region.start;
+        while page < region.end {
+            let dirty = address_space
+                .entry(page)
+                .map_or(false, |entry| entry & (PAGE_PRESENT | PAGE_DIRTY) == PAGE_PRESENT | PAGE_DIRTY);
+            if dirty {
+                object.mark_dirty(region.object_index(page));
+            }
+            page += PAGE_SIZE;
+        }
+        objects.push(object.clone());
+    }
+    objects
+}
+
+pub fn write_back(objects: Vec<Arc<MemoryObject>>) {
+    for object in objects {
+        object.write_back();
+    }
+}
+
+pub fn is_user_range(address: u64, length: u64) -> bool {
+    address != 0
+        && address & (PAGE_SIZE - 1) == 0
+        && address.checked_add(length).map_or(false, |end| end <= USER_SPACE_END)
+}
+
+fn protect_range(address_space: &PageMapper, address: u64, length: u64, prot: u64, pkey: Option<u8>) -> Result<(), Errno> {
+    if address % PAGE_SIZE != 0 {
+        return Err(Errno::EINVAL);
+    }
+    protection::check_prot(prot)?;
+    let length = page_align_up(length).ok_or(Errno::ENOMEM)?;
+    if length == 0 {
+        return Ok(());
+    }
+    if !is_user_range(address, length) {
+        return Err(Errno::ENOMEM);
+    }
+    let end = address + length;
+
+    let mut mapper = *address_space;
+    let mut manager = VM_MANAGER.lock();
+    let regions = manager.space_mut(mapper.root());
+    if !regions.is_covered(address, end) {
+        return Err(Errno::ENOMEM);
+    }
+    regions.update_range(address, end, |region| {
+        region.prot = prot;
+        if let Some(pkey) = pkey {
+            region.pkey = pkey;
+        }
+    });
+
+    let mut page = address;
+    while page < end {
+        if let Some(existing) = mapper.flags(page) {
+            let pkey_flags = match pkey {
+                Some(pkey) => protection::pkey_flags(pkey),
+                None => existing & PAGE_PKEY_MASK,
+            };
+            let _ = mapper.protect_no_flush(page, protected_flags(&mapper, page, existing, prot) | pkey_flags);
+        }
+        page += PAGE_SIZE;
+    }
+    mapper.flush_range(address, length);
+    Ok(())
+}
+
+fn page_align_up(length: u64) -> Option<u64> {
+    Some(length.checked_add(PAGE_SIZE - 1)? & !(PAGE_SIZE - 1))
+}
+
+fn extension(region: &VirtualMemoryRegion, start: u64, end: u64, source: u64) -> VirtualMemoryRegion {
+    let mut extension = region.clone();
+    extension.start = start;
+    extension.end = end;
+    extension.offset = region.offset + (source - region.start);
+    extension
+}
+
+fn protected_flags(mapper: &PageMapper, page: u64, existing: u64, prot: u64) -> u64 {
+    let flags = page_flags(prot) | (existing & PAGE_SHARED);
+    if flags & PAGE_WRITABLE == 0 || existing & PAGE_SHARED != 0 {
+        return flags;
+    }
+
+    let shared = existing & PAGE_COW != 0
+        || mapper
+            .translate(page)
+            .map_or(false, |frame| frame_allocator::frame_references(frame & !(PAGE_SIZE - 1)) > 1);
+    if shared {
+        (flags & !PAGE_WRITABLE) | PAGE_COW
+    } else {
+        flags
+    }
+}
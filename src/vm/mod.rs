@@ -1,36 +1,42 @@
+use crate::memory::frame_allocator::FRAME_ALLOCATOR;
+use crate::memory::paging::{PageMapper, PAGE_SIZE};
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
-use core::sync::atomic::{AtomicU64, Ordering};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
 
 pub mod demand_paging;
 pub mod swapping;
 pub mod mmap;
 pub mod protection;
+pub mod cow;
+pub mod object;
+pub mod region;
+pub mod shm;
+pub mod futex;
 
-#[repr(C, packed)]
-pub struct VirtualMemoryRegion {
-    pub start: u64,
-    pub end: u64,
-    pub permissions: u32,
-    pub flags: u32,
-    pub backing: Option<u64>,
+use region::{RegionTree, VirtualMemoryRegion};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FaultError {
+    NoRegion,
+    AccessViolation,
+    ReservedBit,
+    ProtectionKey,
+    OutOfBounds,
+    OutOfMemory,
 }
 
 pub struct VirtualMemoryManager {
-    pub regions: [Option<VirtualMemoryRegion>; 1024],
-    pub region_count: usize,
-    pub total_pages: AtomicU64,
-    pub used_pages: AtomicU64,
-    pub swapped_pages: AtomicU64,
+    spaces: BTreeMap<u64, RegionTree>,
+    reclaimable: BTreeSet<u64>,
 }
 
 impl VirtualMemoryManager {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
-            regions: [None; 1024],
-            region_count: 0,
-            total_pages: AtomicU64::new(0),
-            used_pages: AtomicU64::new(0),
-            swapped_pages: AtomicU64::new(0),
+            spaces: BTreeMap::new(),
+            reclaimable: BTreeSet::new(),
         }
     }
     
@@ -41,69 +47,76 @@ impl VirtualMemoryManager {
         swapping::init();
         mmap::init();
         protection::init();
+        shm::init();
+        futex::init();
         
         vga::print!("Virtual Memory Manager initialized\n");
     }
     
-    pub fn mmap(&mut self, addr: u64, len: u64, prot: u32, flags: u32) -> Option<u64> {
-        if self.region_count >= 1024 {
-            return None;
+    pub fn space(&self, root: u64) -> Option<&RegionTree> {
+        self.spaces.get(&root)
+    }
+    
+    pub fn space_mut(&mut self, root: u64) -> &mut RegionTree {
+        self.spaces.entry(root).or_insert_with(RegionTree::new)
+    }
+    
+    pub fn remove_space(&mut self, root: u64) -> Option<RegionTree> {
+        self.reclaimable.remove(&root);
+        self.spaces.remove(&root)
+    }
+    
+    pub fn clone_space(&mut self, parent: u64, child: u64) {
+        if let Some(regions) = self.spaces.get(&parent).cloned() {
+            self.spaces.insert(child, regions);
         }
-        
-        let region = VirtualMemoryRegion {
-            start: addr,
-            end: addr + len,
-            permissions: prot,
-            flags,
-            backing: None,
-        };
-        
-        for i in 0..1024 {
-            if self.regions[i].is_none() {
-                self.regions[i] = Some(region);
-                self.region_count += 1;
-                vga::print!("Mapped memory region: 0x{:x}-0x{:x}\n", addr, addr + len);
-                return Some(addr);
-            }
+        if self.reclaimable.contains(&parent) {
+            self.reclaimable.insert(child);
         }
-        
-        None
     }
     
-    pub fn munmap(&mut self, addr: u64) -> bool {
-        for i in 0..1024 {
-            if let Some(region) = &self.regions[i] {
-                if region.start == addr {
-                    self.regions[i] = None;
-                    self.region_count -= 1;
-                    vga::print!("Unmapped memory region: 0x{:x}\n", addr);
-                    return true;
-                }
-            }
+    pub fn set_reclaimable(&mut self, root: u64, reclaimable: bool) {
+        if reclaimable {
+            self.reclaimable.insert(root);
+        } else {
+            self.reclaimable.remove(&root);
         }
-        false
     }
     
-    pub fn handle_page_fault(&mut self, addr: u64, error_code: u64) -> bool {
-        vga::print!("Page fault at 0x{:x}, error code: 0x{:x}\n", addr, error_code);
-        
-        if demand_paging::handle_page_fault(addr, error_code) {
-            return true;
-        }
-        
-        if swapping::handle_page_fault(addr, error_code) {
-            return true;
-        }
-        
-        false
+    pub fn swappable_ranges(&self) -> impl Iterator<Item = (u64, u64, u64)> + '_ {
+        self.reclaimable
+            .iter()
+            .filter_map(move |root| self.spaces.get(root).map(|regions| (*root, regions)))
+            .flat_map(|(root, regions)| {
+                regions
+                    .iter()
+                    .filter(|region| region.object.is_none() && region.flags & mmap::MAP_PRIVATE != 0)
+                    .map(move |region| (root, region.start, region.end))
+            })
+    }
+    
+    pub fn find_region(&self, root: u64, addr: u64) -> Option<VirtualMemoryRegion> {
+        self.spaces.get(&root).and_then(|regions| regions.find(addr)).cloned()
     }
     
     pub fn get_memory_stats(&self) -> MemoryStats {
+        let (zero_fill_faults, file_faults) = demand_paging::demand_faults();
+        let (swap_pages, swapped_pages) = swapping::swap_totals();
+        let (swap_ins, swap_outs) = swapping::swap_activity();
+        let (total_frames, free_frames) = {
+            let allocator = FRAME_ALLOCATOR.lock();
+            (allocator.total_frames() as u64, allocator.free_frames() as u64)
+        };
         MemoryStats {
-            total_pages: self.total_pages.load(Ordering::Relaxed),
-            used_pages: self.used_pages.load(Ordering::Relaxed),
-            swapped_pages: self.swapped_pages.load(Ordering::Relaxed),
-            region_count: self.region_count,
+            total_pages: total_frames,
+            used_pages: total_frames - free_frames,
+            swap_pages,
+            swapped_pages,
+            swap_ins,
+            swap_outs,
+            region_count: self.spaces.values().map(|regions| regions.count()).sum(),
+            zero_fill_faults,
+            file_faults,
         }
     }
 }
@@ -111,32 +124,90 @@ impl VirtualMemoryManager {
 pub struct MemoryStats {
     pub total_pages: u64,
     pub used_pages: u64,
+    pub swap_pages: u64,
     pub swapped_pages: u64,
+    pub swap_ins: u64,
+    pub swap_outs: u64,
     pub region_count: usize,
+    pub zero_fill_faults: u64,
+    pub file_faults: u64,
 }
 
-pub static mut VM_MANAGER: VirtualMemoryManager = VirtualMemoryManager::new();
+pub static VM_MANAGER: SpinLock<VirtualMemoryManager> = SpinLock::new(VirtualMemoryManager::new());
 
 pub fn init() {
-    unsafe {
-        VM_MANAGER.init();
-    }
+    VM_MANAGER.lock().init();
 }
 
-pub fn mmap(addr: u64, len: u64, prot: u32, flags: u32) -> Option<u64> {
-    unsafe {
-        VM_MANAGER.mmap(addr, len, prot, flags)
-    }
+pub fn print_stats() {
+    let stats = VM_MANAGER.lock().get_memory_stats();
+    vga::print!("VM: {}/{} pages used, {} regions, {} zero-fill faults, {} file faults\n",
+        stats.used_pages, stats.total_pages, stats.region_count, stats.zero_fill_faults, stats.file_faults);
+    vga::print!("Swap: {}/{} pages used, {} swap-ins, {} swap-outs\n",
+        stats.swapped_pages, stats.swap_pages, stats.swap_ins, stats.swap_outs);
+    let (active_stacks, pooled_stacks) = protection::stack_counts();
+    vga::print!("Protection: {} W^X violations refused, {} kernel stacks ({} pooled)\n",
+        protection::wx_violations(), active_stacks, pooled_stacks);
+    vga::print!("IPC: {} shared memory objects, {} futex waiters\n",
+        shm::object_count(), futex::waiting_count());
+}
+
+pub fn insert_region(root: u64, region: VirtualMemoryRegion) {
+    VM_MANAGER.lock().space_mut(root).insert(region);
+}
+
+pub fn find_region(root: u64, addr: u64) -> Option<VirtualMemoryRegion> {
+    VM_MANAGER.lock().find_region(root, addr)
+}
+
+pub fn enable_reclaim(address_space: &PageMapper) {
+    VM_MANAGER.lock().set_reclaimable(address_space.root(), true);
+}
+
+pub fn clone_regions(parent: &PageMapper, child: &PageMapper) {
+    VM_MANAGER.lock().clone_space(parent.root(), child.root());
 }
 
-pub fn munmap(addr: u64) -> bool {
-    unsafe {
-        VM_MANAGER.munmap(addr)
+pub fn truncate_object(object: &object::MemoryObject, size: u64) {
+    let first_dropped = size.div_ceil(PAGE_SIZE) * PAGE_SIZE;
+    let manager = VM_MANAGER.lock();
+    for (&root, regions) in &manager.spaces {
+        let mut mapper = PageMapper::from_root(root);
+        for region in regions.iter() {
+            if !region.object.as_ref().map_or(false, |mapped| core::ptr::eq(&**mapped, object)) {
+                continue;
+            }
+            if region.offset + (region.end - region.start) <= first_dropped {
+                continue;
+            }
+            let start = region.start + first_dropped.saturating_sub(region.offset);
+            mmap::unmap(&mut mapper, start, region.end - start);
+        }
     }
 }
 
-pub fn handle_page_fault(addr: u64, error_code: u64) -> bool {
-    unsafe {
-        VM_MANAGER.handle_page_fault(addr, error_code)
+pub fn destroy_address_space(address_space: PageMapper) {
+    let regions = VM_MANAGER.lock().remove_space(address_space.root());
+    let dirty = regions
+        .as_ref()
+        .map_or_else(Vec::new, |regions| mmap::collect_dirty(&address_space, regions.iter()));
+    address_space.destroy();
+    mmap::write_back(dirty);
+    drop(regions);
+}
+
+pub fn handle_page_fault(addr: u64, error_code: u64) -> Result<(), FaultError> {
+    if error_code & demand_paging::FAULT_PROTECTION_KEY != 0 {
+        return Err(FaultError::ProtectionKey);
     }
+    
+    if cow::handle_page_fault(addr, error_code) {
+        return Ok(());
+    }
+    
+    if swapping::handle_page_fault(addr, error_code) {
+        return Ok(());
+    }
+    
+    demand_paging::handle_page_fault(addr, error_code)
 }
@@ -0,0 +1,187 @@
+use crate::memory::frame_allocator;
+use crate::memory::paging::{self, PAGE_SIZE};
+use crate::process::files::{FileDescription, VfsFile};
+use crate::smp::synchronization::{Mutex, SpinLock};
+use crate::syscalls::errno::Errno;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+static FILE_OBJECTS: SpinLock<BTreeMap<String, Weak<MemoryObject>>> = SpinLock::new(BTreeMap::new());
+
+pub struct MemoryObject {
+    pages: SpinLock<BTreeMap<u64, u64>>,
+    dirty: SpinLock<BTreeSet<u64>>,
+    file: Option<Mutex<VfsFile>>,
+    size: AtomicU64,
+}
+
+impl MemoryObject {
+    pub fn anonymous() -> Self {
+        Self::sized(u64::MAX)
+    }
+
+    pub fn sized(size: u64) -> Self {
+        Self {
+            pages: SpinLock::new(BTreeMap::new()),
+            dirty: SpinLock::new(BTreeSet::new()),
+            file: None,
+            size: AtomicU64::new(size),
+        }
+    }
+
+    pub fn for_file(file: &dyn FileDescription) -> Result<Arc<Self>, Errno> {
+        let path = file.path().ok_or(Errno::ENODEV)?;
+        if let Some(object) = FILE_OBJECTS.lock().get(path).and_then(|object| object.upgrade()) {
+            return Ok(object);
+        }
+
+        let backing = VfsFile::open(path)?;
+        let size = backing.size()?;
+        let object = Arc::new(Self {
+            pages: SpinLock::new(BTreeMap::new()),
+            dirty: SpinLock::new(BTreeSet::new()),
+            file: Some(Mutex::new(backing)),
+            size: AtomicU64::new(size),
+        });
+
+        let mut objects = FILE_OBJECTS.lock();
+        objects.retain(|_, object| object.strong_count() > 0);
+        match objects.get(path).and_then(|existing| existing.upgrade()) {
+            Some(existing) => Ok(existing),
+            None => {
+                objects.insert(path.to_string(), Arc::downgrade(&object));
+                Ok(object)
+            }
+        }
+    }
+
+    pub fn is_file_backed(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub fn resident_pages(&self) -> usize {
+        self.pages.lock().len()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    pub fn contains(&self, index: u64) -> bool {
+        index < self.size().div_ceil(PAGE_SIZE)
+    }
+
+    pub fn resize(&self, size: u64) {
+        if size < self.size.swap(size, Ordering::AcqRel) {
+            super::truncate_object(self, size);
+        }
+        let first_dropped = size.div_ceil(PAGE_SIZE);
+        let dropped = self.pages.lock().split_off(&first_dropped);
+        for (_, frame) in dropped {
+            frame_allocator::release_frame(frame);
+        }
+    }
+
+    pub fn mark_dirty(&self, index: u64) {
+        if self.file.is_some() {
+            self.dirty.lock().insert(index);
+        }
+    }
+
+    pub fn write_back(&self) {
+        let file = match &self.file {
+            Some(file) => file.lock(),
+            None => return,
+        };
+        let size = file.size().unwrap_or(0);
+        let dirty = core::mem::take(&mut *self.dirty.lock());
+
+        for index in dirty {
+            let frame = match self.pages.lock().get(&index) {
+                Some(&frame) => {
+                    frame_allocator::share_frame(frame);
+                    frame
+                }
+                None => continue,
+            };
+
+            let offset = index * PAGE_SIZE;
+            if offset < size && file.seek(offset).ok() == Some(offset) {
+                let length = (size - offset).min(PAGE_SIZE) as usize;
+                let contents = unsafe { core::slice::from_raw_parts(paging::phys_to_virt(frame) as *const u8, length) };
+                let _ = file.write_through(contents);
+            }
+            frame_allocator::release_frame(frame);
+        }
+    }
+
+    fn refresh(&self, offset: u64, data: &[u8]) {
+        let end = offset + data.len() as u64;
+        let pages = self.pages.lock();
+        for (&index, &frame) in pages.range(offset / PAGE_SIZE..end.div_ceil(PAGE_SIZE)) {
+            let page_start = index * PAGE_SIZE;
+            let from = offset.max(page_start);
+            let to = end.min(page_start + PAGE_SIZE);
+            let source = &data[(from - offset) as usize..(to - offset) as usize];
+            unsafe {
+                let destination = paging::phys_to_virt(frame) + (from - page_start);
+                core::ptr::copy_nonoverlapping(source.as_ptr(), destination as *mut u8, source.len());
+            }
+        }
+    }
+
+    pub fn page(&self, index: u64) -> Option<u64> {
+        if !self.contains(index) {
+            return None;
+        }
+        if let Some(&frame) = self.pages.lock().get(&index) {
+            frame_allocator::share_frame(frame);
+            return Some(frame);
+        }
+
+        let frame = frame_allocator::allocate_frame()?;
+        let contents = unsafe {
+            core::slice::from_raw_parts_mut(paging::phys_to_virt(frame) as *mut u8, PAGE_SIZE as usize)
+        };
+        contents.fill(0);
+        if let Some(file) = &self.file {
+            let file = file.lock();
+            if file.seek(index * PAGE_SIZE).ok() == Some(index * PAGE_SIZE) {
+                let _ = file.read(contents);
+            }
+        }
+
+        let mut pages = self.pages.lock();
+        match pages.get(&index) {
+            Some(&existing) => {
+                frame_allocator::free_frame(frame);
+                frame_allocator::share_frame(existing);
+                Some(existing)
+            }
+            None => {
+                pages.insert(index, frame);
+                frame_allocator::share_frame(frame);
+                Some(frame)
+            }
+        }
+    }
+}
+
+pub fn file_written(path: &str, offset: u64, data: &[u8]) {
+    let object = FILE_OBJECTS.lock().get(path).and_then(|object| object.upgrade());
+    if let Some(object) = object {
+        object.refresh(offset, data);
+        object.size.fetch_max(offset + data.len() as u64, Ordering::AcqRel);
+    }
+}
+
+impl Drop for MemoryObject {
+    fn drop(&mut self) {
+        self.write_back();
+        for (_, frame) in core::mem::take(&mut *self.pages.lock()) {
+            frame_allocator::release_frame(frame);
+        }
+    }
+}
@@ -1,5 +1,254 @@
+use super::demand_paging::{FaultAccess, FAULT_USER};
+use super::mmap::{PROT_EXEC, PROT_WRITE};
+use super::FaultError;
+use crate::loader::USER_SPACE_END;
+use crate::memory::frame_allocator;
+use crate::memory::heap::{HEAP_MAX_SIZE, HEAP_START};
+use crate::memory::paging::{PageMapper, PAGE_GLOBAL, PAGE_NX, PAGE_PKEY_MASK, PAGE_PKEY_SHIFT, PAGE_SIZE, PAGE_WRITABLE, PHYSICAL_MEMORY_OFFSET};
+use crate::smp::synchronization::SpinLock;
+use crate::syscalls::errno::Errno;
 use crate::vga;
+use alloc::vec::Vec;
+use core::arch::{asm, x86_64::{__cpuid, __cpuid_count}};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+pub const KERNEL_STACK_BASE: u64 = 0xFFFF_D000_0000_0000;
+pub const KERNEL_STACK_END: u64 = 0xFFFF_D800_0000_0000;
+pub const STACK_GUARD_PAGES: u64 = 1;
+
+pub const PKEY_COUNT: u32 = 16;
+pub const PKEY_DISABLE_ACCESS: u32 = 1;
+pub const PKEY_DISABLE_WRITE: u32 = 2;
+pub const PKEY_RIGHTS_MASK: u32 = PKEY_DISABLE_ACCESS | PKEY_DISABLE_WRITE;
+pub const DEFAULT_PKRU: u32 = 0x5555_5554;
+
+const CPUID_PKU: u32 = 1 << 3;
+const CR4_PKE: u64 = 1 << 22;
+
+static PKU_ENABLED: AtomicBool = AtomicBool::new(false);
+static WX_VIOLATIONS: AtomicU64 = AtomicU64::new(0);
+
+struct StackArea {
+    next: u64,
+    pool: Vec<(u64, u64)>,
+    active: usize,
+}
+
+impl StackArea {
+    const fn new() -> Self {
+        Self {
+            next: KERNEL_STACK_BASE,
+            pool: Vec::new(),
+            active: 0,
+        }
+    }
+
+    fn reuse(&mut self, pages: u64) -> Option<u64> {
+        let index = self.pool.iter().position(|&(_, size)| size == pages)?;
+        self.active += 1;
+        Some(self.pool.swap_remove(index).0)
+    }
+
+    fn reserve(&mut self, pages: u64) -> Option<u64> {
+        let bottom = self.next.checked_add(STACK_GUARD_PAGES * PAGE_SIZE)?;
+        let end = bottom.checked_add(pages * PAGE_SIZE)?;
+        if end > KERNEL_STACK_END {
+            return None;
+        }
+        self.next = end;
+        self.active += 1;
+        Some(bottom)
+    }
+
+    fn release(&mut self, bottom: u64, pages: u64) {
+        self.active -= 1;
+        self.pool.push((bottom, pages));
+    }
+}
+
+static STACK_AREA: SpinLock<StackArea> = SpinLock::new(StackArea::new());
 
 pub fn init() {
-    vga::print!("Memory protection system initialized\n");
+    init_cpu();
+    vga::print!("Memory protection system initialized: W^X enforced, {} guard page(s) per kernel stack, PKU {}\n",
+        STACK_GUARD_PAGES, if pku_enabled() { "enabled" } else { "unsupported" });
+}
+
+pub fn init_cpu() {
+    if __cpuid(0).eax < 7 {
+        return;
+    }
+    let features = __cpuid_count(7, 0).ecx;
+    if features & CPUID_PKU == 0 {
+        return;
+    }
+
+    unsafe {
+        let cr4: u64;
+        asm!("mov {}, cr4", out(reg) cr4);
+        asm!("mov cr4, {}", in(reg) cr4 | CR4_PKE);
+    }
+    PKU_ENABLED.store(true, Ordering::Relaxed);
+    write_pkru(DEFAULT_PKRU);
+}
+
+pub fn is_write_execute(flags: u64) -> bool {
+    flags & PAGE_WRITABLE != 0 && flags & PAGE_NX == 0
+}
+
+pub fn check_mapping(virtual_addr: u64, flags: u64) -> bool {
+    if !is_write_execute(flags) {
+        return true;
+    }
+    WX_VIOLATIONS.fetch_add(1, Ordering::Relaxed);
+    vga::print!("W^X violation: refused writable and executable mapping at 0x{:x}, task {:?}\n",
+        virtual_addr, current_task());
+    false
+}
+
+pub fn check_prot(prot: u64) -> Result<(), Errno> {
+    if prot & (PROT_WRITE | PROT_EXEC) == PROT_WRITE | PROT_EXEC {
+        WX_VIOLATIONS.fetch_add(1, Ordering::Relaxed);
+        return Err(Errno::EACCES);
+    }
+    Ok(())
+}
+
+pub fn wx_violations() -> u64 {
+    WX_VIOLATIONS.load(Ordering::Relaxed)
+}
+
+pub fn allocate_stack(size: usize) -> *mut u8 {
+    let pages = stack_pages(size);
+    if let Some(bottom) = STACK_AREA.lock().reuse(pages) {
+        return bottom as *mut u8;
+    }
+    let bottom = match STACK_AREA.lock().reserve(pages) {
+        Some(bottom) => bottom,
+        None => return core::ptr::null_mut(),
+    };
+
+    let mut mapper = PageMapper::kernel();
+    for page in 0..pages {
+        let mapped = frame_allocator::allocate_frame().map(|frame| {
+            (frame, mapper.map(bottom + page * PAGE_SIZE, frame, PAGE_WRITABLE | PAGE_GLOBAL | PAGE_NX))
+        });
+        match mapped {
+            Some((_, Ok(()))) => {}
+            Some((frame, Err(_))) => {
+                frame_allocator::release_frame(frame);
+                return abandon_stack(&mut mapper, bottom, page);
+            }
+            None => return abandon_stack(&mut mapper, bottom, page),
+        }
+    }
+    bottom as *mut u8
+}
+
+pub fn free_stack(stack: *mut u8, size: usize) {
+    STACK_AREA.lock().release(stack as u64, stack_pages(size));
+}
+
+pub fn stack_counts() -> (usize, usize) {
+    let area = STACK_AREA.lock();
+    (area.active, area.pool.len())
+}
+
+pub fn is_stack_guard(address: u64) -> bool {
+    (KERNEL_STACK_BASE..KERNEL_STACK_END).contains(&address) && PageMapper::kernel().translate(address).is_none()
+}
+
+pub fn pku_enabled() -> bool {
+    PKU_ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn read_pkru() -> u32 {
+    if !pku_enabled() {
+        return DEFAULT_PKRU;
+    }
+    let value: u32;
+    unsafe {
+        asm!("rdpkru", in("ecx") 0u32, out("eax") value, out("edx") _);
+    }
+    value
+}
+
+pub fn write_pkru(value: u32) {
+    if !pku_enabled() {
+        return;
+    }
+    unsafe {
+        asm!("wrpkru", in("eax") value, in("ecx") 0u32, in("edx") 0u32);
+    }
+}
+
+pub fn set_pkey_rights(pkey: u8, rights: u32) {
+    let shift = pkey as u32 * 2;
+    let pkru = (read_pkru() & !(PKEY_RIGHTS_MASK << shift)) | ((rights & PKEY_RIGHTS_MASK) << shift);
+    write_pkru(pkru);
+}
+
+pub fn pkey_flags(pkey: u8) -> u64 {
+    ((pkey as u64) << PAGE_PKEY_SHIFT) & PAGE_PKEY_MASK
+}
+
+pub fn report_violation(address: u64, error_code: u64, rip: u64, error: FaultError) {
+    let access = FaultAccess::from_error_code(error_code);
+    let mode = if error_code & FAULT_USER != 0 { "user" } else { "kernel" };
+    vga::print!("Protection violation: {} {:?} at 0x{:x} ({:?}), RIP=0x{:x}, task {:?}\n",
+        mode, access, address, error, rip, current_task());
+    describe_address(address);
+}
+
+pub fn report_stack_overflow(address: u64, rip: u64) -> bool {
+    if !is_stack_guard(address) {
+        return false;
+    }
+    vga::print!("Kernel stack overflow: task {:?} hit guard page at 0x{:x}, RIP=0x{:x}\n",
+        current_task(), address, rip);
+    true
+}
+
+fn describe_address(address: u64) {
+    if address < USER_SPACE_END {
+        let root = PageMapper::current().root();
+        let region = super::VM_MANAGER.try_lock().and_then(|manager| manager.find_region(root, address));
+        match region {
+            Some(region) => vga::print!("  region: 0x{:x}-0x{:x} prot 0x{:x} flags 0x{:x} pkey {}\n",
+                region.start, region.end, region.prot, region.flags, region.pkey),
+            None => vga::print!("  region: none mapped at 0x{:x}\n", address),
+        }
+        return;
+    }
+
+    let area = if is_stack_guard(address) {
+        "kernel stack guard page"
+    } else if (KERNEL_STACK_BASE..KERNEL_STACK_END).contains(&address) {
+        "kernel stack"
+    } else if (HEAP_START as u64..HEAP_START as u64 + HEAP_MAX_SIZE as u64).contains(&address) {
+        "kernel heap"
+    } else if (PHYSICAL_MEMORY_OFFSET..HEAP_START as u64).contains(&address) {
+        "direct physical map"
+    } else {
+        "kernel address space"
+    };
+    vga::print!("  region: {}\n", area);
+}
+
+fn current_task() -> Option<usize> {
+    crate::scheduler::SCHEDULER.try_lock().map(|scheduler| scheduler.get_current_task_id())
+}
+
+fn stack_pages(size: usize) -> u64 {
+    (size as u64).div_ceil(PAGE_SIZE)
+}
+
+fn abandon_stack(mapper: &mut PageMapper, bottom: u64, mapped: u64) -> *mut u8 {
+    for page in 0..mapped {
+        if let Ok(frame) = mapper.unmap(bottom + page * PAGE_SIZE) {
+            frame_allocator::release_frame(frame);
+        }
+    }
+    STACK_AREA.lock().active -= 1;
+    core::ptr::null_mut()
 }
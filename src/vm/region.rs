@@ -0,0 +1,312 @@
+use super::object::MemoryObject;
+use crate::memory::paging::PAGE_SIZE;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+#[derive(Clone)]
+pub struct VirtualMemoryRegion {
+    pub start: u64,
+    pub end: u64,
+    pub prot: u64,
+    pub flags: u64,
+    pub object: Option<Arc<MemoryObject>>,
+    pub offset: u64,
+    pub pkey: u8,
+}
+
+impl VirtualMemoryRegion {
+    pub fn anonymous(start: u64, end: u64, prot: u64, flags: u64) -> Self {
+        Self {
+            start,
+            end,
+            prot,
+            flags,
+            object: None,
+            offset: 0,
+            pkey: 0,
+        }
+    }
+
+    pub fn page_flags(&self) -> u64 {
+        super::mmap::page_flags(self.prot) | super::protection::pkey_flags(self.pkey)
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        address >= self.start && address < self.end
+    }
+
+    pub fn pages(&self) -> u64 {
+        (self.end - self.start) / PAGE_SIZE
+    }
+
+    pub fn object_index(&self, page: u64) -> u64 {
+        (self.offset + (page - self.start)) / PAGE_SIZE
+    }
+
+    fn split_at(&mut self, address: u64) -> VirtualMemoryRegion {
+        let mut upper = self.clone();
+        upper.start = address;
+        upper.offset = self.offset + (address - self.start);
+        self.end = address;
+        upper
+    }
+
+    fn can_merge(&self, next: &VirtualMemoryRegion) -> bool {
+        self.end == next.start
+            && self.prot == next.prot
+            && self.flags == next.flags
+            && self.pkey == next.pkey
+            && self.object.is_none()
+            && next.object.is_none()
+    }
+}
+
+#[derive(Clone)]
+pub struct RegionTree {
+    regions: BTreeMap<u64, VirtualMemoryRegion>,
+}
+
+impl RegionTree {
+    pub const fn new() -> Self {
+        Self {
+            regions: BTreeMap::new(),
+        }
+    }
+
+    pub fn find(&self, address: u64) -> Option<&VirtualMemoryRegion> {
+        self.regions
+            .range(..=address)
+            .next_back()
+            .map(|(_, region)| region)
+            .filter(|region| region.contains(address))
+    }
+
+    pub fn is_free(&self, start: u64, end: u64) -> bool {
+        self.overlapping(start, end).next().is_none()
+    }
+
+    pub fn is_covered(&self, start: u64, end: u64) -> bool {
+        let mut position = start;
+        for region in self.overlapping(start, end) {
+            if region.start > position {
+                return false;
+            }
+            position = region.end;
+        }
+        position >= end
+    }
+
+    pub fn free_ranges(&self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::new();
+        let mut position = start;
+        for region in self.overlapping(start, end) {
+            if region.start > position {
+                ranges.push((position, region.start));
+            }
+            position = position.max(region.end);
+        }
+        if position < end {
+            ranges.push((position, end));
+        }
+        ranges
+    }
+
+    pub fn find_gap(&self, hint: u64, length: u64, lower: u64, upper: u64) -> Option<u64> {
+        let mut candidate = hint.max(lower);
+        for region in self.regions.range(..).map(|(_, region)| region) {
+            if region.end <= candidate {
+                continue;
+            }
+            if region.start >= candidate.checked_add(length)? {
+                break;
+            }
+            candidate = region.end;
+        }
+
+        if candidate.checked_add(length)? <= upper {
+            Some(candidate)
+        } else if hint > lower {
+            self.find_gap(lower, length, lower, upper)
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, region: VirtualMemoryRegion) {
+        let start = region.start;
+        self.regions.insert(start, region);
+        self.merge_around(start);
+    }
+
+    pub fn remove_range(&mut self, start: u64, end: u64) -> Vec<VirtualMemoryRegion> {
+        self.split(start);
+        self.split(end);
+
+        let keys: Vec<u64> = self.overlapping(start, end).map(|region| region.start).collect();
+        keys.iter().filter_map(|key| self.regions.remove(key)).collect()
+    }
+
+    pub fn update_range<F: FnMut(&mut VirtualMemoryRegion)>(&mut self, start: u64, end: u64, mut update: F) {
+        self.split(start);
+        self.split(end);
+
+        let keys: Vec<u64> = self.overlapping(start, end).map(|region| region.start).collect();
+        for key in keys.iter() {
+            if let Some(region) = self.regions.get_mut(key) {
+                update(region);
+            }
+        }
+        for key in keys.iter() {
+            self.merge_around(*key);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &VirtualMemoryRegion> {
+        self.regions.values()
+    }
+
+    pub fn count(&self) -> usize {
+        self.regions.len()
+    }
+
+    fn overlapping(&self, start: u64, end: u64) -> impl Iterator<Item = &VirtualMemoryRegion> {
+        let first = self.find(start).map_or(start, |region| region.start);
+        self.regions
+            .range(first..end)
+            .map(|(_, region)| region)
+            .filter(move |region| region.end > start)
+    }
+
+    fn split(&mut self, address: u64) {
+        let key = match self.find(address) {
+            Some(region) if region.start != address => region.start,
+            _ => return,
+        };
+        if let Some(region) = self.regions.get_mut(&key) {
+            let upper = region.split_at(address);
+            self.regions.insert(address, upper);
+        }
+    }
+
+    fn merge_around(&mut self, start: u64) {
+        let previous = self.regions.range(..start).next_back().map(|(&key, _)| key);
+        let mut key = start;
+        if let Some(previous) = previous {
+            if self.try_merge(previous, start) {
+                key = previous;
+            }
+        }
+
+        if let Some(end) = self.regions.get(&key).map(|region| region.end) {
+            self.try_merge(key, end);
+        }
+    }
+
+    fn try_merge(&mut self, first: u64, second: u64) -> bool {
+        let mergeable = match (self.regions.get(&first), self.regions.get(&second)) {
+            (Some(a), Some(b)) => a.can_merge(b),
+            _ => false,
+        };
+        if !mergeable {
+            return false;
+        }
+
+        if let Some(next) = self.regions.remove(&second) {
+            if let Some(region) = self.regions.get_mut(&first) {
+                region.end = next.end;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROT_READ: u64 = 1;
+    const PROT_WRITE: u64 = 2;
+    const MAP_PRIVATE: u64 = 2;
+
+    fn region(start: u64, end: u64, prot: u64) -> VirtualMemoryRegion {
+        VirtualMemoryRegion::anonymous(start, end, prot, MAP_PRIVATE)
+    }
+
+    #[test]
+    fn adjacent_compatible_regions_merge() {
+        let mut tree = RegionTree::new();
+        tree.insert(region(0x1000, 0x3000, PROT_READ));
+        tree.insert(region(0x3000, 0x5000, PROT_READ));
+
+        assert_eq!(tree.count(), 1);
+        let merged = tree.find(0x4000).unwrap();
+        assert_eq!((merged.start, merged.end), (0x1000, 0x5000));
+    }
+
+    #[test]
+    fn regions_with_different_protection_stay_separate() {
+        let mut tree = RegionTree::new();
+        tree.insert(region(0x1000, 0x3000, PROT_READ));
+        tree.insert(region(0x3000, 0x5000, PROT_READ | PROT_WRITE));
+
+        assert_eq!(tree.count(), 2);
+        assert_eq!(tree.find(0x2fff).unwrap().end, 0x3000);
+        assert_eq!(tree.find(0x3000).unwrap().start, 0x3000);
+    }
+
+    #[test]
+    fn remove_range_splits_around_the_hole() {
+        let mut tree = RegionTree::new();
+        tree.insert(region(0x1000, 0x9000, PROT_READ));
+
+        let removed = tree.remove_range(0x3000, 0x5000);
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!((removed[0].start, removed[0].end), (0x3000, 0x5000));
+        assert_eq!(tree.count(), 2);
+        assert_eq!(tree.find(0x2000).unwrap().end, 0x3000);
+        assert_eq!(tree.find(0x6000).unwrap().start, 0x5000);
+        assert!(tree.find(0x4000).is_none());
+        assert!(tree.is_free(0x3000, 0x5000));
+        assert!(!tree.is_covered(0x1000, 0x9000));
+    }
+
+    #[test]
+    fn update_range_splits_then_merges_back() {
+        let mut tree = RegionTree::new();
+        tree.insert(region(0x1000, 0x9000, PROT_READ));
+
+        tree.update_range(0x3000, 0x5000, |region| region.prot = PROT_READ | PROT_WRITE);
+        assert_eq!(tree.count(), 3);
+        let middle = tree.find(0x3000).unwrap();
+        assert_eq!((middle.start, middle.end, middle.offset), (0x3000, 0x5000, 0x2000));
+
+        tree.update_range(0x3000, 0x5000, |region| region.prot = PROT_READ);
+        assert_eq!(tree.count(), 1);
+        assert!(tree.is_covered(0x1000, 0x9000));
+    }
+
+    #[test]
+    fn find_gap_skips_regions_and_wraps_to_lower_bound() {
+        let mut tree = RegionTree::new();
+        tree.insert(region(0x1000, 0x3000, PROT_READ));
+        tree.insert(region(0x5000, 0x8000, PROT_WRITE));
+
+        assert_eq!(tree.find_gap(0x1000, 0x2000, 0x1000, 0x10000), Some(0x3000));
+        assert_eq!(tree.find_gap(0x1000, 0x3000, 0x1000, 0x10000), Some(0x8000));
+        assert_eq!(tree.find_gap(0x8000, 0x2000, 0x1000, 0x9000), Some(0x3000));
+        assert_eq!(tree.find_gap(0x1000, 0x4000, 0x1000, 0x9000), None);
+    }
+
+    #[test]
+    fn free_ranges_skip_existing_regions() {
+        let mut tree = RegionTree::new();
+        tree.insert(region(0x2000, 0x3000, PROT_READ));
+        tree.insert(region(0x5000, 0x7000, PROT_READ | PROT_WRITE));
+
+        assert_eq!(tree.free_ranges(0x1000, 0x8000), [(0x1000, 0x2000), (0x3000, 0x5000), (0x7000, 0x8000)]);
+        assert_eq!(tree.free_ranges(0x2800, 0x6000), [(0x3000, 0x5000)]);
+        assert!(tree.free_ranges(0x5000, 0x7000).is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+use super::object::MemoryObject;
+use crate::process::files::{FileDescription, FileKind};
+use crate::smp::synchronization::SpinLock;
+use crate::syscalls::errno::Errno;
+use crate::vga;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+
+pub const SHM_PREFIX: &str = "/dev/shm/";
+pub const MAX_NAME_LENGTH: usize = 255;
+
+pub const O_ACCMODE: u64 = 3;
+pub const O_RDONLY: u64 = 0;
+pub const O_CREAT: u64 = 0x40;
+pub const O_EXCL: u64 = 0x80;
+pub const O_TRUNC: u64 = 0x200;
+
+static SHARED_OBJECTS: SpinLock<BTreeMap<String, Arc<MemoryObject>>> = SpinLock::new(BTreeMap::new());
+
+pub struct SharedMemoryFile {
+    object: Arc<MemoryObject>,
+    writable: bool,
+}
+
+impl FileDescription for SharedMemoryFile {
+    fn kind(&self) -> FileKind {
+        FileKind::SharedMemory
+    }
+
+    fn read(&self, _buffer: &mut [u8]) -> Result<usize, Errno> {
+        Err(Errno::EINVAL)
+    }
+
+    fn write(&self, _data: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::EINVAL)
+    }
+
+    fn truncate(&self, length: u64) -> Result<(), Errno> {
+        if !self.writable {
+            return Err(Errno::EINVAL);
+        }
+        self.object.resize(length);
+        Ok(())
+    }
+
+    fn memory_object(&self) -> Option<Arc<MemoryObject>> {
+        Some(self.object.clone())
+    }
+}
+
+pub fn init() {
+    vga::print!("Shared memory objects initialized ({})\n", SHM_PREFIX);
+}
+
+pub fn open(name: &str, flags: u64) -> Result<Arc<dyn FileDescription>, Errno> {
+    let name = validate(name)?;
+    let writable = flags & O_ACCMODE != O_RDONLY;
+    if flags & O_TRUNC != 0 && !writable {
+        return Err(Errno::EACCES);
+    }
+
+    let object = {
+        let mut objects = SHARED_OBJECTS.lock();
+        match objects.get(name) {
+            Some(_) if flags & O_CREAT != 0 && flags & O_EXCL != 0 => return Err(Errno::EEXIST),
+            Some(object) => object.clone(),
+            None if flags & O_CREAT == 0 => return Err(Errno::ENOENT),
+            None => {
+                let object = Arc::new(MemoryObject::sized(0));
+                objects.insert(name.to_string(), object.clone());
+                object
+            }
+        }
+    };
+
+    if flags & O_TRUNC != 0 {
+        object.resize(0);
+    }
+    Ok(Arc::new(SharedMemoryFile { object, writable }))
+}
+
+pub fn unlink(name: &str) -> Result<(), Errno> {
+    let name = validate(name)?;
+    let removed = SHARED_OBJECTS.lock().remove(name).ok_or(Errno::ENOENT)?;
+    drop(removed);
+    Ok(())
+}
+
+pub fn object_count() -> usize {
+    SHARED_OBJECTS.lock().len()
+}
+
+fn validate(name: &str) -> Result<&str, Errno> {
+    let name = name.strip_prefix('/').unwrap_or(name);
+    if name.is_empty() || name.contains('/') {
+        return Err(Errno::EINVAL);
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(Errno::ENAMETOOLONG);
+    }
+    Ok(name)
+}
@@ -1,22 +1,416 @@
+use super::cow::FAULT_PRESENT;
+use super::region::VirtualMemoryRegion;
+use super::{VirtualMemoryManager, VM_MANAGER};
+use crate::memory::frame_allocator::{self, FRAME_ALLOCATOR};
+use crate::memory::paging::{self, PageMapper, PAGE_ACCESSED, PAGE_COW, PAGE_DIRTY, PAGE_PRESENT, PAGE_SHARED, PAGE_SIZE};
+use crate::pci::devices::storage;
+use crate::smp::synchronization::SpinLock;
 use crate::vga;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+pub const SWAP_ENTRY: u64 = 1 << 11;
+pub const DEFAULT_SWAP_DEVICE: usize = 1;
+pub const SECTOR_SIZE: u64 = 512;
+pub const SECTORS_PER_PAGE: u64 = PAGE_SIZE / SECTOR_SIZE;
+pub const MAX_SWAP_SLOTS: u64 = 1 << 20;
+
+pub const LOW_WATERMARK_FRAMES: usize = 256;
+pub const HIGH_WATERMARK_FRAMES: usize = 512;
+pub const RECLAIM_BATCH: usize = 32;
+pub const MAX_SCAN_PAGES: usize = 4096;
+pub const RECLAIM_INTERVAL_MS: u64 = 100;
+
+const SLOT_SHIFT: u32 = 12;
+const SWAP_SIGNATURE: &[u8] = b"SWAPSPACE2";
+const SWAP_HEADER_LAST_PAGE: usize = 1028;
+const RECLAIM_STACK_SIZE: usize = 16384;
+
+static SWAP_INS: AtomicU64 = AtomicU64::new(0);
+static SWAP_OUTS: AtomicU64 = AtomicU64::new(0);
+static RECLAIM_RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SwapError {
+    AlreadyEnabled,
+    Io,
+    BadSignature,
+}
+
+#[derive(Clone, Copy)]
+struct SwapDevice {
+    device_id: usize,
+    start_lba: u64,
+}
+
+impl SwapDevice {
+    fn read(&self, slot: u64, buffer: &mut [u8]) -> bool {
+        read_page(self.device_id, self.start_lba + slot * SECTORS_PER_PAGE, buffer)
+    }
+
+    fn write(&self, slot: u64, data: &[u8]) -> bool {
+        let lba = self.start_lba + slot * SECTORS_PER_PAGE;
+        data.chunks(SECTOR_SIZE as usize)
+            .enumerate()
+            .all(|(index, sector)| storage::write_sector(self.device_id, lba + index as u64, sector))
+    }
+}
+
+struct Victim {
+    root: u64,
+    page: u64,
+    entry: u64,
+    frame: u64,
+    slot: u64,
+}
+
+pub struct SwapArea {
+    device: SwapDevice,
+    references: Vec<u16>,
+    used: u64,
+    next: usize,
+}
+
+impl SwapArea {
+    fn slots(&self) -> u64 {
+        self.references.len() as u64 - 1
+    }
+
+    fn allocate(&mut self) -> Option<u64> {
+        let count = self.references.len();
+        for step in 0..count {
+            let slot = (self.next + step) % count;
+            if slot != 0 && self.references[slot] == 0 {
+                self.references[slot] = 1;
+                self.used += 1;
+                self.next = slot + 1;
+                return Some(slot as u64);
+            }
+        }
+        None
+    }
+
+    fn share(&mut self, slot: u64) {
+        if let Some(references) = self.references.get_mut(slot as usize) {
+            *references = references.saturating_add(1);
+        }
+    }
+
+    fn release(&mut self, slot: u64) {
+        if let Some(references) = self.references.get_mut(slot as usize) {
+            if *references > 0 {
+                *references -= 1;
+                if *references == 0 {
+                    self.used -= 1;
+                }
+            }
+        }
+    }
+}
+
+pub struct SwapManager {
+    area: Option<SwapArea>,
+    hand: (u64, u64),
+}
+
+impl SwapManager {
+    pub const fn new() -> Self {
+        Self {
+            area: None,
+            hand: (0, 0),
+        }
+    }
+
+    pub fn total_slots(&self) -> u64 {
+        self.area.as_ref().map_or(0, |area| area.slots())
+    }
+
+    pub fn used_slots(&self) -> u64 {
+        self.area.as_ref().map_or(0, |area| area.used)
+    }
+
+    fn select(&mut self, mapper: &mut PageMapper, page: u64, entry: u64) -> Option<Victim> {
+        let frame = mapper.translate(page)? & !(PAGE_SIZE - 1);
+        if entry & (PAGE_COW | PAGE_SHARED) != 0 || frame_allocator::frame_references(frame) != 1 {
+            return None;
+        }
+
+        let area = self.area.as_mut()?;
+        let slot = area.allocate()?;
+        let clean = entry & !PAGE_DIRTY;
+        if !matches!(mapper.compare_and_set(page, entry, clean), Ok(true)) {
+            area.release(slot);
+            return None;
+        }
+
+        frame_allocator::share_frame(frame);
+        Some(Victim {
+            root: mapper.root(),
+            page,
+            entry: clean,
+            frame,
+            slot,
+        })
+    }
+
+    fn select_victims(&mut self, manager: &VirtualMemoryManager, target: usize) -> Vec<Victim> {
+        let mut victims = Vec::new();
+        let ranges: Vec<(u64, u64, u64)> = manager.swappable_ranges().collect();
+        if ranges.is_empty() || self.area.is_none() {
+            return victims;
+        }
+
+        let hand = self.hand;
+        let first = ranges.iter().position(|&(root, _, end)| (root, end) > hand).unwrap_or(0);
+        let mut resume = Some(hand);
+        let mut scanned = 0;
+
+        for index in (first..ranges.len()).chain(0..=first) {
+            let (root, start, end) = ranges[index];
+            let mut mapper = PageMapper::from_root(root);
+            let mut page = match resume.take() {
+                Some((hand_root, hand_address)) if hand_root == root => hand_address.clamp(start, end),
+                _ => start,
+            };
+
+            while page < end {
+                if victims.len() >= target || scanned >= MAX_SCAN_PAGES {
+                    self.hand = (root, page);
+                    return victims;
+                }
+                scanned += 1;
+
+                if let Some(entry) = mapper.entry(page).filter(|entry| entry & PAGE_PRESENT != 0) {
+                    if entry & PAGE_ACCESSED != 0 {
+                        mapper.clear_accessed(page);
+                    } else if let Some(victim) = self.select(&mut mapper, page, entry) {
+                        victims.push(victim);
+                    }
+                }
+                page += PAGE_SIZE;
+            }
+        }
+
+        self.hand = (0, 0);
+        victims
+    }
+}
+
+static SWAP_MANAGER: SpinLock<SwapManager> = SpinLock::new(SwapManager::new());
 
 pub fn init() {
     vga::print!("Swapping system initialized\n");
 }
 
+pub fn enable(device_id: usize, start_lba: u64) -> Result<u64, SwapError> {
+    if SWAP_MANAGER.lock().area.is_some() {
+        return Err(SwapError::AlreadyEnabled);
+    }
+
+    let mut header = vec![0u8; PAGE_SIZE as usize];
+    if !read_page(device_id, start_lba, &mut header) {
+        return Err(SwapError::Io);
+    }
+    if &header[PAGE_SIZE as usize - SWAP_SIGNATURE.len()..] != SWAP_SIGNATURE {
+        return Err(SwapError::BadSignature);
+    }
+
+    let mut last_page = [0u8; 4];
+    last_page.copy_from_slice(&header[SWAP_HEADER_LAST_PAGE..SWAP_HEADER_LAST_PAGE + 4]);
+    let slots = (u32::from_le_bytes(last_page) as u64).min(MAX_SWAP_SLOTS - 1);
+    if slots == 0 {
+        return Err(SwapError::BadSignature);
+    }
+
+    {
+        let mut manager = SWAP_MANAGER.lock();
+        if manager.area.is_some() {
+            return Err(SwapError::AlreadyEnabled);
+        }
+        manager.area = Some(SwapArea {
+            device: SwapDevice { device_id, start_lba },
+            references: vec![0; slots as usize + 1],
+            used: 0,
+            next: 1,
+        });
+    }
+
+    if !RECLAIM_RUNNING.swap(true, Ordering::AcqRel) {
+        crate::scheduler::create_task(reclaim_task, RECLAIM_STACK_SIZE);
+    }
+    vga::print!("Swap enabled on device {} at LBA {}: {} pages\n", device_id, start_lba, slots);
+    Ok(slots)
+}
+
+pub fn is_swap_entry(entry: u64) -> bool {
+    entry & PAGE_PRESENT == 0 && entry & SWAP_ENTRY != 0
+}
+
+pub fn share_entry(entry: u64) {
+    if is_swap_entry(entry) {
+        if let Some(area) = SWAP_MANAGER.lock().area.as_mut() {
+            area.share(decode(entry));
+        }
+    }
+}
+
+pub fn release_entry(entry: u64) {
+    if is_swap_entry(entry) {
+        if let Some(area) = SWAP_MANAGER.lock().area.as_mut() {
+            area.release(decode(entry));
+        }
+    }
+}
+
+pub fn discard(mapper: &mut PageMapper, page: u64) {
+    let mut manager = SWAP_MANAGER.lock();
+    if let Some(entry) = mapper.entry(page).filter(|&entry| is_swap_entry(entry)) {
+        let _ = mapper.set_entry(page, 0);
+        if let Some(area) = manager.area.as_mut() {
+            area.release(decode(entry));
+        }
+    }
+}
+
 pub fn handle_page_fault(addr: u64, error_code: u64) -> bool {
-    vga::print!("Handling swap page fault at 0x{:x}\n", addr);
-    
-    if swap_in_page(addr) {
-        vga::print!("Swapped in page for 0x{:x}\n", addr);
-        true
+    if error_code & FAULT_PRESENT != 0 {
+        return false;
+    }
+
+    let page = addr & !(PAGE_SIZE - 1);
+    let mut mapper = PageMapper::current();
+    if !mapper.entry(page).map_or(false, is_swap_entry) {
+        return false;
+    }
+    let region = match super::find_region(mapper.root(), page) {
+        Some(region) => region,
+        None => return false,
+    };
+
+    relieve_pressure();
+    swap_in(&mut mapper, &region, page)
+}
+
+fn swap_in(mapper: &mut PageMapper, region: &VirtualMemoryRegion, page: u64) -> bool {
+    let (entry, device) = {
+        let mut manager = SWAP_MANAGER.lock();
+        let entry = match mapper.entry(page) {
+            Some(entry) if is_swap_entry(entry) => entry,
+            _ => return true,
+        };
+        let area = match manager.area.as_mut() {
+            Some(area) => area,
+            None => return false,
+        };
+        area.share(decode(entry));
+        (entry, area.device)
+    };
+
+    let frame = match frame_allocator::allocate_frame() {
+        Some(frame) => frame,
+        None => {
+            release_entry(entry);
+            return false;
+        }
+    };
+    let contents = unsafe {
+        core::slice::from_raw_parts_mut(paging::phys_to_virt(frame) as *mut u8, PAGE_SIZE as usize)
+    };
+    let value = frame | region.page_flags() | PAGE_PRESENT | PAGE_DIRTY;
+    let mapped = device.read(decode(entry), contents) && matches!(mapper.compare_and_set(page, entry, value), Ok(true));
+
+    if mapped {
+        release_entry(entry);
+        SWAP_INS.fetch_add(1, Ordering::Relaxed);
     } else {
-        vga::print!("Failed to swap in page for 0x{:x}\n", addr);
-        false
+        frame_allocator::free_frame(frame);
     }
+    release_entry(entry);
+    mapped || !mapper.entry(page).map_or(false, is_swap_entry)
 }
 
-fn swap_in_page(addr: u64) -> bool {
-    vga::print!("Swapping in page at 0x{:x}\n", addr);
+pub fn reclaim(target: usize) -> usize {
+    let (victims, device) = {
+        let manager = VM_MANAGER.lock();
+        let mut swap = SWAP_MANAGER.lock();
+        let victims = swap.select_victims(&manager, target);
+        (victims, swap.area.as_ref().map(|area| area.device))
+    };
+    let device = match device {
+        Some(device) => device,
+        None => return 0,
+    };
+
+    let mut reclaimed = 0;
+    for victim in victims {
+        let contents = unsafe {
+            core::slice::from_raw_parts(paging::phys_to_virt(victim.frame) as *const u8, PAGE_SIZE as usize)
+        };
+        if device.write(victim.slot, contents) && commit(&victim) {
+            reclaimed += 1;
+        } else if let Some(area) = SWAP_MANAGER.lock().area.as_mut() {
+            area.release(victim.slot);
+        }
+        frame_allocator::release_frame(victim.frame);
+    }
+    reclaimed
+}
+
+fn commit(victim: &Victim) -> bool {
+    let manager = VM_MANAGER.lock();
+    if manager.find_region(victim.root, victim.page).is_none() {
+        return false;
+    }
+
+    let mut mapper = PageMapper::from_root(victim.root);
+    if !matches!(mapper.compare_and_set(victim.page, victim.entry, encode(victim.slot)), Ok(true)) {
+        return false;
+    }
+    drop(manager);
+
+    frame_allocator::release_frame(victim.frame);
+    SWAP_OUTS.fetch_add(1, Ordering::Relaxed);
     true
 }
+
+pub fn relieve_pressure() {
+    if FRAME_ALLOCATOR.lock().free_frames() < LOW_WATERMARK_FRAMES {
+        reclaim(RECLAIM_BATCH);
+    }
+}
+
+pub fn swap_totals() -> (u64, u64) {
+    let manager = SWAP_MANAGER.lock();
+    (manager.total_slots(), manager.used_slots())
+}
+
+pub fn swap_activity() -> (u64, u64) {
+    (SWAP_INS.load(Ordering::Relaxed), SWAP_OUTS.load(Ordering::Relaxed))
+}
+
+fn reclaim_task() {
+    loop {
+        while FRAME_ALLOCATOR.lock().free_frames() < HIGH_WATERMARK_FRAMES {
+            if reclaim(RECLAIM_BATCH) == 0 {
+                break;
+            }
+        }
+        crate::time::sleep_ms(RECLAIM_INTERVAL_MS);
+    }
+}
+
+fn read_page(device_id: usize, lba: u64, buffer: &mut [u8]) -> bool {
+    buffer
+        .chunks_mut(SECTOR_SIZE as usize)
+        .enumerate()
+        .all(|(index, sector)| storage::read_sector(device_id, lba + index as u64, sector))
+}
+
+fn encode(slot: u64) -> u64 {
+    (slot << SLOT_SHIFT) | SWAP_ENTRY
+}
+
+fn decode(entry: u64) -> u64 {
+    (entry >> SLOT_SHIFT) & (MAX_SWAP_SLOTS - 1)
+}